@@ -0,0 +1,56 @@
+/*
+ * C bindings of drcov2lcov: builds and runs conversions of drcov logs like the drcov2lcov command
+ * line tool does.
+ *
+ *     drcov2lcov_converter *converter = drcov2lcov_converter_new();
+ *     drcov2lcov_converter_add_input(converter, "drcov.app.1234.log");
+ *     drcov2lcov_converter_module_filter(converter, "app");
+ *     if (drcov2lcov_converter_run(converter, "lcov", "app.info") != 0)
+ *         fprintf(stderr, "%s\n", drcov2lcov_last_error());
+ *     drcov2lcov_converter_free(converter);
+ *
+ * Every function that can fail returns 0 on success and -1 on failure, in which case
+ * drcov2lcov_last_error() describes the failure. All strings are NUL-terminated UTF-8.
+ */
+
+#ifndef DRCOV2LCOV_H
+#define DRCOV2LCOV_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Drcov2lcovConverter drcov2lcov_converter;
+
+/* The error of the last failed call of the current thread, or NULL if there was none. The string
+ * is owned by the library and valid until the next failed call of the thread. */
+const char *drcov2lcov_last_error(void);
+
+/* Creates an empty conversion, to be freed with drcov2lcov_converter_free() */
+drcov2lcov_converter *drcov2lcov_converter_new(void);
+void drcov2lcov_converter_free(drcov2lcov_converter *converter);
+
+/* Adds an input of the conversion, e.g. a drcov log or an archive of them */
+int drcov2lcov_converter_add_input(drcov2lcov_converter *converter, const char *path);
+
+/* Only keeps the modules (source files) whose path matches any of the filters, skipping the ones
+ * that match any of the skip filters */
+int drcov2lcov_converter_module_filter(drcov2lcov_converter *converter, const char *regex);
+int drcov2lcov_converter_module_skip_filter(drcov2lcov_converter *converter, const char *regex);
+int drcov2lcov_converter_source_filter(drcov2lcov_converter *converter, const char *regex);
+int drcov2lcov_converter_source_skip_filter(drcov2lcov_converter *converter, const char *regex);
+
+/* Looks for the modules whose path matches regex with the match replaced by replacement */
+int drcov2lcov_converter_path_map(drcov2lcov_converter *converter, const char *regex,
+                                  const char *replacement);
+
+/* Runs the conversion, writing its output in format (any format of --output-format, e.g. "lcov"
+ * or "json") to output. Invalid filters are reported here. The converter can be run again. */
+int drcov2lcov_converter_run(const drcov2lcov_converter *converter, const char *format,
+                             const char *output);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* DRCOV2LCOV_H */
@@ -0,0 +1,350 @@
+//! C bindings of the [`drcov2lcov::Converter`], to embed the conversion of drcov logs into C/C++
+//! fuzzing frameworks and DynamoRIO clients. The declarations are in `include/drcov2lcov.h`.
+//!
+//! Every function that can fail returns 0 on success and -1 on failure, in which case
+//! [`drcov2lcov_last_error`] describes the failure.
+
+use clap::ValueEnum;
+use drcov2lcov::{Converter, OutputFormat};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+thread_local! {
+    /// The error of the last failed call of the current thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    // Messages cannot contain NUL bytes, but the paths and regular expressions they quote can
+    let message = message.to_string().replace('\0', "\\0");
+    LAST_ERROR.with(|error| *error.borrow_mut() = CString::new(message).ok());
+}
+
+/// Converts the C string `s` to a `&str`, recording the error of a NULL or non-UTF-8 one
+///
+/// # Safety
+///
+/// `s` must be NULL or point to a NUL-terminated string that outlives `'a`.
+unsafe fn to_str<'a>(s: *const c_char, what: &str) -> Option<&'a str> {
+    if s.is_null() {
+        set_last_error(format!("The {what} is NULL"));
+        return None;
+    }
+
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_last_error(format!("The {what} is not valid UTF-8"));
+            None
+        }
+    }
+}
+
+/// Runs `f`, turning its failures and panics into -1 so that they never unwind into C
+fn guard(f: impl FnOnce() -> Result<(), String>) -> c_int {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            set_last_error(e);
+            -1
+        }
+        Err(_) => {
+            set_last_error("drcov2lcov panicked");
+            -1
+        }
+    }
+}
+
+/// A conversion that is being built, see [`drcov2lcov::Converter`]
+pub struct Drcov2lcovConverter {
+    converter: Converter,
+}
+
+impl Drcov2lcovConverter {
+    /// Applies the builder method `f` of the converter to the string `s`
+    ///
+    /// # Safety
+    ///
+    /// `converter` must be NULL or a live converter and `s` NULL or a NUL-terminated string.
+    unsafe fn update(
+        converter: *mut Self,
+        s: *const c_char,
+        what: &str,
+        f: impl FnOnce(Converter, &str) -> Converter,
+    ) -> c_int {
+        let Some(converter) = converter.as_mut() else {
+            set_last_error("The converter is NULL");
+            return -1;
+        };
+        let Some(s) = to_str(s, what) else {
+            return -1;
+        };
+
+        guard(|| {
+            converter.converter = f(std::mem::take(&mut converter.converter), s);
+            Ok(())
+        })
+    }
+}
+
+/// Returns the error of the last failed call of the current thread, or NULL if there was none.
+/// The string is owned by the library and valid until the next failed call of the thread.
+#[no_mangle]
+pub extern "C" fn drcov2lcov_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// Creates an empty conversion, to be freed with [`drcov2lcov_converter_free`]
+#[no_mangle]
+pub extern "C" fn drcov2lcov_converter_new() -> *mut Drcov2lcovConverter {
+    Box::into_raw(Box::new(Drcov2lcovConverter {
+        converter: Converter::new(),
+    }))
+}
+
+/// Frees a conversion created by [`drcov2lcov_converter_new`]
+///
+/// # Safety
+///
+/// `converter` must be NULL or a converter that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_free(converter: *mut Drcov2lcovConverter) {
+    if !converter.is_null() {
+        drop(Box::from_raw(converter));
+    }
+}
+
+/// Adds an input of the conversion, e.g. a drcov log or an archive of them
+///
+/// # Safety
+///
+/// `converter` must be a live converter and `path` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_add_input(
+    converter: *mut Drcov2lcovConverter,
+    path: *const c_char,
+) -> c_int {
+    Drcov2lcovConverter::update(converter, path, "input path", |converter, path| {
+        converter.add_input(path)
+    })
+}
+
+/// Only keeps the modules whose path matches `regex` (any of the module filters)
+///
+/// # Safety
+///
+/// `converter` must be a live converter and `regex` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_module_filter(
+    converter: *mut Drcov2lcovConverter,
+    regex: *const c_char,
+) -> c_int {
+    Drcov2lcovConverter::update(converter, regex, "module filter", Converter::module_filter)
+}
+
+/// Skips the modules whose path matches `regex`
+///
+/// # Safety
+///
+/// `converter` must be a live converter and `regex` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_module_skip_filter(
+    converter: *mut Drcov2lcovConverter,
+    regex: *const c_char,
+) -> c_int {
+    Drcov2lcovConverter::update(
+        converter,
+        regex,
+        "module skip filter",
+        Converter::module_skip_filter,
+    )
+}
+
+/// Only keeps the source files whose path matches `regex` (any of the source filters)
+///
+/// # Safety
+///
+/// `converter` must be a live converter and `regex` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_source_filter(
+    converter: *mut Drcov2lcovConverter,
+    regex: *const c_char,
+) -> c_int {
+    Drcov2lcovConverter::update(converter, regex, "source filter", Converter::source_filter)
+}
+
+/// Skips the source files whose path matches `regex`
+///
+/// # Safety
+///
+/// `converter` must be a live converter and `regex` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_source_skip_filter(
+    converter: *mut Drcov2lcovConverter,
+    regex: *const c_char,
+) -> c_int {
+    Drcov2lcovConverter::update(
+        converter,
+        regex,
+        "source skip filter",
+        Converter::source_skip_filter,
+    )
+}
+
+/// Looks for the modules whose path matches `regex` with the match replaced by `replacement`
+///
+/// # Safety
+///
+/// `converter` must be a live converter and `regex` and `replacement` NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_path_map(
+    converter: *mut Drcov2lcovConverter,
+    regex: *const c_char,
+    replacement: *const c_char,
+) -> c_int {
+    let Some(replacement) = to_str(replacement, "path map replacement") else {
+        return -1;
+    };
+
+    Drcov2lcovConverter::update(converter, regex, "path map", |converter, regex| {
+        converter.path_map(regex, replacement)
+    })
+}
+
+/// Runs the conversion, writing its output in `format` (any format of `--output-format`, e.g.
+/// `lcov` or `json`) to `output`. Invalid filters are reported here. The converter can be run
+/// again, e.g. once the inputs have grown.
+///
+/// # Safety
+///
+/// `converter` must be a live converter and `format` and `output` NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn drcov2lcov_converter_run(
+    converter: *const Drcov2lcovConverter,
+    format: *const c_char,
+    output: *const c_char,
+) -> c_int {
+    let Some(converter) = converter.as_ref() else {
+        set_last_error("The converter is NULL");
+        return -1;
+    };
+    let (Some(format), Some(output)) = (
+        to_str(format, "output format"),
+        to_str(output, "output path"),
+    ) else {
+        return -1;
+    };
+
+    guard(|| {
+        let format = OutputFormat::from_str(format, true)
+            .map_err(|_| format!("Unknown output format '{format}'"))?;
+
+        converter
+            .converter
+            .clone()
+            .output(format, output)
+            .run()
+            .map_err(|e| format!("{e:#}"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let error = drcov2lcov_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn reports_null_arguments() {
+        assert!(drcov2lcov_last_error().is_null());
+
+        unsafe {
+            assert_eq!(
+                drcov2lcov_converter_add_input(std::ptr::null_mut(), c"drcov.app.log".as_ptr()),
+                -1
+            );
+            assert_eq!(last_error(), "The converter is NULL");
+
+            let converter = drcov2lcov_converter_new();
+            assert_eq!(
+                drcov2lcov_converter_add_input(converter, std::ptr::null()),
+                -1
+            );
+            assert_eq!(last_error(), "The input path is NULL");
+
+            assert_eq!(
+                drcov2lcov_converter_path_map(converter, c"^/build".as_ptr(), std::ptr::null()),
+                -1
+            );
+            assert_eq!(last_error(), "The path map replacement is NULL");
+
+            assert_eq!(
+                drcov2lcov_converter_run(converter, c"lcov".as_ptr(), std::ptr::null()),
+                -1
+            );
+            assert_eq!(last_error(), "The output path is NULL");
+
+            drcov2lcov_converter_free(converter);
+            drcov2lcov_converter_free(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn reports_invalid_options_when_run() {
+        let output = std::env::temp_dir().join(format!(
+            "drcov2lcov-ffi-{}-coverage.info",
+            std::process::id()
+        ));
+        let output = CString::new(output.to_string_lossy().as_bytes()).unwrap();
+
+        unsafe {
+            let converter = drcov2lcov_converter_new();
+            assert_eq!(
+                drcov2lcov_converter_add_input(converter, c"drcov.app.log".as_ptr()),
+                0
+            );
+            // Filters are only compiled once the conversion runs
+            assert_eq!(
+                drcov2lcov_converter_module_filter(converter, c"(".as_ptr()),
+                0
+            );
+
+            assert_eq!(
+                drcov2lcov_converter_run(converter, c"gcov".as_ptr(), output.as_ptr()),
+                -1
+            );
+            assert_eq!(last_error(), "Unknown output format 'gcov'");
+
+            assert_eq!(
+                drcov2lcov_converter_run(converter, c"lcov".as_ptr(), output.as_ptr()),
+                -1
+            );
+            assert!(last_error().contains('('), "{}", last_error());
+
+            drcov2lcov_converter_free(converter);
+        }
+    }
+
+    #[test]
+    fn turns_panics_into_errors() {
+        assert_eq!(guard(|| panic!("unexpected")), -1);
+        assert_eq!(last_error(), "drcov2lcov panicked");
+
+        // The last error is kept per thread
+        std::thread::spawn(|| assert!(drcov2lcov_last_error().is_null()))
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,222 @@
+use crate::dwarf::{FunctionInfo, ObjectFile};
+use crate::function_keyed::demangle;
+use clap::ValueEnum;
+use itertools::Itertools;
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+
+/// How the coverage of functions that share their code is reported. Aliases are the functions
+/// (DWARF subprograms, or symbols such as weak aliases and ifunc implementations) that start at
+/// the same address.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AliasPolicy {
+    /// Every alias is reported as a function of its own, with the coverage of the shared code
+    #[default]
+    All,
+    /// Only the canonical alias is reported: a function whose symbol is global (not weak), or
+    /// else the first one by name
+    Canonical,
+}
+
+/// A function symbol of a binary
+#[derive(Debug)]
+pub struct FunctionSymbol {
+    name: String,
+    /// Whether the symbol is global and not weak
+    strong: bool,
+}
+
+/// A function of a module, as it is gathered from the debug info, along with its entry address
+#[derive(Debug)]
+pub struct ModuleFunction {
+    pub low_pc: u64,
+    pub file: String,
+    pub info: FunctionInfo,
+}
+
+/// The function symbols and the code of a binary
+#[derive(Debug, Default)]
+pub struct FunctionSymbols {
+    by_address: HashMap<u64, Vec<FunctionSymbol>>,
+    text_ranges: Vec<Range<u64>>,
+}
+
+impl FunctionSymbols {
+    /// Whether `address` is in the code of the binary. Subprograms whose code the linker
+    /// discarded are left at (or near) address zero and are not aliases of each other.
+    fn is_code(&self, address: u64) -> bool {
+        self.text_ranges
+            .iter()
+            .any(|range| range.contains(&address))
+    }
+}
+
+/// Returns the function symbols of `binary` by their address
+pub fn function_symbols(binary: &ObjectFile) -> FunctionSymbols {
+    let object = binary.with_object(|obj| obj);
+    let mut by_address = HashMap::<u64, Vec<FunctionSymbol>>::new();
+
+    let symbols = if object.symbols().next().is_some() {
+        object.symbols()
+    } else {
+        object.dynamic_symbols()
+    };
+
+    for symbol in symbols {
+        if symbol.kind() != SymbolKind::Text || symbol.address() == 0 || symbol.is_undefined() {
+            continue;
+        }
+
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+
+        by_address
+            .entry(symbol.address())
+            .or_default()
+            .push(FunctionSymbol {
+                name: name.to_string(),
+                strong: symbol.is_global() && !symbol.is_weak(),
+            });
+    }
+
+    let text_ranges = object
+        .sections()
+        .filter(|section| section.kind() == SectionKind::Text)
+        .map(|section| section.address()..section.address() + section.size())
+        .collect();
+
+    FunctionSymbols {
+        by_address,
+        text_ranges,
+    }
+}
+
+/// Returns the unqualified name of a function without its template and parameter lists, which
+/// is how DWARF names the function of a (mangled) symbol, e.g. `Foo` for `_ZN2ns3FooC2Ev`
+fn base_name(name: &str) -> String {
+    let demangled = demangle(name);
+    let mut unbracketed = String::new();
+    let mut depth = 0usize;
+
+    for c in demangled.chars() {
+        match c {
+            '<' | '(' => depth += 1,
+            '>' | ')' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => unbracketed.push(c),
+            _ => {}
+        }
+    }
+
+    match unbracketed.rsplit_once("::") {
+        Some((_, base)) => base.to_string(),
+        None => unbracketed,
+    }
+}
+
+/// Groups the functions of a module by their entry address, joins them with the function
+/// `symbols` of the module at the same address and reports every group according to `policy`.
+/// The reported functions list the names of their other aliases.
+pub fn resolve_aliases(
+    functions: Vec<ModuleFunction>,
+    symbols: &FunctionSymbols,
+    policy: AliasPolicy,
+) -> Vec<(String, FunctionInfo)> {
+    let mut groups = BTreeMap::<u64, Vec<ModuleFunction>>::new();
+    let mut res = Vec::new();
+
+    for function in functions {
+        if symbols.is_code(function.low_pc) {
+            groups.entry(function.low_pc).or_default().push(function);
+        } else {
+            res.push((function.file, function.info));
+        }
+    }
+
+    let mut num_alias_groups = 0;
+
+    for (low_pc, mut group) in groups {
+        group.sort_by(|f1, f2| {
+            (&f1.info.name, &f1.file, f1.info.start_line).cmp(&(
+                &f2.info.name,
+                &f2.file,
+                f2.info.start_line,
+            ))
+        });
+
+        let group_symbols = symbols
+            .by_address
+            .get(&low_pc)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        // Symbols of functions the debug info describes are not aliases of their own
+        let debug_info_names = group
+            .iter()
+            .map(|function| base_name(&function.info.name))
+            .collect::<BTreeSet<_>>();
+        let symbol_aliases = group_symbols
+            .iter()
+            .filter(|symbol| !debug_info_names.contains(&base_name(&symbol.name)))
+            .map(|symbol| symbol.name.clone())
+            .collect::<BTreeSet<_>>();
+
+        let names = group
+            .iter()
+            .map(|function| function.info.name.clone())
+            .chain(symbol_aliases.iter().cloned())
+            .collect::<BTreeSet<_>>();
+
+        if names.len() > 1 {
+            num_alias_groups += 1;
+            log::debug!("Aliases at {low_pc:#x}: {}", names.iter().join(", "));
+        }
+
+        let aliases_of = |name: &str| {
+            names
+                .iter()
+                .filter(|alias| *alias != name)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let canonical = group
+            .iter()
+            .position(|function| {
+                group_symbols.iter().any(|symbol| {
+                    symbol.strong && base_name(&symbol.name) == base_name(&function.info.name)
+                })
+            })
+            .unwrap_or_default();
+
+        match policy {
+            AliasPolicy::All => {
+                let canonical = &group[canonical];
+
+                for name in &symbol_aliases {
+                    let mut info = canonical.info.clone();
+                    info.name = name.clone();
+                    info.aliases = aliases_of(name);
+                    res.push((canonical.file.clone(), info));
+                }
+
+                for mut function in group {
+                    function.info.aliases = aliases_of(&function.info.name);
+                    res.push((function.file, function.info));
+                }
+            }
+            AliasPolicy::Canonical => {
+                let mut function = group.swap_remove(canonical);
+                function.info.aliases = aliases_of(&function.info.name);
+                res.push((function.file, function.info));
+            }
+        }
+    }
+
+    if num_alias_groups > 0 {
+        log::info!("Found {num_alias_groups} groups of aliased functions");
+    }
+
+    res
+}
@@ -0,0 +1,224 @@
+use crate::limits::InputLimits;
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::Regex;
+
+    lazy_static! {
+        pub static ref ARCHIVE_FILE_REGEX: Regex = Regex::new(r"\.(tar|tar\.gz|tgz|zip)$").unwrap();
+        pub static ref DRCOV_ENTRY_REGEX: Regex = Regex::new(r"(dr|bb)cov\..*\.?log").unwrap();
+    }
+}
+
+/// Returns whether `path` is an archive whose drcov logs are inputs of their own: a tar archive
+/// (optionally gzip compressed) or a zip archive, as CI systems upload artifacts
+pub fn is_archive(path: &Path) -> bool {
+    path.is_file() && constants::ARCHIVE_FILE_REGEX.is_match(&path.to_string_lossy())
+}
+
+fn is_zip(archive: &Path) -> bool {
+    archive
+        .extension()
+        .is_some_and(|extension| extension == "zip")
+}
+
+/// Whether the archive entry `name` is a drcov log, by its file name
+fn is_drcov_entry(name: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    constants::DRCOV_ENTRY_REGEX.is_match(file_name)
+}
+
+/// Returns the name of an entry at `path` within an archive, without any `.` components (tar
+/// archives often name their entries `./<path>`)
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Opens the tar archive at `path`, decompressing it if needed
+fn open_tar(path: &Path) -> std::io::Result<tar::Archive<Box<dyn Read>>> {
+    let file = File::open(path)?;
+
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|e| e == "tar") {
+        Box::new(file)
+    } else {
+        Box::new(MultiGzDecoder::new(file))
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+/// Returns the input paths of the drcov logs in `archive`: the path of the archive joined with
+/// the name of the entry, e.g. `artifacts.zip/logs/drcov.app.1234.log`
+pub fn list_drcov_entries(archive: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let names = if is_zip(archive) {
+        let zip = zip::ZipArchive::new(File::open(archive)?)?;
+        zip.file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| entry_name(Path::new(name)))
+            .collect::<Vec<_>>()
+    } else {
+        let mut tar = open_tar(archive)?;
+        let mut names = Vec::new();
+
+        for entry in tar.entries()? {
+            let entry = entry?;
+
+            if entry.header().entry_type().is_file() {
+                names.push(entry_name(&entry.path()?));
+            }
+        }
+
+        names
+    };
+
+    let res = names
+        .into_iter()
+        .filter(|name| is_drcov_entry(name))
+        .map(|name| archive.join(name))
+        .collect();
+
+    Ok(res)
+}
+
+/// Splits an input path that names an entry of an archive (see [`list_drcov_entries`]) into the
+/// path of the archive and the name of the entry. Returns `None` for the paths of files.
+pub fn split_entry_path(path: &Path) -> Option<(&Path, String)> {
+    if path.exists() {
+        return None;
+    }
+
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive(ancestor))?;
+    let entry = path.strip_prefix(archive).ok()?;
+
+    Some((archive, entry_name(entry)))
+}
+
+/// Reads the entry `name` of `archive` into memory, up to the maximum input size of `limits`.
+/// Tar archives are read sequentially up to the entry.
+pub fn read_entry(archive: &Path, name: &str, limits: &InputLimits) -> anyhow::Result<Vec<u8>> {
+    if is_zip(archive) {
+        let mut zip = zip::ZipArchive::new(File::open(archive)?)?;
+        let index = (0..zip.len())
+            .find(|index| {
+                zip.name_for_index(*index)
+                    .is_some_and(|entry| entry_name(Path::new(entry)) == name)
+            })
+            .ok_or(anyhow::anyhow!(
+                "Archive '{}' has no entry '{name}'",
+                archive.display()
+            ))?;
+
+        return limits.read_to_end(zip.by_index(index)?);
+    }
+
+    let mut tar = open_tar(archive)?;
+
+    for entry in tar.entries()? {
+        let entry = entry?;
+
+        if entry_name(&entry.path()?) == name {
+            return limits.read_to_end(entry);
+        }
+    }
+
+    anyhow::bail!("Archive '{}' has no entry '{name}'", archive.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::TempPath;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    const LOG: &[u8] = b"DRCOV VERSION: 2\n";
+
+    /// Checks that the only drcov log of `archive` is `logs/drcov.app.1234.log` and reads it back
+    fn check_archive(archive: &Path) {
+        assert!(is_archive(archive));
+
+        let entries = list_drcov_entries(archive).unwrap();
+        assert_eq!(entries, [archive.join("logs/drcov.app.1234.log")]);
+
+        let (entry_archive, name) = split_entry_path(&entries[0]).unwrap();
+        assert_eq!(
+            (entry_archive, name.as_str()),
+            (archive, "logs/drcov.app.1234.log")
+        );
+        assert_eq!(
+            read_entry(archive, &name, &InputLimits::default()).unwrap(),
+            LOG
+        );
+        assert!(read_entry(archive, "logs/drcov.other.log", &InputLimits::default()).is_err());
+    }
+
+    #[test]
+    fn lists_and_reads_the_drcov_logs_of_tar_archives() {
+        let dir = TempPath::dir("archive");
+        let archive = dir.join("artifacts.tar.gz");
+
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        ));
+        // tar names its entries `./<path>` when archiving the current directory
+        for (name, entry_type, contents) in [
+            ("./logs/", tar::EntryType::Directory, &b""[..]),
+            ("./logs/drcov.app.1234.log", tar::EntryType::Regular, LOG),
+            ("./README", tar::EntryType::Regular, b"logs"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(entry_type);
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, contents).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        check_archive(&archive);
+    }
+
+    #[test]
+    fn lists_and_reads_the_drcov_logs_of_zip_archives() {
+        let dir = TempPath::dir("archive");
+        let archive = dir.join("artifacts.zip");
+
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("logs/", options).unwrap();
+        zip.start_file("logs/drcov.app.1234.log", options).unwrap();
+        zip.write_all(LOG).unwrap();
+        zip.start_file("notes.txt", options).unwrap();
+        zip.write_all(b"logs").unwrap();
+        zip.finish().unwrap();
+
+        check_archive(&archive);
+    }
+
+    #[test]
+    fn only_splits_the_paths_of_archive_entries() {
+        let dir = TempPath::dir("archive");
+        let file = dir.join("drcov.app.1234.log");
+        std::fs::write(&file, LOG).unwrap();
+
+        assert_eq!(split_entry_path(&file), None);
+        assert_eq!(split_entry_path(&dir.join("missing/drcov.app.log")), None);
+        assert_eq!(
+            entry_name(Path::new("./logs/./drcov.log")),
+            "logs/drcov.log"
+        );
+    }
+}
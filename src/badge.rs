@@ -0,0 +1,129 @@
+use crate::components::ComponentRollup;
+use crate::read_only;
+use crate::summary::Summary;
+use std::path::Path;
+
+mod constants {
+    pub const OVERALL_BADGE: &str = "coverage";
+    pub const OVERALL_LABEL: &str = "coverage";
+    /// The badge of components whose name has no letters or digits
+    pub const UNNAMED_COMPONENT_BADGE: &str = "component";
+
+    /// The approximate width of a character of the badge font (11px Verdana), in pixels
+    pub const CHAR_WIDTH: usize = 7;
+    /// The horizontal padding of either half of a badge, in pixels
+    pub const PADDING: usize = 10;
+
+    /// The colors of the coverage rates of at least the given percentages, best first
+    pub const COLORS: [(f64, &str); 5] = [
+        (90.0, "#4c1"),
+        (75.0, "#97ca00"),
+        (60.0, "#dfb317"),
+        (40.0, "#fe7d37"),
+        (0.0, "#e05d44"),
+    ];
+    /// The color of the badges of components without any lines
+    pub const UNKNOWN_COLOR: &str = "#9f9f9f";
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a flat, shields.io-style badge with `label` on the left and the line coverage rate of
+/// `summary` on the right
+fn render_badge(label: &str, summary: &Summary) -> String {
+    let (value, color) = if summary.lines_found == 0 {
+        ("unknown".to_string(), constants::UNKNOWN_COLOR)
+    } else {
+        let rate = summary.lines_hit as f64 * 100.0 / summary.lines_found as f64;
+        let color = constants::COLORS
+            .iter()
+            .find(|(min, _)| rate >= *min)
+            .map_or(constants::UNKNOWN_COLOR, |(_, color)| color);
+
+        (format!("{rate:.1}%"), color)
+    };
+
+    let label_width = label.chars().count() * constants::CHAR_WIDTH + constants::PADDING;
+    let value_width = value.chars().count() * constants::CHAR_WIDTH + constants::PADDING;
+    let width = label_width + value_width;
+    let label = escape_xml(label);
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {value}">
+<title>{label}: {value}</title>
+<rect width="{label_width}" height="20" fill="#555"/>
+<rect x="{label_width}" width="{value_width}" height="20" fill="{color}"/>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{}" y="14">{label}</text>
+<text x="{}" y="14">{value}</text>
+</g>
+</svg>
+"##,
+        label_width / 2,
+        label_width + value_width / 2
+    )
+}
+
+/// Returns the file name of the badge of component `name`: its letters and digits, lowercased,
+/// with runs of other characters replaced by dashes
+fn badge_file_name(name: &str) -> String {
+    let slug = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if slug.is_empty() {
+        return format!("{}.svg", constants::UNNAMED_COMPONENT_BADGE);
+    }
+
+    format!("{slug}.svg")
+}
+
+/// Writes the line coverage badge of `summary` to `coverage.svg` in `directory`, along with a
+/// badge for every component of `components`, named after the component
+pub fn write_badges(
+    directory: &Path,
+    summary: &Summary,
+    components: &[ComponentRollup],
+) -> anyhow::Result<()> {
+    read_only::create_dir_all(directory)?;
+
+    read_only::write(
+        directory.join(format!("{}.svg", constants::OVERALL_BADGE)),
+        render_badge(constants::OVERALL_LABEL, summary),
+    )?;
+
+    let mut num_badges = 1;
+
+    for component in components {
+        let file_name = badge_file_name(&component.name);
+
+        if file_name == format!("{}.svg", constants::OVERALL_BADGE) {
+            log::warn!(
+                "Not writing the badge of component {}, whose name clashes with the overall badge",
+                component.name
+            );
+            continue;
+        }
+
+        read_only::write(
+            directory.join(file_name),
+            render_badge(&component.name, &component.summary),
+        )?;
+        num_badges += 1;
+    }
+
+    log::info!(
+        "Wrote {num_badges} coverage badges to '{}'",
+        directory.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,418 @@
+#[cfg(feature = "native")]
+use crate::debuginfod;
+use crate::read_only;
+#[cfg(feature = "native")]
+use itertools::Itertools;
+use std::fs::FileTimes;
+#[cfg(feature = "native")]
+use std::fs::OpenOptions;
+#[cfg(feature = "native")]
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+#[cfg(feature = "native")]
+mod constants {
+    /// The file listing the files this tool created in an entry of a cache it shares with other
+    /// tools, one name per line
+    pub const OWNED_FILES: &str = ".drcov2lcov-owned";
+}
+
+/// A file that is written under a temporary name and only appears at its final path once it has
+/// been completely written, so that interrupted writes (e.g. downloads) never poison a cache.
+/// The temporary file is removed if the `AtomicFile` is dropped without being persisted.
+#[derive(Debug)]
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    persisted: bool,
+}
+
+impl AtomicFile {
+    pub fn new(path: PathBuf) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let temp_path = path.with_file_name(format!(
+            ".{file_name}.{}.{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        Self {
+            path,
+            temp_path,
+            persisted: false,
+        }
+    }
+
+    /// The path the contents have to be written to
+    #[cfg(feature = "native")]
+    pub fn temp_path(&self) -> &Path {
+        &self.temp_path
+    }
+
+    /// Returns whether `temp_path` is the temporary path of an `AtomicFile` at `path`
+    pub fn is_temp_path_of(temp_path: &Path, path: &Path) -> bool {
+        let (Some(temp_name), Some(name)) = (temp_path.file_name(), path.file_name()) else {
+            return false;
+        };
+
+        temp_path.parent() == path.parent()
+            && temp_name
+                .to_string_lossy()
+                .strip_prefix(&format!(".{}.", name.to_string_lossy()))
+                .is_some_and(|suffix| suffix.ends_with(".tmp"))
+    }
+
+    /// Creates the file at the temporary path, if the read-only policy allows writing to the final
+    /// one
+    pub fn create(&self) -> std::io::Result<std::fs::File> {
+        read_only::check_write(&self.path)?;
+        std::fs::File::create(&self.temp_path)
+    }
+
+    /// Moves the completely written file to its final path
+    pub fn persist(mut self) -> std::io::Result<PathBuf> {
+        read_only::check_write(&self.path)?;
+        std::fs::rename(&self.temp_path, &self.path)?;
+        self.persisted = true;
+
+        Ok(self.path.clone())
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Marks the cache entry containing `path` as recently used. The modification time is used
+/// instead of the access time since file systems are commonly mounted with `noatime`.
+pub fn touch(path: &Path) {
+    let now = SystemTime::now();
+
+    for path in [Some(path), path.parent()].into_iter().flatten() {
+        let _ = std::fs::File::open(path)
+            .and_then(|file| file.set_times(FileTimes::new().set_modified(now)));
+    }
+}
+
+/// Records that this tool created the file at `path`, in the entry of a cache shared with other
+/// tools it belongs to, so that evicting the entry only removes the files this tool created
+#[cfg(feature = "native")]
+pub fn mark_owned(path: &Path) -> std::io::Result<()> {
+    let (Some(directory), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(());
+    };
+
+    let marker = directory.join(constants::OWNED_FILES);
+    read_only::check_write(&marker)?;
+
+    let mut file = OpenOptions::new().create(true).append(true).open(marker)?;
+    writeln!(file, "{}", name.to_string_lossy())
+}
+
+/// Returns the files this tool created in the entry at `directory` of a shared cache, or `None` if
+/// it did not create any
+#[cfg(feature = "native")]
+fn owned_files(directory: &Path) -> Option<Vec<PathBuf>> {
+    let marker = std::fs::read_to_string(directory.join(constants::OWNED_FILES)).ok()?;
+
+    let res = marker
+        .lines()
+        // Only file names of the entry itself are ever recorded
+        .filter(|name| {
+            Path::new(name)
+                .file_name()
+                .is_some_and(|file_name| file_name == *name)
+        })
+        .unique()
+        .map(|name| directory.join(name))
+        .collect();
+
+    Some(res)
+}
+
+/// A cache entry; the top level directories of a cache (e.g. the build-ids of the debuginfod cache)
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub last_used: SystemTime,
+    /// The files of the entry this tool created, if the cache is shared with other tools
+    owned_files: Option<Vec<PathBuf>>,
+}
+
+#[cfg(feature = "native")]
+impl CacheEntry {
+    /// Removes the entry, or only the files this tool created in it if the cache is shared
+    fn remove(&self) -> std::io::Result<()> {
+        let Some(owned_files) = &self.owned_files else {
+            return std::fs::remove_dir_all(&self.path);
+        };
+
+        for file in owned_files {
+            match std::fs::remove_file(file) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        std::fs::remove_file(self.path.join(constants::OWNED_FILES))?;
+
+        // The directory is kept if the other tools have files in it
+        let _ = std::fs::remove_dir(&self.path);
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "native")]
+fn directory_size(path: &Path) -> u64 {
+    let Ok(read_dir) = path.read_dir() else {
+        return 0;
+    };
+
+    read_dir
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => directory_size(&entry.path()),
+            Ok(file_type) if file_type.is_file() => {
+                entry.metadata().map(|meta| meta.len()).unwrap_or_default()
+            }
+            _ => 0,
+        })
+        .sum()
+}
+
+/// A size bounded on-disk cache that evicts its least recently used entries
+#[cfg(feature = "native")]
+#[derive(Debug)]
+pub struct Cache {
+    pub name: &'static str,
+    pub root: PathBuf,
+    /// Whether other tools keep entries in the cache too (e.g. elfutils' debuginfod cache), in
+    /// which case only the files this tool created (see [`mark_owned`]) are evicted
+    pub shared: bool,
+}
+
+#[cfg(feature = "native")]
+impl Cache {
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let Ok(read_dir) = self.root.read_dir() else {
+            return Vec::new();
+        };
+
+        read_dir
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+            .filter_map(|entry| {
+                let path = entry.path();
+                let last_used = entry
+                    .metadata()
+                    .and_then(|meta| meta.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+
+                let (size, owned_files) = if self.shared {
+                    let owned_files = owned_files(&path)?;
+                    let size = owned_files
+                        .iter()
+                        .filter_map(|file| file.metadata().ok())
+                        .map(|meta| meta.len())
+                        .sum();
+                    (size, Some(owned_files))
+                } else {
+                    (directory_size(&path), None)
+                };
+
+                Some(CacheEntry {
+                    path,
+                    size,
+                    last_used,
+                    owned_files,
+                })
+            })
+            .sorted_by_key(|entry| entry.last_used)
+            .collect()
+    }
+
+    /// Evicts the least recently used entries until the cache takes up at most `max_size` bytes.
+    /// Returns the number of evicted entries and the bytes freed.
+    pub fn evict_to(&self, max_size: u64) -> (usize, u64) {
+        let entries = self.entries();
+        let mut size = entries.iter().map(|entry| entry.size).sum::<u64>();
+        let mut evicted = (0, 0);
+
+        for entry in entries {
+            if size <= max_size {
+                break;
+            }
+
+            match entry.remove() {
+                Ok(()) => {
+                    size -= entry.size;
+                    evicted.0 += 1;
+                    evicted.1 += entry.size;
+                }
+                Err(e) => log::warn!("Could not evict '{}': {e}", entry.path.display()),
+            }
+        }
+
+        evicted
+    }
+
+    /// Removes every entry of the cache
+    pub fn clear(&self) -> (usize, u64) {
+        self.evict_to(0)
+    }
+}
+
+/// Returns every cache the tool maintains
+#[cfg(feature = "native")]
+pub fn caches() -> Vec<Cache> {
+    let debuginfod = debuginfod::cache_dir().map(|root| Cache {
+        name: "debuginfod",
+        root,
+        shared: true,
+    });
+
+    debuginfod.into_iter().collect()
+}
+
+/// Parses sizes like `512K`, `100M`, `2GiB` or plain byte counts
+#[cfg(feature = "native")]
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits_end);
+
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid size '{s}'"))?;
+
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("Invalid size unit in '{s}'")),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("Size '{s}' is too large"))
+}
+
+/// Formats a byte count for humans
+#[cfg(feature = "native")]
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Evicts the least recently used entries of every one of `caches` until each takes up at most
+/// `max_size` bytes
+#[cfg(feature = "native")]
+pub fn enforce_max_size(caches: Vec<Cache>, max_size: u64) {
+    for cache in caches {
+        let (evicted, freed) = cache.evict_to(max_size);
+
+        if evicted > 0 {
+            log::info!(
+                "Evicted {evicted} entries ({}) from the {} cache",
+                format_size(freed),
+                cache.name
+            );
+        }
+    }
+}
+
+/// Prints the location, size and number of entries of every cache
+#[cfg(feature = "native")]
+pub fn info() {
+    for cache in caches() {
+        let entries = cache.entries();
+
+        println!(
+            "{} cache: '{}', {} entries, {}",
+            cache.name,
+            cache.root.display(),
+            entries.len(),
+            format_size(entries.iter().map(|entry| entry.size).sum())
+        );
+    }
+}
+
+/// Removes every entry of every cache, or only enough of them to honor `max_size`
+#[cfg(feature = "native")]
+pub fn prune(max_size: Option<u64>) {
+    for cache in caches() {
+        let (evicted, freed) = match max_size {
+            Some(max_size) => cache.evict_to(max_size),
+            None => cache.clear(),
+        };
+
+        println!(
+            "{} cache: removed {evicted} entries ({})",
+            cache.name,
+            format_size(freed)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::TempPath;
+
+    #[test]
+    fn only_evicts_its_own_files_from_shared_caches() {
+        let root = TempPath::dir("cache");
+        let ours = root.join("0123");
+        let theirs = root.join("4567");
+        for entry in [&ours, &theirs] {
+            std::fs::create_dir_all(entry).unwrap();
+            std::fs::write(entry.join("debuginfo"), "debug information").unwrap();
+        }
+        // Another client downloaded the executable next to the debug information this tool did
+        std::fs::write(ours.join("executable"), "executable").unwrap();
+        mark_owned(&ours.join("debuginfo")).unwrap();
+
+        let cache = Cache {
+            name: "debuginfod",
+            root: root.path().to_path_buf(),
+            shared: true,
+        };
+        let [entry] = cache.entries().try_into().unwrap();
+        assert_eq!(entry.path, ours);
+        assert_eq!(entry.size, "debug information".len() as u64);
+
+        assert_eq!(cache.clear(), (1, "debug information".len() as u64));
+        assert!(!ours.join("debuginfo").exists());
+        assert!(ours.join("executable").is_file());
+        assert!(theirs.join("debuginfo").is_file());
+        assert!(cache.entries().is_empty());
+    }
+}
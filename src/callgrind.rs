@@ -0,0 +1,49 @@
+use crate::dwarf::SourceCoverage;
+use crate::output::{write_text_output, CoverageReport, CoverageWriter, LineEnding};
+use itertools::Itertools;
+use std::fmt::Write;
+
+/// Writes the per-function line coverage in the callgrind format so that it can be browsed with
+/// KCachegrind. Every executed line gets a cost of 1 for the `Executed` event.
+pub fn write_callgrind_output(
+    path: &str,
+    coverage: &SourceCoverage,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    let mut res = String::new();
+
+    let _ = writeln!(res, "# callgrind format");
+    let _ = writeln!(res, "version: 1");
+    let _ = writeln!(res, "creator: drcov2lcov {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(res, "positions: line");
+    let _ = writeln!(res, "events: Executed");
+
+    for file in coverage.functions.keys().sorted() {
+        let _ = writeln!(res);
+        let _ = writeln!(res, "fl={file}");
+
+        for function in &coverage.functions[file] {
+            let _ = writeln!(res, "fn={}", function.name);
+
+            for info in coverage.function_lines(file, function) {
+                let _ = writeln!(res, "{} {}", info.line, if info.executed { 1 } else { 0 });
+            }
+        }
+    }
+
+    write_text_output(path, &res, line_ending)?;
+
+    Ok(())
+}
+
+pub struct CallgrindWriter;
+
+impl CoverageWriter for CallgrindWriter {
+    fn name(&self) -> &'static str {
+        "Callgrind"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_callgrind_output(path, report.coverage, report.line_ending)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::dwarf::SourceCoverage;
+use crate::util::assert_send_sync;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use std::collections::HashMap;
+
+/// MD5 checksums of the source lines referenced by a coverage report, in the unpadded base64 form
+/// lcov expects as the optional third field of `DA` records.
+#[derive(Debug, Default)]
+pub struct LineChecksums {
+    /// Source file -> checksum of each of its lines (index 0 is line 1)
+    files: HashMap<String, Vec<String>>,
+}
+
+fn checksum_lines(contents: &[u8]) -> Vec<String> {
+    contents
+        .split(|b| *b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .map(|line| STANDARD_NO_PAD.encode(md5::compute(line).0))
+        .collect()
+}
+
+const _: () = assert_send_sync::<LineChecksums>();
+
+impl LineChecksums {
+    /// Reads every source file of `coverage` that is readable and checksums its lines
+    pub fn from_coverage(coverage: &SourceCoverage) -> Self {
+        let mut files = HashMap::new();
+
+        for file in coverage.lines.keys() {
+            match std::fs::read(file) {
+                Ok(contents) => {
+                    files.insert(file.clone(), checksum_lines(&contents));
+                }
+                Err(e) => log::warn!("Could not read '{file}' to checksum its lines: {e}"),
+            }
+        }
+
+        Self { files }
+    }
+
+    pub fn get(&self, file: &str, line: u64) -> Option<&str> {
+        let index = usize::try_from(line).ok()?.checked_sub(1)?;
+
+        self.files
+            .get(file)
+            .and_then(|lines| lines.get(index))
+            .map(String::as_str)
+    }
+
+    /// Renames the source files the checksums belong to (e.g. after scrubbing the report's paths)
+    pub fn map_paths(self, f: impl Fn(&str) -> String) -> Self {
+        let files = self
+            .files
+            .into_iter()
+            .map(|(file, lines)| (f(&file), lines))
+            .collect();
+
+        Self { files }
+    }
+}
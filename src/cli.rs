@@ -1,10 +1,38 @@
-use crate::drcov::DrcovFilters;
-use crate::dwarf::LineInfoFilters;
-use clap::Parser;
-use regex::bytes::Regex;
+use crate::aliases::AliasPolicy;
+use crate::archive;
+use crate::cache::{self, parse_size, Cache};
+use crate::callgrind::CallgrindWriter;
+use crate::compat::{CompatDefaults, LATEST_COMPAT_LEVEL};
+use crate::components::ComponentMap;
+use crate::cross_check::CrossCheckTool;
+use crate::debuginfod::{self, DebuginfodClient};
+use crate::deps::module_filters_for_binary;
+use crate::drcov::{BbOffsetBase, DrcovFilters, IterationMode, Modules};
+use crate::dwarf::{LineHitSemantics, LineInfoFilters};
+pub use crate::filter::{Filter, ReplacementFilter};
+use crate::input::{default_line_hit_semantics, is_stdin};
+use crate::istanbul::IstanbulWriter;
+use crate::lighthouse::LighthouseWriter;
+use crate::limits::InputLimits;
+use crate::notify::{Notifier, PayloadTemplate};
+use crate::object_cache::{LineTableCache, ObjectFileCache};
+use crate::opencover::OpenCoverWriter;
+use crate::output::{CoverageWriter, LineEnding, OutputFormat};
+use crate::output_template::OutputTemplate;
+use crate::progress::Progress;
+use crate::read_only::ReadOnlyPolicy;
+use crate::report::ReportFormat;
+use crate::scrub::Scrubber;
+use crate::source_path::SourcePathResolver;
+use crate::sqlite::SqliteWriter;
+use crate::state::BinaryResolver;
+use clap::{Parser, Subcommand};
+use itertools::Itertools;
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 mod constants {
     use lazy_static::lazy_static;
@@ -12,69 +40,230 @@ mod constants {
 
     pub const DEFAULT_OUTPUT_FILE: &str = "coverage.info";
 
+    /// DynamoRIO's own runtime (core, preloader) and the client and extension libraries loaded
+    /// into every traced process, e.g. `libdynamorio.so`, `libdrpreload.so`, `drcov.dll`
+    pub const DYNAMORIO_MODULES_FILTER: &str = r"(^|[/\\])(lib)?(dynamorio|drpreload|drinjectlib|drconfiglib|drcov|drcovlib|drmgr|drx|drwrap|drreg|drsyms|drutil|drstatecmp|drbbdup|drcontainers)(_static)?\.(so|dll|dylib)(\.[\d.]+)?$";
+
     lazy_static! {
-        pub static ref DRCOV_LOG_FILE_REGEX: Regex = Regex::new(r"(dr|bb)cov\..*\.?log").unwrap();
+        pub static ref DRCOV_LOG_FILE_REGEX: Regex =
+            Regex::new(r"(dr|bb)cov\..*\.?log|\.sancov$|\.prof(raw|data)$|\.gcno$|qemu.*\.log$|perf.*\.txt$|tenet.*\.log$|\.modoff$|bblocks.*\.out$|\.kcov$|cobertura\.xml$").unwrap();
     }
 }
 
-fn default_output_file() -> String {
-    let mut path = std::env::current_dir().unwrap();
-    path.push(constants::DEFAULT_OUTPUT_FILE);
-    path.to_string_lossy().to_string()
+/// Returns whether the input `path` is a glob pattern, rather than the path of an existing file.
+/// Shells on Windows do not expand patterns, so the tool does it itself.
+fn is_input_pattern(path: &str) -> bool {
+    !Path::new(path).exists() && path.contains(['*', '?', '['])
 }
 
-#[derive(Debug, Clone)]
-pub struct Filter {
-    pub matcher: Regex,
+/// Returns the files matching the input glob `pattern`
+fn expand_input_pattern(pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern)
+        .map_err(|e| anyhow::anyhow!("Invalid input pattern '{pattern}': {e}"))?
+        .flatten()
+        .filter(|path| path.is_file())
+        .collect();
+
+    Ok(paths)
 }
 
-impl FromStr for Filter {
-    type Err = String;
+/// Adds the input file at `path` to `files`, or the drcov logs it contains if it is an archive
+fn insert_input_file(path: PathBuf, files: &mut HashSet<PathBuf>) {
+    let path = path.canonicalize().unwrap_or(path);
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let filter = Regex::new(s)
-            .map_err(|_| format!("Could not create a regular expression from '{s}'"))?;
+    if !archive::is_archive(&path) {
+        files.insert(path);
+        return;
+    }
 
-        Ok(Self { matcher: filter })
+    match archive::list_drcov_entries(&path) {
+        Ok(entries) => files.extend(entries),
+        Err(e) => log::warn!("Could not read archive '{}': {e}", path.display()),
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct ReplacementFilter {
-    pub matcher: Regex,
-    pub replacement: String,
-}
+/// Adds the input files of `directory` (and of its subdirectories, if `recursive`) to `files`.
+/// Symbolic links to directories are not followed, so that links cannot form cycles.
+fn collect_directory_inputs(directory: &Path, recursive: bool, files: &mut HashSet<PathBuf>) {
+    let Ok(read_dir) = directory.read_dir() else {
+        return;
+    };
 
-impl FromStr for ReplacementFilter {
-    type Err = String;
+    for entry in read_dir.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let pos = s
-            .find(':')
-            .ok_or_else(|| format!("Invalid path_map argument: no '=' found in '{s}'"))?;
+        if recursive && file_type.is_dir() {
+            collect_directory_inputs(&entry.path(), recursive, files);
+        } else if file_type.is_file()
+            && constants::DRCOV_LOG_FILE_REGEX.is_match(&entry.file_name().to_string_lossy())
+        {
+            let path = entry.path();
+            let path = path.canonicalize().unwrap_or(path);
+            files.insert(path);
+        }
+    }
+}
 
-        let matcher = Regex::new(&s[..pos])
-            .map_err(|_| format!("Could not create a regular expression from '{}'", &s[..pos]))?;
+fn default_output_file() -> String {
+    let mut path = std::env::current_dir().unwrap();
+    path.push(constants::DEFAULT_OUTPUT_FILE);
+    path.to_string_lossy().to_string()
+}
 
-        let res = Self {
-            matcher,
-            replacement: s[pos + 1..].to_string(),
-        };
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    #[clap(about = "Show the location, number of entries and size of every cache")]
+    Info,
+    #[clap(about = "Remove the entries of every cache")]
+    Clear {
+        #[clap(
+            long,
+            value_parser = parse_size,
+            help = "Only evict the least recently used entries until each cache takes up at most the given size (e.g. 500M, 2G)"
+        )]
+        max_size: Option<u64>,
+    },
+}
 
-        Ok(res)
-    }
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    #[clap(
+        about = "Build a tiny demo target, synthesize a drcov log for it and run the whole pipeline on it"
+    )]
+    Demo {
+        #[clap(
+            short,
+            long,
+            help = "Directory in which the demo project will be created. Default: <temp_dir>/drcov2lcov-demo"
+        )]
+        directory: Option<String>,
+        #[clap(long, help = "Do not try to open the generated HTML report")]
+        no_open: bool,
+    },
+    #[clap(about = "Inspect or clear the on-disk caches (e.g. the downloaded debug information)")]
+    Cache {
+        #[clap(subcommand)]
+        command: CacheCommand,
+    },
+    #[clap(
+        about = "Render reports from a coverage state saved with --state-output, without reading any input files"
+    )]
+    Report {
+        #[clap(long, help = "The coverage state file to render")]
+        load_state: String,
+        #[clap(
+            long = "format",
+            value_delimiter = ',',
+            default_value = "lcov",
+            help = "Comma separated formats to render: html (through genhtml) or any output format (lcov, json, cobertura, ...)"
+        )]
+        formats: Vec<ReportFormat>,
+        #[clap(
+            long,
+            default_value = ".",
+            help = "Directory the reports are written to (coverage.info, cobertura.xml, html/, ...)"
+        )]
+        output_dir: String,
+        #[clap(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Options applied while rendering, as given to a conversion (e.g. --source-filters, --relative-to, --binary-dir)"
+        )]
+        options: Vec<String>,
+    },
+    #[clap(
+        about = "Check that a drcov log written by your own drcov producer (e.g. a Frida script or an emulator) conforms to the format DynamoRIO writes: its header, module table and basic block table. Prints every violation of the format found and fails if any of them is an error"
+    )]
+    CheckProducer {
+        #[clap(short, long, help = "The drcov log to check")]
+        input: String,
+    },
+    #[clap(
+        about = "Convert the drcov logs uploaded over HTTP (POST /convert?format=lcov|json), keeping the object files and debug information of their modules loaded between requests"
+    )]
+    Serve {
+        #[clap(
+            long,
+            default_value = "127.0.0.1:8080",
+            help = "The address to listen on"
+        )]
+        listen: String,
+        #[clap(
+            long,
+            default_value_t = 4,
+            value_parser = clap::value_parser!(u16).range(1..),
+            help = "The number of requests that are converted concurrently"
+        )]
+        workers: u16,
+        #[clap(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            help = "Options applied to every conversion, as given to a conversion (e.g. --module-filters, --source-filters, --path-map, --max-input-size)"
+        )]
+        options: Vec<String>,
+    },
 }
 
 #[derive(Debug, Parser)]
+#[clap(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub struct CliOptions {
-    #[clap(short, long, required_unless_present_any(["directory", "list"]), help = "The path to the input file")]
-    pub input: Option<String>,
-    #[clap(short, long, required_unless_present_any(["input", "list"]), help = "Directory with drcov.*.log files to process")]
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+    #[clap(short, long, required_unless_present_any(["directory", "list", "job"]), help = "The path to the input file (a drcov log, a QEMU or Tenet trace, a module+offset trace, Pin basic block output, perf script output, a KCOV trace, a .sancov, .profraw, .profdata, .gcno, .gcda, kcov cobertura.xml, lcov tracefile or .covstate file). Use - to read a drcov log from the standard input, or a glob pattern (e.g. 'logs/drcov.app.*.log') to process every matching file. The drcov logs of .tar, .tar.gz, .tgz and .zip archives are processed without extracting them. You can provide this option multiple times, and along with --directory and --list")]
+    pub input: Vec<String>,
+    #[clap(short, long, required_unless_present_any(["input", "list", "job"]), help = "Directory with drcov.*.log (or qemu*.log, tenet*.log, *.modoff, bblocks*.out, perf*.txt, *.sancov, *.profraw, *.profdata, *.gcno, *.kcov and cobertura.xml) files to process")]
     pub directory: Option<String>,
-    #[clap(short, long, required_unless_present_any(["input", "directory"]), help = "Text file listing log files to process")]
+    #[clap(
+        long,
+        requires = "directory",
+        help = "Also process the files of the subdirectories of --directory, e.g. the per-worker directories of a fuzzing campaign"
+    )]
+    pub recursive: bool,
+    #[clap(short, long, required_unless_present_any(["input", "directory", "job"]), help = "Text file listing log files to process")]
     pub list: Option<String>,
-    #[clap(short, long, default_value_t = default_output_file(), help = "The path to the output file")]
+    #[clap(
+        long,
+        conflicts_with_all(["input", "directory", "list"]),
+        help = "Run the conversions described by a YAML job instead: a list of targets, each a mapping of the options of one conversion (named like the command line options, without their dashes), plus optional defaults for every target. The targets share the thread pool"
+    )]
+    pub job: Option<String>,
+    #[clap(short, long, default_value_t = default_output_file(), help = "The path to the output file. Default: coverage.info in the current directory")]
     pub output: String,
+    #[clap(
+        long,
+        conflicts_with = "output",
+        help = "Name the output file after the inputs instead, e.g. '{target}.{date}.info': {target} is the file name of the first module of the first input and {date} the modification time of the first input (YYYYMMDDTHHMMSSZ, UTC), so that conversions of different targets in the same directory do not overwrite each other"
+    )]
+    pub output_template: Option<OutputTemplate>,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Lcov,
+        help = "The format the output file is written in"
+    )]
+    pub output_format: OutputFormat,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = LineEnding::Lf,
+        help = "The line endings of the text outputs (lcov, JSON, XML, manifests, ...), which are always UTF-8 without a byte order mark. Some Windows based report ingestion tools require crlf"
+    )]
+    pub output_eol: LineEnding,
+    #[clap(
+        long,
+        default_value_t = LATEST_COMPAT_LEVEL,
+        value_parser = clap::value_parser!(u8).range(1..=LATEST_COMPAT_LEVEL as i64),
+        help = "Pin the defaults that change what the outputs contain to the ones of the given compatibility level, so that scripts keep getting the same outputs from newer versions: 1 processes byte-identical inputs, the dynamic loader and DynamoRIO's own modules and requires drcov headers with the version line followed by the flavor line; 2 skips them and accepts the headers in either order or without a flavor line; 3 takes the basic block offsets of frida logs relative to their modules. Options given explicitly still apply. Default: the latest level"
+    )]
+    pub compat_level: u8,
+    #[clap(
+        long,
+        visible_alias = "merge-output",
+        help = "If the output file already exists, union its line records with the new coverage instead of overwriting it (lcov output format only)"
+    )]
+    pub append: bool,
     #[clap(
         long,
         value_parser = clap::value_parser!(Filter),
@@ -87,6 +276,150 @@ pub struct CliOptions {
         help = "Skip coverage for the modules that match the given regular expressions"
     )]
     pub module_skip_filters: Vec<Filter>,
+    #[clap(
+        long,
+        help = "Keep the coverage of DynamoRIO's own modules (libdynamorio, libdrpreload, the drcov client and the DynamoRIO extensions), which is skipped by default"
+    )]
+    pub keep_dynamorio_modules: bool,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = IterationMode::Merge,
+        help = "How logs of persistent-mode harnesses, with a basic block table per iteration, are reported: their iterations are merged (merge), or the coverage of every iteration is also written as its own test (TN) section of the lcov output (per-iteration). Every iteration is symbolized on its own with per-iteration"
+    )]
+    pub iteration_mode: IterationMode,
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Only use the first N iterations of logs with a basic block table per iteration"
+    )]
+    pub max_iterations: Option<u64>,
+    #[clap(
+        long,
+        help = "Reject drcov logs whose header does not have the version line followed by the flavor line (the legacy bbcov layout without a flavor line is still accepted). By default the header lines may come in either order and the flavor defaults to drcov"
+    )]
+    pub strict_format: bool,
+    #[clap(
+        long,
+        value_enum,
+        help = "What the start offsets of the basic blocks of drcov logs are relative to: the segment they are recorded against (segment, like DynamoRIO's drcov), the module that contains the segment (module, like frida-drcov), or chosen by the flavor of the log (auto). Default: auto"
+    )]
+    pub bb_offset_base: Option<BbOffsetBase>,
+    #[clap(
+        long,
+        help = "Reject inputs larger than the given number of bytes, before or after decompression"
+    )]
+    pub max_input_size: Option<u64>,
+    #[clap(
+        long,
+        help = "Reject inputs with more than the given number of modules (after applying the module filters)"
+    )]
+    pub max_modules: Option<usize>,
+    #[clap(
+        long,
+        help = "Reject inputs with more than the given number of basic blocks, over all of their modules. The entries of drcov logs are counted as their basic block table headers declare them, before the tables are parsed"
+    )]
+    pub max_basic_blocks: Option<usize>,
+    #[clap(
+        long,
+        value_name = "SECS",
+        help = "Reject inputs whose symbolization takes longer than the given number of seconds"
+    )]
+    pub input_timeout: Option<u64>,
+    #[clap(
+        long,
+        help = "Only include coverage for the given binary and its direct dynamic dependencies (resolved like the dynamic loader does)"
+    )]
+    pub modules_from_binary: Option<String>,
+    #[clap(
+        long = "module-table",
+        help = "Module table applied to inputs that do not carry one (e.g. traces of lightweight tracers that only emit the basic block table). Either a drcov module table or a /proc/<pid>/maps listing, whose file backed mappings are the entries of the table in order"
+    )]
+    pub module_table_path: Option<String>,
+    #[clap(skip)]
+    pub module_table: Option<Modules>,
+    #[clap(
+        long = "component-map",
+        help = "YAML file mapping source path patterns to named components (e.g. the code owned by a team), whose coverage is rolled up in a section of the summary, the JSON output and the HTML report"
+    )]
+    pub component_map_path: Option<String>,
+    #[clap(skip)]
+    pub component_map: Option<ComponentMap>,
+    #[clap(
+        long,
+        help = "Write an SVG badge with the line coverage rate to coverage.svg in the given directory, plus a badge for every component of the component map (named after the component)"
+    )]
+    pub badge_dir: Option<String>,
+    #[clap(
+        long,
+        help = "Write the coverage totals (overall, per module and per component) to the given file in the OpenMetrics text format, e.g. a .prom file in the directory of the textfile collector of the Prometheus node exporter"
+    )]
+    pub metrics_output: Option<String>,
+    #[clap(
+        long,
+        help = "Gather line coverage for the dynamic loader (ld.so) too, which is skipped by default like the other pseudo-modules (e.g. [vdso])"
+    )]
+    pub include_loader: bool,
+    #[clap(
+        long,
+        value_enum,
+        help = "When a line counts as executed: if the first byte of its code (first-byte), any byte of it (any-byte, like llvm-cov) or all of its bytes (all-bytes) have been executed. Default: first-byte, any-byte for .sancov inputs, QEMU, Tenet and module+offset traces, Pin basic block output and perf script output"
+    )]
+    pub line_hit_semantics: Option<LineHitSemantics>,
+    #[clap(
+        long,
+        help = "Emit the blocks of lines that have been compiled into several ones (told apart by their DWARF discriminators, e.g. the arms of a ? b : c) as pseudo-branch records (BRDA) of the lcov output, with the discriminator as the branch number"
+    )]
+    pub discriminator_branches: bool,
+    #[clap(
+        long,
+        help = "Cross-check the function boundaries of the debug info of every module against its symbol table and warn about modules where they diverge, which usually means that the debug info belongs to a different build"
+    )]
+    pub consistency_checks: bool,
+    #[clap(
+        long,
+        value_enum,
+        help = "Compare the source lines of a sample of the covered addresses of every module with the ones the given tool reports for them (which must be on the PATH) and report the agreement rate"
+    )]
+    pub cross_check: Option<CrossCheckTool>,
+    #[clap(
+        long,
+        value_name = "SECS",
+        help = "Abandon gathering the debug information of a module after the given number of seconds and continue with the rest of the modules. Abandoned modules contribute no coverage and are listed at the end of the run"
+    )]
+    pub module_timeout: Option<u64>,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = AliasPolicy::All,
+        help = "How the coverage of functions that share their code (e.g. weak aliases and ifunc implementations, found through the symbol table) is reported: every alias as a function of its own (all), or only one canonical alias per address (canonical). The JSON output lists the aliases of every function"
+    )]
+    pub alias_policy: AliasPolicy,
+    #[clap(
+        long = "sancov-binary",
+        help = "The binary a .sancov input was dumped for, in case it does not reside next to the .sancov file. You can provide this option multiple times; binaries are matched to .sancov files (<module name>.<pid>.sancov) by name"
+    )]
+    pub sancov_binaries: Vec<String>,
+    #[clap(
+        long = "qemu-binary",
+        help = "A binary (or kernel image) run under QEMU whose code is at its link-time addresses (non-PIE executables, vmlinux), used to map the guest addresses of QEMU traces to modules. Modules loaded elsewhere have to be described with --module-table. You can provide this option multiple times"
+    )]
+    pub qemu_binaries: Vec<String>,
+    #[clap(
+        long = "kcov-binary",
+        help = "A kernel image (vmlinux) whose code is at its link-time addresses, used to map the addresses of KCOV traces to modules. Kernel modules and KASLR relocated kernels have to be described with --module-table. You can provide this option multiple times"
+    )]
+    pub kcov_binaries: Vec<String>,
+    #[clap(
+        long = "binary-dir",
+        help = "Directory searched (recursively) for the binaries of the modules of .covstate and .profraw inputs, which are matched by build-id. You can provide this option multiple times"
+    )]
+    pub binary_dirs: Vec<String>,
+    #[clap(
+        long = "llvm-profile-binary",
+        help = "The binary .profraw/.profdata inputs have been collected from. You can provide this option multiple times; raw profiles are matched to binaries by build-id"
+    )]
+    pub llvm_profile_binaries: Vec<String>,
     #[clap(
         long,
         value_parser = clap::value_parser!(Filter),
@@ -112,19 +445,270 @@ pub struct CliOptions {
         help = "Reduce the set of drov files from the input to a smaller set of drcov files containing the same coverage information and store the input files into the given path"
     )]
     pub reduce_set_path: Option<String>,
+    #[clap(
+        long,
+        requires = "reduce_set_path",
+        help = "Also write the size and SHA-256 hash of every file of the reduced set to the given path, as a manifest that can be checked with --verify-manifest"
+    )]
+    pub reduce_set_manifest: Option<String>,
+    #[clap(
+        long,
+        help = "Also write the executed basic blocks in the module+offset format consumed by Lighthouse (IDA/Binary Ninja) to the given path"
+    )]
+    pub lighthouse_output: Option<String>,
+    #[clap(
+        long,
+        help = "Write the graph of the traced modules and of the libraries they depend on (DT_NEEDED) to the given path, with every module sized by its executed basic blocks, to see which parts of the dependency tree have been exercised. Written as JSON if the path ends with .json, in the DOT format of Graphviz otherwise"
+    )]
+    pub module_graph: Option<String>,
+    #[clap(
+        long,
+        help = "Disassemble the modules with objdump, recover their basic blocks and write how many of the reachable ones have been covered, along with the list of the ones that never have been, to the given path. A sharper metric than line coverage for binaries without (complete) debug information. The lines whose code spans several basic blocks also get a branch record (BRDA) per block instead of per discriminator, and the executed ones with missed blocks are marked as partial in the JSON output. Supports x86 and AArch64 modules"
+    )]
+    pub reachable_oracle: Option<String>,
+    #[clap(
+        long,
+        help = "Also write the per-function line coverage in the callgrind format (for KCachegrind) to the given path"
+    )]
+    pub callgrind_output: Option<String>,
+    #[clap(
+        long,
+        help = "Also write the coverage as an OpenCover XML report (for ReportGenerator) to the given path"
+    )]
+    pub opencover_output: Option<String>,
+    #[clap(
+        long,
+        help = "Also write the coverage as an Istanbul/NYC coverage-final.json document to the given path"
+    )]
+    pub istanbul_output: Option<String>,
+    #[clap(
+        long,
+        help = "Also append the coverage as a new run to the SQLite database at the given path (created if it does not exist)"
+    )]
+    pub sqlite_output: Option<String>,
+    #[clap(
+        long,
+        help = "Also write the executed code of every module, keyed by build-id, to a coverage state file at the given path (e.g. coverage.covstate). State files can be given as inputs in another environment and are symbolized there, with --binary-dir locating the binaries"
+    )]
+    pub state_output: Option<String>,
+    #[clap(
+        long,
+        help = "Experimental: also write the coverage keyed by (source file, demangled function name) instead of line numbers to the given path, for comparisons across builds with --compare-functions"
+    )]
+    pub function_keyed_output: Option<String>,
+    #[clap(
+        long,
+        help = "Experimental: compare the function keyed coverage against the one of another build written with --function-keyed-output, and log the functions that were added, removed, newly covered or no longer covered. Use --relative-to so that the source paths of both builds match"
+    )]
+    pub compare_functions: Option<String>,
+    #[clap(
+        long,
+        help = "Check the produced lcov file with `lcov --summary` (if lcov is installed) and fail if its totals differ from the computed ones"
+    )]
+    pub verify_output: bool,
+    #[clap(
+        long,
+        help = "Append the MD5 checksum of each source line to its DA record (DA:<line>,<count>,<checksum>) when the source file is readable"
+    )]
+    pub checksum: bool,
+    #[clap(
+        long,
+        help = "Download the debug information of modules that lack it by their build-id from the debuginfod servers given with --debuginfod-url (or $DEBUGINFOD_URLS). Downloads are cached like elfutils' debuginfod client does"
+    )]
+    pub debuginfod: bool,
+    #[clap(
+        long = "debuginfod-url",
+        requires = "debuginfod",
+        help = "URL of a debuginfod server to fetch debug information from. You can provide this option multiple times; the servers are tried in order"
+    )]
+    pub debuginfod_urls: Vec<String>,
+    #[clap(
+        long,
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Maximum number of debug information downloads that are in flight at the same time"
+    )]
+    pub max_concurrent_downloads: u16,
+    #[clap(
+        long,
+        requires = "debuginfod",
+        help = "Stop downloading debug information once the given number of bytes have been downloaded, over the whole run (or the lifetime of a server), so that the debuginfod cache cannot fill the disk"
+    )]
+    pub max_download_size: Option<u64>,
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of input files that are hashed, parsed and symbolized in parallel. Default: the number of CPUs"
+    )]
+    pub jobs: Option<u16>,
+    #[clap(
+        long,
+        value_parser = parse_size,
+        help = "Evict the least recently used entries of the on-disk caches after the conversion until each cache takes up at most the given size (e.g. 500M, 2G)"
+    )]
+    pub cache_max_size: Option<u64>,
+    #[clap(
+        long,
+        help = "Keep the line tables read from the debug information of the modules (their source lines by address and their functions) in the given directory, so that later runs against the same binaries skip reading their debug information. They are keyed by the build-id of the modules (or their path and modification time) and by the source filters, and --cache-max-size bounds the directory as well"
+    )]
+    pub cache_dir: Option<String>,
+    #[clap(
+        long,
+        help = "Keep the object files and line tables of at most N modules in memory between the input files (or the requests of drcov2lcov serve, where it defaults to 64), evicting the least recently used ones"
+    )]
+    pub max_cached_modules: Option<usize>,
+    #[clap(
+        long,
+        help = "Emit the source file paths relative to the given directory (e.g. the repository root) instead of the absolute paths found in the debug information. Paths outside of it are left untouched"
+    )]
+    pub relative_to: Option<String>,
+    #[clap(
+        long = "scrub",
+        value_parser = clap::value_parser!(ReplacementFilter),
+        help = "Rewrite every path and piece of metadata written to any output using the given regular expression and replacement, separated by a colon (:). You can provide this option multiple times; the rewrites are applied in order"
+    )]
+    pub scrub_filters: Vec<ReplacementFilter>,
+    #[clap(
+        long,
+        help = "Process every input file even if it is byte-identical to one that has already been processed"
+    )]
+    pub keep_duplicates: bool,
+    #[clap(
+        long,
+        conflicts_with = "keep_duplicates",
+        help = "Write the content hash of every input file, along with the input it duplicates (if any), to the given path"
+    )]
+    pub dedup_manifest: Option<String>,
+    #[clap(
+        long,
+        requires = "directory",
+        conflicts_with = "watch",
+        help = "Instead of converting, check the files of the input directory against a manifest written with --dedup-manifest or --reduce-set-manifest and fail if any of them is missing or has changed"
+    )]
+    pub verify_manifest: Option<String>,
+    #[clap(
+        long,
+        requires = "directory",
+        help = "Keep running, process new input files as they appear in the input directory and rewrite the outputs after every batch"
+    )]
+    pub watch: bool,
+    #[clap(
+        long,
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Seconds between two polls of the input directory in watch mode"
+    )]
+    pub watch_interval: u64,
+    #[clap(
+        long,
+        requires = "directory",
+        help = "Continuously rewrite an lcov file at the given path (e.g. lcov.info in the workspace) with the coverage of the covered project files, for live in-editor coverage with VS Code's Coverage Gutters. Implies --watch"
+    )]
+    pub vscode_watch: Option<String>,
+    #[clap(
+        long,
+        help = "In watch mode, post a JSON notification to the given webhook URL whenever the coverage reaches a milestone: its line coverage rate grew by --notify-threshold or a target of --notify-on has been covered for the first time"
+    )]
+    pub notify_webhook: Option<String>,
+    #[clap(
+        long,
+        requires = "notify_webhook",
+        default_value_t = 1.0,
+        help = "Percentage points the line coverage rate has to grow by since the last notification (or the first batch) to notify about it"
+    )]
+    pub notify_threshold: f64,
+    #[clap(
+        long,
+        requires = "notify_webhook",
+        value_parser = clap::value_parser!(Filter),
+        help = "Notify when a source file or function whose path or name matches the given regular expression has been covered for the first time. You can provide this option multiple times"
+    )]
+    pub notify_on: Vec<Filter>,
+    #[clap(
+        long = "notify-payload",
+        requires = "notify_webhook",
+        help = "JSON file with the payload of the notifications, with the placeholders {event}, {message}, {target}, {line_rate}, {lines_hit} and {lines_found}. Default: {\"text\": \"{message}\"}"
+    )]
+    pub notify_payload_path: Option<String>,
+    #[clap(skip)]
+    pub notify_payload: Option<PayloadTemplate>,
+    #[clap(
+        long,
+        help = "Only write to the outputs given on the command line and to the caches, and do not access the network (debuginfod, webhooks) unless --allow-network is given, e.g. to convert untrusted drcov files in a locked-down triage environment"
+    )]
+    pub read_only: bool,
+    #[clap(
+        long,
+        requires = "read_only",
+        help = "With --read-only, still allow accessing the network"
+    )]
+    pub allow_network: bool,
+    #[clap(
+        long,
+        help = "Do not draw the progress bar of the processed input files (it is only drawn when the standard error is a terminal)"
+    )]
+    pub no_progress: bool,
+    #[clap(
+        long,
+        help = "Write a JSON report of what the conversion did (its inputs and what happened to them, its arguments, timings, and the versions of the external tools it ran) to the given path, e.g. to attach it to a bug report. It is only written locally; paths are scrubbed like in the outputs"
+    )]
+    pub run_report: Option<String>,
+    /// Receives the progress of the conversion
+    #[clap(skip)]
+    pub progress: Option<Arc<dyn Progress>>,
+    /// Rewrites the source file paths of the debug information before the source filters
+    #[clap(skip)]
+    pub source_path_resolver: Option<Arc<dyn SourcePathResolver>>,
+    /// Keeps the object files of the modules loaded between conversions
+    #[clap(skip)]
+    pub object_files: Option<Arc<ObjectFileCache>>,
+    /// Keeps the line tables of the modules between the input files and conversions
+    #[clap(skip)]
+    pub line_tables: Option<Arc<LineTableCache>>,
+    /// Keeps the binaries of `--binary-dir` indexed by build-id between the input files
+    #[clap(skip)]
+    pub(crate) binary_resolver: Option<Arc<BinaryResolver>>,
 }
 
 impl CliOptions {
     pub fn parse_and_validate() -> anyhow::Result<Self> {
         let self_ = Self::parse();
 
-        if let Some(input_path) = self_.input.as_ref().map(Path::new) {
-            if !input_path.exists() {
-                anyhow::bail!("Input path '{}' does not exist", input_path.display());
-            }
+        if self_.command.is_some() {
+            return Ok(self_);
+        }
+
+        self_.validate()
+    }
 
-            if !input_path.is_file() {
-                anyhow::bail!("Input path '{}' is not a file", input_path.display());
+    pub fn validate(self) -> anyhow::Result<Self> {
+        let mut self_ = self;
+
+        for input in &self_.input {
+            let input_path = Path::new(input);
+
+            if is_input_pattern(input) {
+                if expand_input_pattern(input)?.is_empty() {
+                    anyhow::bail!("Input pattern '{input}' does not match any file");
+                }
+            } else if !is_stdin(input_path) {
+                if !input_path.exists() {
+                    anyhow::bail!("Input path '{}' does not exist", input_path.display());
+                }
+
+                if !input_path.is_file() {
+                    anyhow::bail!("Input path '{}' is not a file", input_path.display());
+                }
+
+                if archive::is_archive(input_path)
+                    && archive::list_drcov_entries(input_path)?.is_empty()
+                {
+                    anyhow::bail!(
+                        "Archive '{}' does not contain any drcov logs",
+                        input_path.display()
+                    );
+                }
             }
         }
 
@@ -151,66 +735,435 @@ impl CliOptions {
             }
         }
 
-        let output_path = Path::new(&self_.output);
+        let output = self_
+            .output_template
+            .as_ref()
+            .map_or(self_.output.as_str(), OutputTemplate::as_str);
+
+        if Path::new(output)
+            .parent()
+            .is_some_and(|parent| !parent.is_dir())
+        {
+            anyhow::bail!("Target output path '{output}' does not point to a valid directory");
+        }
+
+        let compat = CompatDefaults::of(self_.compat_level);
+        if !compat.skip_duplicates {
+            if self_.dedup_manifest.is_some() {
+                anyhow::bail!("--dedup-manifest requires --compat-level 2 or later, which skips byte-identical inputs");
+            }
+
+            self_.keep_duplicates = true;
+        }
+        self_.include_loader |= !compat.skip_loader;
+        self_.keep_dynamorio_modules |= !compat.skip_dynamorio_modules;
+        self_.strict_format |= !compat.lenient_headers;
+        if !compat.auto_bb_offset_base {
+            self_.bb_offset_base.get_or_insert(BbOffsetBase::Segment);
+        }
+
+        if !self_.keep_dynamorio_modules {
+            // Safety: The filter is a valid regular expression
+            self_
+                .module_skip_filters
+                .push(Filter::from_str(constants::DYNAMORIO_MODULES_FILTER).unwrap());
+        }
+
+        if let Some(binary) = self_.modules_from_binary.as_ref().map(Path::new) {
+            if !binary.is_file() {
+                anyhow::bail!("Binary '{}' does not exist", binary.display());
+            }
+
+            let filters = module_filters_for_binary(binary)?;
+            self_.module_filters.extend(filters);
+        }
+
+        if let Some(module_table_path) = self_.module_table_path.as_ref().map(Path::new) {
+            if !module_table_path.is_file() {
+                anyhow::bail!(
+                    "Module table '{}' does not exist",
+                    module_table_path.display()
+                );
+            }
+
+            let module_table = Modules::from_module_table_file(module_table_path)?;
+            log::info!(
+                "Loaded {} modules from '{}'",
+                module_table.table.len(),
+                module_table_path.display()
+            );
+            self_.module_table = Some(module_table);
+        }
+
+        if let Some(component_map_path) = self_.component_map_path.as_ref().map(Path::new) {
+            if !component_map_path.is_file() {
+                anyhow::bail!(
+                    "Component map '{}' does not exist",
+                    component_map_path.display()
+                );
+            }
+
+            let component_map = ComponentMap::from_file(component_map_path)?;
+            log::info!(
+                "Loaded {} components from '{}'",
+                component_map.len(),
+                component_map_path.display()
+            );
+            self_.component_map = Some(component_map);
+        }
+
+        if self_.debuginfod && self_.debuginfod_urls.is_empty() {
+            self_.debuginfod_urls = debuginfod::urls_from_env();
+
+            if self_.debuginfod_urls.is_empty() {
+                anyhow::bail!("--debuginfod requires at least one --debuginfod-url or $DEBUGINFOD_URLS to be set");
+            }
+        }
+
+        if let Some(root) = self_.relative_to.as_ref() {
+            let root_path = Path::new(root);
+
+            if !root_path.is_dir() {
+                anyhow::bail!("Directory '{root}' given to --relative-to is not a directory");
+            }
+
+            self_.relative_to = Some(root_path.canonicalize()?.to_string_lossy().to_string());
+        }
+
+        if let Some(baseline) = self_.compare_functions.as_ref().map(Path::new) {
+            if !baseline.is_file() {
+                anyhow::bail!("Function coverage '{}' does not exist", baseline.display());
+            }
+        }
 
-        if output_path.parent().is_some_and(|parent| !parent.is_dir()) {
+        if let Some(manifest) = self_.verify_manifest.as_ref().map(Path::new) {
+            if !manifest.is_file() {
+                anyhow::bail!("Manifest '{}' does not exist", manifest.display());
+            }
+        }
+
+        for binary_dir in &self_.binary_dirs {
+            if !Path::new(binary_dir).is_dir() {
+                anyhow::bail!("Binary directory '{binary_dir}' is not a directory");
+            }
+        }
+
+        if self_.vscode_watch.is_some() {
+            self_.watch = true;
+        }
+
+        if self_.notify_webhook.is_some() && !self_.watch {
             anyhow::bail!(
-                "Target output path '{}' does not point to a valid directory",
-                self_.output
+                "--notify-webhook can only be used in watch mode (--watch or --vscode-watch)"
             );
         }
 
+        if self_.notify_threshold <= 0.0 {
+            anyhow::bail!("--notify-threshold must be positive");
+        }
+
+        if let Some(notify_payload_path) = self_.notify_payload_path.as_ref().map(Path::new) {
+            if !notify_payload_path.is_file() {
+                anyhow::bail!(
+                    "Payload template '{}' does not exist",
+                    notify_payload_path.display()
+                );
+            }
+
+            self_.notify_payload = Some(PayloadTemplate::from_file(notify_payload_path)?);
+        }
+
+        self_.check_output_format(self_.output_format)?;
+
+        let extra_outputs = [
+            ("Lighthouse", &self_.lighthouse_output),
+            ("Reachable oracle", &self_.reachable_oracle),
+            ("Module graph", &self_.module_graph),
+            ("Callgrind", &self_.callgrind_output),
+            ("OpenCover", &self_.opencover_output),
+            ("Istanbul", &self_.istanbul_output),
+            ("SQLite", &self_.sqlite_output),
+            ("State", &self_.state_output),
+            ("Dedup manifest", &self_.dedup_manifest),
+            ("Reduce set manifest", &self_.reduce_set_manifest),
+            ("Function keyed", &self_.function_keyed_output),
+            ("VS Code watch", &self_.vscode_watch),
+            ("Run report", &self_.run_report),
+        ];
+
+        for (kind, extra_output) in extra_outputs {
+            let Some(extra_output) = extra_output.as_ref().map(Path::new) else {
+                continue;
+            };
+
+            if extra_output.parent().is_some_and(|parent| !parent.is_dir()) {
+                anyhow::bail!(
+                    "{kind} output path '{}' does not point to a valid directory",
+                    extra_output.display()
+                );
+            }
+        }
+
+        if self_.run_report.is_some() && self_.job.is_some() {
+            anyhow::bail!(
+                "--run-report cannot be used with --job, give it to the targets of the job instead"
+            );
+        }
+
+        if self_.read_only {
+            if self_.job.is_some() {
+                anyhow::bail!("--read-only cannot be used with --job, whose outputs are not given on the command line");
+            }
+
+            if !self_.allow_network {
+                if self_.debuginfod {
+                    anyhow::bail!("--debuginfod accesses the network, which --read-only does not allow without --allow-network");
+                }
+
+                if self_.notify_webhook.is_some() {
+                    anyhow::bail!("--notify-webhook accesses the network, which --read-only does not allow without --allow-network");
+                }
+            }
+        }
+
         Ok(self_)
     }
 
+    /// Checks that the options can be used with the output `format`
+    pub fn check_output_format(&self, format: OutputFormat) -> anyhow::Result<()> {
+        if format != OutputFormat::Lcov {
+            if self.append {
+                anyhow::bail!("--append can only be used with the lcov output format");
+            }
+
+            if self.verify_output {
+                anyhow::bail!("--verify-output can only be used with the lcov output format");
+            }
+
+            if self.checksum && format != OutputFormat::Json {
+                log::warn!(
+                    "Line checksums are not supported by the {format:?} output format and will not be written"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn get_input_files(&self) -> Vec<PathBuf> {
         let mut unique_files = HashSet::new();
 
-        if let Some(input) = self.input.as_ref().map(PathBuf::from) {
-            let input = input.canonicalize().unwrap_or(input);
-            unique_files.insert(input);
+        for input in &self.input {
+            if is_input_pattern(input) {
+                for path in expand_input_pattern(input).unwrap_or_default() {
+                    insert_input_file(path, &mut unique_files);
+                }
+            } else if is_stdin(Path::new(input)) {
+                unique_files.insert(PathBuf::from(input));
+            } else {
+                insert_input_file(PathBuf::from(input), &mut unique_files);
+            }
         }
 
         if let Some(list_file) = &self.list {
             if let Ok(contents) = std::fs::read_to_string(list_file) {
                 for line in contents.lines() {
                     let path = PathBuf::from(line);
-                    let path = path.canonicalize().unwrap_or(path);
-                    unique_files.insert(path);
+                    insert_input_file(path, &mut unique_files);
                 }
             }
         }
 
         if let Some(directory) = self.directory.as_ref().map(Path::new) {
-            if let Ok(read_dir) = directory.read_dir() {
-                for entry in read_dir.flatten() {
-                    if entry.file_type().is_ok_and(|file_type| file_type.is_file())
-                        && constants::DRCOV_LOG_FILE_REGEX
-                            .is_match(&entry.file_name().to_string_lossy())
-                    {
-                        let path = entry.path();
-                        let path = path.canonicalize().unwrap_or(path);
-                        unique_files.insert(path);
-                    }
-                }
-            }
+            collect_directory_inputs(directory, self.recursive, &mut unique_files);
         }
 
-        unique_files.into_iter().collect()
+        unique_files.into_iter().sorted().collect()
     }
 
-    pub fn get_drcov_filters(&self) -> DrcovFilters {
+    pub fn get_drcov_filters(&self) -> DrcovFilters<'_> {
         DrcovFilters {
             module_filters: self.module_filters.as_slice(),
             module_skip_filters: self.module_skip_filters.as_slice(),
             path_map_filters: self.path_map_filters.as_slice(),
+            max_iterations: self.max_iterations.map(|max| max as usize),
+            iteration_mode: self.iteration_mode,
+            strict_format: self.strict_format,
+            bb_offset_base: self.bb_offset_base.unwrap_or_default(),
+            limits: self.get_input_limits(),
+        }
+    }
+
+    pub fn get_input_limits(&self) -> InputLimits {
+        InputLimits {
+            max_input_size: self.max_input_size,
+            max_modules: self.max_modules,
+            max_basic_blocks: self.max_basic_blocks,
+            max_duration: self.input_timeout.map(Duration::from_secs),
+        }
+    }
+
+    pub fn get_debuginfod_client(&self) -> anyhow::Result<Option<DebuginfodClient>> {
+        if !self.debuginfod {
+            return Ok(None);
+        }
+
+        let client = DebuginfodClient::new(
+            self.debuginfod_urls.clone(),
+            self.max_concurrent_downloads as usize,
+            self.max_download_size,
+        )?;
+
+        Ok(Some(client))
+    }
+
+    /// Whether the inputs are hashed with SHA-256 as well, for the manifests that record it
+    pub fn needs_sha256(&self) -> bool {
+        self.dedup_manifest.is_some() || self.reduce_set_manifest.is_some()
+    }
+
+    /// Returns the number of input files to process in parallel
+    pub fn get_jobs(&self) -> usize {
+        self.jobs.map_or_else(
+            || std::thread::available_parallelism().map_or(1, usize::from),
+            usize::from,
+        )
+    }
+
+    /// Returns the policy of `--read-only`: the outputs of the conversion and the caches are the
+    /// only paths it may write to
+    pub fn get_read_only_policy(&self) -> Option<ReadOnlyPolicy> {
+        if !self.read_only {
+            return None;
+        }
+
+        let mut writable = match &self.output_template {
+            // The outputs are named after the inputs, in the directory of the template
+            Some(template) => vec![template.directory().to_path_buf()],
+            None => vec![PathBuf::from(&self.output)],
+        };
+
+        let outputs = [
+            &self.badge_dir,
+            &self.metrics_output,
+            &self.reduce_set_path,
+            &self.reachable_oracle,
+            &self.module_graph,
+            &self.state_output,
+            &self.dedup_manifest,
+            &self.reduce_set_manifest,
+            &self.function_keyed_output,
+            &self.vscode_watch,
+            &self.run_report,
+        ];
+
+        writable.extend(outputs.into_iter().flatten().map(PathBuf::from));
+        writable.extend(
+            self.get_extra_writers()
+                .into_iter()
+                .map(|(_, path)| PathBuf::from(path)),
+        );
+        writable.extend(self.get_caches().into_iter().map(|cache| cache.root));
+
+        Some(ReadOnlyPolicy::new(writable, self.allow_network))
+    }
+
+    pub fn get_notifier(&self) -> anyhow::Result<Option<Notifier>> {
+        let Some(url) = &self.notify_webhook else {
+            return Ok(None);
+        };
+
+        let notifier = Notifier::new(
+            url.clone(),
+            self.notify_payload.clone().unwrap_or_default(),
+            self.notify_threshold,
+            self.notify_on.clone(),
+        )?;
+
+        Ok(Some(notifier))
+    }
+
+    /// Returns the writers of the additional output formats that were requested, along with the
+    /// path each one writes to
+    pub fn get_extra_writers(&self) -> Vec<(Box<dyn CoverageWriter>, &str)> {
+        let writers: [(Box<dyn CoverageWriter>, &Option<String>); 5] = [
+            (Box::new(CallgrindWriter), &self.callgrind_output),
+            (Box::new(OpenCoverWriter), &self.opencover_output),
+            (Box::new(IstanbulWriter), &self.istanbul_output),
+            (Box::new(SqliteWriter), &self.sqlite_output),
+            (Box::new(LighthouseWriter), &self.lighthouse_output),
+        ];
+
+        writers
+            .into_iter()
+            .filter_map(|(writer, path)| path.as_deref().map(|path| (writer, path)))
+            .collect()
+    }
+
+    pub fn get_scrubber(&self) -> Scrubber<'_> {
+        Scrubber {
+            scrub_filters: self.scrub_filters.as_slice(),
         }
     }
 
-    pub fn get_line_info_filters(&self) -> LineInfoFilters {
+    /// Returns the on-disk caches of the conversion: the caches of the tool and the line tables of
+    /// `--cache-dir`
+    pub fn get_caches(&self) -> Vec<Cache> {
+        let mut caches = cache::caches();
+
+        if let Some(cache_dir) = &self.cache_dir {
+            caches.push(Cache {
+                name: "line table",
+                root: PathBuf::from(cache_dir),
+                shared: false,
+            });
+        }
+
+        caches
+    }
+
+    /// Returns a cache for the line tables of the modules of the conversion, which also stores
+    /// them in `--cache-dir` if given
+    pub fn get_line_table_cache(&self) -> LineTableCache {
+        let cache = match &self.cache_dir {
+            Some(cache_dir) => LineTableCache::with_directory(cache_dir),
+            None => LineTableCache::default(),
+        };
+
+        match self.max_cached_modules {
+            Some(max_cached_modules) => cache.with_capacity(max_cached_modules),
+            None => cache,
+        }
+    }
+
+    /// Returns the resolver of the build-ids of coverage state files and LLVM profiles to the
+    /// binaries of `--binary-dir`, which is shared by the inputs of the conversion if set
+    pub fn get_binary_resolver(&self) -> Arc<BinaryResolver> {
+        self.binary_resolver
+            .clone()
+            .unwrap_or_else(|| Arc::new(BinaryResolver::new(&self.binary_dirs)))
+    }
+
+    /// Returns the line info filters for coverage of the given input flavor
+    pub fn get_line_info_filters(&self, flavor: &str) -> LineInfoFilters<'_> {
         LineInfoFilters {
             src_filters: self.source_filters.as_slice(),
             src_skip_filters: self.source_skip_filters.as_slice(),
+            include_loader: self.include_loader,
+            line_hit_semantics: self
+                .line_hit_semantics
+                .unwrap_or_else(|| default_line_hit_semantics(flavor)),
+            discriminator_branches: self.discriminator_branches,
+            block_branches: self.reachable_oracle.is_some(),
+            consistency_checks: self.consistency_checks,
+            module_timeout: self.module_timeout.map(Duration::from_secs),
+            deadline: None,
+            alias_policy: self.alias_policy,
+            cross_check: self.cross_check,
+            progress: self.progress.as_deref(),
+            source_path_resolver: self.source_path_resolver.as_deref(),
+            object_files: self.object_files.as_deref(),
+            line_tables: self.line_tables.as_deref(),
         }
     }
 }
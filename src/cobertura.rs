@@ -0,0 +1,149 @@
+use crate::dwarf::{LineInfo, SourceCoverage};
+use crate::output::{write_text_output, CoverageReport, CoverageWriter, LineEnding};
+use crate::summary::Summary;
+use crate::util::xml_escape;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn line_rate<'l>(lines: impl Iterator<Item = &'l LineInfo>) -> f64 {
+    let (found, hit) = lines.fold((0usize, 0usize), |(found, hit), info| {
+        (found + 1, hit + info.executed as usize)
+    });
+
+    if found == 0 {
+        0.0
+    } else {
+        hit as f64 / found as f64
+    }
+}
+
+fn write_lines<'l>(res: &mut String, lines: impl Iterator<Item = &'l LineInfo>, indent: &str) {
+    let _ = writeln!(res, "{indent}<lines>");
+    for info in lines {
+        let _ = writeln!(
+            res,
+            r#"{indent}  <line number="{}" hits="{}" branch="false"/>"#,
+            info.line, info.executed as usize
+        );
+    }
+    let _ = writeln!(res, "{indent}</lines>");
+}
+
+/// Writes the coverage as a Cobertura XML report (as consumed by Jenkins, GitLab and Azure
+/// DevOps).
+///
+/// Source files are grouped into packages by their directory, every source file becomes a
+/// `class` and the DWARF subprograms of a file its `method`s.
+pub fn write_cobertura_output(
+    path: &str,
+    coverage: &SourceCoverage,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    // package (directory) -> source files
+    let mut packages = BTreeMap::<String, Vec<&String>>::new();
+
+    for file in coverage.lines.keys().sorted() {
+        let package = Path::new(file)
+            .parent()
+            .map(|parent| parent.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        packages.entry(package).or_default().push(file);
+    }
+
+    let summary = Summary::from_coverage(coverage);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+
+    let mut res = String::new();
+    let _ = writeln!(res, r#"<?xml version="1.0" ?>"#);
+    let _ = writeln!(
+        res,
+        r#"<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#
+    );
+    let _ = writeln!(
+        res,
+        r#"<coverage line-rate="{:.4}" branch-rate="0" lines-covered="{}" lines-valid="{}" branches-covered="0" branches-valid="0" complexity="0" version="drcov2lcov {}" timestamp="{timestamp}">"#,
+        line_rate(coverage.lines.values().flatten()),
+        summary.lines_hit,
+        summary.lines_found,
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(res, "  <sources>");
+    let _ = writeln!(res, "    <source>.</source>");
+    let _ = writeln!(res, "  </sources>");
+    let _ = writeln!(res, "  <packages>");
+
+    for (package, files) in &packages {
+        let _ = writeln!(
+            res,
+            r#"    <package name="{}" line-rate="{:.4}" branch-rate="0" complexity="0">"#,
+            xml_escape(package),
+            line_rate(files.iter().flat_map(|file| &coverage.lines[*file]))
+        );
+        let _ = writeln!(res, "      <classes>");
+
+        for file in files {
+            let lines = &coverage.lines[*file];
+            let name = Path::new(file)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| file.to_string());
+
+            let _ = writeln!(
+                res,
+                r#"        <class name="{}" filename="{}" line-rate="{:.4}" branch-rate="0" complexity="0">"#,
+                xml_escape(&name),
+                xml_escape(file),
+                line_rate(lines.iter())
+            );
+            let _ = writeln!(res, "          <methods>");
+
+            for function in coverage.functions.get(*file).into_iter().flatten() {
+                let _ = writeln!(
+                    res,
+                    r#"            <method name="{}" signature="" line-rate="{:.4}" branch-rate="0" complexity="0">"#,
+                    xml_escape(&function.name),
+                    line_rate(coverage.function_lines(file, function))
+                );
+                write_lines(
+                    &mut res,
+                    coverage.function_lines(file, function),
+                    "              ",
+                );
+                let _ = writeln!(res, "            </method>");
+            }
+
+            let _ = writeln!(res, "          </methods>");
+            write_lines(&mut res, lines.iter(), "          ");
+            let _ = writeln!(res, "        </class>");
+        }
+
+        let _ = writeln!(res, "      </classes>");
+        let _ = writeln!(res, "    </package>");
+    }
+
+    let _ = writeln!(res, "  </packages>");
+    let _ = writeln!(res, "</coverage>");
+
+    write_text_output(path, &res, line_ending)?;
+
+    Ok(())
+}
+
+pub struct CoberturaWriter;
+
+impl CoverageWriter for CoberturaWriter {
+    fn name(&self) -> &'static str {
+        "Cobertura"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_cobertura_output(path, report.coverage, report.line_ending)
+    }
+}
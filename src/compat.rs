@@ -0,0 +1,73 @@
+/// The latest compatibility level, which `--compat-level` defaults to. Whenever a default that
+/// changes what the outputs contain shifts, the level is bumped and the defaults of the previous
+/// levels are added to [`CompatDefaults::of`], so that scripts pinning a level keep getting the
+/// same outputs from newer versions.
+pub const LATEST_COMPAT_LEVEL: u8 = 3;
+
+/// The defaults pinned by a compatibility level, for the options whose defaults have shifted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompatDefaults {
+    /// Byte-identical inputs are processed once
+    pub skip_duplicates: bool,
+    /// The coverage of the dynamic loader is left out, like the one of the other pseudo-modules
+    pub skip_loader: bool,
+    /// The coverage of DynamoRIO's own modules is left out
+    pub skip_dynamorio_modules: bool,
+    /// The version and flavor lines of drcov logs may come in either order, and the flavor line
+    /// may be missing
+    pub lenient_headers: bool,
+    /// The basic block offsets of frida logs are taken relative to their modules rather than
+    /// their segments
+    pub auto_bb_offset_base: bool,
+}
+
+impl CompatDefaults {
+    /// The defaults of compatibility `level`:
+    /// - 1: the defaults of the first releases, which processed every input and module they were
+    ///   given and only accepted drcov headers with the version line followed by the flavor line
+    /// - 2: byte-identical inputs, the dynamic loader and DynamoRIO's own modules are skipped, and
+    ///   drcov headers are accepted in either order or without a flavor line
+    /// - 3: the basic block offsets of frida logs are relative to their modules
+    pub fn of(level: u8) -> Self {
+        let since = |introduced: u8| level >= introduced;
+
+        Self {
+            skip_duplicates: since(2),
+            skip_loader: since(2),
+            skip_dynamorio_modules: since(2),
+            lenient_headers: since(2),
+            auto_bb_offset_base: since(3),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::CliOptions;
+    use crate::drcov::BbOffsetBase;
+    use crate::test_fixtures::test_binary;
+    use clap::Parser;
+
+    fn options(args: &[&str]) -> anyhow::Result<CliOptions> {
+        let input = test_binary();
+
+        CliOptions::try_parse_from(["drcov2lcov", "--input", input.as_str()].iter().chain(args))?
+            .validate()
+    }
+
+    #[test]
+    fn pins_the_defaults_of_the_first_level() {
+        let latest = options(&[]).unwrap();
+        assert!(!latest.keep_duplicates && !latest.include_loader && !latest.strict_format);
+        assert_eq!(latest.module_skip_filters.len(), 1);
+        assert_eq!(latest.bb_offset_base, None);
+
+        let first = options(&["--compat-level", "1"]).unwrap();
+        assert!(first.keep_duplicates && first.include_loader && first.strict_format);
+        assert!(first.module_skip_filters.is_empty());
+        assert_eq!(first.bb_offset_base, Some(BbOffsetBase::Segment));
+
+        assert!(options(&["--compat-level", "1", "--dedup-manifest", "dedup.txt"]).is_err());
+        assert!(options(&["--compat-level", "4"]).is_err());
+    }
+}
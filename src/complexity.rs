@@ -0,0 +1,118 @@
+use crate::dwarf::FunctionInfo;
+
+/// The basic blocks of a function, recovered from the disassembly of its module (see
+/// `--reachable-oracle`), and the edges of the conditional branches between them, each with
+/// whether it has been executed. An edge has been executed if both the block it leaves and the
+/// block it goes to have been.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionBlocks {
+    pub blocks: Vec<bool>,
+    pub branches: Vec<bool>,
+}
+
+impl FunctionBlocks {
+    /// Adds the executed blocks and branches of `other`, the blocks of the same function in
+    /// another input
+    pub fn merge(&mut self, other: &Self) {
+        if self.blocks.len() != other.blocks.len() || self.branches.len() != other.branches.len() {
+            log::debug!("Not merging the blocks of different builds of a function");
+            return;
+        }
+
+        for (hit, other) in self.blocks.iter_mut().zip(&other.blocks) {
+            *hit |= other;
+        }
+        for (hit, other) in self.branches.iter_mut().zip(&other.branches) {
+            *hit |= other;
+        }
+    }
+}
+
+/// How much of the control flow of a function has been covered, as opposed to how many of its
+/// lines: its basic blocks and the edges of its conditional branches, as found in the disassembly
+/// of its module
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionComplexity {
+    pub blocks_found: usize,
+    pub blocks_hit: usize,
+    pub branches_found: usize,
+    pub branches_hit: usize,
+}
+
+impl FunctionComplexity {
+    /// Computes the complexity coverage of `function`, if its blocks are known
+    pub fn of(function: &FunctionInfo) -> Option<Self> {
+        let blocks = function.blocks.as_ref()?;
+
+        Some(Self {
+            blocks_found: blocks.blocks.len(),
+            blocks_hit: blocks.blocks.iter().filter(|hit| **hit).count(),
+            branches_found: blocks.branches.len(),
+            branches_hit: blocks.branches.iter().filter(|hit| **hit).count(),
+        })
+    }
+
+    /// The share of the covered blocks and branches, where branches weigh in on top of the
+    /// blocks they are made of, so that the decisions of a function count more than its
+    /// straight-line code. Functions without blocks have a score of 0.
+    pub fn score(&self) -> f64 {
+        let found = self.blocks_found + self.branches_found;
+
+        if found == 0 {
+            return 0.0;
+        }
+
+        (self.blocks_hit + self.branches_hit) as f64 / found as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_the_blocks_and_branches_of_functions() {
+        let mut blocks = FunctionBlocks {
+            blocks: vec![true, false, false],
+            branches: vec![true, false],
+        };
+        blocks.merge(&FunctionBlocks {
+            blocks: vec![true, false, true],
+            branches: vec![false, false],
+        });
+        // Another build of the function is not merged
+        blocks.merge(&FunctionBlocks {
+            blocks: vec![true; 4],
+            branches: vec![true; 2],
+        });
+
+        let function = FunctionInfo {
+            name: "f".to_string(),
+            module: "m".to_string(),
+            start_line: 1,
+            end_line: 2,
+            executed: true,
+            aliases: Vec::new(),
+            blocks: Some(blocks),
+        };
+
+        let complexity = FunctionComplexity::of(&function).unwrap();
+        assert_eq!(
+            complexity,
+            FunctionComplexity {
+                blocks_found: 3,
+                blocks_hit: 2,
+                branches_found: 2,
+                branches_hit: 1,
+            }
+        );
+        assert_eq!(complexity.score(), 0.6);
+
+        let function = FunctionInfo {
+            blocks: None,
+            ..function
+        };
+        assert_eq!(FunctionComplexity::of(&function), None);
+    }
+}
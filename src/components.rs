@@ -0,0 +1,270 @@
+use crate::dwarf::SourceCoverage;
+#[cfg(feature = "native")]
+use crate::read_only;
+use crate::summary::Summary;
+use anyhow::anyhow;
+use glob::Pattern;
+use std::collections::BTreeSet;
+#[cfg(feature = "native")]
+use std::fmt::Write;
+#[cfg(feature = "native")]
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use yaml_rust2::{Yaml, YamlLoader};
+
+mod constants {
+    use glob::MatchOptions;
+
+    /// The component of the source files that no component claims
+    pub const UNOWNED_COMPONENT: &str = "(unowned)";
+
+    /// `*` does not cross directories, `**` does
+    pub const PATH_MATCH_OPTIONS: MatchOptions = MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+}
+
+/// A named part of a code base, e.g. a library or the code a team owns
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub name: String,
+    pub owner: Option<String>,
+    /// Glob patterns of the source paths of the component, as they appear in the outputs
+    patterns: Vec<Pattern>,
+}
+
+impl Component {
+    fn contains(&self, path: &str) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matches_with(path, constants::PATH_MATCH_OPTIONS))
+    }
+}
+
+/// Maps source paths to the components they belong to
+#[derive(Debug, Clone)]
+pub struct ComponentMap {
+    components: Vec<Component>,
+}
+
+/// The coverage totals of a component
+#[derive(Debug, Clone)]
+pub struct ComponentRollup {
+    pub name: String,
+    pub owner: Option<String>,
+    pub files: usize,
+    pub summary: Summary,
+}
+
+fn yaml_strings(value: &Yaml, what: &str) -> anyhow::Result<Vec<String>> {
+    match value {
+        Yaml::String(value) => Ok(vec![value.clone()]),
+        Yaml::Array(values) => values
+            .iter()
+            .map(|value| {
+                value
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or(anyhow!("The {what} must be strings"))
+            })
+            .collect(),
+        _ => Err(anyhow!(
+            "Expected a string or a list of strings as the {what}"
+        )),
+    }
+}
+
+impl ComponentMap {
+    /// Parses the component map at `path`: a YAML document with a list of `components`, each with
+    /// a `name`, an optional `owner` and the glob `paths` of its source files, e.g.
+    ///
+    /// ```yaml
+    /// components:
+    ///   - name: networking
+    ///     owner: net-team
+    ///     paths: ["src/net/**", "third_party/curl/**"]
+    /// ```
+    ///
+    /// Source files belong to the first component with a matching pattern.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let documents = YamlLoader::load_from_str(&contents)
+            .map_err(|e| anyhow!("Could not parse component map '{}': {e}", path.display()))?;
+
+        let entries = documents
+            .first()
+            .and_then(|document| document["components"].as_vec())
+            .ok_or(anyhow!(
+                "Component map '{}' does not list any components",
+                path.display()
+            ))?;
+
+        let mut components = Vec::with_capacity(entries.len());
+
+        for (index, entry) in entries.iter().enumerate() {
+            let name = entry["name"]
+                .as_str()
+                .ok_or(anyhow!("Component {} has no name", index + 1))?
+                .to_string();
+
+            let patterns = yaml_strings(&entry["paths"], "paths")
+                .map_err(|e| anyhow!("{name}: {e}"))?
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern)
+                        .map_err(|e| anyhow!("{name}: Invalid path pattern '{pattern}': {e}"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            components.push(Component {
+                name,
+                owner: entry["owner"].as_str().map(str::to_string),
+                patterns,
+            });
+        }
+
+        Ok(Self { components })
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Computes the coverage totals of every component, in the order of the map, followed by the
+    /// totals of the source files no component claims (if there are any)
+    pub fn rollups(&self, coverage: &SourceCoverage) -> Vec<ComponentRollup> {
+        let mut res = self
+            .components
+            .iter()
+            .map(|component| ComponentRollup {
+                name: component.name.clone(),
+                owner: component.owner.clone(),
+                files: 0,
+                summary: Summary::default(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut unowned = ComponentRollup {
+            name: constants::UNOWNED_COMPONENT.to_string(),
+            owner: None,
+            files: 0,
+            summary: Summary::default(),
+        };
+
+        let files = coverage
+            .lines
+            .keys()
+            .chain(coverage.functions.keys())
+            .collect::<BTreeSet<_>>();
+
+        for file in files {
+            let rollup = match self.components.iter().position(|c| c.contains(file)) {
+                Some(index) => &mut res[index],
+                None => &mut unowned,
+            };
+
+            rollup.files += 1;
+            rollup.summary.add_file(coverage, file);
+        }
+
+        if unowned.files > 0 {
+            res.push(unowned);
+        }
+
+        res
+    }
+}
+
+/// The per-component section of the summary
+#[cfg(feature = "native")]
+pub struct ComponentSummary<'r>(pub &'r [ComponentRollup]);
+
+#[cfg(feature = "native")]
+impl Display for ComponentSummary<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, rollup) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+
+            let owner = rollup
+                .owner
+                .as_ref()
+                .map(|owner| format!(" (owner: {owner})"))
+                .unwrap_or_default();
+
+            write!(
+                f,
+                "{}{owner}: {} files\n{}",
+                rollup.name, rollup.files, rollup.summary
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "native")]
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(feature = "native")]
+fn percentage_cell(hit: usize, found: usize) -> String {
+    if found == 0 {
+        return "<td>-</td>".to_string();
+    }
+
+    format!(
+        "<td>{:.1}% ({hit} of {found})</td>",
+        hit as f64 * 100.0 / found as f64
+    )
+}
+
+/// Writes the per-component section of an HTML report as a page of its own at `path`, next to the
+/// file-level report of genhtml
+#[cfg(feature = "native")]
+pub fn write_html(rollups: &[ComponentRollup], path: &Path) -> anyhow::Result<()> {
+    let mut rows = String::new();
+
+    for rollup in rollups {
+        let _ = writeln!(
+            rows,
+            "<tr><td>{}</td><td>{}</td><td>{}</td>{}{}</tr>",
+            escape_html(&rollup.name),
+            escape_html(rollup.owner.as_deref().unwrap_or_default()),
+            rollup.files,
+            percentage_cell(rollup.summary.lines_hit, rollup.summary.lines_found),
+            percentage_cell(rollup.summary.functions_hit, rollup.summary.functions_found),
+        );
+    }
+
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Coverage by component</title>
+<link rel="stylesheet" type="text/css" href="gcov.css">
+</head>
+<body>
+<h1>Coverage by component</h1>
+<p><a href="index.html">File coverage</a></p>
+<table>
+<tr><th>Component</th><th>Owner</th><th>Files</th><th>Lines</th><th>Functions</th></tr>
+{rows}</table>
+</body>
+</html>
+"#
+    );
+
+    read_only::write(path, page)?;
+    log::info!("Component report written to '{}'", path.display());
+
+    Ok(())
+}
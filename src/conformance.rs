@@ -0,0 +1,934 @@
+use crate::util::{decompress, parse_capture_group};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    /// The drcov version DynamoRIO writes, which is the one consumers expect
+    pub const DRCOV_VERSION: u32 = 2;
+
+    /// The versions of the module table DynamoRIO writes
+    pub const MODULE_TABLE_VERSIONS: std::ops::RangeInclusive<u32> = 2..=5;
+
+    /// The size of an entry of a binary basic block table: its offset in its module (`u32`), its
+    /// size (`u16`) and the id of its module (`u16`), all little-endian
+    pub const BB_ENTRY_SIZE: usize = 8;
+
+    /// How many violations of the same rule are listed before the rest of them are only counted
+    pub const MAX_LISTED_PER_RULE: usize = 10;
+
+    // Unlike the ones of the parser, which accept what the various writers produce, these match
+    // the lines exactly as DynamoRIO writes them
+    lazy_static! {
+        pub static ref VERSION_LINE_REGEX: Regex =
+            Regex::new(r"^DRCOV VERSION: (?P<version>\d+)$").unwrap();
+        pub static ref FLAVOR_LINE_REGEX: Regex =
+            Regex::new(r"^DRCOV FLAVOR: (?P<flavor>\S+)$").unwrap();
+        pub static ref MODULE_HEADER_REGEX: Regex =
+            Regex::new(r"^Module Table: version (?P<version>\d+), count (?P<count>\d+)$").unwrap();
+        pub static ref LEGACY_MODULE_HEADER_REGEX: Regex =
+            Regex::new(r"^Module Table: (?P<count>\d+)$").unwrap();
+        pub static ref COLUMNS_LINE_REGEX: Regex =
+            Regex::new(r"^Columns: (?P<columns>.*)$").unwrap();
+        pub static ref BB_HEADER_REGEX: Regex =
+            Regex::new(r"^BB Table: (?P<count>\d+) bbs$").unwrap();
+        pub static ref TEXT_BB_COLUMNS_REGEX: Regex =
+            Regex::new(r"^module id, start, size:$").unwrap();
+        pub static ref TEXT_BB_ENTRY_REGEX: Regex =
+            Regex::new(r"^module\[\s*(?P<id>\d+)\]: 0x(?P<start>[[:xdigit:]]+),\s*(?P<size>\d+)$")
+                .unwrap();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The log breaks the format, and consumers reject it or misreport its coverage
+    Error,
+    /// The log deviates from what DynamoRIO writes, which some consumers do not accept
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Location {
+    Line(usize),
+    Offset(usize),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Line(line) => write!(f, "line {line}"),
+            Location::Offset(offset) => write!(f, "offset {offset:#x}"),
+        }
+    }
+}
+
+/// A place where a log deviates from the drcov format
+#[derive(Debug)]
+pub struct Violation {
+    pub severity: Severity,
+    pub location: Option<Location>,
+    /// The part of the format that has been violated, e.g. `header/flavor` or `bb/bounds`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+/// The violations of the drcov format found in a log, written by `check-producer`
+#[derive(Debug, Default)]
+pub struct ConformanceReport {
+    /// The first violations of every rule
+    pub violations: Vec<Violation>,
+    /// Rule -> the severity and the number of its violations, listed or not
+    counts: BTreeMap<&'static str, (Severity, usize)>,
+    /// What the log has been recognized as, e.g. `drcov version 2`
+    recognized: Vec<String>,
+}
+
+impl ConformanceReport {
+    fn add(
+        &mut self,
+        severity: Severity,
+        location: Option<Location>,
+        rule: &'static str,
+        message: impl Into<String>,
+    ) {
+        let (_, count) = self.counts.entry(rule).or_insert((severity, 0));
+        *count += 1;
+
+        if *count <= constants::MAX_LISTED_PER_RULE {
+            self.violations.push(Violation {
+                severity,
+                location,
+                rule,
+                message: message.into(),
+            });
+        }
+    }
+
+    fn error(
+        &mut self,
+        location: Option<Location>,
+        rule: &'static str,
+        message: impl Into<String>,
+    ) {
+        self.add(Severity::Error, location, rule, message);
+    }
+
+    fn warning(
+        &mut self,
+        location: Option<Location>,
+        rule: &'static str,
+        message: impl Into<String>,
+    ) {
+        self.add(Severity::Warning, location, rule, message);
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.counts
+            .values()
+            .filter(|(rule_severity, _)| *rule_severity == severity)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    /// Returns whether `rule` has been violated
+    pub fn violates(&self, rule: &str) -> bool {
+        self.counts.contains_key(rule)
+    }
+}
+
+impl std::fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for violation in &self.violations {
+            let severity = match violation.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+
+            write!(f, "{severity}: ")?;
+            if let Some(location) = violation.location {
+                write!(f, "{location}: ")?;
+            }
+            writeln!(f, "[{}] {}", violation.rule, violation.message)?;
+        }
+
+        for (rule, (_, count)) in &self.counts {
+            if let Some(unlisted) = count.checked_sub(constants::MAX_LISTED_PER_RULE) {
+                if unlisted > 0 {
+                    writeln!(f, "... and {unlisted} more violations of [{rule}]")?;
+                }
+            }
+        }
+
+        if !self.recognized.is_empty() {
+            writeln!(f, "Recognized {}", self.recognized.join(", "))?;
+        }
+
+        writeln!(f, "{} errors, {} warnings", self.errors(), self.warnings())
+    }
+}
+
+/// A row of the module table, as far as it could be parsed
+struct ModuleRow {
+    path: String,
+    /// The address range of the module, or only its size for the legacy tables
+    start: u64,
+    end: u64,
+}
+
+/// The columns DynamoRIO writes for a module table of `version`. On Windows, the checksum and the
+/// timestamp of the modules come before their path.
+fn expected_columns(version: u32, windows: bool) -> Vec<&'static str> {
+    let mut columns = match version {
+        2 => vec!["id", "base", "end", "entry"],
+        3 => vec!["id", "containing_id", "start", "end", "entry"],
+        4 => vec!["id", "containing_id", "start", "end", "entry", "offset"],
+        _ => vec![
+            "id",
+            "containing_id",
+            "start",
+            "end",
+            "entry",
+            "offset",
+            "preferred_base",
+        ],
+    };
+
+    if windows {
+        columns.extend(["checksum", "timestamp"]);
+    }
+    columns.push("path");
+
+    columns
+}
+
+fn parse_decimal(field: &str) -> Option<u64> {
+    field.parse().ok()
+}
+
+fn parse_hex(field: &str) -> Option<u64> {
+    u64::from_str_radix(field.strip_prefix("0x")?, 16).ok()
+}
+
+/// The offset, size and module id of an entry of a binary basic block table
+fn parse_bb_entry(entry: &[u8; constants::BB_ENTRY_SIZE], big_endian: bool) -> (u32, u16, u16) {
+    let (start, size, module_id) = (
+        [entry[0], entry[1], entry[2], entry[3]],
+        [entry[4], entry[5]],
+        [entry[6], entry[7]],
+    );
+
+    if big_endian {
+        (
+            u32::from_be_bytes(start),
+            u16::from_be_bytes(size),
+            u16::from_be_bytes(module_id),
+        )
+    } else {
+        (
+            u32::from_le_bytes(start),
+            u16::from_le_bytes(size),
+            u16::from_le_bytes(module_id),
+        )
+    }
+}
+
+struct Checker<'a> {
+    contents: &'a [u8],
+    /// The offset of the next line
+    pos: usize,
+    /// The number of the last line that has been read
+    line: usize,
+    crlf: bool,
+    report: ConformanceReport,
+}
+
+impl<'a> Checker<'a> {
+    fn next_line(&mut self) -> Option<(usize, &'a [u8])> {
+        let rest = self
+            .contents
+            .get(self.pos..)
+            .filter(|rest| !rest.is_empty())?;
+        let len = rest.iter().position(|b| *b == b'\n');
+        self.pos += len.map_or(rest.len(), |len| len + 1);
+        self.line += 1;
+
+        let line = &rest[..len.unwrap_or(rest.len())];
+        match line.strip_suffix(b"\r") {
+            Some(line) => {
+                if !self.crlf {
+                    self.crlf = true;
+                    self.report.warning(
+                        Some(Location::Line(self.line)),
+                        "header/line-endings",
+                        "The text lines end with \\r\\n instead of \\n",
+                    );
+                }
+                Some((self.line, line))
+            }
+            None => Some((self.line, line)),
+        }
+    }
+
+    fn peek_line(&mut self) -> Option<&'a [u8]> {
+        let (pos, line, crlf) = (self.pos, self.line, self.crlf);
+        let next = self.next_line().map(|(_, line)| line);
+        (self.pos, self.line, self.crlf) = (pos, line, crlf);
+
+        next
+    }
+
+    fn check_header(&mut self) {
+        let Some((line, first)) = self.next_line() else {
+            self.report
+                .error(None, "header/version", "The log is empty");
+            return;
+        };
+
+        let version_line = if constants::FLAVOR_LINE_REGEX.is_match(first) {
+            self.report.error(
+                Some(Location::Line(line)),
+                "header/order",
+                "The flavor line comes before the version line",
+            );
+            self.check_flavor(first);
+            self.next_line()
+        } else {
+            Some((line, first))
+        };
+
+        match version_line {
+            Some((line, version_line)) => {
+                match constants::VERSION_LINE_REGEX.captures(version_line) {
+                    Some(cap) => {
+                        let version =
+                            parse_capture_group::<u32>(&cap, "version").unwrap_or_default();
+                        self.report
+                            .recognized
+                            .push(format!("drcov version {version}"));
+
+                        if version != constants::DRCOV_VERSION {
+                            self.report.warning(
+                                Some(Location::Line(line)),
+                                "header/version",
+                                format!(
+                                    "Version {version} is not the one DynamoRIO writes ({})",
+                                    constants::DRCOV_VERSION
+                                ),
+                            );
+                        }
+                    }
+                    None => self.report.error(
+                        Some(Location::Line(line)),
+                        "header/version",
+                        format!(
+                            "Expected 'DRCOV VERSION: {}', found '{}'",
+                            constants::DRCOV_VERSION,
+                            version_line.escape_ascii()
+                        ),
+                    ),
+                }
+            }
+            None => self
+                .report
+                .error(None, "header/version", "The version line is missing"),
+        }
+
+        if self.report.violates("header/order") {
+            return;
+        }
+
+        match self.peek_line() {
+            Some(flavor_line) if constants::FLAVOR_LINE_REGEX.is_match(flavor_line) => {
+                self.next_line();
+                self.check_flavor(flavor_line);
+            }
+            _ => self.report.error(
+                Some(Location::Line(self.line + 1)),
+                "header/flavor",
+                "The version line must be followed by a 'DRCOV FLAVOR: <flavor>' line",
+            ),
+        }
+    }
+
+    fn check_flavor(&mut self, flavor_line: &[u8]) {
+        if let Some(cap) = constants::FLAVOR_LINE_REGEX.captures(flavor_line) {
+            let flavor = String::from_utf8_lossy(&cap["flavor"]).to_string();
+            self.report.recognized.push(format!("flavor {flavor}"));
+        }
+    }
+
+    /// Checks the module table and returns its rows, or `None` if the table is missing
+    fn check_module_table(&mut self) -> Option<Vec<ModuleRow>> {
+        let Some((line, header)) = self.next_line() else {
+            self.report
+                .error(None, "module-table/header", "The module table is missing");
+            return None;
+        };
+        let location = Some(Location::Line(line));
+
+        let (version, count) = if let Some(cap) = constants::MODULE_HEADER_REGEX.captures(header) {
+            let version = parse_capture_group::<u32>(&cap, "version").unwrap_or_default();
+            if !constants::MODULE_TABLE_VERSIONS.contains(&version) {
+                self.report.error(
+                    location,
+                    "module-table/version",
+                    format!(
+                        "Module table version {version} is not one of the versions {}-{}",
+                        constants::MODULE_TABLE_VERSIONS.start(),
+                        constants::MODULE_TABLE_VERSIONS.end()
+                    ),
+                );
+            }
+
+            (
+                Some(version),
+                parse_capture_group::<usize>(&cap, "count").unwrap_or_default(),
+            )
+        } else if let Some(cap) = constants::LEGACY_MODULE_HEADER_REGEX.captures(header) {
+            self.report.warning(
+                location,
+                "module-table/header",
+                "Legacy module table header without a version, use 'Module Table: version <version>, count <count>'",
+            );
+
+            (
+                None,
+                parse_capture_group::<usize>(&cap, "count").unwrap_or_default(),
+            )
+        } else {
+            self.report.error(
+                location,
+                "module-table/header",
+                format!(
+                    "Expected 'Module Table: version <version>, count <count>', found '{}'",
+                    header.escape_ascii()
+                ),
+            );
+            return None;
+        };
+
+        let columns = match version {
+            Some(version) => self.check_columns(version.clamp(2, 5)),
+            None => vec!["id", "size", "path"],
+        };
+
+        self.report.recognized.push(match version {
+            Some(version) => format!("module table version {version} with {count} modules"),
+            None => format!("legacy module table with {count} modules"),
+        });
+
+        let mut rows = Vec::new();
+        for index in 0..count {
+            match self.peek_line() {
+                Some(line) if !constants::BB_HEADER_REGEX.is_match(line) => {}
+                _ => {
+                    self.report.error(
+                        Some(Location::Line(self.line + 1)),
+                        "module-table/count",
+                        format!("The module table declares {count} modules but lists {index}"),
+                    );
+                    break;
+                }
+            }
+
+            let Some((line, row)) = self.next_line() else {
+                break;
+            };
+
+            if let Some(row) = self.check_module_row(line, row, index, &columns, &rows) {
+                rows.push(row);
+            }
+        }
+
+        self.check_overlaps(&rows, version.is_some());
+
+        Some(rows)
+    }
+
+    /// Checks the columns line of a module table of `version` and returns the columns of its rows
+    fn check_columns(&mut self, version: u32) -> Vec<&'static str> {
+        let expected = expected_columns(version, false);
+        let expected_windows = expected_columns(version, true);
+
+        let Some((line, columns_line)) = self.next_line() else {
+            self.report
+                .error(None, "module-table/columns", "The columns line is missing");
+            return expected;
+        };
+
+        let columns = constants::COLUMNS_LINE_REGEX
+            .captures(columns_line)
+            .map(|cap| String::from_utf8_lossy(&cap["columns"]).to_string());
+
+        match columns {
+            Some(columns) if columns == expected.join(", ") => expected,
+            Some(columns) if columns == expected_windows.join(", ") => expected_windows,
+            _ => {
+                self.report.error(
+                    Some(Location::Line(line)),
+                    "module-table/columns",
+                    format!(
+                        "Expected 'Columns: {}' (or 'Columns: {}' on Windows) for a version {version} module table, found '{}'",
+                        expected.join(", "),
+                        expected_windows.join(", "),
+                        columns_line.escape_ascii()
+                    ),
+                );
+                expected
+            }
+        }
+    }
+
+    fn check_module_row(
+        &mut self,
+        line: usize,
+        row: &[u8],
+        index: usize,
+        columns: &[&'static str],
+        rows: &[ModuleRow],
+    ) -> Option<ModuleRow> {
+        let location = Some(Location::Line(line));
+        let row = String::from_utf8_lossy(row);
+
+        // The path is the last column and may contain commas itself
+        let fields = row
+            .splitn(columns.len(), ',')
+            .map(str::trim)
+            .collect::<Vec<_>>();
+        if fields.len() < columns.len() {
+            self.report.error(
+                location,
+                "module-table/row",
+                format!(
+                    "Module {index} has {} of the {} columns",
+                    fields.len(),
+                    columns.len()
+                ),
+            );
+            return None;
+        }
+
+        let mut values = BTreeMap::new();
+        let mut path = String::new();
+
+        for (column, field) in columns.iter().zip(&fields) {
+            let value = match *column {
+                "path" => {
+                    path = field.to_string();
+                    continue;
+                }
+                "id" | "containing_id" | "size" => parse_decimal(field),
+                _ => parse_hex(field),
+            };
+
+            match value {
+                Some(value) => {
+                    values.insert(*column, value);
+                }
+                None => self.report.error(
+                    location,
+                    "module-table/number",
+                    format!(
+                        "The {column} of module {index} is not a {} number: '{field}'",
+                        match *column {
+                            "id" | "containing_id" | "size" => "decimal",
+                            _ => "0x-prefixed hexadecimal",
+                        }
+                    ),
+                ),
+            }
+        }
+
+        if values.get("id").is_some_and(|id| *id != index as u64) {
+            self.report.error(
+                location,
+                "module-table/id",
+                format!("Module {index} has the id {}", values["id"]),
+            );
+        }
+
+        if path.is_empty() {
+            self.report.error(
+                location,
+                "module-table/path",
+                format!("Module {index} has no path"),
+            );
+        }
+
+        if let Some(containing_id) = values.get("containing_id").map(|id| *id as usize) {
+            match rows.get(containing_id) {
+                _ if containing_id > index => self.report.error(
+                    location,
+                    "module-table/containing-id",
+                    format!("Module {index} is contained in the later module {containing_id}"),
+                ),
+                Some(containing) if containing.path != path => self.report.error(
+                    location,
+                    "module-table/containing-id",
+                    format!(
+                        "Module {index} is contained in module {containing_id}, which has a different path ('{}')",
+                        containing.path
+                    ),
+                ),
+                _ => {}
+            }
+        }
+
+        let (start, end) = match (
+            values.get("base").or(values.get("start")),
+            values.get("end"),
+            values.get("size"),
+        ) {
+            (Some(start), Some(end), _) => (*start, *end),
+            (_, _, Some(size)) => (0, *size),
+            _ => return None,
+        };
+
+        if start >= end {
+            self.report.error(
+                location,
+                "module-table/range",
+                format!("Module {index} ends ({end:#x}) before it starts ({start:#x})"),
+            );
+        }
+
+        Some(ModuleRow { path, start, end })
+    }
+
+    /// Reports the modules whose address ranges overlap, which makes the module of the addresses
+    /// in between ambiguous
+    fn check_overlaps(&mut self, rows: &[ModuleRow], has_ranges: bool) {
+        if !has_ranges {
+            return;
+        }
+
+        let mut ranges = rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| row.start < row.end)
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|(_, row)| row.start);
+
+        for pair in ranges.windows(2) {
+            let ((first_index, first), (second_index, second)) = (pair[0], pair[1]);
+
+            if second.start < first.end {
+                self.report.error(
+                    None,
+                    "module-table/overlap",
+                    format!(
+                        "Modules {first_index} ({:#x}-{:#x}) and {second_index} ({:#x}-{:#x}) overlap",
+                        first.start, first.end, second.start, second.end
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Checks a basic block of the module with `module_id`, at `offset` of size `size`
+    fn check_basic_block(
+        &mut self,
+        location: Location,
+        modules: &[ModuleRow],
+        module_id: usize,
+        offset: u64,
+        size: u64,
+    ) {
+        let Some(module) = modules.get(module_id) else {
+            self.report.error(
+                Some(location),
+                "bb/module-id",
+                format!(
+                    "The basic block refers to module {module_id}, but the table has {} modules",
+                    modules.len()
+                ),
+            );
+            return;
+        };
+
+        if size == 0 {
+            self.report
+                .warning(Some(location), "bb/size", "The basic block is empty");
+        }
+
+        // Modules that end before they start are reported with the module table
+        let Some(module_size) = module.end.checked_sub(module.start) else {
+            return;
+        };
+
+        if offset + size > module_size {
+            self.report.error(
+                Some(location),
+                "bb/bounds",
+                format!(
+                    "The basic block at offset {offset:#x} of size {size} ends past the end of module {module_id} (size {module_size:#x}); offsets are relative to the start of the module"
+                ),
+            );
+        }
+    }
+
+    fn check_bb_table(&mut self, modules: &[ModuleRow]) {
+        let Some((line, header)) = self.next_line() else {
+            self.report
+                .error(None, "bb-table/header", "The basic block table is missing");
+            return;
+        };
+
+        let Some(count) = constants::BB_HEADER_REGEX
+            .captures(header)
+            .and_then(|cap| parse_capture_group::<usize>(&cap, "count"))
+        else {
+            self.report.error(
+                Some(Location::Line(line)),
+                "bb-table/header",
+                format!(
+                    "Expected 'BB Table: <count> bbs', found '{}'",
+                    header.escape_ascii()
+                ),
+            );
+            return;
+        };
+
+        if self
+            .peek_line()
+            .is_some_and(|line| constants::TEXT_BB_COLUMNS_REGEX.is_match(line))
+        {
+            self.next_line();
+            self.check_text_bb_table(count, modules);
+        } else {
+            self.check_binary_bb_table(count, modules);
+        }
+    }
+
+    fn check_text_bb_table(&mut self, count: usize, modules: &[ModuleRow]) {
+        self.report
+            .recognized
+            .push(format!("{count} basic blocks (text)"));
+
+        for index in 0..count {
+            let Some((line, entry)) = self.next_line() else {
+                self.report.error(
+                    None,
+                    "bb-table/truncated",
+                    format!("The basic block table ends after {index} of its {count} entries"),
+                );
+                return;
+            };
+
+            let location = Location::Line(line);
+            let Some((id, start, size)) =
+                constants::TEXT_BB_ENTRY_REGEX
+                    .captures(entry)
+                    .and_then(|cap| {
+                        let start = std::str::from_utf8(&cap["start"]).ok()?;
+                        Some((
+                            parse_capture_group::<usize>(&cap, "id")?,
+                            u64::from_str_radix(start, 16).ok()?,
+                            parse_capture_group::<u64>(&cap, "size")?,
+                        ))
+                    })
+            else {
+                self.report.error(
+                    Some(location),
+                    "bb/text-entry",
+                    format!(
+                        "Expected 'module[<id>]: 0x<start>, <size>', found '{}'",
+                        entry.escape_ascii()
+                    ),
+                );
+                continue;
+            };
+
+            self.check_basic_block(location, modules, id, start, size);
+        }
+
+        if self.next_line().is_some() {
+            self.report.warning(
+                Some(Location::Line(self.line)),
+                "bb-table/trailing-data",
+                "There are lines after the basic block table",
+            );
+        }
+    }
+
+    fn check_binary_bb_table(&mut self, count: usize, modules: &[ModuleRow]) {
+        self.report
+            .recognized
+            .push(format!("{count} basic blocks (binary)"));
+
+        let table_start = self.pos.min(self.contents.len());
+        let data = &self.contents[table_start..];
+        let (entries, _) = data.as_chunks::<{ constants::BB_ENTRY_SIZE }>();
+
+        if entries.len() < count {
+            self.report.error(
+                Some(Location::Offset(
+                    table_start + entries.len() * constants::BB_ENTRY_SIZE,
+                )),
+                "bb-table/truncated",
+                format!(
+                    "The basic block table ends after {} of its {count} entries",
+                    entries.len()
+                ),
+            );
+        }
+
+        let entries = &entries[..count.min(entries.len())];
+        let within_module = |(start, size, module_id): (u32, u16, u16)| {
+            modules
+                .get(module_id as usize)
+                .and_then(|module| module.end.checked_sub(module.start))
+                .is_some_and(|module_size| start as u64 + size as u64 <= module_size)
+        };
+
+        // Writers that dump their entries in the byte order of a big-endian host produce entries
+        // that only make sense the other way around
+        let little_endian = entries
+            .iter()
+            .filter(|entry| within_module(parse_bb_entry(entry, false)))
+            .count();
+        let big_endian = entries
+            .iter()
+            .filter(|entry| within_module(parse_bb_entry(entry, true)))
+            .count();
+
+        if big_endian > 2 * little_endian {
+            self.report.error(
+                Some(Location::Offset(table_start)),
+                "bb/endianness",
+                format!(
+                    "Only {little_endian} of the {} basic blocks are within their modules when read as little-endian, but {big_endian} are as big-endian; the entries must be little-endian",
+                    entries.len()
+                ),
+            );
+        }
+
+        for (index, entry) in entries.iter().enumerate() {
+            let (start, size, module_id) = parse_bb_entry(entry, false);
+
+            self.check_basic_block(
+                Location::Offset(table_start + index * constants::BB_ENTRY_SIZE),
+                modules,
+                module_id as usize,
+                start as u64,
+                size as u64,
+            );
+        }
+
+        let table_end = table_start + entries.len() * constants::BB_ENTRY_SIZE;
+        if table_end < self.contents.len() {
+            self.report.warning(
+                Some(Location::Offset(table_end)),
+                "bb-table/trailing-data",
+                format!(
+                    "There are {} bytes after the basic block table",
+                    self.contents.len() - table_end
+                ),
+            );
+        }
+    }
+}
+
+/// Checks that the drcov log in `contents` conforms to the format DynamoRIO writes
+pub fn check(contents: &[u8]) -> ConformanceReport {
+    let mut checker = Checker {
+        contents,
+        pos: 0,
+        line: 0,
+        crlf: false,
+        report: ConformanceReport::default(),
+    };
+
+    checker.check_header();
+    if let Some(modules) = checker.check_module_table() {
+        checker.check_bb_table(&modules);
+    }
+
+    checker.report
+}
+
+/// Checks the drcov log at `path` for `check-producer`, prints the violations of the format that
+/// have been found and fails if any of them are errors
+pub fn check_producer(path: &Path) -> anyhow::Result<()> {
+    let contents = std::fs::read(path)?;
+    let report = check(&decompress(&contents, None)?);
+
+    print!("{report}");
+
+    if report.errors() > 0 {
+        anyhow::bail!(
+            "'{}' does not conform to the drcov format ({} errors)",
+            path.display(),
+            report.errors()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(header: &str, entries: &[(u32, u16, u16)]) -> Vec<u8> {
+        let mut log = header.as_bytes().to_vec();
+        for (start, size, module_id) in entries {
+            log.extend_from_slice(&start.to_le_bytes());
+            log.extend_from_slice(&size.to_le_bytes());
+            log.extend_from_slice(&module_id.to_le_bytes());
+        }
+        log
+    }
+
+    #[test]
+    fn reports_the_violations_of_the_format() {
+        let valid = log(
+            "DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 2\nColumns: id, base, end, entry, path\n  0, 0x400000, 0x401000, 0x400100, /bin/app\n  1, 0x7f0000, 0x7f2000, 0x000000, /lib/libc.so\nBB Table: 2 bbs\n",
+            &[(0x100, 4, 0), (0x1ff0, 16, 1)],
+        );
+        let report = check(&valid);
+        assert_eq!((report.errors(), report.warnings()), (0, 0), "{report}");
+
+        let invalid = log(
+            "DRCOV FLAVOR: frida\nDRCOV VERSION: 2\nModule Table: version 2, count 3\nColumns: id, base, end, entry, path\n  0, 0x400000, 0x401000, 0x400100, /bin/app\n  2, 0x400800, 0x402000, 0x000000, /lib/libc.so\nBB Table: 3 bbs\n",
+            &[(0xff0, 32, 0), (0x10, 4, 7)],
+        );
+        let report = check(&invalid);
+        let big_endian = valid
+            .iter()
+            .copied()
+            .take(valid.len() - 16)
+            .chain([0, 0, 1, 0, 0, 4, 0, 0, 0, 0, 0x1f, 0xf0, 0, 16, 0, 1])
+            .collect::<Vec<_>>();
+        assert!(check(&big_endian).violates("bb/endianness"));
+
+        for rule in [
+            "header/order",
+            "module-table/count",
+            "module-table/id",
+            "module-table/overlap",
+            "bb-table/truncated",
+            "bb/bounds",
+            "bb/module-id",
+        ] {
+            assert!(report.violates(rule), "{rule} not reported in:\n{report}");
+        }
+
+        let inverted = log(
+            "DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x401000, 0x400000, 0x400100, /bin/app\nBB Table: 1 bbs\n",
+            &[(0x100, 4, 0)],
+        );
+        assert!(check(&inverted).violates("module-table/range"));
+    }
+}
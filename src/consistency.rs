@@ -0,0 +1,152 @@
+use crate::drcov::Module;
+use crate::dwarf::ObjectFile;
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+use std::collections::HashMap;
+
+mod constants {
+    /// How many bytes the size of a function may differ between its DWARF subprogram and its
+    /// symbol (e.g. because of trailing padding) before they are considered to disagree
+    pub const SIZE_TOLERANCE: u64 = 16;
+
+    /// The share of disagreeing functions above which a module is flagged
+    pub const MAX_DISAGREEING_RATIO: f64 = 0.1;
+
+    /// How many disagreeing functions are listed in the debug log
+    pub const MAX_LISTED_FUNCTIONS: usize = 5;
+}
+
+/// How the function boundaries of the DWARF subprograms of a module compare to its symbol table
+#[derive(Debug, Default)]
+pub struct BoundaryComparison {
+    /// Subprograms that were compared
+    pub checked: usize,
+    /// Subprograms that do not start at any function symbol
+    pub without_symbol: Vec<(u64, u64)>,
+    /// Subprograms whose size differs from the size of the symbol at their start
+    pub size_mismatches: Vec<(u64, u64)>,
+}
+
+impl BoundaryComparison {
+    pub fn disagreeing(&self) -> usize {
+        self.without_symbol.len() + self.size_mismatches.len()
+    }
+
+    /// Whether enough functions disagree to distrust the debug info, which is what happens when
+    /// the debug file belongs to a different build than the binary
+    pub fn diverges(&self) -> bool {
+        self.checked > 0
+            && self.disagreeing() as f64 / self.checked as f64 > constants::MAX_DISAGREEING_RATIO
+    }
+}
+
+/// Compares the `[begin, end)` ranges of the DWARF subprograms of a module (their main, entry
+/// range) against the function symbols of `binary`. Subprograms outside of the code of the binary
+/// are not compared. Returns `None` if the binary has no
+/// function symbols to compare against (e.g. it has been stripped).
+pub fn compare_function_boundaries(
+    binary: &ObjectFile,
+    subprograms: &[(u64, u64)],
+) -> Option<BoundaryComparison> {
+    let object = binary.with_object(|obj| obj);
+
+    // Address -> size of the largest function symbol at the address
+    let mut symbol_sizes = HashMap::<u64, u64>::new();
+
+    let symbols = if object.symbols().next().is_some() {
+        object.symbols()
+    } else {
+        object.dynamic_symbols()
+    };
+
+    for symbol in symbols {
+        if symbol.kind() != SymbolKind::Text || symbol.size() == 0 {
+            continue;
+        }
+
+        let size = symbol_sizes.entry(symbol.address()).or_default();
+        *size = (*size).max(symbol.size());
+    }
+
+    if symbol_sizes.is_empty() {
+        return None;
+    }
+
+    // Subprograms whose code the linker discarded are left at (or near) address zero
+    let text_ranges = object
+        .sections()
+        .filter(|section| section.kind() == SectionKind::Text)
+        .map(|section| section.address()..section.address() + section.size())
+        .collect::<Vec<_>>();
+
+    let mut res = BoundaryComparison::default();
+
+    for &(begin, end) in subprograms {
+        if !text_ranges.iter().any(|range| range.contains(&begin)) {
+            continue;
+        }
+
+        res.checked += 1;
+
+        match symbol_sizes.get(&begin) {
+            None => res.without_symbol.push((begin, end)),
+            Some(size) if size.abs_diff(end - begin) > constants::SIZE_TOLERANCE => {
+                res.size_mismatches.push((begin, end))
+            }
+            Some(_) => {}
+        }
+    }
+
+    Some(res)
+}
+
+/// Warns if the function boundaries of the debug info of `module` diverge from its symbol table.
+/// The symbols are taken from the module itself if it can be read, so that a separate debug file
+/// of another build is caught, and from `debug_object` otherwise.
+pub fn check_function_boundaries(
+    module: &Module,
+    debug_object: &ObjectFile,
+    subprograms: &[(u64, u64)],
+) {
+    let binary = ObjectFile::from_path(&module.path).ok();
+
+    let Some(comparison) =
+        compare_function_boundaries(binary.as_ref().unwrap_or(debug_object), subprograms)
+    else {
+        log::debug!(
+            "Module {} has no function symbols to check its debug info against",
+            module.path
+        );
+        return;
+    };
+
+    if !comparison.diverges() {
+        log::debug!(
+            "The debug info of module {} agrees with its symbol table ({} of {} functions disagree)",
+            module.path,
+            comparison.disagreeing(),
+            comparison.checked
+        );
+        return;
+    }
+
+    log::warn!(
+        "The DWARF function boundaries of module {} disagree with its symbol table: {} of {} functions ({} without a symbol at their start, {} of a different size). The debug info probably belongs to a different build; do not trust its coverage",
+        module.path,
+        comparison.disagreeing(),
+        comparison.checked,
+        comparison.without_symbol.len(),
+        comparison.size_mismatches.len()
+    );
+
+    for (begin, end) in comparison
+        .without_symbol
+        .iter()
+        .chain(&comparison.size_mismatches)
+        .take(constants::MAX_LISTED_FUNCTIONS)
+    {
+        log::debug!(
+            "Disagreeing function of {}: [{begin:#x}, {end:#x})",
+            module.path
+        );
+    }
+}
@@ -0,0 +1,1116 @@
+use crate::cache::AtomicFile;
+use crate::checksum::LineChecksums;
+use crate::cli::CliOptions;
+use crate::components::ComponentSummary;
+use crate::debuginfod::DebuginfodClient;
+use crate::dedup::Deduplicator;
+use crate::drcov::{Drcov, IterationMode};
+use crate::dwarf::{
+    gather_line_info, gather_line_info_with_timeouts, LineInfoFilters, SourceCoverage,
+};
+use crate::error::Error;
+use crate::function_keyed::FunctionKeyedCoverage;
+use crate::input::{is_stdin, parse_drcov_bytes, parse_input_file, ParsedInput};
+use crate::job::JobTarget;
+use crate::json::render_json_output;
+use crate::lcov::{read_lcov_coverage, TracefileWriter};
+use crate::limits::LimitExceeded;
+use crate::manifest::FileDigests;
+use crate::module_key::{ModuleKey, ModuleKeys};
+use crate::output::{write_text_output, CoverageReport, LineEnding, OutputFormat};
+use crate::output_template::OutputTarget;
+use crate::progress::Progress;
+use crate::report::ReportFormat;
+use crate::run_report::{InputOutcome, RunReport};
+use crate::state::{BinaryResolver, CoverageState};
+use crate::summary::Summary;
+use crate::util::relative_path;
+use crate::verify::verify_lcov_output;
+use crate::{
+    badge, cache, components, job, manifest, metrics, module_graph, oracle, read_only, report,
+    staleness, watch,
+};
+use clap::Parser;
+use futures::StreamExt;
+use itertools::Itertools;
+use roaring::RoaringBitmap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// An input file along with its line coverage
+struct GatheredInput {
+    /// The coverage of the input per flavor (see [`ParsedInput`])
+    drcovs: Vec<Drcov>,
+    coverage: SourceCoverage,
+    /// The line coverage of every iteration, if it is reported per iteration
+    iterations: Vec<SourceCoverage>,
+    /// The modules whose symbolization was abandoned after the module timeout
+    timed_out_modules: Vec<String>,
+}
+
+/// Parses `input_file` and gathers its line coverage. Parsing and symbolization are CPU-bound and
+/// run on the blocking pool, while the debug info of the modules is fetched asynchronously.
+async fn process_input_file(
+    options: Arc<CliOptions>,
+    debuginfod: Option<Arc<DebuginfodClient>>,
+    input_file: PathBuf,
+) -> (PathBuf, anyhow::Result<GatheredInput>, Duration) {
+    let started = Instant::now();
+    let result = async {
+        let ParsedInput { drcovs, coverage } = tokio::task::spawn_blocking({
+            let options = options.clone();
+            let input_file = input_file.clone();
+            move || parse_input_file(&input_file, &options)
+        })
+        .await??;
+
+        if let Some(progress) = &options.progress {
+            progress.file_parsed(&input_file);
+        }
+
+        if let Some(coverage) = coverage {
+            return Ok(GatheredInput {
+                drcovs,
+                coverage,
+                iterations: Vec::new(),
+                timed_out_modules: Vec::new(),
+            });
+        }
+
+        if let Some(debuginfod) = debuginfod {
+            debuginfod
+                .fetch_missing_debug_info(drcovs.iter().flat_map(module_paths).collect())
+                .await;
+        }
+
+        tokio::task::spawn_blocking(move || gather_input(&options, drcovs)).await?
+    }
+    .await;
+
+    (input_file, result, started.elapsed())
+}
+
+/// Hashes `input_files` for the deduplicator, `jobs` at a time on the blocking pool. The standard
+/// input can only be read once, so it is never hashed.
+async fn hash_input_files(
+    input_files: &[PathBuf],
+    jobs: usize,
+    with_sha256: bool,
+) -> Vec<Option<std::io::Result<FileDigests>>> {
+    futures::stream::iter(input_files.iter().cloned())
+        .map(|input_file| async move {
+            if is_stdin(&input_file) {
+                return None;
+            }
+
+            let digests =
+                tokio::task::spawn_blocking(move || FileDigests::of(&input_file, with_sha256))
+                    .await
+                    .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+
+            Some(digests)
+        })
+        .buffered(jobs)
+        .collect()
+        .await
+}
+
+/// The paths of the modules of `drcov` that may have debug information
+fn module_paths(drcov: &Drcov) -> Vec<String> {
+    drcov
+        .modules
+        .table
+        .iter()
+        .filter(|module| !module.is_pseudo_module())
+        .map(|module| module.path.clone())
+        .collect()
+}
+
+/// Gathers the line coverage of the parsed `drcovs`, each with the line info filters of its
+/// flavor. Fails if the symbolization takes longer than the input limits allow, instead of
+/// returning the partial coverage.
+fn gather_input(options: &CliOptions, drcovs: Vec<Drcov>) -> anyhow::Result<GatheredInput> {
+    let max_duration = options.get_input_limits().max_duration;
+    let deadline = max_duration.map(|max_duration| Instant::now() + max_duration);
+    let mut coverage = SourceCoverage::default();
+    let mut iterations = Vec::new();
+    let mut timed_out_modules = Vec::new();
+
+    for drcov in &drcovs {
+        let filters = LineInfoFilters {
+            deadline,
+            ..options.get_line_info_filters(&drcov.flavor)
+        };
+        let (drcov_coverage, drcov_timed_out_modules) =
+            gather_line_info_with_timeouts(&drcov.modules, &filters);
+        coverage.merge(drcov_coverage);
+        timed_out_modules.extend(drcov_timed_out_modules);
+
+        if options.iteration_mode == IterationMode::PerIteration {
+            iterations.extend(
+                drcov
+                    .iterations
+                    .iter()
+                    .map(|iteration| gather_line_info(iteration, &filters)),
+            );
+        }
+    }
+
+    if let Some(limit) =
+        max_duration.filter(|_| deadline.is_some_and(|deadline| Instant::now() >= deadline))
+    {
+        return Err(LimitExceeded::Duration { limit }.into());
+    }
+
+    Ok(GatheredInput {
+        drcovs,
+        coverage,
+        iterations,
+        timed_out_modules,
+    })
+}
+
+/// The coverage accumulated so far, the way it appears in the outputs
+struct FinalizedCoverage {
+    coverage: SourceCoverage,
+    module_coverage: HashMap<ModuleKey, RoaringBitmap>,
+    checksums: Option<LineChecksums>,
+    tests: Vec<(String, SourceCoverage)>,
+}
+
+/// The coverage accumulated over the input files processed so far
+#[derive(Default)]
+struct Conversion {
+    coverage: SourceCoverage,
+    // Test name -> coverage of the iterations of persistent-mode harnesses
+    iteration_coverage: Vec<(String, SourceCoverage)>,
+    // Module -> module relative offsets of the executed basic blocks
+    module_coverage: HashMap<ModuleKey, RoaringBitmap>,
+    // Module -> module relative offsets of the executed bytes, for the reachable oracle
+    module_bytes: HashMap<ModuleKey, RoaringBitmap>,
+    module_keys: ModuleKeys,
+    skipped_pseudo_modules: BTreeSet<String>,
+    timed_out_modules: BTreeSet<String>,
+    // Module captured from another build than its binary -> the number of inputs it is in
+    other_builds: BTreeMap<ModuleKey, usize>,
+    previous_coverages: Vec<RoaringBitmap>,
+    reduced_input_set: Vec<String>,
+    deduplicator: Option<Deduplicator>,
+    state: Option<CoverageState>,
+    num_input_files: usize,
+    /// What the output template is resolved from: the first input
+    output_target: Option<OutputTarget>,
+    run_report: Option<RunReport>,
+}
+
+impl Conversion {
+    fn new(options: &CliOptions) -> Self {
+        Self {
+            deduplicator: (!options.keep_duplicates).then(Deduplicator::default),
+            state: options.state_output.is_some().then(CoverageState::default),
+            run_report: options.run_report.is_some().then(RunReport::default),
+            ..Default::default()
+        }
+    }
+
+    /// Processes `input_files` and accumulates their coverage
+    fn process_input_files(
+        &mut self,
+        runtime: &tokio::runtime::Runtime,
+        options: &Arc<CliOptions>,
+        debuginfod: Option<&Arc<DebuginfodClient>>,
+        input_files: &[PathBuf],
+    ) {
+        self.num_input_files += input_files.len();
+
+        let jobs = options.get_jobs();
+        // The inputs are hashed in parallel but deduplicated in order, so that the first one of
+        // identical inputs is the one that gets processed
+        let mut digests = match self.deduplicator {
+            Some(_) => {
+                runtime.block_on(hash_input_files(input_files, jobs, options.needs_sha256()))
+            }
+            None => Vec::new(),
+        };
+        digests.resize_with(input_files.len(), || None);
+
+        let mut input_files_to_process = Vec::new();
+
+        for (input_file, digests) in input_files.iter().zip(digests) {
+            let Some((deduplicator, digests)) = self.deduplicator.as_mut().zip(digests) else {
+                input_files_to_process.push(input_file.clone());
+                continue;
+            };
+
+            match digests.map(|digests| deduplicator.find_duplicate(input_file, digests)) {
+                Ok(Some(original)) => {
+                    log::info!(
+                        "Skipping '{}' since it is identical to '{}'",
+                        input_file.display(),
+                        original.display()
+                    );
+                    if let Some(run_report) = self.run_report.as_mut() {
+                        run_report.add_input(input_file, InputOutcome::Duplicate, None, None);
+                    }
+                }
+                Ok(None) => input_files_to_process.push(input_file.clone()),
+                Err(e) => {
+                    log::warn!("Could not hash '{}': {e}", input_file.display());
+                    input_files_to_process.push(input_file.clone());
+                }
+            }
+        }
+
+        let progress = options.progress.as_deref();
+
+        if let Some(progress) = progress {
+            progress.files_found(input_files_to_process.len());
+        }
+
+        let started = Instant::now();
+
+        runtime.block_on(async {
+            // Inputs are processed concurrently but their results are consumed in order, so that
+            // the reduced set and the logs do not depend on scheduling
+            let mut results = futures::stream::iter(input_files_to_process)
+                .map(|input_file| {
+                    process_input_file(options.clone(), debuginfod.cloned(), input_file)
+                })
+                .buffered(jobs);
+
+            while let Some((input_file, result, duration)) = results.next().await {
+                let (outcome, error) = match result {
+                    Ok(gathered) => {
+                        self.add(options, &input_file, gathered);
+                        (InputOutcome::Converted, None)
+                    }
+                    Err(e) => {
+                        match e.downcast_ref::<Error>() {
+                            Some(Error::Io(_)) => log::warn!("Could not read '{}'. Skipping from line coverage analysis. Reason: {e}", input_file.display()),
+                            _ => log::warn!("Could not parse '{}' as a coverage file. Skipping from line coverage analysis. Reason: {e}", input_file.display()),
+                        }
+                        (InputOutcome::Failed, Some(e.to_string()))
+                    }
+                };
+
+                if let Some(run_report) = self.run_report.as_mut() {
+                    run_report.add_input(&input_file, outcome, error, Some(duration));
+                }
+
+                if let Some(progress) = progress {
+                    progress.file_processed(&input_file);
+                }
+            }
+        });
+
+        if let Some(progress) = progress {
+            progress.finished();
+        }
+
+        if let Some(run_report) = self.run_report.as_mut() {
+            run_report.add_processing_time(started.elapsed());
+        }
+    }
+
+    fn add(&mut self, options: &CliOptions, input_file: &Path, gathered: GatheredInput) {
+        let GatheredInput {
+            drcovs,
+            coverage: info,
+            iterations,
+            timed_out_modules,
+        } = gathered;
+
+        self.coverage.merge(info);
+
+        if let Some(drcov) = drcovs.first().filter(|_| self.output_target.is_none()) {
+            self.output_target = Some(OutputTarget::of(input_file, drcov));
+        }
+
+        // lcov test names may only consist of letters, digits and underscores
+        let test_name_prefix = input_file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+
+        self.iteration_coverage
+            .extend(iterations.into_iter().enumerate().map(|(index, coverage)| {
+                (
+                    format!("{test_name_prefix}_iteration_{}", index + 1),
+                    coverage,
+                )
+            }));
+
+        self.timed_out_modules.extend(timed_out_modules);
+
+        for drcov in &drcovs {
+            self.add_modules(options, input_file, drcov);
+        }
+
+        if options.reduce_set_path.is_some() {
+            let mut modules_coverage = RoaringBitmap::new();
+            for drcov in &drcovs {
+                modules_coverage |= drcov.modules.get_coverage_all();
+            }
+
+            if !self.previous_coverages.contains(&modules_coverage) {
+                self.reduced_input_set
+                    .push(input_file.to_string_lossy().to_string());
+                self.previous_coverages.push(modules_coverage);
+            }
+        }
+    }
+
+    /// Adds the modules of `drcov`, one of the flavors of `input_file`
+    fn add_modules(&mut self, options: &CliOptions, input_file: &Path, drcov: &Drcov) {
+        log::debug!(
+            "Parsed '{}' (drcov version: {}, flavor: {}, module table version: {})",
+            input_file.display(),
+            drcov.version,
+            drcov.flavor,
+            drcov.modules.version
+        );
+
+        if let Some(state) = self.state.as_mut() {
+            state.add(drcov);
+        }
+
+        self.skipped_pseudo_modules.extend(
+            drcov
+                .modules
+                .table
+                .iter()
+                .filter(|module| {
+                    module.is_pseudo_module()
+                        || (module.is_dynamic_loader() && !options.include_loader)
+                })
+                .map(|module| module.path.clone()),
+        );
+
+        let captured_builds = drcov.modules.captured_builds();
+        let mut other_builds = BTreeSet::new();
+
+        for module in &drcov.modules.table {
+            let key = self.module_keys.key(
+                &module.path,
+                &captured_builds
+                    .get(module.path.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+            );
+            if key.other_build.is_some() {
+                other_builds.insert(key.clone());
+            }
+
+            // The disassembly of the binary at the path is the one of another build
+            if options.reachable_oracle.is_some()
+                && key.other_build.is_none()
+                && !self.skipped_pseudo_modules.contains(&module.path)
+            {
+                self.module_bytes.entry(key.clone()).or_default().extend(
+                    module
+                        .bb_bitmap
+                        .iter()
+                        .filter_map(|offset| offset.checked_add(module.segment_offset as u32)),
+                );
+            }
+
+            self.module_coverage
+                .entry(key)
+                .or_default()
+                .extend(module.bb_module_offsets());
+        }
+
+        for key in other_builds {
+            *self.other_builds.entry(key).or_default() += 1;
+        }
+    }
+
+    /// Rewrites the source paths of `coverage` the way they appear in the outputs
+    fn finalize_paths(options: &CliOptions, coverage: SourceCoverage) -> SourceCoverage {
+        let coverage = match options.relative_to.as_ref().map(Path::new) {
+            Some(root) => coverage.map_source_paths(|path| relative_path(path, root)),
+            None => coverage,
+        };
+
+        options.get_scrubber().scrub_coverage(coverage)
+    }
+
+    /// The coverage the webhook is notified about, with its paths rewritten like in the outputs
+    fn notified_coverage(&self, options: &CliOptions) -> SourceCoverage {
+        Self::finalize_paths(options, self.coverage.clone())
+    }
+
+    /// The path of the output file, with the output template (if any) resolved from the inputs
+    /// processed so far
+    fn output_path(&self, options: &CliOptions) -> String {
+        match &options.output_template {
+            Some(template) => {
+                let target = self.output_target.clone().unwrap_or_default();
+                let output = template.resolve(&target);
+                log::info!("Resolved the output template to '{output}'");
+                output
+            }
+            None => options.output.clone(),
+        }
+    }
+
+    /// Writes every requested output and then the run report (if requested), which records how
+    /// writing the outputs went
+    fn write_outputs_and_report(&mut self, options: &CliOptions) -> anyhow::Result<()> {
+        let started = Instant::now();
+        let res = self.write_outputs(options);
+
+        if let Some((path, run_report)) = options.run_report.as_ref().zip(self.run_report.as_mut())
+        {
+            run_report.add_writing_time(started.elapsed());
+            run_report.write(path, options, &Summary::from_coverage(&self.coverage), &res)?;
+        }
+
+        res
+    }
+
+    /// Finalizes the paths of the coverage accumulated so far and checksums its lines on request
+    fn finalize(&self, options: &CliOptions) -> FinalizedCoverage {
+        let coverage = self.coverage.clone();
+
+        if options.checksum {
+            staleness::warn_about_stale_sources(&coverage, options.source_path_resolver.as_deref());
+        }
+
+        // Checksums have to be computed before scrubbing since they need the real source paths
+        let checksums = options
+            .checksum
+            .then(|| LineChecksums::from_coverage(&coverage));
+
+        let checksums = match options.relative_to.as_ref().map(Path::new) {
+            Some(root) => {
+                checksums.map(|checksums| checksums.map_paths(|path| relative_path(path, root)))
+            }
+            None => checksums,
+        };
+
+        let scrubber = options.get_scrubber();
+        let checksums = checksums.map(|checksums| checksums.map_paths(|path| scrubber.scrub(path)));
+        let coverage = Self::finalize_paths(options, coverage);
+        let module_coverage = scrubber.scrub_modules(self.module_coverage.clone());
+        let tests = self
+            .iteration_coverage
+            .iter()
+            .map(|(name, coverage)| {
+                (
+                    name.clone(),
+                    Self::finalize_paths(options, coverage.clone()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        FinalizedCoverage {
+            coverage,
+            module_coverage,
+            checksums,
+            tests,
+        }
+    }
+
+    /// Renders the coverage accumulated so far in the text `format` instead of writing it to a
+    /// file
+    fn render_output(&self, options: &CliOptions, format: OutputFormat) -> anyhow::Result<String> {
+        let FinalizedCoverage {
+            coverage,
+            module_coverage,
+            checksums,
+            tests,
+        } = self.finalize(options);
+
+        let report = CoverageReport {
+            coverage: &coverage,
+            modules: &module_coverage,
+            checksums: checksums.as_ref(),
+            tests: &tests,
+            components: options.component_map.as_ref(),
+            line_ending: LineEnding::Lf,
+        };
+
+        match format {
+            OutputFormat::Lcov => Ok(TracefileWriter {
+                checksums: report.checksums,
+                ..Default::default()
+            }
+            .render(report.coverage, report.tests)),
+            OutputFormat::Json => render_json_output(&report),
+            _ => anyhow::bail!(
+                "The {} format can only be written to a file",
+                format.writer().name()
+            ),
+        }
+    }
+
+    /// Writes every requested output from the coverage accumulated so far
+    fn write_outputs(&self, options: &CliOptions) -> anyhow::Result<()> {
+        let output = self.output_path(options);
+        let FinalizedCoverage {
+            coverage,
+            module_coverage,
+            checksums,
+            tests,
+        } = self.finalize(options);
+        let coverage = Self::append_to_output(options, &output, coverage)?;
+
+        let report = CoverageReport {
+            coverage: &coverage,
+            modules: &module_coverage,
+            checksums: checksums.as_ref(),
+            tests: &tests,
+            components: options.component_map.as_ref(),
+            line_ending: options.output_eol,
+        };
+
+        Self::write_output(options, options.output_format, &output, &report)?;
+        self.write_side_outputs(options, &report)
+    }
+
+    /// Merges the coverage of the lcov tracefile at `output` into `coverage` on `--append`
+    fn append_to_output(
+        options: &CliOptions,
+        output: &str,
+        coverage: SourceCoverage,
+    ) -> anyhow::Result<SourceCoverage> {
+        if !options.append || !Path::new(output).is_file() {
+            return Ok(coverage);
+        }
+
+        let previous = read_lcov_coverage(output)
+            .map_err(|e| anyhow::anyhow!("Could not read '{output}' to merge into it: {e}"))?;
+
+        log::info!(
+            "Merging the coverage of {} source files from '{}'",
+            previous.lines.len(),
+            output
+        );
+
+        let mut merged = coverage;
+        merged.merge(SourceCoverage {
+            lines: previous.lines,
+            branches: previous.branches,
+            ..Default::default()
+        });
+
+        Ok(merged)
+    }
+
+    /// Writes `report` to `output` in `format` and verifies it on request
+    fn write_output(
+        options: &CliOptions,
+        format: OutputFormat,
+        output: &str,
+        report: &CoverageReport,
+    ) -> anyhow::Result<()> {
+        let writer = format.writer();
+        writer
+            .write(output, report)
+            .map_err(|e| anyhow::anyhow!("Could not write the {} output: {e}", writer.name()))?;
+
+        if options.verify_output {
+            verify_lcov_output(output, report.coverage)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the outputs that come along with the main output (the reduced input set, badges,
+    /// metrics, state, ...) and logs how the conversion went. They are written once, however many
+    /// formats the main output is written in.
+    fn write_side_outputs(
+        &self,
+        options: &CliOptions,
+        report: &CoverageReport,
+    ) -> anyhow::Result<()> {
+        let scrubber = options.get_scrubber();
+
+        let num_duplicates = self
+            .deduplicator
+            .as_ref()
+            .map(Deduplicator::num_duplicates)
+            .unwrap_or_default();
+
+        if let Some(reduce_set_path) = &options.reduce_set_path {
+            let reduced_input_set = self
+                .reduced_input_set
+                .iter()
+                .map(|path| scrubber.scrub(path))
+                .collect::<Vec<_>>();
+            write_text_output(
+                reduce_set_path,
+                &reduced_input_set.join("\n"),
+                options.output_eol,
+            )?;
+
+            if let Some(reduce_set_manifest) = &options.reduce_set_manifest {
+                let mut manifest = vec!["# path\tsize\tsha256".to_string()];
+                for (path, scrubbed_path) in self.reduced_input_set.iter().zip(&reduced_input_set) {
+                    // The inputs hashed to be deduplicated are not read again
+                    let digests = match self
+                        .deduplicator
+                        .as_ref()
+                        .and_then(|deduplicator| deduplicator.digests(Path::new(path)))
+                    {
+                        Some(digests) if digests.sha256.is_some() => digests.clone(),
+                        _ => FileDigests::of(Path::new(path), true)?,
+                    };
+                    manifest.push(format!(
+                        "{scrubbed_path}\t{}\t{}",
+                        digests.size,
+                        digests.sha256.unwrap_or_default()
+                    ));
+                }
+                write_text_output(
+                    reduce_set_manifest,
+                    &manifest.join("\n"),
+                    options.output_eol,
+                )?;
+            }
+
+            log::info!(
+                "Reduced the set of {} input files to {} files with the same coverage ({num_duplicates} duplicates skipped before)",
+                self.num_input_files,
+                self.reduced_input_set.len()
+            );
+        }
+
+        log::info!(
+            "Processed {} input files ({num_duplicates} duplicates skipped)",
+            self.num_input_files - num_duplicates
+        );
+
+        if !self.skipped_pseudo_modules.is_empty() {
+            log::info!(
+                "Skipped {} pseudo-modules without line information: {} (use --include-loader to include the dynamic loader)",
+                self.skipped_pseudo_modules.len(),
+                self.skipped_pseudo_modules.iter().join(", ")
+            );
+        }
+
+        if !self.timed_out_modules.is_empty() {
+            log::warn!(
+                "Abandoned gathering the debug information of {} modules after the module timeout: {}",
+                self.timed_out_modules.len(),
+                self.timed_out_modules.iter().join(", ")
+            );
+        }
+
+        if !self.other_builds.is_empty() {
+            log::warn!(
+                "The coverage of {} modules was captured from other builds than the binaries at their paths, so it is reported apart from theirs and without line coverage (builds are told apart by their size once loaded unless the module table records their build-id, so rebuilds of the same size go unnoticed): {}",
+                self.other_builds.len(),
+                self.other_builds
+                    .iter()
+                    .map(|(key, num_inputs)| format!("{key} in {num_inputs} inputs"))
+                    .join(", ")
+            );
+        }
+
+        let summary = Summary::from_coverage(report.coverage);
+        log::info!("Summary coverage rate:\n{summary}");
+
+        let component_rollups = options
+            .component_map
+            .as_ref()
+            .map(|component_map| component_map.rollups(report.coverage))
+            .unwrap_or_default();
+
+        if options.component_map.is_some() {
+            log::info!(
+                "Coverage rate by component:\n{}",
+                ComponentSummary(&component_rollups)
+            );
+        }
+
+        if let Some(badge_dir) = &options.badge_dir {
+            badge::write_badges(Path::new(badge_dir), &summary, &component_rollups)?;
+        }
+
+        if let Some(metrics_output) = &options.metrics_output {
+            metrics::write_metrics(metrics_output, &summary, report.modules, &component_rollups)?;
+        }
+
+        if let Some((dedup_manifest, deduplicator)) = options
+            .dedup_manifest
+            .as_ref()
+            .zip(self.deduplicator.as_ref())
+        {
+            deduplicator.write_manifest(
+                dedup_manifest,
+                |path| scrubber.scrub(path),
+                options.output_eol,
+            )?;
+        }
+
+        if options.function_keyed_output.is_some() || options.compare_functions.is_some() {
+            let function_coverage = FunctionKeyedCoverage::from_coverage(report.coverage);
+
+            if let Some(function_keyed_output) = &options.function_keyed_output {
+                function_coverage.write(function_keyed_output, options.output_eol)?;
+            }
+
+            if let Some(baseline) = &options.compare_functions {
+                let comparison = function_coverage.compare(&FunctionKeyedCoverage::read(baseline)?);
+                log::info!("Function coverage compared to '{baseline}':\n{comparison}");
+            }
+        }
+
+        for (writer, path) in options.get_extra_writers() {
+            writer.write(path, report).map_err(|e| {
+                anyhow::anyhow!("Could not write the {} output: {e}", writer.name())
+            })?;
+        }
+
+        if let Some(reachable_oracle) = &options.reachable_oracle {
+            oracle::write_oracle_report(
+                reachable_oracle,
+                &self.module_bytes,
+                |path| scrubber.scrub(path),
+                options.output_eol,
+            )?;
+        }
+
+        if let Some(module_graph) = &options.module_graph {
+            module_graph::write_module_graph(
+                module_graph,
+                &self.module_coverage,
+                |path| scrubber.scrub(path),
+                options.output_eol,
+            )?;
+        }
+
+        if let Some((state_output, state)) = options.state_output.as_ref().zip(self.state.as_ref())
+        {
+            state.write(state_output, &scrubber)?;
+        }
+
+        if let Some(vscode_watch) = &options.vscode_watch {
+            let project_coverage = watch::project_coverage(&self.coverage, options);
+            let project_coverage = Self::finalize_paths(options, project_coverage);
+            watch::write_vscode_watch_file(vscode_watch, &project_coverage, options.output_eol)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts the inputs of `options` to the requested outputs, processing them on `runtime`
+pub fn convert(mut options: CliOptions, runtime: &tokio::runtime::Runtime) -> anyhow::Result<()> {
+    // The debug information of a module is only walked for the first input it appears in
+    if options.line_tables.is_none() {
+        options.line_tables = Some(Arc::new(options.get_line_table_cache()));
+    }
+    // The binary directories are only indexed for the first input that needs them
+    if options.binary_resolver.is_none() {
+        options.binary_resolver = Some(Arc::new(BinaryResolver::new(&options.binary_dirs)));
+    }
+    let options = Arc::new(options);
+    let debuginfod = options.get_debuginfod_client()?.map(Arc::new);
+
+    if let Some(manifest) = &options.verify_manifest {
+        let corpus = options.directory.as_deref().unwrap_or_default();
+        return manifest::verify(Path::new(manifest), Path::new(corpus));
+    }
+
+    let mut conversion = Conversion::new(&options);
+
+    if options.watch {
+        let mut notifier = options.get_notifier()?;
+
+        return watch::run(&options, |input_files| {
+            conversion.process_input_files(runtime, &options, debuginfod.as_ref(), input_files);
+            conversion.write_outputs_and_report(&options)?;
+
+            if let Some(notifier) = &mut notifier {
+                notifier.notify(runtime, &conversion.notified_coverage(&options));
+            }
+
+            Ok(())
+        });
+    }
+
+    let input_files = options.get_input_files();
+    conversion.process_input_files(runtime, &options, debuginfod.as_ref(), &input_files);
+    conversion.write_outputs_and_report(&options)?;
+
+    if let Some(cache_max_size) = options.cache_max_size {
+        cache::enforce_max_size(options.get_caches(), cache_max_size);
+    }
+
+    Ok(())
+}
+
+/// Converts the drcov log `contents` (e.g. an upload named `name`) on its own and returns its
+/// coverage rendered in the text `format`. The debug information of its modules is fetched from
+/// `debuginfod` on `runtime` if it is missing.
+pub fn convert_contents(
+    options: &CliOptions,
+    runtime: &tokio::runtime::Runtime,
+    debuginfod: Option<&DebuginfodClient>,
+    name: &str,
+    contents: &[u8],
+    format: OutputFormat,
+) -> anyhow::Result<String> {
+    let drcov = parse_drcov_bytes(contents, options)?;
+
+    if let Some(debuginfod) = debuginfod {
+        runtime.block_on(debuginfod.fetch_missing_debug_info(module_paths(&drcov)));
+    }
+
+    let mut conversion = Conversion::new(options);
+    conversion.num_input_files = 1;
+    conversion.add(
+        options,
+        Path::new(name),
+        gather_input(options, vec![drcov])?,
+    );
+
+    conversion.render_output(options, format)
+}
+
+/// Runs the conversions of the targets of the job at `job`, one after the other on a shared
+/// runtime, reporting their progress to `progress`. A failing target does not stop the others.
+pub fn run_job(job: &Path, progress: Option<Arc<dyn Progress>>) -> anyhow::Result<()> {
+    let targets = job::parse_job(job)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let mut failed_targets = Vec::new();
+
+    for JobTarget { name, mut options } in targets {
+        log::info!("Running target {name}");
+        options.progress = progress.clone();
+
+        match convert(options, &runtime) {
+            Ok(()) => log::info!("Target {name} done"),
+            Err(e) => {
+                log::error!("Target {name} failed: {e}");
+                failed_targets.push(name);
+            }
+        }
+    }
+
+    if !failed_targets.is_empty() {
+        anyhow::bail!(
+            "{} targets of the job failed: {}",
+            failed_targets.len(),
+            failed_targets.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Renders the coverage state at `load_state` in every one of `formats`. The state is only
+/// symbolized once; `options` are parsed like the options of a conversion.
+pub fn report(
+    load_state: String,
+    formats: Vec<ReportFormat>,
+    output_dir: String,
+    options: Vec<String>,
+) -> anyhow::Result<()> {
+    let output_dir = PathBuf::from(output_dir);
+
+    let args = [
+        "drcov2lcov".to_string(),
+        "--input".to_string(),
+        load_state,
+        "--output".to_string(),
+        ReportFormat::Output(OutputFormat::Lcov)
+            .output_path(&output_dir)
+            .to_string_lossy()
+            .to_string(),
+    ]
+    .into_iter()
+    .chain(options);
+
+    let options = CliOptions::try_parse_from(args)?;
+    // Every format is written to the output directory, which has to exist for the options to be
+    // valid
+    read_only::enforce(
+        options
+            .get_read_only_policy()
+            .map(|policy| policy.with_writable(&output_dir)),
+    );
+    read_only::create_dir_all(&output_dir)?;
+    let options = Arc::new(options.validate()?);
+
+    let formats = formats.into_iter().unique().collect::<Vec<_>>();
+    for format in &formats {
+        match format {
+            ReportFormat::Output(output_format) => options.check_output_format(*output_format)?,
+            ReportFormat::Html if options.append => {
+                anyhow::bail!("--append can only be used with the lcov output format")
+            }
+            ReportFormat::Html => {}
+        }
+    }
+
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    let mut conversion = Conversion::new(&options);
+    conversion.process_input_files(&runtime, &options, None, &options.get_input_files());
+
+    let FinalizedCoverage {
+        coverage,
+        module_coverage,
+        checksums,
+        tests,
+    } = conversion.finalize(&options);
+    let coverage = Conversion::append_to_output(&options, &options.output, coverage)?;
+
+    let report = CoverageReport {
+        coverage: &coverage,
+        modules: &module_coverage,
+        checksums: checksums.as_ref(),
+        tests: &tests,
+        components: options.component_map.as_ref(),
+        line_ending: options.output_eol,
+    };
+
+    for format in formats {
+        let output_path = format.output_path(&output_dir);
+
+        match format {
+            ReportFormat::Output(output_format) => {
+                let output = output_path.to_string_lossy();
+                Conversion::write_output(&options, output_format, &output, &report)?;
+            }
+            ReportFormat::Html => {
+                if !options.checksum {
+                    staleness::warn_about_stale_sources(
+                        &conversion.coverage,
+                        options.source_path_resolver.as_deref(),
+                    );
+                }
+
+                let lcov_file = AtomicFile::new(output_dir.join(".html.info"));
+                let lcov_output = lcov_file.temp_path().to_string_lossy();
+                Conversion::write_output(&options, OutputFormat::Lcov, &lcov_output, &report)?;
+                report::generate_html(lcov_file.temp_path(), &output_path)?;
+
+                if let Some(component_map) = &options.component_map {
+                    components::write_html(
+                        &component_map.rollups(&coverage),
+                        &output_path.join(report::COMPONENTS_PAGE),
+                    )?;
+                }
+            }
+        }
+    }
+
+    conversion.write_side_outputs(&options, &report)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drcov::DrcovFilters;
+    use crate::dwarf::LineInfo;
+    use crate::scrub::Scrubber;
+    use crate::test_fixtures::{drcov_log, small_binary, TempPath};
+
+    #[test]
+    fn reports_into_a_fresh_directory_and_writes_the_side_outputs_once() {
+        let dir = TempPath::dir("report");
+        let drcov = Drcov::from_bytes(
+            &drcov_log(&small_binary(), &[(0x1000, 16)]),
+            &DrcovFilters::default(),
+            None,
+        )
+        .unwrap();
+        let mut state = CoverageState::default();
+        state.add(&drcov);
+        let state_path = dir.join("app.covstate");
+        state
+            .write(
+                &state_path.to_string_lossy(),
+                &Scrubber { scrub_filters: &[] },
+            )
+            .unwrap();
+
+        let output_dir = dir.join("reports");
+        let sqlite_output = dir.join("runs.sqlite");
+        report(
+            state_path.to_string_lossy().to_string(),
+            ["lcov", "json", "cobertura"]
+                .into_iter()
+                .map(|format| format.parse().unwrap())
+                .collect(),
+            output_dir.to_string_lossy().to_string(),
+            vec![
+                "--sqlite-output".to_string(),
+                sqlite_output.to_string_lossy().to_string(),
+            ],
+        )
+        .unwrap();
+
+        for file_name in ["coverage.info", "coverage.json", "cobertura.xml"] {
+            assert!(output_dir.join(file_name).is_file(), "{file_name}");
+        }
+
+        let db = rusqlite::Connection::open(&sqlite_output).unwrap();
+        let runs: i64 = db
+            .query_row("SELECT COUNT(*) FROM runs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn reports_check_the_options_of_every_format() {
+        let dir = TempPath::dir("report");
+        let state_path = dir.join("app.covstate");
+        std::fs::write(&state_path, "").unwrap();
+
+        let err = report(
+            state_path.to_string_lossy().to_string(),
+            vec!["lcov".parse().unwrap(), "json".parse().unwrap()],
+            dir.join("reports").to_string_lossy().to_string(),
+            vec!["--verify-output".to_string()],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("lcov output format"), "{err}");
+    }
+
+    #[test]
+    fn notifies_of_the_scrubbed_paths() {
+        let options = CliOptions::try_parse_from([
+            "drcov2lcov",
+            "--input",
+            "app.drcov",
+            "--scrub",
+            "^/home/[^/]+/:",
+            "--notify-webhook",
+            "http://localhost/",
+            "--notify-on",
+            "parser",
+        ])
+        .unwrap();
+        let mut notifier = options.get_notifier().unwrap().unwrap();
+
+        let mut conversion = Conversion::new(&options);
+        conversion.coverage.lines.insert(
+            "/home/alice/src/parser.c".to_string(),
+            vec![LineInfo {
+                line: 1,
+                executed: true,
+            }],
+        );
+
+        let payloads = notifier
+            .payloads(&conversion.notified_coverage(&options))
+            .into_iter()
+            .map(|(_, payload)| payload)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            payloads,
+            [r#"{"text": "The file src/parser.c has been covered for the first time"}"#]
+        );
+    }
+}
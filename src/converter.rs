@@ -0,0 +1,269 @@
+use crate::cli::CliOptions;
+use crate::conversion;
+use crate::filter::{Filter, ReplacementFilter};
+use crate::output::OutputFormat;
+use crate::progress::Progress;
+use crate::source_path::SourcePathResolver;
+use clap::Parser;
+use regex::bytes::Regex;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Builds and runs a conversion programmatically, like the command line tool would run it:
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// drcov2lcov::Converter::new()
+///     .add_input("drcov.app.1234.log")
+///     .module_filter("app")
+///     .path_map("^/build/", "/home/user/src/")
+///     .output_lcov("app.info")
+///     .run()
+/// # }
+/// ```
+///
+/// Invalid regular expressions are reported by [`Converter::run`].
+#[derive(Debug, Clone, Default)]
+pub struct Converter {
+    inputs: Vec<PathBuf>,
+    module_filters: Vec<Filter>,
+    module_skip_filters: Vec<Filter>,
+    source_filters: Vec<Filter>,
+    source_skip_filters: Vec<Filter>,
+    path_map_filters: Vec<ReplacementFilter>,
+    output: Option<(OutputFormat, PathBuf)>,
+    progress: Option<Arc<dyn Progress>>,
+    source_path_resolver: Option<Arc<dyn SourcePathResolver>>,
+    errors: Vec<String>,
+}
+
+impl Converter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an input: any input of the command line tool, e.g. a drcov log or an archive of them
+    pub fn add_input(mut self, path: impl Into<PathBuf>) -> Self {
+        self.inputs.push(path.into());
+        self
+    }
+
+    /// Records `regex` as a filter, or the error of an invalid one
+    fn filter(errors: &mut Vec<String>, filters: &mut Vec<Filter>, regex: &str) {
+        match regex.parse() {
+            Ok(filter) => filters.push(filter),
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    /// Only keeps the modules whose path matches `regex` (any of the module filters)
+    pub fn module_filter(mut self, regex: &str) -> Self {
+        Self::filter(&mut self.errors, &mut self.module_filters, regex);
+        self
+    }
+
+    /// Skips the modules whose path matches `regex`
+    pub fn module_skip_filter(mut self, regex: &str) -> Self {
+        Self::filter(&mut self.errors, &mut self.module_skip_filters, regex);
+        self
+    }
+
+    /// Only keeps the source files whose path matches `regex` (any of the source filters)
+    pub fn source_filter(mut self, regex: &str) -> Self {
+        Self::filter(&mut self.errors, &mut self.source_filters, regex);
+        self
+    }
+
+    /// Skips the source files whose path matches `regex`
+    pub fn source_skip_filter(mut self, regex: &str) -> Self {
+        Self::filter(&mut self.errors, &mut self.source_skip_filters, regex);
+        self
+    }
+
+    /// Looks for the modules whose path matches `regex` with the match replaced by `replacement`
+    pub fn path_map(mut self, regex: &str, replacement: impl Into<String>) -> Self {
+        match Regex::new(regex) {
+            Ok(matcher) => self.path_map_filters.push(ReplacementFilter {
+                matcher,
+                replacement: replacement.into(),
+            }),
+            Err(_) => self.errors.push(format!(
+                "Could not create a regular expression from '{regex}'"
+            )),
+        }
+        self
+    }
+
+    /// Writes the output in `format` to `path`. Without an output, the lcov output is written to
+    /// `coverage.info` in the current directory.
+    pub fn output(mut self, format: OutputFormat, path: impl Into<PathBuf>) -> Self {
+        self.output = Some((format, path.into()));
+        self
+    }
+
+    /// Writes the lcov output to `path`
+    pub fn output_lcov(self, path: impl Into<PathBuf>) -> Self {
+        self.output(OutputFormat::Lcov, path)
+    }
+
+    /// Reports the progress of the conversion to `progress`
+    pub fn progress(mut self, progress: impl Progress + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Rewrites the source file paths of the debug information with `resolver` before they are
+    /// filtered and reported
+    pub fn source_path_resolver(mut self, resolver: impl SourcePathResolver + 'static) -> Self {
+        self.source_path_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Returns the validated options of the command line tool that describe the conversion
+    pub fn options(self) -> anyhow::Result<CliOptions> {
+        if let Some(error) = self.errors.first() {
+            anyhow::bail!("{error}");
+        }
+
+        if self.inputs.is_empty() {
+            anyhow::bail!("The conversion has no inputs");
+        }
+
+        let args = std::iter::once("drcov2lcov".to_string()).chain(
+            self.inputs
+                .iter()
+                .flat_map(|input| ["--input".to_string(), input.to_string_lossy().to_string()]),
+        );
+
+        let mut options = CliOptions::try_parse_from(args)?;
+        options.module_filters = self.module_filters;
+        options.module_skip_filters = self.module_skip_filters;
+        options.source_filters = self.source_filters;
+        options.source_skip_filters = self.source_skip_filters;
+        options.path_map_filters = self.path_map_filters;
+        options.progress = self.progress;
+        options.source_path_resolver = self.source_path_resolver;
+
+        if let Some((format, path)) = self.output {
+            options.output_format = format;
+            options.output = path.to_string_lossy().to_string();
+        }
+
+        options.validate()
+    }
+
+    /// Runs the conversion
+    pub fn run(self) -> anyhow::Result<()> {
+        conversion::convert(self.options()?, &tokio::runtime::Runtime::new()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lcov::read_lcov_coverage;
+    use crate::summary::Summary;
+    use crate::test_fixtures::{test_binary_log, TempPath};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn converts_end_to_end() {
+        let directory = TempPath::dir("converter");
+        let input = directory.join("drcov.test.log");
+        let output = directory.join("coverage.info");
+        std::fs::write(&input, test_binary_log(&[(0, u16::MAX)])).unwrap();
+
+        let res = Converter::new()
+            .add_input(&input)
+            .source_filter(r"converter\.rs$")
+            .output_lcov(&output)
+            .run();
+
+        res.unwrap();
+        let coverage = read_lcov_coverage(&output.to_string_lossy()).unwrap();
+        assert!(coverage
+            .lines
+            .keys()
+            .all(|file| file.ends_with("converter.rs")));
+        assert!(Summary::from_coverage(&coverage).lines_found > 0);
+    }
+
+    #[test]
+    fn reports_progress() {
+        #[derive(Default)]
+        struct Counts {
+            files_found: AtomicUsize,
+            files_processed: AtomicUsize,
+            modules_processed: AtomicUsize,
+        }
+
+        impl Progress for Arc<Counts> {
+            fn files_found(&self, num_files: usize) {
+                self.files_found.fetch_add(num_files, Ordering::Relaxed);
+            }
+
+            fn module_processed(&self, _path: &str) {
+                self.modules_processed.fetch_add(1, Ordering::Relaxed);
+            }
+
+            fn file_processed(&self, _path: &std::path::Path) {
+                self.files_processed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let directory = TempPath::dir("progress");
+        let input = directory.join("drcov.test.log");
+        std::fs::write(&input, test_binary_log(&[(0, 1)])).unwrap();
+
+        let counts = Arc::new(Counts::default());
+        let res = Converter::new()
+            .add_input(&input)
+            .source_filter(r"converter\.rs$")
+            .output_lcov(directory.join("coverage.info"))
+            .progress(counts.clone())
+            .run();
+
+        res.unwrap();
+        assert_eq!(
+            [
+                counts.files_found.load(Ordering::Relaxed),
+                counts.files_processed.load(Ordering::Relaxed),
+                counts.modules_processed.load(Ordering::Relaxed)
+            ],
+            [1, 1, 1]
+        );
+    }
+
+    #[test]
+    fn resolves_source_paths() {
+        let directory = TempPath::dir("resolver");
+        let input = directory.join("drcov.test.log");
+        std::fs::write(&input, test_binary_log(&[(0, 1)])).unwrap();
+
+        let output = directory.join("coverage.info");
+        let res = Converter::new()
+            .add_input(&input)
+            .source_filter(r"^/checkout/.*converter\.rs$")
+            .output_lcov(&output)
+            .source_path_resolver(|_module: &str, path: &str| {
+                Some(format!(
+                    "/checkout/{}",
+                    path.rsplit('/').next().unwrap_or(path)
+                ))
+            })
+            .run()
+            .and_then(|_| Ok(std::fs::read_to_string(&output)?));
+
+        assert!(res.unwrap().contains("SF:/checkout/converter.rs\n"));
+    }
+
+    #[test]
+    fn reports_invalid_filters() {
+        let res = Converter::new()
+            .add_input("drcov.app.log")
+            .module_filter("(")
+            .options();
+
+        assert!(res.is_err());
+    }
+}
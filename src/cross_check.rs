@@ -0,0 +1,219 @@
+use crate::drcov::Module;
+use crate::read_only;
+use clap::ValueEnum;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::path::Path;
+use xxhash_rust::xxh3::xxh3_64_with_seed;
+
+mod constants {
+    /// How many covered addresses of every module are symbolized by the reference tool
+    pub const SAMPLE_SIZE: usize = 256;
+
+    /// Seeds the sampling of the covered addresses, so that runs sample the same addresses
+    pub const SAMPLE_SEED: u64 = 0x6c69_6e65_696e_666f;
+
+    /// The share of agreeing addresses below which a module is flagged
+    pub const MIN_AGREEING_RATIO: f64 = 0.99;
+
+    /// How many disagreeing addresses are listed in the debug log
+    pub const MAX_LISTED_ADDRESSES: usize = 5;
+}
+
+/// A reference toolchain the line info we gather can be cross-checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CrossCheckTool {
+    /// LLVM's symbolizer, which must be on the PATH
+    LlvmSymbolizer,
+}
+
+impl CrossCheckTool {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            CrossCheckTool::LlvmSymbolizer => "llvm-symbolizer",
+        }
+    }
+}
+
+/// The source lines the line info of a module attributes to each of its covered (link time)
+/// addresses. An address can start rows of several lines, e.g. when code of a line has been
+/// folded into the next one.
+pub type CoveredRows = HashMap<u64, Vec<(String, u64)>>;
+
+/// How the sampled addresses of a module compare to the reference tool
+#[derive(Debug, Default)]
+pub struct LineInfoComparison {
+    /// Addresses that were compared
+    pub checked: usize,
+    /// Addresses the reference tool attributes to another source line: (address, file, line)
+    pub disagreeing: Vec<(u64, String, u64)>,
+}
+
+impl LineInfoComparison {
+    pub fn agreeing(&self) -> usize {
+        self.checked - self.disagreeing.len()
+    }
+
+    pub fn agreement_rate(&self) -> f64 {
+        if self.checked == 0 {
+            return 1.0;
+        }
+
+        self.agreeing() as f64 / self.checked as f64
+    }
+}
+
+/// Returns a sample of the covered addresses of `rows`. The sample is pseudo-random but the same
+/// from run to run.
+fn sample_addresses(rows: &CoveredRows) -> Vec<u64> {
+    rows.keys()
+        .copied()
+        .sorted_by_key(|address| xxh3_64_with_seed(&address.to_le_bytes(), constants::SAMPLE_SEED))
+        .take(constants::SAMPLE_SIZE)
+        .sorted()
+        .collect()
+}
+
+/// Whether `file`, as the reference tool names it, is the source file `ours`. The paths of line
+/// programs are not always joined with the compilation directory, which the reference tools do.
+fn same_file(file: &str, ours: &str) -> bool {
+    Path::new(file) == Path::new(ours) || Path::new(file).ends_with(ours)
+}
+
+/// Symbolizes `addresses` of `binary` with llvm-symbolizer. Returns the file and line of the
+/// innermost (inlined) frame of every address, or `None` for the addresses it has no line for.
+fn run_llvm_symbolizer(
+    binary: &str,
+    addresses: &[u64],
+) -> anyhow::Result<Vec<Option<(String, u64)>>> {
+    let output = read_only::tool("llvm-symbolizer")
+        .arg("--output-style=JSON")
+        .arg(format!("--obj={binary}"))
+        .args(addresses.iter().map(|address| format!("{address:#x}")))
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run llvm-symbolizer: {e}"))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "llvm-symbolizer failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let frames: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    let frames = frames
+        .as_array()
+        .ok_or(anyhow::anyhow!("Unexpected llvm-symbolizer output"))?;
+
+    if frames.len() != addresses.len() {
+        anyhow::bail!(
+            "llvm-symbolizer symbolized {} of {} addresses",
+            frames.len(),
+            addresses.len()
+        );
+    }
+
+    let res = frames
+        .iter()
+        .map(|frame| {
+            let innermost = &frame["Symbol"][0];
+            let file = innermost["FileName"].as_str().unwrap_or_default();
+            let line = innermost["Line"].as_u64().unwrap_or_default();
+
+            (!file.is_empty() && line != 0).then(|| (file.to_string(), line))
+        })
+        .collect();
+
+    Ok(res)
+}
+
+/// Compares the source lines of a sample of the covered `rows` of `binary` with the ones `tool`
+/// reports for them
+pub fn compare_line_info(
+    binary: &str,
+    rows: &CoveredRows,
+    tool: CrossCheckTool,
+) -> anyhow::Result<LineInfoComparison> {
+    let addresses = sample_addresses(rows);
+
+    let reference = match tool {
+        CrossCheckTool::LlvmSymbolizer => run_llvm_symbolizer(binary, &addresses)?,
+    };
+
+    let mut res = LineInfoComparison::default();
+
+    for (address, reference) in addresses.into_iter().zip(reference) {
+        res.checked += 1;
+
+        let agrees = reference.as_ref().is_some_and(|(file, line)| {
+            rows[&address]
+                .iter()
+                .any(|(ours, our_line)| our_line == line && same_file(file, ours))
+        });
+
+        if !agrees {
+            let (file, line) = reference.unwrap_or_default();
+            res.disagreeing.push((address, file, line));
+        }
+    }
+
+    Ok(res)
+}
+
+/// Reports how many of a sample of the covered addresses of `module` the reference `tool`
+/// attributes to the same source lines as its line info (`rows`) does, and warns if they
+/// disagree on too many of them
+pub fn check_line_info(module: &Module, rows: &CoveredRows, tool: CrossCheckTool) {
+    if rows.is_empty() {
+        log::debug!("Module {} has no covered lines to cross-check", module.path);
+        return;
+    }
+
+    let comparison = match compare_line_info(&module.path, rows, tool) {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            log::warn!(
+                "Could not cross-check the line info of module {}, skipping it. Reason: {e}",
+                module.path
+            );
+            return;
+        }
+    };
+
+    let rate = comparison.agreement_rate() * 100.0;
+
+    if comparison.agreement_rate() >= constants::MIN_AGREEING_RATIO {
+        log::info!(
+            "The line info of module {} agrees with {} on {} of {} sampled addresses ({rate:.1}%)",
+            module.path,
+            tool.name(),
+            comparison.agreeing(),
+            comparison.checked
+        );
+        return;
+    }
+
+    log::warn!(
+        "The line info of module {} disagrees with {} on {} of {} sampled addresses (agreement: {rate:.1}%)",
+        module.path,
+        tool.name(),
+        comparison.disagreeing.len(),
+        comparison.checked
+    );
+
+    for (address, file, line) in comparison
+        .disagreeing
+        .iter()
+        .take(constants::MAX_LISTED_ADDRESSES)
+    {
+        log::debug!(
+            "Disagreeing address of {}: {address:#x} is {file}:{line} according to {}, {} according to us",
+            module.path,
+            tool.name(),
+            rows[address]
+                .iter()
+                .map(|(file, line)| format!("{file}:{line}"))
+                .join(", ")
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use crate::cache::{self, AtomicFile};
+use crate::dwarf::missing_debug_info_build_id;
+use crate::read_only;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{OnceCell, Semaphore};
+
+mod constants {
+    use std::time::Duration;
+
+    pub const DEBUGINFOD_URLS_ENV: &str = "DEBUGINFOD_URLS";
+    pub const DEBUGINFOD_CACHE_PATH_ENV: &str = "DEBUGINFOD_CACHE_PATH";
+
+    /// The directory (relative to the user's cache directory) elfutils' debuginfod client caches
+    /// its downloads in. Sharing it means that debug info fetched by gdb & co. is reused.
+    pub const DEBUGINFOD_CACHE_DIRECTORY: &str = "debuginfod_client";
+
+    pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+}
+
+/// Returns the debuginfod server URLs configured through `$DEBUGINFOD_URLS`
+pub fn urls_from_env() -> Vec<String> {
+    std::env::var(constants::DEBUGINFOD_URLS_ENV)
+        .map(|urls| urls.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Returns the directory debug info downloads are cached in
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(constants::DEBUGINFOD_CACHE_PATH_ENV) {
+        return Some(PathBuf::from(path));
+    }
+
+    let cache_home = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+
+    Some(cache_home.join(constants::DEBUGINFOD_CACHE_DIRECTORY))
+}
+
+/// Returns where the debug info of the object file with the given build-id is (or would be)
+/// cached, using the same layout as elfutils' debuginfod client
+pub fn cached_debug_info_path(build_id: &str) -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(build_id).join("debuginfo"))
+}
+
+/// Downloads missing debug information from debuginfod servers.
+///
+/// At most `max_concurrent_downloads` downloads are in flight at any time and every build-id is
+/// only requested once, no matter how many inputs reference it. Response bodies are streamed to
+/// the cache instead of being buffered in memory. Once `max_download_size` bytes have been
+/// downloaded over the lifetime of the client (e.g. of a server), the downloads fail.
+pub struct DebuginfodClient {
+    client: reqwest::Client,
+    urls: Vec<String>,
+    downloads: Semaphore,
+    max_download_size: Option<u64>,
+    /// The number of bytes of the downloads written to the cache so far
+    downloaded: AtomicU64,
+    /// Build-id -> the outcome of fetching its debug info
+    fetched: Mutex<HashMap<String, Arc<OnceCell<Option<PathBuf>>>>>,
+}
+
+impl DebuginfodClient {
+    pub fn new(
+        urls: Vec<String>,
+        max_concurrent_downloads: usize,
+        max_download_size: Option<u64>,
+    ) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(constants::CONNECT_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            client,
+            urls,
+            downloads: Semaphore::new(max_concurrent_downloads),
+            max_download_size,
+            downloaded: AtomicU64::new(0),
+            fetched: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn download(&self, url: &str, build_id: &str) -> anyhow::Result<Option<PathBuf>> {
+        let Some(path) = cached_debug_info_path(build_id) else {
+            anyhow::bail!("Could not determine the debuginfod cache directory");
+        };
+
+        let url = format!("{}/buildid/{build_id}/debuginfo", url.trim_end_matches('/'));
+        read_only::check_network(&url)?;
+        read_only::check_write(&path)?;
+        let response = self.client.get(&url).send().await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let response = response.error_for_status()?;
+
+        // Safety: We can unwrap here since the path always has a parent
+        let directory = path.parent().unwrap();
+        read_only::create_dir_all(directory)?;
+
+        // Write to a temporary file first so that an interrupted download never looks complete
+        let atomic_file = AtomicFile::new(path);
+        let mut file = tokio::fs::File::from_std(atomic_file.create()?);
+
+        // The bytes of a download that fails are taken off the total again, since its temporary
+        // file is removed
+        let mut written = 0;
+        let res = async {
+            let mut body = response.bytes_stream();
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk?;
+                written += chunk.len() as u64;
+                self.reserve(chunk.len() as u64)?;
+                file.write_all(&chunk).await?;
+            }
+
+            file.flush().await?;
+            anyhow::Ok(())
+        }
+        .await;
+
+        if let Err(e) = res {
+            self.downloaded.fetch_sub(written, Ordering::Relaxed);
+            return Err(e);
+        }
+
+        let path = atomic_file.persist()?;
+        // The cache is shared with the other debuginfod clients, whose downloads are not evicted
+        cache::mark_owned(&path)?;
+
+        Ok(Some(path))
+    }
+
+    /// Counts `size` more bytes against the maximum download size
+    fn reserve(&self, size: u64) -> anyhow::Result<()> {
+        let downloaded = self.downloaded.fetch_add(size, Ordering::Relaxed) + size;
+
+        match self.max_download_size {
+            Some(limit) if downloaded > limit => {
+                anyhow::bail!("The downloads exceed the maximum download size of {limit} bytes")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn fetch(&self, build_id: &str) -> Option<PathBuf> {
+        let _permit = self.downloads.acquire().await.ok()?;
+
+        for url in &self.urls {
+            log::info!("Fetching the debug info of build-id {build_id} from {url}");
+
+            match self.download(url, build_id).await {
+                Ok(Some(path)) => {
+                    log::info!(
+                        "Downloaded the debug info of build-id {build_id} to '{}'",
+                        path.display()
+                    );
+                    return Some(path);
+                }
+                Ok(None) => {
+                    log::debug!("{url} does not have the debug info of build-id {build_id}")
+                }
+                Err(e) => log::warn!(
+                    "Could not fetch the debug info of build-id {build_id} from {url}: {e}"
+                ),
+            }
+        }
+
+        log::warn!("None of the debuginfod servers has the debug info of build-id {build_id}");
+
+        None
+    }
+
+    /// Fetches the debug info of the object file with the given build-id, unless it has already
+    /// been (or is being) fetched
+    pub async fn fetch_debug_info(&self, build_id: &str) -> Option<PathBuf> {
+        let cell = self
+            .fetched
+            .lock()
+            .unwrap()
+            .entry(build_id.to_string())
+            .or_default()
+            .clone();
+
+        cell.get_or_init(|| self.fetch(build_id)).await.clone()
+    }
+
+    /// Fetches the debug info of every object file in `paths` for which no debug info can be
+    /// found locally
+    pub async fn fetch_missing_debug_info(&self, paths: Vec<String>) {
+        let build_ids = tokio::task::spawn_blocking(move || {
+            paths
+                .iter()
+                .filter_map(|path| missing_debug_info_build_id(path))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+
+        futures::future::join_all(
+            build_ids
+                .iter()
+                .map(|build_id| self.fetch_debug_info(build_id)),
+        )
+        .await;
+    }
+}
@@ -0,0 +1,144 @@
+use crate::manifest::FileDigests;
+use crate::output::{write_text_output, LineEnding};
+use crate::util::assert_send_sync;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+struct DedupEntry {
+    digests: FileDigests,
+    path: PathBuf,
+    duplicate_of: Option<usize>,
+}
+
+/// Detects byte-identical input files by hashing their contents before they get parsed. Inputs are
+/// identical when their xxh3 hashes and sizes match, and so do their SHA-256 hashes when those have
+/// been computed.
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    // (xxh3, size, sha256) -> the first input with these digests
+    seen: HashMap<(u64, u64, Option<String>), usize>,
+    // Path -> its entry
+    indices: HashMap<PathBuf, usize>,
+    entries: Vec<DedupEntry>,
+}
+
+const _: () = assert_send_sync::<Deduplicator>();
+
+impl Deduplicator {
+    /// Records the input at `path`, whose contents hash to `digests`, and returns the previously
+    /// seen input with the same contents, if any
+    pub fn find_duplicate(&mut self, path: &Path, digests: FileDigests) -> Option<&Path> {
+        let key = (digests.xxh3, digests.size, digests.sha256.clone());
+        let index = self.entries.len();
+        let duplicate_of = self.seen.get(&key).copied();
+
+        self.entries.push(DedupEntry {
+            digests,
+            path: path.to_path_buf(),
+            duplicate_of,
+        });
+
+        if duplicate_of.is_none() {
+            self.seen.insert(key, index);
+        }
+        self.indices.insert(path.to_path_buf(), index);
+
+        duplicate_of.map(|original| self.entries[original].path.as_path())
+    }
+
+    /// The digests of the input at `path`, if it has been hashed
+    pub fn digests(&self, path: &Path) -> Option<&FileDigests> {
+        self.indices
+            .get(path)
+            .map(|index| &self.entries[*index].digests)
+    }
+
+    pub fn num_duplicates(&self) -> usize {
+        self.entries.len() - self.seen.len()
+    }
+
+    /// Writes a tab separated manifest with the hashes and size of every input and the input it
+    /// duplicates
+    pub fn write_manifest(
+        &self,
+        path: &str,
+        scrub: impl Fn(&str) -> String,
+        line_ending: LineEnding,
+    ) -> anyhow::Result<()> {
+        let mut res = String::new();
+
+        let _ = writeln!(res, "# xxh3\tsha256\tsize\tpath\tduplicate_of");
+        for entry in &self.entries {
+            let duplicate_of = entry
+                .duplicate_of
+                .map(|original| scrub(&self.entries[original].path.to_string_lossy()))
+                .unwrap_or_else(|| "-".to_string());
+
+            let _ = writeln!(
+                res,
+                "{:016x}\t{}\t{}\t{}\t{duplicate_of}",
+                entry.digests.xxh3,
+                entry.digests.sha256.as_deref().unwrap_or("-"),
+                entry.digests.size,
+                scrub(&entry.path.to_string_lossy())
+            );
+        }
+
+        write_text_output(path, &res, line_ending)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digests(xxh3: u64, sha256: Option<&str>) -> FileDigests {
+        FileDigests {
+            size: 4,
+            xxh3,
+            sha256: sha256.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn compares_the_sha256_hashes_when_they_have_been_computed() {
+        let mut deduplicator = Deduplicator::default();
+
+        assert_eq!(
+            deduplicator.find_duplicate(Path::new("a"), digests(1, Some("aa"))),
+            None
+        );
+        // An xxh3 collision is told apart by the SHA-256 hashes
+        assert_eq!(
+            deduplicator.find_duplicate(Path::new("b"), digests(1, Some("bb"))),
+            None
+        );
+        assert_eq!(
+            deduplicator.find_duplicate(Path::new("c"), digests(1, Some("bb"))),
+            Some(Path::new("b"))
+        );
+        assert_eq!(deduplicator.num_duplicates(), 1);
+    }
+
+    #[test]
+    fn compares_the_xxh3_hashes_without_sha256_hashes() {
+        let mut deduplicator = Deduplicator::default();
+
+        assert_eq!(
+            deduplicator.find_duplicate(Path::new("a"), digests(1, None)),
+            None
+        );
+        assert_eq!(
+            deduplicator.find_duplicate(Path::new("b"), digests(2, None)),
+            None
+        );
+        assert_eq!(
+            deduplicator.find_duplicate(Path::new("c"), digests(1, None)),
+            Some(Path::new("a"))
+        );
+    }
+}
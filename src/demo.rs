@@ -0,0 +1,183 @@
+use crate::cli::CliOptions;
+use crate::dwarf::ObjectFile;
+use clap::Parser;
+use object::{Object, ObjectSymbol};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod constants {
+    pub const DEMO_DIRECTORY_NAME: &str = "drcov2lcov-demo";
+    pub const DEMO_SOURCE_FILE: &str = "demo.c";
+    pub const DEMO_BINARY_FILE: &str = "demo";
+    pub const DEMO_DRCOV_FILE: &str = "drcov.demo.00000.0000.proc.log";
+    pub const DEMO_OUTPUT_FILE: &str = "coverage.info";
+    pub const DEMO_HTML_DIRECTORY: &str = "html";
+
+    /// Base address the demo module pretends to have been loaded at
+    pub const DEMO_MODULE_BASE: u64 = 0x5555_5555_4000;
+
+    /// Functions of the demo program that the synthesized trace marks as executed
+    pub const DEMO_EXECUTED_FUNCTIONS: [&str; 2] = ["main", "square"];
+
+    pub const DEMO_SOURCE: &str = r#"#include <stdio.h>
+
+static int square(int x) {
+    return x * x;
+}
+
+static int never_called(int x) {
+    if (x > 3)
+        return x - 1;
+    return x + 1;
+}
+
+int main(int argc, char **argv) {
+    int value = square(argc);
+    if (value > 100)
+        printf("never printed\n");
+    return value > 1000 ? never_called(value) : 0;
+}
+"#;
+}
+
+fn compile_demo_binary(directory: &Path) -> anyhow::Result<PathBuf> {
+    let source = directory.join(constants::DEMO_SOURCE_FILE);
+    let binary = directory.join(constants::DEMO_BINARY_FILE);
+
+    std::fs::write(&source, constants::DEMO_SOURCE)?;
+
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(&compiler)
+        .args(["-g", "-O0", "-o"])
+        .arg(&binary)
+        .arg(&source)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Could not run the C compiler '{compiler}': {e}"))?;
+
+    if !status.success() {
+        anyhow::bail!("Compiling the demo program with '{compiler}' failed ({status})");
+    }
+
+    Ok(binary)
+}
+
+/// Synthesizes a drcov log for `binary` in which the basic blocks of
+/// [`constants::DEMO_EXECUTED_FUNCTIONS`] have been executed.
+fn synthesize_drcov_log(binary: &Path, output: &Path) -> anyhow::Result<()> {
+    let binary_path = binary.to_string_lossy().to_string();
+    let object_file = ObjectFile::from_path(&binary_path)?;
+    let load_base = object_file.load_base();
+    let object = object_file.with_object(|obj| obj);
+
+    let image_size = object_file.image_size();
+
+    let mut bb_table = Vec::new();
+
+    for symbol in object.symbols() {
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+
+        if !constants::DEMO_EXECUTED_FUNCTIONS.contains(&name) || symbol.size() == 0 {
+            continue;
+        }
+
+        let start = (symbol.address() - load_base) as u32;
+        let size = symbol.size().min(u16::MAX as u64) as u16;
+
+        bb_table.extend_from_slice(&start.to_le_bytes());
+        bb_table.extend_from_slice(&size.to_le_bytes());
+        bb_table.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    let num_bb = bb_table.len() / 8;
+    if num_bb == 0 {
+        anyhow::bail!("Could not find the demo functions in '{binary_path}'");
+    }
+
+    let base = constants::DEMO_MODULE_BASE;
+    let mut contents = format!(
+        "DRCOV VERSION: 2\n\
+         DRCOV FLAVOR: drcov\n\
+         Module Table: version 2, count 1\n\
+         Columns: id, base, end, entry, path\n\
+         \x20 0, {base:#018x}, {:#018x}, 0x0000000000000000, {binary_path}\n\
+         BB Table: {num_bb} bbs\n",
+        base + image_size
+    )
+    .into_bytes();
+    contents.extend_from_slice(&bb_table);
+
+    std::fs::write(output, contents)?;
+
+    Ok(())
+}
+
+fn generate_html_report(coverage: &Path, directory: &Path) -> Option<PathBuf> {
+    let html_directory = directory.join(constants::DEMO_HTML_DIRECTORY);
+
+    let status = Command::new("genhtml")
+        .arg("--quiet")
+        .arg(coverage)
+        .arg("--output-directory")
+        .arg(&html_directory)
+        .status()
+        .ok()?;
+
+    status.success().then(|| html_directory.join("index.html"))
+}
+
+fn open_in_browser(path: &Path) {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    if let Err(e) = Command::new(opener).arg(path).status() {
+        log::warn!("Could not open '{}' using '{opener}': {e}", path.display());
+    }
+}
+
+pub fn run(directory: Option<String>, no_open: bool) -> anyhow::Result<()> {
+    let directory = directory
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join(constants::DEMO_DIRECTORY_NAME));
+
+    std::fs::create_dir_all(&directory)?;
+
+    println!("Creating the demo project in '{}'", directory.display());
+
+    let binary = compile_demo_binary(&directory)?;
+    let drcov_log = directory.join(constants::DEMO_DRCOV_FILE);
+    synthesize_drcov_log(&binary, &drcov_log)?;
+
+    println!("Synthesized drcov log '{}'", drcov_log.display());
+
+    let coverage = directory.join(constants::DEMO_OUTPUT_FILE);
+    let options = CliOptions::try_parse_from([
+        "drcov2lcov".as_ref(),
+        "--input".as_ref(),
+        drcov_log.as_os_str(),
+        "--output".as_ref(),
+        coverage.as_os_str(),
+    ])?
+    .validate()?;
+
+    crate::conversion::convert(options, &tokio::runtime::Runtime::new()?)?;
+
+    println!("Line coverage written to '{}'", coverage.display());
+
+    match generate_html_report(&coverage, &directory) {
+        Some(index) => {
+            println!("HTML report written to '{}'", index.display());
+            if !no_open {
+                open_in_browser(&index);
+            }
+        }
+        None => println!("genhtml is not available, skipping the HTML report"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::filter::Filter;
+use object::elf::{FileHeader32, FileHeader64, DT_NEEDED, DT_RPATH, DT_RUNPATH};
+use object::read::elf::{Dyn, FileHeader};
+use object::{Endianness, FileKind};
+use std::path::{Path, PathBuf};
+
+mod constants {
+    /// Directories searched by the dynamic loader after the ones configured through
+    /// `LD_LIBRARY_PATH`, `DT_RUNPATH`/`DT_RPATH` and `/etc/ld.so.conf`
+    pub const DEFAULT_LIBRARY_DIRS: [&str; 8] = [
+        "/lib",
+        "/usr/lib",
+        "/lib64",
+        "/usr/lib64",
+        "/lib/x86_64-linux-gnu",
+        "/usr/lib/x86_64-linux-gnu",
+        "/lib/aarch64-linux-gnu",
+        "/usr/lib/aarch64-linux-gnu",
+    ];
+
+    pub const LD_SO_CONF: &str = "/etc/ld.so.conf";
+}
+
+/// The dynamic dependencies recorded in an ELF file's dynamic section
+#[derive(Debug, Default)]
+pub struct ElfDependencies {
+    pub needed: Vec<String>,
+    pub rpath: Vec<String>,
+    pub runpath: Vec<String>,
+}
+
+fn parse_dependencies<Elf: FileHeader>(data: &[u8]) -> anyhow::Result<ElfDependencies> {
+    let header = Elf::parse(data)?;
+    let endian = header.endian()?;
+    let sections = header.sections(endian, data)?;
+
+    let mut res = ElfDependencies::default();
+
+    let Some((entries, strings_index)) = sections.dynamic(endian, data)? else {
+        return Ok(res);
+    };
+
+    let strings = sections.strings(endian, data, strings_index)?;
+
+    for entry in entries {
+        let Some(tag) = entry.tag32(endian) else {
+            continue;
+        };
+
+        if !matches!(tag, DT_NEEDED | DT_RPATH | DT_RUNPATH) {
+            continue;
+        }
+
+        let value = String::from_utf8_lossy(entry.string(endian, strings)?).to_string();
+
+        match tag {
+            DT_NEEDED => res.needed.push(value),
+            DT_RPATH => res.rpath.extend(value.split(':').map(str::to_string)),
+            _ => res.runpath.extend(value.split(':').map(str::to_string)),
+        }
+    }
+
+    Ok(res)
+}
+
+impl ElfDependencies {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let data = std::fs::read(path.as_ref())?;
+
+        match FileKind::parse(data.as_slice())? {
+            FileKind::Elf32 => parse_dependencies::<FileHeader32<Endianness>>(&data),
+            FileKind::Elf64 => parse_dependencies::<FileHeader64<Endianness>>(&data),
+            _ => anyhow::bail!("'{}' is not an ELF file", path.as_ref().display()),
+        }
+    }
+}
+
+fn ld_so_conf_dirs(conf: &Path, dirs: &mut Vec<PathBuf>) {
+    let Ok(contents) = std::fs::read_to_string(conf) else {
+        return;
+    };
+
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+
+        if let Some(pattern) = line.strip_prefix("include") {
+            let pattern = pattern.trim();
+            // Only the common `include /etc/ld.so.conf.d/*.conf` form is supported
+            let (dir, suffix) = pattern.rsplit_once("/*").unwrap_or((pattern, ""));
+
+            if let Ok(read_dir) = Path::new(dir).read_dir() {
+                for entry in read_dir.flatten() {
+                    if entry.file_name().to_string_lossy().ends_with(suffix) {
+                        ld_so_conf_dirs(&entry.path(), dirs);
+                    }
+                }
+            }
+        } else if !line.is_empty() {
+            dirs.push(PathBuf::from(line));
+        }
+    }
+}
+
+/// Resolves the direct dependencies of the ELF file at `binary` the same way the dynamic loader
+/// would. Dependencies that could not be found are returned as `Err` with their `DT_NEEDED` name.
+pub fn resolve_dependencies(binary: &Path) -> anyhow::Result<Vec<Result<PathBuf, String>>> {
+    let dependencies = ElfDependencies::from_path(binary)?;
+    let origin = binary
+        .canonicalize()
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+
+    let expand_origin = |dir: &String| {
+        PathBuf::from(
+            dir.replace("$ORIGIN", &origin.to_string_lossy())
+                .replace("${ORIGIN}", &origin.to_string_lossy()),
+        )
+    };
+
+    let mut search_dirs = Vec::new();
+
+    // DT_RPATH is ignored when DT_RUNPATH is present
+    if dependencies.runpath.is_empty() {
+        search_dirs.extend(dependencies.rpath.iter().map(expand_origin));
+    }
+
+    if let Ok(ld_library_path) = std::env::var("LD_LIBRARY_PATH") {
+        search_dirs.extend(
+            ld_library_path
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        );
+    }
+
+    search_dirs.extend(dependencies.runpath.iter().map(expand_origin));
+    ld_so_conf_dirs(Path::new(constants::LD_SO_CONF), &mut search_dirs);
+    search_dirs.extend(constants::DEFAULT_LIBRARY_DIRS.iter().map(PathBuf::from));
+
+    let res = dependencies
+        .needed
+        .into_iter()
+        .map(|needed| {
+            if needed.contains('/') {
+                return Some(PathBuf::from(&needed))
+                    .filter(|path| path.is_file())
+                    .ok_or(needed);
+            }
+
+            search_dirs
+                .iter()
+                .map(|dir| dir.join(&needed))
+                .find(|path| path.is_file())
+                .ok_or(needed)
+        })
+        .collect();
+
+    Ok(res)
+}
+
+fn module_name_filter(name: &str) -> anyhow::Result<Filter> {
+    // Module filters are matched against whole module table lines, which end with the path
+    Ok(format!(r"(^|[\s/\\,]){}\s*$", regex::escape(name)).parse()?)
+}
+
+/// Generates module filters matching `binary` and each of its direct dependencies
+pub fn module_filters_for_binary(binary: &Path) -> anyhow::Result<Vec<Filter>> {
+    let mut names = Vec::new();
+
+    let mut push_names = |path: &Path| {
+        for path in [Some(path.to_path_buf()), path.canonicalize().ok()]
+            .into_iter()
+            .flatten()
+        {
+            if let Some(name) = path.file_name() {
+                names.push(name.to_string_lossy().to_string());
+            }
+        }
+    };
+
+    push_names(binary);
+
+    for dependency in resolve_dependencies(binary)? {
+        match dependency {
+            Ok(path) => {
+                log::info!("'{}' depends on '{}'", binary.display(), path.display());
+                push_names(&path);
+            }
+            Err(needed) => {
+                log::warn!(
+                    "Could not resolve dependency '{needed}' of '{}'",
+                    binary.display()
+                );
+                push_names(Path::new(&needed));
+            }
+        }
+    }
+
+    names.sort();
+    names.dedup();
+
+    names.iter().map(|name| module_name_filter(name)).collect()
+}
@@ -1,58 +1,207 @@
-use crate::cli::{Filter, ReplacementFilter};
-use crate::util::{parse_capture_group, Hex};
-use anyhow::anyhow;
+use crate::error::Error;
+use crate::filter::{Filter, ReplacementFilter};
+use crate::limits::InputLimits;
+use crate::module_key::ModuleKey;
+#[cfg(feature = "native")]
+use crate::util::decompress;
+use crate::util::{assert_send_sync, parse_capture_group, read_maybe_compressed, Hex};
 use byteorder::{LittleEndian, ReadBytesExt};
+use clap::ValueEnum;
 use roaring::RoaringBitmap;
 use std::borrow::Cow;
-use std::io::{Cursor, Read};
+use std::collections::HashMap;
+use std::io::Read;
 use std::path::Path;
 
 mod constants {
     use lazy_static::lazy_static;
+
+    /// The flavor written by Lighthouse's `frida-drcov.py`
+    pub const FRIDA_FLAVOR: &str = "frida";
+
+    /// The flavor given to the logs of the legacy bbcov layout, which have no flavor line
+    pub const BBCOV_FLAVOR: &str = "bbcov";
+
+    /// The flavor given to other logs without a flavor line, unless the format is strict
+    pub const DEFAULT_FLAVOR: &str = "drcov";
+
+    /// The start of the header of a basic block table
+    pub const BB_TABLE_PREFIX: &[u8] = b"BB Table:";
+
+    /// The number of basic blocks buffered while a basic block table is read before they are
+    /// inserted into the bitmaps of their modules, which bounds the memory the tables of large
+    /// logs take on top of their bitmaps
+    pub const MAX_PENDING_BASIC_BLOCKS: usize = 1 << 22;
     use regex::bytes::Regex;
 
     lazy_static! {
         pub static ref DRCOV_VERSION_REGEX: Regex =
-            Regex::new(r"DRCOV VERSION: (?P<version>\d+)").unwrap();
+            Regex::new(r"(DR|BB)COV VERSION: (?P<version>\d+)").unwrap();
         pub static ref DRCOV_FLAVOR_REGEX: Regex =
-            Regex::new(r"DRCOV FLAVOR: (?P<flavor>[^\s]+)").unwrap();
+            Regex::new(r"(DR|BB)COV FLAVOR: (?P<flavor>[^\s]+)").unwrap();
         pub static ref DRCOV_MODULE_HEADER_OLD_REGEX: Regex =
             Regex::new(r"Module Table: (?P<count>\d+)").unwrap();
         pub static ref DRCOV_MODULE_HEADER_REGEX: Regex =
             Regex::new(r"Module Table: version (?P<version>\d+), count (?P<count>\d+)").unwrap();
         pub static ref DRCOV_MODULE_V1_REGEX: Regex =
-            Regex::new(r"\s*(?P<id>\d+),\s*(?P<size>\d+),\s*(?P<path>[^\s]+)").unwrap();
+            Regex::new(r"\s*(?P<id>\d+),\s*(?P<size>\d+),\s*(?P<path>\S.*?)\s*$").unwrap();
         pub static ref DRCOV_MODULE_V2_REGEX: Regex =
             Regex::new(r"\s*(?P<id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*(?P<path>[^\s]+)")
                 .unwrap();
         pub static ref DRCOV_MODULE_V3_REGEX: Regex = Regex::new(r"\s*(?P<id>\d+),\s*(?P<containing_id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*(?P<path>[^\s]+)").unwrap();
         pub static ref DRCOV_MODULE_V4_REGEX: Regex = Regex::new(r"\s*(?P<id>\d+),\s*(?P<containing_id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*0[xX](?P<offset>[[:xdigit:]]+),\s*(?P<path>[^\s]+)").unwrap();
         pub static ref DRCOV_MODULE_V5_REGEX: Regex = Regex::new(r"\s*(?P<id>\d+),\s*(?P<containing_id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*0[xX](?P<offset>[[:xdigit:]]+),\s*0[xX](?P<preferred_base>[[:xdigit:]]+),\s*(?P<path>[^\s]+)").unwrap();
+        pub static ref WINDOWS_COLUMNS_REGEX: Regex = Regex::new(r"^\s*Columns:.*\bchecksum,\s*timestamp\b").unwrap();
+        pub static ref WINDOWS_MODULE_V2_REGEX: Regex = Regex::new(r"^\s*(?P<id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*0[xX](?P<checksum>[[:xdigit:]]+),\s*0[xX](?P<timestamp>[[:xdigit:]]+),\s*(?P<path>.+?)\s*$").unwrap();
+        pub static ref WINDOWS_MODULE_V3_REGEX: Regex = Regex::new(r"^\s*(?P<id>\d+),\s*(?P<containing_id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*0[xX](?P<checksum>[[:xdigit:]]+),\s*0[xX](?P<timestamp>[[:xdigit:]]+),\s*(?P<path>.+?)\s*$").unwrap();
+        pub static ref WINDOWS_MODULE_V4_REGEX: Regex = Regex::new(r"^\s*(?P<id>\d+),\s*(?P<containing_id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*0[xX](?P<offset>[[:xdigit:]]+),\s*0[xX](?P<checksum>[[:xdigit:]]+),\s*0[xX](?P<timestamp>[[:xdigit:]]+),\s*(?P<path>.+?)\s*$").unwrap();
+        pub static ref WINDOWS_MODULE_V5_REGEX: Regex = Regex::new(r"^\s*(?P<id>\d+),\s*(?P<containing_id>\d+),\s*0[xX](?P<base>[[:xdigit:]]+),\s*0[xX](?P<end>[[:xdigit:]]+),\s*0[xX](?P<entry>[[:xdigit:]]+),\s*0[xX](?P<offset>[[:xdigit:]]+),\s*0[xX](?P<preferred_base>[[:xdigit:]]+),\s*0[xX](?P<checksum>[[:xdigit:]]+),\s*0[xX](?P<timestamp>[[:xdigit:]]+),\s*(?P<path>.+?)\s*$").unwrap();
         pub static ref DRCOV_BB_HEADER_REGEX: Regex = Regex::new(r"BB Table: (?P<count>\d+) bbs").unwrap();
+        pub static ref TEXT_BB_COLUMNS_REGEX: Regex = Regex::new(r"^\s*module id, start, size:\s*$").unwrap();
+        pub static ref TEXT_BB_ENTRY_REGEX: Regex = Regex::new(r"^\s*module\[\s*(?P<id>\d+)\]:\s*0[xX](?P<start>[[:xdigit:]]+),\s*(?P<size>\d+)").unwrap();
+        pub static ref ITERATION_HEADER_REGEX: Regex = Regex::new(r"^Iteration:\s*\d+$").unwrap();
+        pub static ref FRIDA_MODULE_HEADER_REGEX: Regex = Regex::new(r"(?i)Module\s*Table:\s*(?:version\s*(?P<version>\d+)\s*,\s*)?(?:count\s*)?(?P<count>\d+)").unwrap();
+        pub static ref FRIDA_COLUMNS_REGEX: Regex = Regex::new(r"(?i)^\s*Columns:\s*(?P<columns>.*?)\s*$").unwrap();
+        pub static ref PROC_MAPS_LINE_REGEX: Regex = Regex::new(r"^(?P<start>[[:xdigit:]]+)-(?P<end>[[:xdigit:]]+)\s+\S+\s+(?P<offset>[[:xdigit:]]+)\s+\S+\s+\d+\s+(?P<path>\S.*?)\s*$").unwrap();
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
     pub size: usize,
     pub segment_start: usize,
     pub segment_offset: usize,
     pub containing_index: Option<usize>,
     pub path: String,
+    /// The build-id of the module the tracer recorded, for module tables with a `build_id`
+    /// column, or coverage state files
+    pub build_id: Option<String>,
     pub bb_bitmap: RoaringBitmap,
+    pub bb_starts: RoaringBitmap,
+}
+
+/// What an input recorded about the build of a module it captured, to tell whether it is the
+/// build of the binary at the path of the module
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CapturedBuild<'m> {
+    /// The size the module had once loaded, i.e. the end of its last segment
+    pub loaded_size: Option<usize>,
+    pub build_id: Option<&'m str>,
+}
+
+/// Splits the module lines of drcov logs into their fields by hand, which is much faster than
+/// matching them against the regexes of their versions for tables with thousands of modules. It
+/// only accepts ASCII lines laid out the way DynamoRIO writes them, and whenever it accepts a
+/// line, its fields are the ones the regexes capture. Every other line is left to the regexes.
+struct ModuleLineTokenizer<'a> {
+    line: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ModuleLineTokenizer<'a> {
+    fn new(line: &'a [u8]) -> Option<Self> {
+        // Outside of ASCII, the classes of the regexes (e.g. \s and \d) are Unicode aware
+        line.is_ascii().then_some(Self { line, pos: 0 })
+    }
+
+    /// Whether `b` is matched by `\s`, which unlike [`u8::is_ascii_whitespace`] includes the
+    /// vertical tab
+    fn is_space(b: u8) -> bool {
+        matches!(b, b'\t'..=b'\r' | b' ')
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.line.get(self.pos).copied().is_some_and(Self::is_space) {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads the digits of a number in `radix`, checking for overflow like parsing the captures
+    /// of the regexes does
+    fn digits(&mut self, radix: u32) -> Option<usize> {
+        let start = self.pos;
+        let mut value = 0usize;
+
+        while let Some(digit) = self
+            .line
+            .get(self.pos)
+            .and_then(|b| (*b as char).to_digit(radix))
+        {
+            value = value
+                .checked_mul(radix as usize)?
+                .checked_add(digit as usize)?;
+            self.pos += 1;
+        }
+
+        (self.pos > start).then_some(value)
+    }
+
+    /// Reads a decimal field, followed by its comma
+    fn decimal(&mut self) -> Option<usize> {
+        self.skip_spaces();
+        let value = self.digits(10)?;
+        self.comma()?;
+
+        Some(value)
+    }
+
+    /// Reads a `0x` prefixed hexadecimal field, followed by its comma
+    fn hex(&mut self) -> Option<usize> {
+        self.skip_spaces();
+        match self.line.get(self.pos..self.pos + 2)? {
+            b"0x" | b"0X" => self.pos += 2,
+            _ => return None,
+        }
+        let value = self.digits(16)?;
+        self.comma()?;
+
+        Some(value)
+    }
+
+    fn comma(&mut self) -> Option<()> {
+        (self.line.get(self.pos) == Some(&b',')).then(|| self.pos += 1)
+    }
+
+    /// Reads a path that ends at the first whitespace, like those of DynamoRIO's tables on Linux
+    fn path_word(&mut self) -> Option<String> {
+        self.skip_spaces();
+        let rest = &self.line[self.pos..];
+        let path = &rest[..rest
+            .iter()
+            .position(|b| Self::is_space(*b))
+            .unwrap_or(rest.len())];
+
+        (!path.is_empty()).then(|| String::from_utf8_lossy(path).to_string())
+    }
+
+    /// Reads a path that takes up the rest of the line, along with its spaces, like those of the
+    /// legacy and the Windows tables
+    fn path_rest(&mut self) -> Option<String> {
+        self.skip_spaces();
+        let rest = &self.line[self.pos..];
+        let path = &rest[..rest
+            .iter()
+            .rposition(|b| !Self::is_space(*b))
+            .map_or(0, |i| i + 1)];
+
+        (!path.is_empty()).then(|| String::from_utf8_lossy(path).to_string())
+    }
 }
 
 impl Module {
-    pub fn from_line_v1(line: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_line_v1(line: &[u8]) -> Result<Self, Error> {
         let cap = constants::DRCOV_MODULE_V1_REGEX
             .captures(line)
-            .ok_or(anyhow!("Module line is invalid (version = 1)"))?;
+            .ok_or(Error::parse("Module line is invalid (version = 1)"))?;
 
-        let size = parse_capture_group(&cap, "size")
-            .ok_or(anyhow!("Could not find size in module line (version = 1)"))?;
+        let size = parse_capture_group(&cap, "size").ok_or(Error::parse(
+            "Could not find size in module line (version = 1)",
+        ))?;
 
-        let path = parse_capture_group(&cap, "path")
-            .ok_or(anyhow!("Could not find path in module line (version = 1)"))?;
+        let path = parse_capture_group(&cap, "path").ok_or(Error::parse(
+            "Could not find path in module line (version = 1)",
+        ))?;
 
         Ok(Self {
             size,
@@ -61,19 +210,22 @@ impl Module {
         })
     }
 
-    pub fn from_line_v2(line: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_line_v2(line: &[u8]) -> Result<Self, Error> {
         let cap = constants::DRCOV_MODULE_V2_REGEX
             .captures(line)
-            .ok_or(anyhow!("Module line is invalid (version = 2)"))?;
+            .ok_or(Error::parse("Module line is invalid (version = 2)"))?;
 
-        let segment_start: Hex<usize> = parse_capture_group(&cap, "base")
-            .ok_or(anyhow!("Could not find base in module line (version = 2)"))?;
+        let segment_start: Hex<usize> = parse_capture_group(&cap, "base").ok_or(Error::parse(
+            "Could not find base in module line (version = 2)",
+        ))?;
 
-        let end: Hex<usize> = parse_capture_group(&cap, "end")
-            .ok_or(anyhow!("Could not find end in module line (version = 2)"))?;
+        let end: Hex<usize> = parse_capture_group(&cap, "end").ok_or(Error::parse(
+            "Could not find end in module line (version = 2)",
+        ))?;
 
-        let path = parse_capture_group(&cap, "path")
-            .ok_or(anyhow!("Could not find path in module line (version = 2)"))?;
+        let path = parse_capture_group(&cap, "path").ok_or(Error::parse(
+            "Could not find path in module line (version = 2)",
+        ))?;
 
         let size = end.value - segment_start.value;
 
@@ -85,22 +237,25 @@ impl Module {
         })
     }
 
-    pub fn from_line_v3(line: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_line_v3(line: &[u8]) -> Result<Self, Error> {
         let cap = constants::DRCOV_MODULE_V3_REGEX
             .captures(line)
-            .ok_or(anyhow!("Module line is invalid (version = 3)"))?;
+            .ok_or(Error::parse("Module line is invalid (version = 3)"))?;
 
-        let segment_start: Hex<usize> = parse_capture_group(&cap, "base")
-            .ok_or(anyhow!("Could not find base in module line (version = 3)"))?;
+        let segment_start: Hex<usize> = parse_capture_group(&cap, "base").ok_or(Error::parse(
+            "Could not find base in module line (version = 3)",
+        ))?;
 
-        let end: Hex<usize> = parse_capture_group(&cap, "end")
-            .ok_or(anyhow!("Could not find end in module line (version = 3)"))?;
+        let end: Hex<usize> = parse_capture_group(&cap, "end").ok_or(Error::parse(
+            "Could not find end in module line (version = 3)",
+        ))?;
 
-        let path = parse_capture_group(&cap, "path")
-            .ok_or(anyhow!("Could not find path in module line (version = 3)"))?;
+        let path = parse_capture_group(&cap, "path").ok_or(Error::parse(
+            "Could not find path in module line (version = 3)",
+        ))?;
 
-        let containing_index = parse_capture_group(&cap, "containing_id").ok_or(anyhow!(
-            "Could not find containing id in module line (version = 3)"
+        let containing_index = parse_capture_group(&cap, "containing_id").ok_or(Error::parse(
+            "Could not find containing id in module line (version = 3)",
         ))?;
 
         let size = end.value - segment_start.value;
@@ -114,28 +269,31 @@ impl Module {
         })
     }
 
-    pub fn from_line_v4(line: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_line_v4(line: &[u8]) -> Result<Self, Error> {
         let cap = constants::DRCOV_MODULE_V4_REGEX
             .captures(line)
-            .ok_or(anyhow!("Module line is invalid (version = 4)"))?;
-
-        let segment_start: Hex<usize> = parse_capture_group(&cap, "base")
-            .ok_or(anyhow!("Could not find base in module line (version = 4)"))?;
+            .ok_or(Error::parse("Module line is invalid (version = 4)"))?;
 
-        let end: Hex<usize> = parse_capture_group(&cap, "end")
-            .ok_or(anyhow!("Could not find end in module line (version = 4)"))?;
+        let segment_start: Hex<usize> = parse_capture_group(&cap, "base").ok_or(Error::parse(
+            "Could not find base in module line (version = 4)",
+        ))?;
 
-        let path = parse_capture_group(&cap, "path")
-            .ok_or(anyhow!("Could not find path in module line (version = 4)"))?;
+        let end: Hex<usize> = parse_capture_group(&cap, "end").ok_or(Error::parse(
+            "Could not find end in module line (version = 4)",
+        ))?;
 
-        let containing_index = parse_capture_group(&cap, "containing_id").ok_or(anyhow!(
-            "Could not find containing id in module line (version = 4)"
+        let path = parse_capture_group(&cap, "path").ok_or(Error::parse(
+            "Could not find path in module line (version = 4)",
         ))?;
 
-        let segment_offset: Hex<usize> = parse_capture_group(&cap, "offset").ok_or(anyhow!(
-            "Could not find offset in module line (version = 4)"
+        let containing_index = parse_capture_group(&cap, "containing_id").ok_or(Error::parse(
+            "Could not find containing id in module line (version = 4)",
         ))?;
 
+        let segment_offset: Hex<usize> = parse_capture_group(&cap, "offset").ok_or(
+            Error::parse("Could not find offset in module line (version = 4)"),
+        )?;
+
         let size = end.value - segment_start.value;
 
         Ok(Self {
@@ -148,28 +306,31 @@ impl Module {
         })
     }
 
-    pub fn from_line_v5(line: &[u8]) -> anyhow::Result<Self> {
+    pub fn from_line_v5(line: &[u8]) -> Result<Self, Error> {
         let cap = constants::DRCOV_MODULE_V5_REGEX
             .captures(line)
-            .ok_or(anyhow!("Module line is invalid (version >= 5)"))?;
-
-        let segment_start: Hex<usize> = parse_capture_group(&cap, "base")
-            .ok_or(anyhow!("Could not find base in module line (version >= 5)"))?;
+            .ok_or(Error::parse("Module line is invalid (version >= 5)"))?;
 
-        let end: Hex<usize> = parse_capture_group(&cap, "end")
-            .ok_or(anyhow!("Could not find end in module line (version >= 5)"))?;
+        let segment_start: Hex<usize> = parse_capture_group(&cap, "base").ok_or(Error::parse(
+            "Could not find base in module line (version >= 5)",
+        ))?;
 
-        let path = parse_capture_group(&cap, "path")
-            .ok_or(anyhow!("Could not find path in module line (version >= 5)"))?;
+        let end: Hex<usize> = parse_capture_group(&cap, "end").ok_or(Error::parse(
+            "Could not find end in module line (version >= 5)",
+        ))?;
 
-        let containing_index = parse_capture_group(&cap, "containing_id").ok_or(anyhow!(
-            "Could not find containing id in module line (version >= 5)"
+        let path = parse_capture_group(&cap, "path").ok_or(Error::parse(
+            "Could not find path in module line (version >= 5)",
         ))?;
 
-        let segment_offset: Hex<usize> = parse_capture_group(&cap, "offset").ok_or(anyhow!(
-            "Could not find offset in module line (version >= 5)"
+        let containing_index = parse_capture_group(&cap, "containing_id").ok_or(Error::parse(
+            "Could not find containing id in module line (version >= 5)",
         ))?;
 
+        let segment_offset: Hex<usize> = parse_capture_group(&cap, "offset").ok_or(
+            Error::parse("Could not find offset in module line (version >= 5)"),
+        )?;
+
         let size = end.value - segment_start.value;
 
         Ok(Self {
@@ -181,15 +342,424 @@ impl Module {
             ..Default::default()
         })
     }
+
+    /// Parses a module line of a log written by drcov on Windows, whose module tables have the
+    /// `checksum` and `timestamp` columns of the PE header right before the path. Paths may
+    /// contain spaces.
+    pub fn from_line_windows(line: &[u8], version: u32) -> Result<Self, Error> {
+        let regex: &regex::bytes::Regex = match version {
+            1 | 2 => &constants::WINDOWS_MODULE_V2_REGEX,
+            3 => &constants::WINDOWS_MODULE_V3_REGEX,
+            4 => &constants::WINDOWS_MODULE_V4_REGEX,
+            _ => &constants::WINDOWS_MODULE_V5_REGEX,
+        };
+
+        let cap = regex.captures(line).ok_or(Error::parse(format!(
+            "Module line is invalid (version = {version}, Windows)"
+        )))?;
+
+        let segment_start: Hex<usize> = parse_capture_group(&cap, "base").ok_or(Error::parse(
+            format!("Could not find base in module line (version = {version}, Windows)"),
+        ))?;
+
+        let end: Hex<usize> = parse_capture_group(&cap, "end").ok_or(Error::parse(format!(
+            "Could not find end in module line (version = {version}, Windows)"
+        )))?;
+
+        let path = parse_capture_group(&cap, "path").ok_or(Error::parse(format!(
+            "Could not find path in module line (version = {version}, Windows)"
+        )))?;
+
+        let segment_offset = parse_capture_group::<Hex<usize>>(&cap, "offset")
+            .map(|offset| offset.value)
+            .unwrap_or_default();
+
+        let size = end
+            .value
+            .checked_sub(segment_start.value)
+            .ok_or(Error::parse(format!(
+                "Module ends ({:#x}) before it starts ({:#x}) (version = {version}, Windows)",
+                end.value, segment_start.value
+            )))?;
+
+        Ok(Self {
+            segment_start: segment_start.value,
+            segment_offset,
+            size,
+            path,
+            containing_index: parse_capture_group(&cap, "containing_id"),
+            ..Default::default()
+        })
+    }
+
+    /// Parses a module line of a frida-drcov log.
+    ///
+    /// The numeric fields are matched from the left with the named `columns` (the path column
+    /// excluded), so rows that lack trailing columns such as the entry point are still accepted.
+    /// The path is everything after the last numeric field and may contain commas and spaces.
+    pub fn from_line_frida(line: &[u8], columns: &[String]) -> Result<Self, Error> {
+        fn parse_number(field: &str) -> Option<usize> {
+            let field = field.trim();
+
+            match field
+                .strip_prefix("0x")
+                .or_else(|| field.strip_prefix("0X"))
+            {
+                Some(hex) => usize::from_str_radix(hex, 16).ok(),
+                None => field.parse().ok(),
+            }
+        }
+
+        let line = String::from_utf8_lossy(line);
+        let fields = line.split(',').collect::<Vec<_>>();
+
+        // The build-id is the only field before the path that is not a number
+        let build_id_index = columns.iter().position(|column| column == "build_id");
+        let num_numeric = fields
+            .iter()
+            .enumerate()
+            .take_while(|(index, field)| {
+                Some(*index) == build_id_index || parse_number(field).is_some()
+            })
+            .count();
+
+        let path = fields[num_numeric..].join(",").trim().to_string();
+        if path.is_empty() {
+            return Err(Error::parse(
+                "Could not find path in module line (flavor = frida)",
+            ));
+        }
+
+        let build_id = build_id_index
+            .filter(|index| *index < num_numeric)
+            .map(|index| fields[index].trim())
+            .filter(|build_id| {
+                !build_id.is_empty() && build_id.bytes().all(|b| b.is_ascii_hexdigit())
+            })
+            .map(str::to_ascii_lowercase);
+
+        let values = columns
+            .iter()
+            .filter(|column| *column != "path" && *column != "build_id")
+            .zip(
+                fields[..num_numeric]
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| Some(*index) != build_id_index)
+                    .filter_map(|(_, field)| parse_number(field)),
+            )
+            .collect::<Vec<_>>();
+
+        let value = |name: &str| {
+            values
+                .iter()
+                .find(|(column, _)| *column == name)
+                .map(|(_, value)| *value)
+        };
+
+        let (segment_start, size) = match (value("base"), value("end"), value("size")) {
+            (Some(base), Some(end), _) if end >= base => (base, end - base),
+            (base, _, Some(size)) => (base.unwrap_or_default(), size),
+            _ => {
+                return Err(Error::parse(
+                    "Could not find base and end in module line (flavor = frida)",
+                ))
+            }
+        };
+
+        Ok(Self {
+            segment_start,
+            size,
+            segment_offset: value("offset").unwrap_or_default(),
+            containing_index: value("containing_id"),
+            path,
+            build_id,
+            ..Default::default()
+        })
+    }
+
+    /// Parses a module line of a DynamoRIO table of `version` with [`ModuleLineTokenizer`],
+    /// returning `None` for the lines that have to be parsed with the regexes
+    fn tokenize_line(line: &[u8], version: u32, is_windows: bool) -> Option<Self> {
+        let mut tokenizer = ModuleLineTokenizer::new(line)?;
+
+        // The Windows tables of the first version have the columns of the second one
+        if version == 1 && !is_windows {
+            let _id = tokenizer.decimal()?;
+            let size = tokenizer.decimal()?;
+
+            return Some(Self {
+                size,
+                path: tokenizer.path_rest()?,
+                ..Default::default()
+            });
+        }
+
+        let _id = tokenizer.decimal()?;
+        let containing_index = if version >= 3 {
+            Some(tokenizer.decimal()?)
+        } else {
+            None
+        };
+        let segment_start = tokenizer.hex()?;
+        let end = tokenizer.hex()?;
+        let _entry = tokenizer.hex()?;
+        let segment_offset = if version >= 4 { tokenizer.hex()? } else { 0 };
+        if version >= 5 {
+            let _preferred_base = tokenizer.hex()?;
+        }
+
+        let path = if is_windows {
+            let _checksum = tokenizer.hex()?;
+            let _timestamp = tokenizer.hex()?;
+            tokenizer.path_rest()?
+        } else {
+            tokenizer.path_word()?
+        };
+
+        Some(Self {
+            size: end.checked_sub(segment_start)?,
+            segment_start,
+            segment_offset,
+            containing_index,
+            path,
+            ..Default::default()
+        })
+    }
+
+    /// The identity of the module, read from its binary if it can be opened
+    pub fn key(&self) -> ModuleKey {
+        ModuleKey::of_module(&self.path)
+    }
+
+    pub fn file_name(&self) -> &str {
+        self.path
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(self.path.as_str())
+    }
+
+    /// Whether this is a kernel provided mapping (e.g. the vdso) that has no backing file
+    pub fn is_pseudo_module(&self) -> bool {
+        let file_name = self.file_name();
+
+        (self.path.starts_with('[') && self.path.ends_with(']'))
+            || file_name.starts_with("linux-vdso.so")
+            || file_name.starts_with("linux-gate.so")
+    }
+
+    /// Whether this is the dynamic loader (ld.so)
+    pub fn is_dynamic_loader(&self) -> bool {
+        let file_name = self.file_name();
+
+        file_name.starts_with("ld-linux")
+            || file_name.starts_with("ld-musl")
+            || file_name.starts_with("ld.so")
+            || file_name.starts_with("ld64.so")
+    }
+
+    /// Returns the offsets of the executed basic blocks relative to the start of the module
+    /// (rather than the start of the segment they were recorded against).
+    pub fn bb_module_offsets(&self) -> impl Iterator<Item = u32> + '_ {
+        self.bb_starts
+            .iter()
+            .filter_map(|start| start.checked_add(self.segment_offset as u32))
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Modules {
     pub version: u32,
     pub table: Vec<Module>,
 }
 
 impl Modules {
+    /// Returns the same module table without any executed basic blocks
+    pub fn without_coverage(&self) -> Self {
+        Self {
+            version: self.version,
+            table: self
+                .table
+                .iter()
+                .map(|module| Module {
+                    bb_bitmap: RoaringBitmap::new(),
+                    bb_starts: RoaringBitmap::new(),
+                    ..module.clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// Adds the executed basic blocks of `other`, which has the same module table, to the ones of
+    /// this table
+    pub fn union_coverage(&mut self, other: &Modules) {
+        for (module, other) in self.table.iter_mut().zip(&other.table) {
+            module.bb_bitmap |= &other.bb_bitmap;
+            module.bb_starts |= &other.bb_starts;
+        }
+    }
+
+    /// Returns what the table recorded about the build of every module: the size it had once
+    /// loaded, i.e. the end of its last segment, if it was recorded at its load address, and its
+    /// build-id if the table has a `build_id` column. Tables without load addresses (e.g. the
+    /// legacy ones, or the ones written by hand) or build-ids do not tell builds apart.
+    pub fn captured_builds(&self) -> HashMap<&str, CapturedBuild<'_>> {
+        let mut res = HashMap::<&str, CapturedBuild>::new();
+
+        for module in &self.table {
+            let build = res.entry(module.path.as_str()).or_default();
+
+            if module.segment_start != 0 {
+                let end = module.segment_offset + module.size;
+                build.loaded_size = Some(build.loaded_size.map_or(end, |size| size.max(end)));
+            }
+
+            build.build_id = build.build_id.or(module.build_id.as_deref());
+        }
+
+        res
+    }
+
+    /// Reads a module table supplied out-of-band, for inputs that do not carry one. The file is
+    /// either a drcov module table (starting at its `Module Table:` header) or a `/proc/<pid>/maps`
+    /// listing, in which case the file backed mappings become the entries of the table in order,
+    /// like the per-segment entries of drcov's own tables.
+    pub fn from_module_table_file(path: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read(path)?;
+        let mut lines_iter = contents
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .skip_while(|line| {
+                !is_module_table_header(line) && !constants::PROC_MAPS_LINE_REGEX.is_match(line)
+            })
+            .peekable();
+
+        let Some(first_line) = lines_iter.peek() else {
+            return Err(Error::parse(format!(
+                "'{}' does not contain a module table",
+                path.display()
+            )));
+        };
+
+        if is_module_table_header(first_line) {
+            return parse_module_table(&contents, &mut lines_iter, &DrcovFilters::default());
+        }
+
+        let mut table: Vec<Module> = Vec::new();
+
+        for line in lines_iter {
+            let Some(cap) = constants::PROC_MAPS_LINE_REGEX.captures(line) else {
+                continue;
+            };
+
+            let (Some(start), Some(end), Some(path)) = (
+                parse_capture_group::<Hex<usize>>(&cap, "start"),
+                parse_capture_group::<Hex<usize>>(&cap, "end"),
+                parse_capture_group::<String>(&cap, "path"),
+            ) else {
+                return Err(Error::parse("Invalid maps line").at_line(&contents, line));
+            };
+
+            // Anonymous and kernel provided mappings ([heap], [stack], ...) are not modules
+            if !path.starts_with('/') {
+                continue;
+            }
+
+            let Some(size) = end.value.checked_sub(start.value) else {
+                return Err(Error::parse(format!(
+                    "Mapping of '{path}' ends ({:#x}) before it starts ({:#x})",
+                    end.value, start.value
+                ))
+                .at_line(&contents, line));
+            };
+
+            let containing = table
+                .iter()
+                .rposition(|module| module.path == path && module.containing_index.is_none());
+            let segment_offset = match containing {
+                Some(index) => {
+                    let Some(offset) = start.value.checked_sub(table[index].segment_start) else {
+                        return Err(Error::parse(format!(
+                            "Mapping of '{path}' at {:#x} starts before its first mapping at {:#x}",
+                            start.value, table[index].segment_start
+                        ))
+                        .at_line(&contents, line));
+                    };
+                    offset
+                }
+                None => 0,
+            };
+
+            table.push(Module {
+                size,
+                segment_start: start.value,
+                segment_offset,
+                containing_index: containing,
+                path,
+                ..Default::default()
+            });
+        }
+
+        Ok(Self { version: 0, table })
+    }
+
+    /// Records the basic block of `size` bytes executed at the absolute `address` in the module
+    /// (segment) that contains it. Returns whether any module contains the address.
+    pub fn insert_address(&mut self, address: u64, size: u32) -> bool {
+        let Some(module) = self.table.iter_mut().find(|module| {
+            let start = module.segment_start as u64;
+            (start..start + module.size as u64).contains(&address)
+        }) else {
+            return false;
+        };
+
+        let start = (address - module.segment_start as u64) as u32;
+        module
+            .bb_bitmap
+            .insert_range(start..start.saturating_add(size));
+        module.bb_starts.insert(start);
+
+        true
+    }
+
+    /// Records the `(address, size)` basic blocks like [`Self::insert_address`] does, logging how
+    /// many of them no module contains
+    pub fn insert_addresses(&mut self, blocks: impl IntoIterator<Item = (u64, u32)>) {
+        let mut num_blocks = 0usize;
+        let mut unmapped = 0usize;
+
+        for (address, size) in blocks {
+            num_blocks += 1;
+
+            if !self.insert_address(address, size) {
+                unmapped += 1;
+            }
+        }
+
+        if unmapped > 0 {
+            log::debug!(
+                "{unmapped} of {num_blocks} executed basic blocks are not part of any known module"
+            );
+        }
+    }
+
+    /// Applies the path map filters to the paths of the modules and drops the modules the module
+    /// filters exclude. This is for tables that did not go through the filters while being parsed
+    /// (e.g. ones supplied out-of-band), and only once the basic blocks have been recorded, since
+    /// those refer to modules by their index in the unfiltered table.
+    pub fn apply_filters(&mut self, filters: &DrcovFilters) {
+        self.table.retain_mut(|module| {
+            module.path = String::from_utf8_lossy(
+                &filters.maybe_replace_with_path_map_filter(module.path.as_bytes()),
+            )
+            .to_string();
+
+            filters.matches_any_module_filter(module.path.as_bytes())
+                && !filters.matches_any_module_skip_filter(module.path.as_bytes())
+        });
+    }
+
     pub fn get_coverage_all(&self) -> RoaringBitmap {
         let mut res = RoaringBitmap::new();
 
@@ -203,6 +773,7 @@ impl Modules {
 
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BBEntry {
     start: u32,
     size: u16,
@@ -210,7 +781,10 @@ pub struct BBEntry {
 }
 
 impl BBEntry {
-    pub fn from_reader<R: Read>(reader: &mut R) -> anyhow::Result<Self> {
+    /// The size of an entry of the basic block table
+    pub const SIZE: usize = 8;
+
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self, Error> {
         let start = reader.read_u32::<LittleEndian>()?;
         let size = reader.read_u16::<LittleEndian>()?;
         let module_id = reader.read_u16::<LittleEndian>()?;
@@ -218,6 +792,43 @@ impl BBEntry {
         Ok(Self::new(start, size, module_id))
     }
 
+    /// Decodes an entry of a binary basic block table
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        Self::new(
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            u16::from_le_bytes([bytes[4], bytes[5]]),
+            u16::from_le_bytes([bytes[6], bytes[7]]),
+        )
+    }
+
+    /// Parses an entry of a text basic block table (`module[<id>]: 0x<start>, <size>`), as
+    /// written by drcov's `-dump_text`
+    pub fn from_text_line(line: &[u8]) -> Result<Self, Error> {
+        let cap = constants::TEXT_BB_ENTRY_REGEX
+            .captures(line)
+            .ok_or(Error::parse(
+                "Basic block line does not match the expected format",
+            ))?;
+
+        let module_id = parse_capture_group(&cap, "id")
+            .ok_or(Error::parse("Could not find module id in basic block line"))?;
+
+        let start: Hex<usize> = parse_capture_group(&cap, "start")
+            .ok_or(Error::parse("Could not find start in basic block line"))?;
+
+        let size = parse_capture_group(&cap, "size")
+            .ok_or(Error::parse("Could not find size in basic block line"))?;
+
+        let start = u32::try_from(start.value).map_err(|_| {
+            Error::parse(format!(
+                "Basic block start {:#x} is out of range",
+                start.value
+            ))
+        })?;
+
+        Ok(Self::new(start, size, module_id))
+    }
+
     pub fn new(start: u32, size: u16, module_id: u16) -> Self {
         Self {
             start,
@@ -227,11 +838,60 @@ impl BBEntry {
     }
 }
 
-#[derive(Debug, Clone)]
+/// How the basic block tables of the iterations of persistent-mode harnesses are reported
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IterationMode {
+    /// The coverage of all iterations is merged
+    #[default]
+    Merge,
+    /// The coverage of every iteration is also written as its own test (`TN:`) section of lcov
+    /// outputs
+    PerIteration,
+}
+
+/// What the start offsets of the basic blocks of drcov logs are relative to
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BbOffsetBase {
+    /// The start of the segment the basic block is recorded against, like DynamoRIO's drcov does
+    Segment,
+    /// The start of the module that contains the segment, like frida-drcov does
+    Module,
+    /// Chosen by the flavor of the log: module for frida logs and segment for the others. Logs
+    /// before version 3 have no segments, so both are the same for them
+    #[default]
+    Auto,
+}
+
+impl BbOffsetBase {
+    /// Resolves [`BbOffsetBase::Auto`] for a log of `flavor`
+    fn of_flavor(self, flavor: &str) -> Self {
+        match self {
+            BbOffsetBase::Auto if flavor == constants::FRIDA_FLAVOR => BbOffsetBase::Module,
+            BbOffsetBase::Auto => BbOffsetBase::Segment,
+            base => base,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct DrcovFilters<'r> {
     pub module_filters: &'r [Filter],
     pub module_skip_filters: &'r [Filter],
     pub path_map_filters: &'r [ReplacementFilter],
+    /// Only the first basic block tables of logs with a table per iteration are kept
+    pub max_iterations: Option<usize>,
+    /// The basic block tables of logs with a table per iteration are merged as they are parsed,
+    /// unless every iteration is reported on its own
+    pub iteration_mode: IterationMode,
+    /// Only accept logs whose version line is followed by their flavor line (unless they have the
+    /// legacy bbcov layout), instead of tolerating headers in either order or without a flavor
+    pub strict_format: bool,
+    /// What the start offsets of the basic blocks are relative to. Module relative offsets are
+    /// made relative to their segments when they are inserted, like the offsets of drcov.
+    pub bb_offset_base: BbOffsetBase,
+    /// The size of the (decompressed) log and the number of basic blocks its headers declare are
+    /// checked against them before the tables are parsed
+    pub limits: InputLimits,
 }
 
 impl DrcovFilters<'_> {
@@ -251,7 +911,7 @@ impl DrcovFilters<'_> {
                 .any(|filter| filter.matcher.is_match(input))
     }
 
-    pub fn maybe_replace_with_path_map_filter<'d>(&'d self, input: &'d [u8]) -> Cow<[u8]> {
+    pub fn maybe_replace_with_path_map_filter<'d>(&'d self, input: &'d [u8]) -> Cow<'d, [u8]> {
         self.path_map_filters
             .iter()
             .find_map(|filter| {
@@ -264,208 +924,1140 @@ impl DrcovFilters<'_> {
     }
 }
 
+/// A parsed drcov log.
+///
+/// A `Drcov` owns all of its data and does not use interior mutability, so it is `Send + Sync`
+/// and parsing is re-entrant: any number of files can be parsed concurrently.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Drcov {
     pub version: u32,
     pub flavor: String,
+    /// The coverage of all iterations
     pub modules: Modules,
+    /// The coverage of every iteration of logs of persistent-mode harnesses, which have a basic
+    /// block table per iteration, if they are parsed with [`IterationMode::PerIteration`]; empty
+    /// for other logs
+    pub iterations: Vec<Modules>,
+}
+
+const _: () = assert_send_sync::<Module>();
+const _: () = assert_send_sync::<Modules>();
+const _: () = assert_send_sync::<DrcovFilters>();
+const _: () = assert_send_sync::<Drcov>();
+
+/// Finds the basic block table that follows the one of the previous iteration at the start of
+/// `data`, skipping blank lines and `Iteration: <n>` markers. Returns its number of basic blocks
+/// and where its entries start.
+fn find_next_bb_table(data: &[u8]) -> Option<(usize, usize)> {
+    let mut offset = 0;
+
+    loop {
+        let rest = &data[offset..];
+        let line_end = rest.iter().position(|b| *b == b'\n')?;
+        let line = rest[..line_end].trim_ascii();
+        offset += line_end + 1;
+
+        if line.is_empty() || constants::ITERATION_HEADER_REGEX.is_match(line) {
+            continue;
+        }
+
+        let cap = constants::DRCOV_BB_HEADER_REGEX.captures(line)?;
+        return Some((parse_capture_group(&cap, "count")?, offset));
+    }
+}
+
+/// Adds the basic blocks of every module, the `[start, end)` address ranges of `blocks` indexed by
+/// module id, to its bitmaps and empties `blocks`
+fn insert_basic_blocks(modules: &mut [Module], blocks: &mut [Vec<(u32, u32)>]) {
+    for (module, blocks) in modules.iter_mut().zip(blocks) {
+        blocks.sort_unstable();
+
+        let mut starts = blocks.iter().map(|(start, _)| *start).collect::<Vec<_>>();
+        starts.dedup();
+        module.bb_starts |= RoaringBitmap::from_sorted_iter(starts).unwrap_or_default();
+
+        // Overlapping blocks are merged so that every range is only inserted once
+        let mut pending: Option<(u32, u32)> = None;
+        for (start, end) in blocks.drain(..) {
+            match &mut pending {
+                Some((_, pending_end)) if start <= *pending_end => {
+                    *pending_end = (*pending_end).max(end)
+                }
+                _ => {
+                    if let Some((start, end)) = pending.replace((start, end)) {
+                        module.bb_bitmap.insert_range(start..end);
+                    }
+                }
+            }
+        }
+
+        if let Some((start, end)) = pending {
+            module.bb_bitmap.insert_range(start..end);
+        }
+    }
+}
+
+/// Parses the header of the basic block table at the start of `data` (`BB Table: <count> bbs`),
+/// skipping the iteration markers before it, and returns the number of basic blocks along with the
+/// offset of the first one
+fn parse_bb_table_header(data: &[u8]) -> Result<(usize, usize), Error> {
+    let mut offset = 0;
+
+    loop {
+        let rest = &data[offset..];
+        let line_end = rest.iter().position(|b| *b == b'\n');
+        let line = &rest[..line_end.unwrap_or(rest.len())];
+        let next_line = line_end.map_or(rest.len(), |line_end| line_end + 1);
+
+        if let Some(cap) = constants::DRCOV_BB_HEADER_REGEX.captures(line) {
+            let count = parse_capture_group(&cap, "count")
+                .ok_or(Error::parse("Coud not parse number of basic blocks"))?;
+
+            return Ok((count, offset + next_line));
+        }
+
+        if line_end.is_some()
+            && (constants::ITERATION_HEADER_REGEX.is_match(line.trim_ascii())
+                || line.trim_ascii().is_empty())
+        {
+            offset += next_line;
+            continue;
+        }
+
+        return Err(Error::parse("Invalid Basic Block header line"));
+    }
+}
+
+fn is_module_table_header(line: &[u8]) -> bool {
+    constants::DRCOV_MODULE_HEADER_OLD_REGEX.is_match(line)
+        || constants::DRCOV_MODULE_HEADER_REGEX.is_match(line)
+}
+
+/// Whether `line`, the line after the flavor of a drcov log, shows that the log has no module
+/// table: either the basic block table follows right away or the module table is empty
+fn declares_no_modules(line: &[u8]) -> bool {
+    if line.starts_with(constants::BB_TABLE_PREFIX) {
+        return true;
+    }
+
+    constants::DRCOV_MODULE_HEADER_REGEX
+        .captures(line)
+        .or_else(|| constants::DRCOV_MODULE_HEADER_OLD_REGEX.captures(line))
+        .and_then(|cap| parse_capture_group::<usize>(&cap, "count"))
+        == Some(0)
+}
+
+/// Parses a drcov module table, starting at its header line. The lines are lines of `contents`,
+/// which parse errors are located in.
+fn parse_module_table<'a, I: Iterator<Item = &'a [u8]>>(
+    contents: &[u8],
+    lines_iter: &mut I,
+    filters: &DrcovFilters,
+) -> Result<Modules, Error> {
+    log::debug!("Parsing modules");
+
+    let header_line = lines_iter
+        .next()
+        .ok_or(Error::parse("Modules header line missing"))?;
+
+    let invalid_module_header_line_err = || {
+        Error::parse("Modules header line does not match the expected format")
+            .at_line(contents, header_line)
+    };
+
+    let (version, num_modules, is_windows) =
+        if let Some(cap) = constants::DRCOV_MODULE_HEADER_OLD_REGEX.captures(header_line) {
+            let version = 1u32;
+
+            let count =
+                parse_capture_group(&cap, "count").ok_or_else(invalid_module_header_line_err)?;
+
+            (version, count, false)
+        } else if let Some(cap) = constants::DRCOV_MODULE_HEADER_REGEX.captures(header_line) {
+            let version =
+                parse_capture_group(&cap, "version").ok_or_else(invalid_module_header_line_err)?;
+
+            let count =
+                parse_capture_group(&cap, "count").ok_or_else(invalid_module_header_line_err)?;
+
+            // Tables written on Windows have extra columns
+            let is_windows = lines_iter.next().is_some_and(|columns_line| {
+                constants::WINDOWS_COLUMNS_REGEX.is_match(columns_line)
+            });
+
+            (version, count, is_windows)
+        } else {
+            return Err(invalid_module_header_line_err());
+        };
+
+    let parser = |line: &[u8]| match version {
+        _ if is_windows => Module::from_line_windows(line, version),
+        1 => Module::from_line_v1(line),
+        2 => Module::from_line_v2(line),
+        3 => Module::from_line_v3(line),
+        4 => Module::from_line_v4(line),
+        _ => Module::from_line_v5(line),
+    };
+
+    let mut table = Vec::with_capacity(num_modules);
+
+    for _ in 0..num_modules {
+        let original_line = lines_iter
+            .next()
+            .ok_or(Error::parse("Invalid module table (lines missing)"))?;
+        let line = filters.maybe_replace_with_path_map_filter(original_line);
+
+        if !filters.matches_any_module_filter(line.as_ref())
+            || filters.matches_any_module_skip_filter(line.as_ref())
+        {
+            continue;
+        }
+
+        let module = match Module::tokenize_line(line.as_ref(), version, is_windows) {
+            Some(module) => module,
+            None => parser(line.as_ref()).map_err(|e| e.at_line(contents, original_line))?,
+        };
+
+        table.push(module);
+    }
+
+    // Resolve offsets based on containing_index
+    if version >= 3 {
+        for i in 0..table.len() {
+            if let Some(containing_index) = table[i].containing_index {
+                if containing_index != i {
+                    assert!(i < containing_index);
+                    table[i].segment_offset =
+                        table[i].segment_start - table[containing_index].segment_start;
+                }
+            }
+        }
+    }
+
+    log::debug!("Modules version: {version}, Number of modules: {num_modules}");
+
+    Ok(Modules { version, table })
+}
+
+/// Parses the module table of a frida-drcov log, starting at its header line. Unlike the tables
+/// written by DynamoRIO, the header text varies between versions of the script, the `Columns:`
+/// line may be missing and rows may lack some of the announced columns.
+fn parse_frida_module_table<'a, I: Iterator<Item = &'a [u8]>>(
+    contents: &[u8],
+    lines_iter: &mut std::iter::Peekable<I>,
+    filters: &DrcovFilters,
+) -> Result<Modules, Error> {
+    log::debug!("Parsing modules (flavor = frida)");
+
+    let header_line = lines_iter
+        .next()
+        .ok_or(Error::parse("Modules header line missing"))?;
+
+    let invalid_module_header_line_err = || {
+        Error::parse("Modules header line does not match the expected format")
+            .at_line(contents, header_line)
+    };
+
+    let cap = constants::FRIDA_MODULE_HEADER_REGEX
+        .captures(header_line)
+        .ok_or_else(invalid_module_header_line_err)?;
+
+    let version = parse_capture_group(&cap, "version").unwrap_or(2u32);
+    let num_modules =
+        parse_capture_group(&cap, "count").ok_or_else(invalid_module_header_line_err)?;
+
+    let columns = match lines_iter
+        .peek()
+        .and_then(|line| constants::FRIDA_COLUMNS_REGEX.captures(line))
+    {
+        Some(cap) => {
+            let columns = String::from_utf8_lossy(&cap["columns"])
+                .split(',')
+                .map(|column| column.trim().to_ascii_lowercase())
+                .collect();
+            lines_iter.next();
+            columns
+        }
+        None => match version {
+            1 => vec!["id", "size", "path"],
+            2 => vec!["id", "base", "end", "entry", "path"],
+            3 => vec!["id", "containing_id", "base", "end", "entry", "path"],
+            _ => vec![
+                "id",
+                "containing_id",
+                "base",
+                "end",
+                "entry",
+                "offset",
+                "path",
+            ],
+        }
+        .into_iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>(),
+    };
+
+    let mut table = Vec::with_capacity(num_modules);
+
+    for _ in 0..num_modules {
+        let original_line = lines_iter
+            .next()
+            .ok_or(Error::parse("Invalid module table (lines missing)"))?;
+        let line = filters.maybe_replace_with_path_map_filter(original_line.trim_ascii_end());
+
+        if !filters.matches_any_module_filter(line.as_ref())
+            || filters.matches_any_module_skip_filter(line.as_ref())
+        {
+            continue;
+        }
+
+        table.push(
+            Module::from_line_frida(line.as_ref(), &columns)
+                .map_err(|e| e.at_line(contents, original_line))?,
+        );
+    }
+
+    for i in 0..table.len() {
+        if let Some(containing_index) = table[i].containing_index {
+            if containing_index < i && table[i].segment_offset == 0 {
+                table[i].segment_offset = table[i]
+                    .segment_start
+                    .saturating_sub(table[containing_index].segment_start);
+            }
+        }
+    }
+
+    log::debug!("Modules version: {version}, Number of modules: {num_modules}");
+
+    Ok(Modules { version, table })
 }
 
 impl Drcov {
-    pub fn from_file<P: AsRef<Path>>(path: P, filters: &DrcovFilters) -> anyhow::Result<Self> {
-        fn parse_version<'a, I: Iterator<Item = &'a [u8]>>(
-            lines_iter: &mut I,
-        ) -> anyhow::Result<u32> {
+    /// The coverage of the modules of an input of the given flavor that is not a drcov log, and
+    /// so has a single basic block table
+    pub fn from_modules(flavor: &str, modules: Modules) -> Self {
+        Self {
+            version: 0,
+            flavor: flavor.to_string(),
+            modules,
+            iterations: Vec::new(),
+        }
+    }
+
+    /// Coverage without any modules, for inputs that describe source lines themselves
+    pub fn without_modules(flavor: &str) -> Self {
+        Self::from_modules(
+            flavor,
+            Modules {
+                version: 0,
+                table: Vec::new(),
+            },
+        )
+    }
+
+    /// Parses the drcov log at `path`. `fallback_modules` is used as the module table of logs that
+    /// do not carry one.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        filters: &DrcovFilters,
+        fallback_modules: Option<&Modules>,
+    ) -> Result<Self, Error> {
+        log::info!("Loading drcov file: {}", path.as_ref().display());
+
+        // The logs of long fuzzing campaigns run into gigabytes, so they are mapped rather than
+        // read, and the basic block tables are parsed straight from the mapping
+        #[cfg(feature = "native")]
+        let drcov = {
+            let file = std::fs::File::open(path)?;
+            let contents = unsafe { memmap2::Mmap::map(&file)? };
+            Self::from_bytes(
+                &decompress(&contents, filters.limits.max_input_size)?,
+                filters,
+                fallback_modules,
+            )?
+        };
+        #[cfg(not(feature = "native"))]
+        let drcov = Self::from_reader(std::fs::File::open(path)?, filters, fallback_modules)?;
+
+        log::info!("Drcov file loaded");
+
+        Ok(drcov)
+    }
+
+    /// Parses a drcov log read from `reader` (e.g. a pipe), like [`Drcov::from_file`]
+    pub fn from_reader<R: Read>(
+        reader: R,
+        filters: &DrcovFilters,
+        fallback_modules: Option<&Modules>,
+    ) -> Result<Self, Error> {
+        // Fuzzing clusters often store their logs compressed
+        Self::from_bytes(
+            &read_maybe_compressed(reader, filters.limits.max_input_size)?,
+            filters,
+            fallback_modules,
+        )
+    }
+
+    /// Parses the (uncompressed) drcov log in `contents`, like [`Drcov::from_file`]
+    pub fn from_bytes(
+        contents: &[u8],
+        filters: &DrcovFilters,
+        fallback_modules: Option<&Modules>,
+    ) -> Result<Self, Error> {
+        fn parse_version(contents: &[u8], version_line: &[u8]) -> Result<u32, Error> {
             log::debug!("Parsing version number");
-            let version_line = lines_iter.next().ok_or(anyhow!("Version line missing"))?;
+
+            let invalid_version_line_err = || {
+                Error::parse("Version line does not match the expected format")
+                    .at_line(contents, version_line)
+            };
 
             let cap = constants::DRCOV_VERSION_REGEX
                 .captures(version_line)
-                .ok_or(anyhow!("Version line does not match the expected format"))?;
+                .ok_or_else(invalid_version_line_err)?;
 
-            let version = parse_capture_group(&cap, "version")
-                .ok_or(anyhow!("Version line does not match the expected format"))?;
+            let version =
+                parse_capture_group(&cap, "version").ok_or_else(invalid_version_line_err)?;
 
             log::debug!("Version number: {version}");
 
             Ok(version)
         }
 
-        fn parse_flavor<'a, I: Iterator<Item = &'a [u8]>>(
-            lines_iter: &mut I,
-        ) -> anyhow::Result<String> {
+        fn parse_flavor(contents: &[u8], flavor_line: &[u8]) -> Result<String, Error> {
             log::debug!("Parsing flavor");
 
-            let flavor_line = lines_iter.next().ok_or(anyhow!("Flavor line missing"))?;
+            let invalid_flavor_line_err = || {
+                Error::parse("Flavor line does not match the expected format")
+                    .at_line(contents, flavor_line)
+            };
 
             let cap = constants::DRCOV_FLAVOR_REGEX
                 .captures(flavor_line)
-                .ok_or(anyhow!("Flavor line does not match the expected format"))?;
+                .ok_or_else(invalid_flavor_line_err)?;
 
-            let flavor = parse_capture_group(&cap, "flavor")
-                .ok_or(anyhow!("Flavor line does not match the expected format"))?;
+            let flavor = parse_capture_group(&cap, "flavor").ok_or_else(invalid_flavor_line_err)?;
 
             log::debug!("Flavor: {flavor}");
 
             Ok(flavor)
         }
 
-        fn parse_num_basic_blocks<'a, I: Iterator<Item = &'a [u8]>>(
-            lines_iter: &mut I,
-        ) -> anyhow::Result<usize> {
-            let bb_header_line = lines_iter
+        /// Parses the version and flavor lines at the start of a log. Unless the format is
+        /// `strict`, they may come in either order and the flavor line may be missing, since
+        /// several third-party writers reorder or omit it.
+        fn parse_header<'a, I: Iterator<Item = &'a [u8]>>(
+            contents: &[u8],
+            lines_iter: &mut std::iter::Peekable<I>,
+            strict: bool,
+        ) -> Result<(u32, String), Error> {
+            let first_line = lines_iter
                 .next()
-                .ok_or(anyhow!("Basic Block header line missing"))?;
+                .ok_or(Error::parse("Version line missing"))?;
+
+            if !strict && constants::DRCOV_FLAVOR_REGEX.is_match(first_line) {
+                log::debug!("The flavor line precedes the version line");
+                let flavor = parse_flavor(contents, first_line)?;
+                let version_line = lines_iter
+                    .next()
+                    .ok_or(Error::parse("Version line missing"))?;
 
-            let bb_cap = constants::DRCOV_BB_HEADER_REGEX
-                .captures(bb_header_line)
-                .ok_or(anyhow!("Invalid Basic Block header line"))?;
+                return Ok((parse_version(contents, version_line)?, flavor));
+            }
+
+            let version = parse_version(contents, first_line)?;
 
-            let num_bb = parse_capture_group(&bb_cap, "count")
-                .ok_or(anyhow!("Coud not parse number of basic blocks"))?;
+            let flavor = match lines_iter.peek() {
+                // The logs of bbcov, the DynamoRIO client drcov replaced, (and of the first
+                // versions of drcov) have no flavor line and go straight to a module table
+                // without a version, whose rows only have the id, size and path of every module
+                Some(line) if constants::DRCOV_MODULE_HEADER_OLD_REGEX.is_match(line) => {
+                    log::debug!("Legacy bbcov layout without a flavor line");
+                    constants::BBCOV_FLAVOR.to_string()
+                }
+                Some(line) if !strict && !constants::DRCOV_FLAVOR_REGEX.is_match(line) => {
+                    log::debug!("No flavor line, assuming {}", constants::DEFAULT_FLAVOR);
+                    constants::DEFAULT_FLAVOR.to_string()
+                }
+                _ => {
+                    let flavor_line = lines_iter
+                        .next()
+                        .ok_or(Error::parse("Flavor line missing"))?;
+                    parse_flavor(contents, flavor_line)?
+                }
+            };
 
-            Ok(num_bb)
+            Ok((version, flavor))
         }
 
+        /// Parses the basic block table at the start of `bb_data`, a part of `contents`, and returns
+        /// its size in bytes
         fn parse_basic_blocks(
+            contents: &[u8],
             bb_data: &[u8],
             num_bb: usize,
-            modules: &mut Vec<Module>,
-        ) -> anyhow::Result<()> {
-            let mut cursor = Cursor::new(bb_data);
-
-            let num_modules = modules.len();
+            modules: &mut [Module],
+            offset_base: BbOffsetBase,
+        ) -> Result<usize, Error> {
+            // Module id -> the address ranges of its basic blocks, which are inserted into the
+            // bitmaps in order, a batch at a time, since roaring bitmaps are much faster to build
+            // that way
+            let mut blocks = vec![Vec::new(); modules.len()];
+            let mut pending = 0;
+
+            let mut add_basic_block = |bb: BBEntry| {
+                if let Some(module) = modules.get(bb.module_id as usize) {
+                    let start = match offset_base {
+                        BbOffsetBase::Module => {
+                            match bb.start.checked_sub(module.segment_offset as u32) {
+                                Some(start) => start,
+                                // Before the segment the block is recorded against
+                                None => return,
+                            }
+                        }
+                        _ => bb.start,
+                    };
+
+                    // Past the end of the module, or of the address space of the offsets
+                    let Some(end) = start
+                        .checked_add(bb.size as u32)
+                        .filter(|end| (*end as usize) < module.size)
+                    else {
+                        return;
+                    };
+
+                    blocks[bb.module_id as usize].push((start, end));
+                    pending += 1;
+
+                    if pending == constants::MAX_PENDING_BASIC_BLOCKS {
+                        insert_basic_blocks(modules, &mut blocks);
+                        pending = 0;
+                    }
+                }
+            };
 
-            for _ in 0..num_bb {
-                let bb = BBEntry::from_reader(&mut cursor)?;
-                if (bb.module_id as usize) < num_modules {
-                    let module = &mut modules[bb.module_id as usize];
+            // drcov -dump_text writes the entries as lines, after a line naming their columns
+            let columns_line_end = bb_data.iter().position(|b| *b == b'\n');
+            if let Some(columns_line_end) = columns_line_end.filter(|columns_line_end| {
+                constants::TEXT_BB_COLUMNS_REGEX.is_match(&bb_data[..*columns_line_end])
+            }) {
+                log::debug!("Parsing text basic block table");
+                let mut offset = columns_line_end + 1;
 
-                    if module.size <= (bb.start + bb.size as u32) as usize {
-                        continue;
-                    }
+                for _ in 0..num_bb {
+                    let rest = &bb_data[offset..];
+                    let line_end = rest.iter().position(|b| *b == b'\n');
+                    let line = &rest[..line_end.unwrap_or(rest.len())];
 
-                    let addr_start = bb.start;
-                    let addr_end = bb.start + bb.size as u32 - 1;
+                    add_basic_block(
+                        BBEntry::from_text_line(line).map_err(|e| e.at_line(contents, line))?,
+                    );
 
-                    module.bb_bitmap.insert_range(addr_start..addr_end);
+                    offset += line_end.map_or(rest.len(), |line_end| line_end + 1);
                 }
-            }
-
-            Ok(())
-        }
 
-        fn parse_modules<'a, I: Iterator<Item = &'a [u8]>>(
-            lines_iter: &mut I,
-            filters: &DrcovFilters,
-        ) -> anyhow::Result<Modules> {
-            log::debug!("Parsing modules");
+                insert_basic_blocks(modules, &mut blocks);
 
-            let header_line = lines_iter
-                .next()
-                .ok_or(anyhow!("Modules header line missing"))?;
+                return Ok(offset);
+            }
 
-            let invalid_module_header_line_err =
-                "Modules header line does not match the expected format";
+            let num_entries = bb_data.len() / BBEntry::SIZE;
+            if num_entries < num_bb {
+                let table_offset = bb_data.as_ptr() as usize - contents.as_ptr() as usize;
 
-            let (version, num_modules) = if let Some(cap) =
-                constants::DRCOV_MODULE_HEADER_OLD_REGEX.captures(header_line)
-            {
-                let version = 1u32;
+                return Err(Error::parse(format!(
+                    "The basic block table ends after {num_entries} of its {num_bb} entries"
+                ))
+                .at_offset(table_offset + num_entries * BBEntry::SIZE));
+            }
 
-                let count = parse_capture_group(&cap, "count")
-                    .ok_or(anyhow!(invalid_module_header_line_err))?;
+            let (entries, _) = bb_data[..num_bb * BBEntry::SIZE].as_chunks::<{ BBEntry::SIZE }>();
+            for entry in entries {
+                add_basic_block(BBEntry::from_bytes(entry));
+            }
 
-                (version, count)
-            } else if let Some(cap) = constants::DRCOV_MODULE_HEADER_REGEX.captures(header_line) {
-                let version = parse_capture_group(&cap, "version")
-                    .ok_or(anyhow!(invalid_module_header_line_err))?;
+            insert_basic_blocks(modules, &mut blocks);
 
-                let count = parse_capture_group(&cap, "count")
-                    .ok_or(anyhow!(invalid_module_header_line_err))?;
+            Ok(num_bb * BBEntry::SIZE)
+        }
 
-                lines_iter.next();
+        // The offset of a line of `contents`, which the tables after the text headers start at
+        let offset_of = |line: &[u8]| line.as_ptr() as usize - contents.as_ptr() as usize;
 
-                (version, count)
-            } else {
-                anyhow::bail!(invalid_module_header_line_err)
-            };
+        let mut lines_iter = contents
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .peekable();
 
-            let parser = match version {
-                1 => Module::from_line_v1,
-                2 => Module::from_line_v2,
-                3 => Module::from_line_v3,
-                4 => Module::from_line_v4,
-                _ => Module::from_line_v5,
-            };
+        let (version, flavor) = parse_header(contents, &mut lines_iter, filters.strict_format)?;
 
-            let mut table = Vec::with_capacity(num_modules);
+        // Lightweight tracers only emit the basic block table and rely on a module table supplied
+        // out-of-band
+        let lacks_module_table = lines_iter
+            .peek()
+            .is_some_and(|line| declares_no_modules(line));
 
-            for _ in 0..num_modules {
-                let line = lines_iter
-                    .next()
-                    .map(|line| filters.maybe_replace_with_path_map_filter(line))
-                    .ok_or(anyhow!("Invalid module table (lines missing)"))?;
+        let mut modules = match fallback_modules.filter(|_| lacks_module_table) {
+            Some(fallback_modules) => {
+                log::debug!("Using the module table supplied out-of-band");
 
-                if !filters.matches_any_module_filter(line.as_ref())
-                    || filters.matches_any_module_skip_filter(line.as_ref())
+                // Skip the (empty) module table, if any
+                while lines_iter
+                    .peek()
+                    .is_some_and(|line| !line.starts_with(constants::BB_TABLE_PREFIX))
                 {
-                    continue;
+                    lines_iter.next();
                 }
 
-                let module = parser(line.as_ref())?;
-
-                table.push(module);
+                fallback_modules.clone()
             }
-
-            // Resolve offsets based on containing_index
-            if version >= 3 {
-                for i in 0..table.len() {
-                    if let Some(containing_index) = table[i].containing_index {
-                        if containing_index != i {
-                            assert!(i < containing_index);
-                            table[i].segment_offset =
-                                table[i].segment_start - table[containing_index].segment_start;
-                        }
-                    }
-                }
+            None if lines_iter
+                .peek()
+                .is_some_and(|line| line.starts_with(constants::BB_TABLE_PREFIX)) =>
+            {
+                return Err(Error::parse(
+                    "The file does not contain a module table (use --module-table)",
+                ))
+            }
+            None if flavor == constants::FRIDA_FLAVOR => {
+                parse_frida_module_table(contents, &mut lines_iter, filters)?
             }
+            None => parse_module_table(contents, &mut lines_iter, filters)?,
+        };
 
-            log::debug!("Modules version: {version}, Number of modules: {num_modules}");
+        let tables_start = lines_iter
+            .peek()
+            .map_or(contents.len(), |line| offset_of(line));
 
-            Ok(Modules { version, table })
-        }
+        drop(lines_iter);
 
-        log::info!("Loading drcov file: {}", path.as_ref().display());
-        let mut cursor: usize = 0;
-        let contents = std::fs::read(path)?;
+        // The table of the first iteration may be marked too
+        let (mut num_bb, bb_data_start) = parse_bb_table_header(&contents[tables_start..])
+            .map_err(|e| e.at_line(contents, &contents[tables_start..]))?;
+        let mut declared_bbs = num_bb;
+
+        // Tracers of persistent-mode harnesses write a basic block table per iteration, which
+        // are only kept apart if they are reported per iteration
+        let keep_iterations = filters.iteration_mode == IterationMode::PerIteration;
+        let mut iterations = Vec::new();
+        let mut num_iterations = 0;
+        let mut bb_data = &contents[tables_start + bb_data_start..];
+
+        loop {
+            log::debug!("Number of Basic Blocks: {num_bb}");
+            filters.limits.check_declared_basic_blocks(declared_bbs)?;
+
+            let mut iteration = keep_iterations.then(|| modules.without_coverage());
+            let table_len = parse_basic_blocks(
+                contents,
+                bb_data,
+                num_bb,
+                &mut iteration.as_mut().unwrap_or(&mut modules).table,
+                filters.bb_offset_base.of_flavor(&flavor),
+            )?;
+            iterations.extend(iteration);
+            num_iterations += 1;
+
+            if filters
+                .max_iterations
+                .is_some_and(|max_iterations| num_iterations >= max_iterations)
+            {
+                break;
+            }
 
-        let mut lines_iter = contents
-            .as_slice()
-            .split(|b| *b == b'\n')
-            .filter(|line| !line.is_empty())
-            .inspect(|v| cursor += v.len() + 1);
+            let rest = &bb_data[table_len.min(bb_data.len())..];
+            let Some((next_num_bb, offset)) = find_next_bb_table(rest) else {
+                break;
+            };
 
-        let version = parse_version(&mut lines_iter)?;
-        let flavor = parse_flavor(&mut lines_iter)?;
-        let mut modules = parse_modules(&mut lines_iter, &filters)?;
-        let num_bb = parse_num_basic_blocks(&mut lines_iter)?;
+            num_bb = next_num_bb;
+            declared_bbs = declared_bbs.saturating_add(num_bb);
+            bb_data = &rest[offset..];
+        }
 
-        log::debug!("Number of Basic Blocks: {num_bb}");
+        if iterations.len() > 1 {
+            for iteration in &iterations {
+                modules.union_coverage(iteration);
+            }
+        } else if let Some(iteration) = iterations.pop() {
+            modules = iteration;
+        }
 
-        drop(lines_iter);
+        if num_iterations > 1 {
+            log::info!("Found {num_iterations} iterations");
+        }
 
-        let bb_data = &contents[cursor..];
+        if lacks_module_table {
+            modules.apply_filters(filters);
 
-        parse_basic_blocks(bb_data, num_bb, &mut modules.table)?;
+            for iteration in &mut iterations {
+                iteration.apply_filters(filters);
+            }
+        }
 
         log::debug!("Modules parsed: {:#?}", modules.table);
-        log::info!("Drcov file loaded");
 
         Ok(Self {
             version,
             flavor,
             modules,
+            iterations,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::LimitExceeded;
+    use crate::test_fixtures::TempPath;
+
+    #[test]
+    fn reads_module_tables_from_maps_listings() {
+        let read = |maps: &str| {
+            let path = TempPath::new("maps");
+            std::fs::write(path.path(), maps).unwrap();
+            Modules::from_module_table_file(path.path())
+        };
+
+        let modules = read("00400000-00401000 r-xp 00000000 08:01 42 /usr/bin/app\n00600000-00601000 rw-p 00000000 00:00 0 [heap]\n00601000-00603000 rw-p 00001000 08:01 42 /usr/bin/app\n").unwrap();
+        assert_eq!(
+            modules
+                .table
+                .iter()
+                .map(|module| (module.size, module.segment_offset, module.containing_index))
+                .collect::<Vec<_>>(),
+            [(0x1000, 0, None), (0x2000, 0x201000, Some(0))]
+        );
+
+        // An inverted mapping, and a file mapped again below its first mapping
+        for maps in [
+            "00401000-00400000 r-xp 00000000 08:01 42 /usr/bin/app\n",
+            "00400000-00401000 r-xp 00000000 08:01 42 /usr/bin/app\n00300000-00301000 rw-p 00001000 08:01 42 /usr/bin/app\n",
+        ] {
+            let err = read(maps).unwrap_err();
+            assert!(err.to_string().contains("line"), "{err}");
+        }
+    }
+
+    #[test]
+    fn parses_legacy_bbcov_logs() {
+        let mut log = b"BBCOV VERSION: 1\nModule Table: 2\n  0, 4096, /usr/bin/app\n  1, 8192, C:\\Program Files\\app\\lib.dll\nBB Table: 1 bbs\n".to_vec();
+        log.extend_from_slice(&16u32.to_le_bytes());
+        log.extend_from_slice(&4u16.to_le_bytes());
+        log.extend_from_slice(&1u16.to_le_bytes());
+
+        let drcov = Drcov::from_reader(log.as_slice(), &DrcovFilters::default(), None).unwrap();
+
+        assert_eq!((drcov.version, drcov.flavor.as_str()), (1, "bbcov"));
+        assert_eq!(
+            drcov
+                .modules
+                .table
+                .iter()
+                .map(|module| (module.path.as_str(), module.size))
+                .collect::<Vec<_>>(),
+            [
+                ("/usr/bin/app", 4096),
+                ("C:\\Program Files\\app\\lib.dll", 8192)
+            ]
+        );
+        assert_eq!(
+            drcov.modules.table[1].bb_bitmap.iter().collect::<Vec<_>>(),
+            [16, 17, 18, 19]
+        );
+    }
+
+    #[test]
+    fn basic_blocks_cover_their_last_byte() {
+        let mut log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\nBB Table: 2 bbs\n".to_vec();
+        for (start, size) in [(16u32, 1u16), (32, 3)] {
+            log.extend_from_slice(&start.to_le_bytes());
+            log.extend_from_slice(&size.to_le_bytes());
+            log.extend_from_slice(&0u16.to_le_bytes());
+        }
+
+        let drcov = Drcov::from_reader(log.as_slice(), &DrcovFilters::default(), None).unwrap();
+
+        // A block of `size` bytes spans [start, start + size), so a single byte block is not empty
+        assert_eq!(
+            drcov.modules.table[0].bb_bitmap.iter().collect::<Vec<_>>(),
+            [16, 32, 33, 34]
+        );
+    }
+
+    #[test]
+    fn skips_basic_blocks_that_overflow_the_offsets() {
+        let mut log = format!("DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x0, {:#x}, 0x0, /usr/bin/app\nBB Table: 2 bbs\n", u64::MAX).into_bytes();
+        for (start, size) in [(u32::MAX - 8, 16u16), (16, 4)] {
+            log.extend_from_slice(&start.to_le_bytes());
+            log.extend_from_slice(&size.to_le_bytes());
+            log.extend_from_slice(&0u16.to_le_bytes());
+        }
+
+        let drcov = Drcov::from_reader(log.as_slice(), &DrcovFilters::default(), None).unwrap();
+
+        assert_eq!(
+            drcov.modules.table[0].bb_starts.iter().collect::<Vec<_>>(),
+            [16]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_basic_block_table_headers() {
+        let parse = |bb_table: &[u8]| {
+            let mut log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\n".to_vec();
+            log.extend_from_slice(bb_table);
+            Drcov::from_reader(log.as_slice(), &DrcovFilters::default(), None)
+        };
+
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&16u32.to_le_bytes());
+        entry.extend_from_slice(&4u16.to_le_bytes());
+        entry.extend_from_slice(&0u16.to_le_bytes());
+
+        let valid = [b"Iteration: 1\n\nBB Table: 1 bbs\n".as_slice(), &entry].concat();
+        assert!(parse(&valid).is_ok());
+
+        // Neither a binary count nor a table without a header is guessed at
+        let binary_count = [b"BB Table: ".as_slice(), &1u32.to_le_bytes(), &entry].concat();
+        assert!(parse(b"BB Table: abc bbs\n").is_err());
+        assert!(parse(&binary_count).is_err());
+        assert!(parse(&entry).is_err());
+    }
+
+    #[test]
+    fn rejects_windows_modules_that_end_before_they_start() {
+        let module = Module::from_line_windows(
+            b"  0, 0x1000, 0x2000, 0x1000, 0x0001a2b3, 0x5f5e0f00, C:\\Program Files\\app.exe",
+            2,
+        )
+        .unwrap();
+        assert_eq!((module.segment_start, module.size), (0x1000, 0x1000));
+        assert_eq!(module.path, "C:\\Program Files\\app.exe");
+
+        assert!(Module::from_line_windows(
+            b"  0, 0x2000, 0x1000, 0x1000, 0x0001a2b3, 0x5f5e0f00, C:\\Program Files\\app.exe",
+            2
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parses_text_basic_block_tables() {
+        let parse = |bb_table: &[u8]| {
+            let mut log = b"DRCOV VERSION: 2\r\nDRCOV FLAVOR: drcov\r\nModule Table: version 2, count 1\r\nColumns: id, base, end, entry, path\r\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\r\n".to_vec();
+            log.extend_from_slice(bb_table);
+            Drcov::from_reader(log.as_slice(), &DrcovFilters::default(), None)
+        };
+
+        // -dump_text right-aligns the sizes, and the log may have been converted to CRLF
+        let drcov = parse(b"BB Table: 2 bbs\r\nmodule id, start, size:\r\nmodule[  0]: 0x0000000000000010,   4\r\nmodule[  0]: 0x0000000000000100,  12\r\n").unwrap();
+        assert_eq!(
+            drcov.modules.table[0].bb_bitmap.iter().collect::<Vec<_>>(),
+            (0x10..0x14).chain(0x100..0x10c).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            drcov.modules.table[0].bb_starts.iter().collect::<Vec<_>>(),
+            vec![0x10, 0x100]
+        );
+
+        // A table with fewer lines than its header counts is truncated
+        assert!(parse(
+            b"BB Table: 3 bbs\r\nmodule id, start, size:\r\nmodule[  0]: 0x0000000000000010,   4\r\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn merges_iterations_unless_they_are_reported_on_their_own() {
+        let mut log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\n".to_vec();
+        for (iteration, start) in [(1, 16u32), (2, 32)] {
+            log.extend_from_slice(format!("Iteration: {iteration}\nBB Table: 1 bbs\n").as_bytes());
+            log.extend_from_slice(&start.to_le_bytes());
+            log.extend_from_slice(&2u16.to_le_bytes());
+            log.extend_from_slice(&0u16.to_le_bytes());
+        }
+
+        let parse = |iteration_mode| {
+            let filters = DrcovFilters {
+                iteration_mode,
+                ..Default::default()
+            };
+            Drcov::from_reader(log.as_slice(), &filters, None).unwrap()
+        };
+        let executed = |modules: &Modules| modules.table[0].bb_bitmap.iter().collect::<Vec<_>>();
+
+        let merged = parse(IterationMode::Merge);
+        assert_eq!(executed(&merged.modules), [16, 17, 32, 33]);
+        assert!(merged.iterations.is_empty());
+
+        let per_iteration = parse(IterationMode::PerIteration);
+        assert_eq!(executed(&per_iteration.modules), [16, 17, 32, 33]);
+        assert_eq!(
+            per_iteration
+                .iterations
+                .iter()
+                .map(executed)
+                .collect::<Vec<_>>(),
+            [[16, 17], [32, 33]]
+        );
+    }
+
+    #[test]
+    fn enforces_the_input_limits_before_parsing_the_tables() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let limits = |max_input_size, max_basic_blocks| DrcovFilters {
+            limits: InputLimits {
+                max_input_size,
+                max_basic_blocks,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // The table is cut short, so the count is rejected before the entries are read
+        let log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\nBB Table: 1000000 bbs\n";
+        let res = Drcov::from_reader(log.as_slice(), &limits(None, Some(10)), None);
+        assert!(matches!(
+            res,
+            Err(Error::Limit(LimitExceeded::BasicBlocks {
+                count: 1000000,
+                limit: 10
+            }))
+        ));
+
+        // A small compressed log must not be inflated past the size limit
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(log).unwrap();
+        encoder.write_all(&vec![0; 1 << 20]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.len() < 4096);
+
+        let res = Drcov::from_reader(compressed.as_slice(), &limits(Some(4096), None), None);
+        assert!(matches!(
+            res,
+            Err(Error::Limit(LimitExceeded::InputSize { limit: 4096, .. }))
+        ));
+    }
+
+    #[test]
+    fn parses_logs_read_from_a_stream_like_files() {
+        let mut log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 2\nColumns: id, base, end, entry, path\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\n  1, 0x7f0000, 0x7f8000, 0x0, /usr/lib/libc.so\nBB Table: 3 bbs\n".to_vec();
+        for (start, size, module_id) in [(16u32, 4u16, 0u16), (0x100, 8, 1), (64, 2, 0)] {
+            log.extend_from_slice(&start.to_le_bytes());
+            log.extend_from_slice(&size.to_le_bytes());
+            log.extend_from_slice(&module_id.to_le_bytes());
+        }
+
+        let summary = |drcov: Drcov| {
+            (
+                drcov.version,
+                drcov.flavor,
+                drcov
+                    .modules
+                    .table
+                    .into_iter()
+                    .map(|module| {
+                        (
+                            module.path,
+                            module.segment_start,
+                            module.size,
+                            module.bb_bitmap.iter().collect::<Vec<_>>(),
+                            module.bb_starts.iter().collect::<Vec<_>>(),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        };
+
+        let filters = DrcovFilters::default();
+        let path = TempPath::new("drcov.log");
+        std::fs::write(path.path(), &log).unwrap();
+
+        // `--input -` reads the log from the standard input through `from_reader`
+        let from_file = summary(Drcov::from_file(path.path(), &filters, None).unwrap());
+        let from_reader = summary(Drcov::from_reader(log.as_slice(), &filters, None).unwrap());
+
+        assert_eq!(from_reader, from_file);
+        assert_eq!(from_file.2.len(), 2);
+        assert_eq!(from_file.2[0].3, vec![16, 17, 18, 19, 64, 65]);
+        assert_eq!(from_file.2[1].4, vec![0x100]);
+    }
+
+    #[test]
+    fn reads_gzip_and_zstd_compressed_logs() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let mut log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\nBB Table: 2 bbs\n".to_vec();
+        for (start, size) in [(16u32, 4u16), (64, 2)] {
+            log.extend_from_slice(&start.to_le_bytes());
+            log.extend_from_slice(&size.to_le_bytes());
+            log.extend_from_slice(&0u16.to_le_bytes());
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&log).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let zstd = zstd::encode_all(log.as_slice(), 0).unwrap();
+
+        let filters = DrcovFilters::default();
+        let executed = |drcov: Drcov| drcov.modules.table[0].bb_bitmap.iter().collect::<Vec<_>>();
+        let expected = vec![16, 17, 18, 19, 64, 65];
+
+        for compressed in [gzip, zstd] {
+            assert_ne!(compressed, log);
+
+            let path = TempPath::new("drcov.log");
+            std::fs::write(path.path(), &compressed).unwrap();
+            assert_eq!(
+                executed(Drcov::from_file(path.path(), &filters, None).unwrap()),
+                expected
+            );
+
+            assert_eq!(
+                executed(Drcov::from_reader(compressed.as_slice(), &filters, None).unwrap()),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn tolerates_reordered_and_missing_flavor_lines() {
+        let table = "Module Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x1000, 0x2000, 0x1000, /usr/bin/app\nBB Table: 0 bbs\n";
+        let parse = |header: &str, strict_format: bool| {
+            let filters = DrcovFilters {
+                strict_format,
+                ..Default::default()
+            };
+            Drcov::from_reader(format!("{header}{table}").as_bytes(), &filters, None)
+                .map(|drcov| (drcov.version, drcov.flavor))
+        };
+
+        let reordered = "DRCOV FLAVOR: frida\nDRCOV VERSION: 2\n";
+        let flavorless = "DRCOV VERSION: 2\n";
+
+        assert_eq!(parse(reordered, false).unwrap(), (2, "frida".to_string()));
+        assert_eq!(parse(flavorless, false).unwrap(), (2, "drcov".to_string()));
+        assert!(parse(reordered, true).is_err());
+        assert!(parse(flavorless, true).is_err());
+    }
+
+    #[test]
+    fn records_the_build_ids_of_frida_module_tables() {
+        let log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: frida\nModule Table: version 2, count 2\nColumns: id, base, end, entry, build_id, path\n  0, 0x400000, 0x405000, 0x0, AB12cd, /bin/my app, v2\n  1, 0x7f0000, 0x7f1000, 0x0, , /lib/libc.so\nBB Table: 0 bbs\n";
+
+        let drcov = Drcov::from_reader(log.as_slice(), &DrcovFilters::default(), None).unwrap();
+        let table = &drcov.modules.table;
+
+        assert_eq!(
+            (
+                table[0].path.as_str(),
+                table[0].size,
+                table[0].build_id.as_deref()
+            ),
+            ("/bin/my app, v2", 0x5000, Some("ab12cd"))
+        );
+        assert_eq!(
+            (table[1].size, table[1].build_id.as_deref()),
+            (0x1000, None)
+        );
+        assert_eq!(
+            drcov.modules.captured_builds()["/bin/my app, v2"],
+            CapturedBuild {
+                loaded_size: Some(0x5000),
+                build_id: Some("ab12cd"),
+            }
+        );
+    }
+
+    #[test]
+    fn reads_basic_block_offsets_relative_to_their_base() {
+        let mut log = b"DRCOV VERSION: 3\nDRCOV FLAVOR: frida\nModule Table: version 3, count 2\nColumns: id, containing_id, base, end, entry, path\n  0, 0, 0x400000, 0x401000, 0x0, /bin/app\n  1, 0, 0x401000, 0x403000, 0x0, /bin/app\nBB Table: 1 bbs\n".to_vec();
+        log.extend_from_slice(&0x1010u32.to_le_bytes());
+        log.extend_from_slice(&4u16.to_le_bytes());
+        log.extend_from_slice(&1u16.to_le_bytes());
+
+        let offsets = |bb_offset_base: BbOffsetBase| {
+            let filters = DrcovFilters {
+                bb_offset_base,
+                ..Default::default()
+            };
+            let drcov = Drcov::from_reader(log.as_slice(), &filters, None).unwrap();
+            drcov.modules.table[1]
+                .bb_module_offsets()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(offsets(BbOffsetBase::Auto), [0x1010]);
+        assert_eq!(offsets(BbOffsetBase::Module), [0x1010]);
+        assert_eq!(offsets(BbOffsetBase::Segment), [0x2010]);
+    }
+
+    #[test]
+    fn tokenizes_module_lines_like_the_regexes() {
+        let fields = |module: Module| {
+            (
+                module.segment_start,
+                module.size,
+                module.segment_offset,
+                module.containing_index,
+                module.path,
+            )
+        };
+        let lines: [(&[u8], u32, bool); 8] = [
+            (b"  0, 4096, /usr/bin/my app \r", 1, false),
+            (b"  1, 0x1000, 0X2fff, 0x1010, /lib/libc.so extra", 2, false),
+            (b"\t2,  1, 0x1000, 0x2000, 0x1000, /lib/ld.so", 3, false),
+            (
+                b"  3, 3, 0x1000, 0x2000, 0x1000, 0x400, /lib/ld.so\x0b",
+                4,
+                false,
+            ),
+            (
+                b"  4, 4, 0x1000, 0x2000, 0x1000, 0x0, 0x0, /bin/app",
+                5,
+                false,
+            ),
+            (
+                b" 5, 0x1000, 0x2000, 0x1000, 0xab, 0x5e, C:\\Program Files\\a.dll ",
+                1,
+                true,
+            ),
+            (
+                b" 6, 6, 0x1000, 0x2000, 0x1000, 0x0, 0x0, 0xab, 0x5e, C:\\b.dll",
+                5,
+                true,
+            ),
+            (
+                "  7, 0x1000, 0x2000, 0x1000, /home/ünï/app".as_bytes(),
+                2,
+                false,
+            ),
+        ];
+
+        for (line, version, is_windows) in lines {
+            let regex = match version {
+                _ if is_windows => Module::from_line_windows(line, version),
+                1 => Module::from_line_v1(line),
+                2 => Module::from_line_v2(line),
+                3 => Module::from_line_v3(line),
+                4 => Module::from_line_v4(line),
+                _ => Module::from_line_v5(line),
+            }
+            .unwrap();
+
+            match Module::tokenize_line(line, version, is_windows) {
+                Some(module) => assert_eq!(fields(module), fields(regex)),
+                None => assert!(!line.is_ascii()),
+            }
+        }
+
+        assert!(Module::tokenize_line(b"  0, 0x2000, 0x1000, 0x0, /bin/app", 2, false).is_none());
+        assert!(Module::tokenize_line(b"  0 , 0x1000, 0x2000, 0x0, /bin/app", 2, false).is_none());
+    }
+}
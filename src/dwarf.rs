@@ -1,56 +1,209 @@
-use crate::cli::Filter;
-use crate::drcov::{Module, Modules};
-use gimli::{Dwarf, DwarfSections, LineProgramHeader, LineRow, Reader, Unit};
-use itertools::Itertools;
-use object::{Object, ObjectSection, ObjectSegment, SegmentFlags};
+use crate::aliases::{self, AliasPolicy, FunctionSymbols, ModuleFunction};
+#[cfg(feature = "native")]
+use crate::cache;
+use crate::complexity::FunctionBlocks;
+use crate::consistency;
+use crate::cross_check::{self, CoveredRows, CrossCheckTool};
+#[cfg(feature = "native")]
+use crate::debuginfod;
+use crate::drcov::{CapturedBuild, Module, Modules};
+use crate::error::Error;
+use crate::filter::Filter;
+use crate::module_key;
+use crate::object_cache::{LineTableCache, ObjectFileCache};
+use crate::oracle::{self, StaticBlock};
+use crate::progress::Progress;
+use crate::source_path::SourcePathResolver;
+use crate::util::assert_send_sync;
+use clap::ValueEnum;
+use gimli::{
+    AttributeValue, DebuggingInformationEntry, Dwarf, DwarfSections, EndianSlice, FileEntry,
+    LineProgramHeader, LineRow, Reader, RunTimeEndian, Unit, UnitHeader, UnitRef,
+};
+use object::{BinaryFormat, Object, ObjectSection, ObjectSegment};
 use ouroboros::self_referencing;
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::os::unix::fs::MetadataExt;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
 mod constants {
 
     pub const UNKNOWN_MODULE: &str = "<unknown>";
+
+    /// The granularity with which the loader maps segments
+    pub const PAGE_SIZE: u64 = 0x1000;
+
+    /// How many line program rows are processed between two checks of the module deadline
+    pub const ROWS_PER_DEADLINE_CHECK: usize = 4096;
+
+    /// The number of units each thread that reads the units of a module has to read at least,
+    /// below which they are not worth the threads
+    pub const MIN_UNITS_PER_THREAD: usize = 16;
 }
 
-#[self_referencing]
+/// The error with which gathering the debug information of a module is abandoned once its
+/// deadline has passed
 #[derive(Debug)]
-struct ObjectFile {
-    mmap: memmap2::Mmap,
+struct ModuleTimedOut;
+
+impl std::fmt::Display for ModuleTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The module timeout has expired")
+    }
+}
+
+impl std::error::Error for ModuleTimedOut {}
+
+fn check_deadline(deadline: Option<Instant>) -> anyhow::Result<()> {
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Err(ModuleTimedOut.into());
+    }
+
+    Ok(())
+}
+
+/// The contents of an object file: memory mapped, or read into memory where files cannot be mapped
+/// (e.g. on wasm32) or when they do not come from a file at all
+type ObjectData = Box<dyn AsRef<[u8]> + Send + Sync>;
+
+#[self_referencing]
+pub struct ObjectFile {
+    mmap: ObjectData,
     #[borrows(mmap)]
     #[not_covariant]
     pub object: object::File<'this>,
 }
 
 impl ObjectFile {
+    /// Returns the link time address that corresponds to the address the module gets loaded at.
+    ///
+    /// For ELF files this is the page aligned virtual address of the first `PT_LOAD` segment,
+    /// since that is where the loader maps the start of the module. Other formats use their
+    /// preferred image base.
     pub fn load_base(&self) -> u64 {
-        self.with_object(|obj| obj)
+        let object = self.with_object(|obj| obj);
+
+        if object.format() != BinaryFormat::Elf {
+            return object.relative_address_base();
+        }
+
+        // The segments of ELF files are their PT_LOAD program headers
+        object
             .segments()
-            .filter_map(|s| {
-                if let SegmentFlags::Elf { p_flags } = s.flags() {
-                    (p_flags & object::elf::PT_LOAD != 0).then_some(s.address() - s.file_range().0)
-                } else {
-                    None
-                }
-            })
+            .map(|s| s.address())
             .min()
+            .map(|address| address & !(constants::PAGE_SIZE - 1))
             .unwrap_or_default()
     }
+
+    /// Returns the size of the module once loaded, i.e. the distance from the load base to the end
+    /// of its last segment
+    pub fn image_size(&self) -> u64 {
+        let image_end = self
+            .with_object(|obj| obj)
+            .segments()
+            .map(|segment| segment.address() + segment.size())
+            .max()
+            .unwrap_or_default();
+
+        image_end.saturating_sub(self.load_base())
+    }
+
+    /// Whether the `captured` build of a module is another build than this one, e.g. because the
+    /// binary was rebuilt after the coverage was captured. Only binaries with a build-id are
+    /// checked, like the builds of modules are told apart in the outputs.
+    pub fn is_other_build(&self, captured: &CapturedBuild) -> bool {
+        module_key::is_other_build(
+            self.image_size(),
+            build_id_hex(self.with_object(|obj| obj)).as_deref(),
+            captured,
+        )
+    }
+}
+
+impl std::fmt::Debug for ObjectFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.with_object(|object| {
+            f.debug_struct("ObjectFile")
+                .field("object", object)
+                .finish()
+        })
+    }
 }
 
 impl ObjectFile {
-    pub fn from_path(path: &str) -> anyhow::Result<Self> {
-        let file = std::fs::File::open(path)?;
-        let mmap = unsafe { memmap2::Mmap::map(&file)? };
-        let res = ObjectFileBuilder {
-            mmap,
-            object_builder: |mmap| object::File::parse(&**mmap).unwrap(),
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        check_regular_file(path)?;
+
+        #[cfg(feature = "native")]
+        let data: ObjectData = {
+            let file = std::fs::File::open(path)?;
+            Box::new(unsafe { memmap2::Mmap::map(&file)? })
+        };
+        #[cfg(not(feature = "native"))]
+        let data: ObjectData = Box::new(std::fs::read(path)?);
+
+        Self::from_data(data, path)
+    }
+
+    /// Reads the object file at `path` into memory instead of mapping it, for object files that
+    /// are kept for long (e.g. by an [`ObjectFileCache`]): a mapped file that is rewritten in place
+    /// meanwhile would fault on its next access
+    pub fn read_from_path(path: &str) -> Result<Self, Error> {
+        check_regular_file(path)?;
+
+        Self::from_data(Box::new(std::fs::read(path)?), path)
+    }
+
+    /// Parses the object file of the module at `path` from its contents, e.g. the ones of a file
+    /// that has been uploaded to a browser
+    pub fn from_bytes(data: Vec<u8>, path: &str) -> Result<Self, Error> {
+        Self::from_data(Box::new(data), path)
+    }
+
+    fn from_data(data: ObjectData, path: &str) -> Result<Self, Error> {
+        ObjectFileTryBuilder {
+            mmap: data,
+            object_builder: |data| object::File::parse((**data).as_ref()),
         }
-        .build();
+        .try_build()
+        .map_err(|e| Error::debug_info(path, e))
+    }
 
-        Ok(res)
+    /// Opens the object file with the debug information of the module at `path`: the module
+    /// itself, or the separate debug file its `.gnu_debuglink` or build-id leads to. Fails with
+    /// [`Error::DebugInfo`] if there is none.
+    pub fn with_debug_info(path: &str) -> Result<Self, Error> {
+        get_object_with_debug_info(path)?
+            .ok_or_else(|| Error::debug_info(path, "No debug information found"))
+    }
+}
+
+/// Fails unless `path` is a regular file, since opening a FIFO or a device (e.g. as the path of a
+/// module of an upload) would block or read forever
+fn check_regular_file(path: &str) -> Result<(), Error> {
+    if !std::fs::metadata(path)?.is_file() {
+        return Err(Error::debug_info(path, "Not a regular file"));
     }
+
+    Ok(())
+}
+
+/// Returns whether the existing files `a` and `b` are the same file
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(a.metadata().ok()?.ino() == b.metadata().ok()?.ino())
+}
+
+/// Returns whether the existing files `a` and `b` are the same file
+#[cfg(not(unix))]
+fn is_same_file(a: &Path, b: &Path) -> Option<bool> {
+    Some(a.canonicalize().ok()? == b.canonicalize().ok()?)
 }
 
 /*
@@ -94,16 +247,8 @@ fn follow_debug_link(object: &object::File) -> Option<String> {
     let mut mod_path = mod_dir.to_path_buf();
     mod_path.push(debug_link.as_ref());
 
-    if mod_path.exists() {
-        if let Some((mod_path_meta, debug_link_meta)) = mod_path
-            .metadata()
-            .ok()
-            .zip(debug_link_path.metadata().ok())
-        {
-            if mod_path_meta.ino() != debug_link_meta.ino() {
-                return Some(mod_path.to_string_lossy().to_string());
-            }
-        }
+    if mod_path.exists() && is_same_file(&mod_path, &debug_link_path) == Some(false) {
+        return Some(mod_path.to_string_lossy().to_string());
     }
 
     // 3. Check $mod_dir/.debug/$debuglink
@@ -127,31 +272,167 @@ fn follow_debug_link(object: &object::File) -> Option<String> {
     None
 }
 
-fn get_module_object_with_debug_info(module: &Module) -> anyhow::Result<Option<ObjectFile>> {
+/// Returns the build-id of `object` as a lowercase hex string
+pub fn build_id_hex(object: &object::File) -> Option<String> {
+    let Ok(Some(build_id)) = object.build_id() else {
+        return None;
+    };
+
+    (!build_id.is_empty()).then(|| build_id.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// Looks for the separate debug info file of `object` by its build-id, under /usr/lib/debug/.build-id
+/// (which is where distributions install them, e.g. for the dynamic loader) and in the debuginfod
+/// cache
+fn find_debug_file_by_build_id(object: &object::File) -> Option<String> {
+    debug_file_path_by_build_id(&build_id_hex(object)?)
+}
+
+/// Looks for a separate debug info file with the given build-id, see
+/// [`find_debug_file_by_build_id`]
+pub(crate) fn debug_file_path_by_build_id(build_id: &str) -> Option<String> {
+    if build_id.len() < 2 || !build_id.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let (first, rest) = build_id.split_at(2);
+
+    let path = format!("/usr/lib/debug/.build-id/{first}/{rest}.debug");
+
+    if Path::new(&path).is_file() {
+        return Some(path);
+    }
+
+    cached_debug_file_path(build_id)
+}
+
+/// Returns the debug file of `build_id` if it has been downloaded from a debuginfod server before
+#[cfg(feature = "native")]
+fn cached_debug_file_path(build_id: &str) -> Option<String> {
+    let path = debuginfod::cached_debug_info_path(build_id)?;
+
+    if !path.is_file() {
+        return None;
+    }
+
+    cache::touch(&path);
+
+    Some(path.to_string_lossy().to_string())
+}
+
+/// Returns the debug file of `build_id` if it has been downloaded from a debuginfod server before
+#[cfg(not(feature = "native"))]
+fn cached_debug_file_path(_build_id: &str) -> Option<String> {
+    None
+}
+
+/// Returns the build-id of the object file at `path` if no debug information could be found for it
+/// locally, so that it can be fetched from a debuginfod server
+pub fn missing_debug_info_build_id(path: &str) -> Option<String> {
+    match get_object_with_debug_info(path) {
+        Ok(None) => ObjectFile::from_path(path)
+            .ok()
+            .and_then(|object_file| build_id_hex(object_file.with_object(|obj| obj))),
+        _ => None,
+    }
+}
+
+pub(crate) fn get_object_with_debug_info(path: &str) -> Result<Option<ObjectFile>, Error> {
+    find_object_with_debug_info(path, ObjectFile::from_path)
+}
+
+/// Finds the object file with the debug information of the module at `path`, opening the object
+/// files along the way with `open`
+pub(crate) fn find_object_with_debug_info(
+    path: &str,
+    open: impl Fn(&str) -> Result<ObjectFile, Error>,
+) -> Result<Option<ObjectFile>, Error> {
     let mut stack = Vec::new();
-    stack.push(ObjectFile::from_path(&module.path)?);
+    stack.push(open(path)?);
+
+    let mut tried_build_id = false;
 
     while let Some(module_object) = stack.pop() {
         let object = module_object.with_object(|obj| obj);
 
         if let Some(debug_link_module_path) = follow_debug_link(object) {
-            stack.push(ObjectFile::from_path(&debug_link_module_path)?);
+            stack.push(open(&debug_link_module_path)?);
         } else if object.has_debug_symbols() {
             return Ok(Some(module_object));
+        } else if !tried_build_id {
+            tried_build_id = true;
+            if let Some(debug_file_path) = find_debug_file_by_build_id(object) {
+                stack.push(open(&debug_file_path)?);
+            }
         }
     }
 
     Ok(None)
 }
 
-#[derive(Debug, Clone)]
+/// When a line counts as executed, given the executed bytes of the basic blocks that overlap the
+/// address ranges the line program assigns to it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LineHitSemantics {
+    /// The first byte of one of the line's address ranges has been executed
+    #[default]
+    FirstByte,
+    /// Any byte of the line's address ranges has been executed (like llvm-cov)
+    AnyByte,
+    /// Every byte of all of the line's address ranges has been executed
+    AllBytes,
+}
+
+impl LineHitSemantics {
+    /// Combines whether two address ranges of the same line have been executed
+    fn combine(self, a: bool, b: bool) -> bool {
+        match self {
+            LineHitSemantics::FirstByte | LineHitSemantics::AnyByte => a || b,
+            LineHitSemantics::AllBytes => a && b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct LineInfoFilters<'r> {
     pub src_filters: &'r [Filter],
     pub src_skip_filters: &'r [Filter],
+    pub include_loader: bool,
+    pub line_hit_semantics: LineHitSemantics,
+    pub discriminator_branches: bool,
+    /// Split the lines into the basic blocks of the disassembly of their modules instead of their
+    /// discriminators, so that the lines whose blocks have only partly been executed stand out
+    pub block_branches: bool,
+    pub consistency_checks: bool,
+    /// How long the debug information of a single module may take to be gathered
+    pub module_timeout: Option<Duration>,
+    /// When the debug information of all the modules must have been gathered. The modules it
+    /// interrupts, or that are left once it has passed, are abandoned like the ones that outlast
+    /// the module timeout.
+    pub deadline: Option<Instant>,
+    pub alias_policy: AliasPolicy,
+    /// The reference tool a sample of the covered lines of every module is compared against
+    pub cross_check: Option<CrossCheckTool>,
+    /// Receives the modules processed and the line program rows scanned
+    pub progress: Option<&'r dyn Progress>,
+    /// Rewrites the source file paths of the debug information before the source filters
+    pub source_path_resolver: Option<&'r dyn SourcePathResolver>,
+    /// Keeps the object files of the modules loaded for the next calls
+    pub object_files: Option<&'r ObjectFileCache>,
+    /// Keeps the line tables of the modules for the next calls, which must use the same source
+    /// filters and source path resolver
+    pub line_tables: Option<&'r LineTableCache>,
 }
 
 impl LineInfoFilters<'_> {
-    pub fn matches_any_source_filter(&self, source: Option<&String>) -> bool {
+    /// Returns the path the source file at `path` of `module` is reported under
+    fn resolve_source_path(&self, module: &Module, path: String) -> String {
+        self.source_path_resolver
+            .and_then(|resolver| resolver.resolve(&module.path, &path))
+            .unwrap_or(path)
+    }
+
+    pub fn matches_any_source_filter(&self, source: Option<&str>) -> bool {
         source.is_some_and(|source| {
             self.src_filters.is_empty()
                 || self
@@ -161,7 +442,7 @@ impl LineInfoFilters<'_> {
         })
     }
 
-    pub fn matches_any_source_skip_filter(&self, source: Option<&String>) -> bool {
+    pub fn matches_any_source_skip_filter(&self, source: Option<&str>) -> bool {
         source.is_some_and(|source| {
             (!self.src_skip_filters.is_empty())
                 && self
@@ -172,164 +453,1656 @@ impl LineInfoFilters<'_> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineInfo {
     pub line: u64,
     pub executed: bool,
 }
 
-fn get_program_file<R: Reader>(
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionInfo {
+    pub name: String,
+    pub module: String,
+    pub start_line: u64,
+    pub end_line: u64,
+    pub executed: bool,
+    /// The names of the other functions that share the code of the function (see
+    /// [`AliasPolicy`])
+    pub aliases: Vec<String>,
+    /// The basic blocks and branches of the function, if the disassembly of its module has been
+    /// recovered (see [`crate::complexity::FunctionComplexity`])
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub blocks: Option<FunctionBlocks>,
+}
+
+/// A basic block of a line that has been compiled into several of them, told apart by its DWARF
+/// discriminator (e.g. the two arms of `a ? b : c`) or, with block branches, by its position among
+/// the basic blocks of the disassembly the code of the line spans
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BranchInfo {
+    pub line: u64,
+    pub discriminator: u64,
+    pub executed: bool,
+}
+
+/// Line and function coverage keyed by source file path.
+///
+/// It is `Send + Sync`; coverage gathered on different threads is combined with
+/// [`SourceCoverage::merge`].
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceCoverage {
+    pub lines: HashMap<String, Vec<LineInfo>>,
+    pub functions: HashMap<String, Vec<FunctionInfo>>,
+    /// The blocks of the lines with several discriminators or basic blocks, if they were requested
+    pub branches: HashMap<String, Vec<BranchInfo>>,
+}
+
+const _: () = assert_send_sync::<ObjectFile>();
+const _: () = assert_send_sync::<LineInfoFilters>();
+const _: () = assert_send_sync::<SourceCoverage>();
+
+impl SourceCoverage {
+    /// Unions the coverage of `other` into `self`
+    pub fn merge(&mut self, other: SourceCoverage) {
+        self.extend(other);
+        self.coalesce();
+    }
+
+    /// Appends the records of `other` to `self`, leaving duplicates to be unioned by
+    /// [`SourceCoverage::coalesce`]
+    fn extend(&mut self, other: SourceCoverage) {
+        for (file, lines) in other.lines {
+            self.lines.entry(file).or_default().extend(lines);
+        }
+
+        for (file, functions) in other.functions {
+            self.functions.entry(file).or_default().extend(functions);
+        }
+
+        for (file, branches) in other.branches {
+            self.branches.entry(file).or_default().extend(branches);
+        }
+    }
+
+    /// Returns the executed lines of `file` some of whose blocks (see [`BranchInfo`]) have not
+    /// been executed
+    pub fn partial_lines(&self, file: &str) -> HashSet<u64> {
+        let missed_lines = self
+            .branches
+            .get(file)
+            .into_iter()
+            .flatten()
+            .filter(|branch| !branch.executed)
+            .map(|branch| branch.line)
+            .collect::<HashSet<_>>();
+
+        self.lines
+            .get(file)
+            .into_iter()
+            .flatten()
+            .filter(|info| info.executed && missed_lines.contains(&info.line))
+            .map(|info| info.line)
+            .collect()
+    }
+
+    /// Returns the line records of `file` that belong to `function`
+    pub fn function_lines<'c>(
+        &'c self,
+        file: &str,
+        function: &'c FunctionInfo,
+    ) -> impl Iterator<Item = &'c LineInfo> + 'c {
+        self.lines
+            .get(file)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter(|info| (function.start_line..=function.end_line).contains(&info.line))
+    }
+
+    /// Renames every source file using `f`, merging the records of files that end up with the
+    /// same name
+    pub fn map_source_paths(self, f: impl Fn(&str) -> String) -> Self {
+        let mut res = SourceCoverage::default();
+
+        for (file, lines) in self.lines {
+            res.lines.entry(f(&file)).or_default().extend(lines);
+        }
+
+        for (file, functions) in self.functions {
+            res.functions.entry(f(&file)).or_default().extend(functions);
+        }
+
+        for (file, branches) in self.branches {
+            res.branches.entry(f(&file)).or_default().extend(branches);
+        }
+
+        res.coalesce();
+
+        res
+    }
+
+    /// Sorts the records of every file and unions the duplicate ones
+    pub fn coalesce(&mut self) {
+        coalesce_line_info(&mut self.lines);
+        coalesce_function_info(&mut self.functions);
+        coalesce_branch_info(&mut self.branches);
+    }
+}
+
+/// Address range `[begin, end)` of a function along with its index in the unit's function list
+pub(crate) type FunctionRange = (u64, u64, usize);
+
+/// A function of a compilation unit, as found while walking its DIEs
+pub(crate) struct UnitFunction {
+    pub(crate) name: String,
+    pub(crate) file: Option<Arc<str>>,
+    pub(crate) decl_line: u64,
+    pub(crate) low_pc: u64,
+}
+
+fn get_file_path<R: Reader>(
     dwarf: &Dwarf<R>,
     unit: &Unit<R>,
     header: &LineProgramHeader<R>,
-    row: &LineRow,
+    file: &FileEntry<R>,
 ) -> Option<String> {
-    if let Some(file) = row.file(header) {
-        let mut path = PathBuf::new();
-
-        if let Some(dir) = file.directory(header) {
-            path.push(
-                dwarf
-                    .attr_string(unit, dir)
-                    .ok()?
-                    .to_string_lossy()
-                    .ok()?
-                    .as_ref(),
-            );
-        }
+    let mut path = PathBuf::new();
 
+    if let Some(dir) = file.directory(header) {
         path.push(
             dwarf
-                .attr_string(unit, file.path_name())
+                .attr_string(unit, dir)
                 .ok()?
                 .to_string_lossy()
                 .ok()?
                 .as_ref(),
         );
+    }
 
-        Some(path.to_string_lossy().to_string())
-    } else {
-        None
+    path.push(
+        dwarf
+            .attr_string(unit, file.path_name())
+            .ok()?
+            .to_string_lossy()
+            .ok()?
+            .as_ref(),
+    );
+
+    Some(path.to_string_lossy().to_string())
+}
+
+fn get_program_file<R: Reader>(
+    dwarf: &Dwarf<R>,
+    unit: &Unit<R>,
+    header: &LineProgramHeader<R>,
+    row: &LineRow,
+) -> Option<String> {
+    row.file(header)
+        .and_then(|file| get_file_path(dwarf, unit, header, file))
+}
+
+/// Returns the name, declaration file index and declaration line of a subprogram DIE, following
+/// `DW_AT_specification`/`DW_AT_abstract_origin` for the attributes the DIE itself lacks.
+fn get_function_attributes<R: Reader>(
+    unit: &UnitRef<R>,
+    entry: &DebuggingInformationEntry<R>,
+) -> gimli::Result<(Option<String>, Option<u64>, Option<u64>)> {
+    let mut name = None;
+    let mut decl_file = None;
+    let mut decl_line = None;
+
+    let mut current = entry.clone();
+
+    // Specifications can be chained (e.g. abstract origin -> declaration), but never deeply
+    for _ in 0..3 {
+        if name.is_none() {
+            if let Some(value) = current.attr_value(gimli::DW_AT_name)? {
+                name = Some(unit.attr_string(value)?.to_string_lossy()?.to_string());
+            }
+        }
+
+        if decl_file.is_none() {
+            if let Some(AttributeValue::FileIndex(index)) =
+                current.attr_value(gimli::DW_AT_decl_file)?
+            {
+                decl_file = Some(index);
+            }
+        }
+
+        if decl_line.is_none() {
+            decl_line = current
+                .attr_value(gimli::DW_AT_decl_line)?
+                .and_then(|value| value.udata_value());
+        }
+
+        let origin = match current.attr_value(gimli::DW_AT_specification)? {
+            Some(origin) => Some(origin),
+            None => current.attr_value(gimli::DW_AT_abstract_origin)?,
+        };
+
+        match origin {
+            Some(AttributeValue::UnitRef(offset)) => current = unit.entry(offset)?,
+            _ => break,
+        }
     }
+
+    Ok((name, decl_file, decl_line))
 }
 
-fn gather_object_file_debug_info(
+/// Collects the subprograms of `unit` along with their address ranges, sorted by start address
+fn get_unit_functions<R: Reader>(
+    dwarf: &Dwarf<R>,
+    unit: &UnitRef<R>,
     module: &Module,
-    object_file: &ObjectFile,
-    line_table: &mut HashMap<String, Vec<LineInfo>>,
     filters: &LineInfoFilters,
-) -> anyhow::Result<()> {
-    let object = object_file.with_object(|obj| obj);
-    let load_base = object_file.load_base();
+    interner: &mut PathInterner,
+) -> gimli::Result<(Vec<UnitFunction>, Vec<FunctionRange>)> {
+    let mut functions = Vec::new();
+    let mut function_ranges = Vec::new();
 
-    let endian = if object.is_little_endian() {
-        gimli::RunTimeEndian::Little
-    } else {
-        gimli::RunTimeEndian::Big
-    };
+    let mut entries = unit.entries();
 
-    let load_section = |id: gimli::SectionId| -> Result<Cow<[u8]>, gimli::Error> {
-        match object.section_by_name(id.name()) {
-            Some(ref section) => Ok(section
-                .uncompressed_data()
-                .unwrap_or(Cow::Borrowed(&[][..]))),
-            None => Ok(Cow::Borrowed(&[][..])),
+    while let Some((_, entry)) = entries.next_dfs()? {
+        if entry.tag() != gimli::DW_TAG_subprogram {
+            continue;
         }
-    };
 
-    let borrow_section=
-        |section| gimli::EndianSlice::new(Cow::as_ref(section), endian);
+        let index = functions.len();
+        let mut low_pc: Option<u64> = None;
+        let mut ranges = unit.die_ranges(entry)?;
 
-    let dwarf = DwarfSections::load(&load_section)?;
-    let dwarf = dwarf.borrow(&borrow_section);
+        while let Some(range) = ranges.next()? {
+            if range.begin >= range.end {
+                continue;
+            }
 
-    let mut units_iter = dwarf.units();
+            low_pc = Some(low_pc.map_or(range.begin, |low_pc| low_pc.min(range.begin)));
+            function_ranges.push((range.begin, range.end, index));
+        }
 
-    while let Some(header) = units_iter.next()? {
-        let unit = dwarf.unit(header)?;
-        let unit = unit.unit_ref(&dwarf);
+        let Some(low_pc) = low_pc else {
+            continue;
+        };
 
-        if let Some(program) = unit.line_program.clone() {
-            let mut rows = program.rows();
+        let (name, decl_file, decl_line) = get_function_attributes(unit, entry)?;
 
-            while let Some((header, row)) = rows.next_row()? {
-                let program_file = get_program_file(&dwarf, &unit, header, row);
+        let file = decl_file
+            .zip(unit.line_program.as_ref())
+            .and_then(|(index, program)| {
+                let header = program.header();
+                header
+                    .file(index)
+                    .and_then(|file| get_file_path(dwarf, unit, header, file))
+            })
+            .map(|path| interner.intern(filters.resolve_source_path(module, path)));
 
-                if !filters.matches_any_source_filter(program_file.as_ref())
-                    || filters.matches_any_source_skip_filter(program_file.as_ref())
-                {
-                    continue;
-                }
+        functions.push(UnitFunction {
+            name: name.unwrap_or_else(|| format!("{low_pc:#x}")),
+            file,
+            decl_line: decl_line.unwrap_or_default(),
+            low_pc,
+        });
+    }
 
-                let Some(line) = row.line().map(|v| v.get()) else {
-                    continue;
-                };
-                let addr = row.address() - load_base - module.segment_offset as u64;
+    function_ranges.sort_unstable_by_key(|(begin, _, _)| *begin);
 
-                if addr > u32::MAX as u64 || module.size <= addr as usize {
-                    continue;
-                }
+    Ok((functions, function_ranges))
+}
 
-                let executed = module.bb_bitmap.contains(addr as u32);
-                let line_info = LineInfo { line, executed };
+fn find_function(function_ranges: &[FunctionRange], address: u64) -> Option<usize> {
+    let pos = function_ranges.partition_point(|(begin, _, _)| *begin <= address);
 
-                line_table
-                    .entry(program_file.as_ref().unwrap().to_string())
-                    .or_default()
-                    .push(line_info);
-            }
+    pos.checked_sub(1)
+        .map(|pos| function_ranges[pos])
+        .and_then(|(_, end, index)| (address < end).then_some(index))
+}
+
+/// The reader of the DWARF sections of the object files
+type SectionReader<'d> = EndianSlice<'d, RunTimeEndian>;
+
+/// The ids of the source files of a line program by their file index, if they pass the source
+/// filters; `None` for the files that have not been resolved yet
+type FileTable = Vec<Option<Option<usize>>>;
+
+/// Hands out a single allocation per distinct path, since millions of rows and functions of large
+/// projects refer to a few thousand source files
+#[derive(Default)]
+pub(crate) struct PathInterner {
+    paths: HashSet<Arc<str>>,
+}
+
+impl PathInterner {
+    pub(crate) fn intern(&mut self, path: String) -> Arc<str> {
+        if let Some(interned) = self.paths.get(path.as_str()) {
+            return interned.clone();
         }
+
+        let interned = Arc::<str>::from(path);
+        self.paths.insert(interned.clone());
+
+        interned
     }
+}
 
-    Ok(())
+/// The source files of the line programs of an object file, resolved and matched against the
+/// source filters once per file table instead of once per row. Units that share a line program
+/// (e.g. the partial units of dwz-compressed files) share its file table as well.
+#[derive(Default)]
+struct SourceFiles<'d> {
+    /// The paths of the source files that pass the source filters, indexed by their id
+    paths: Vec<Arc<str>>,
+    ids: HashMap<Arc<str>, usize>,
+    /// The paths of the source files and of the files the functions are declared in
+    interner: PathInterner,
+    /// (.debug_line offset of the line program, compilation directory) -> file table
+    file_tables: HashMap<(usize, Option<&'d [u8]>), FileTable>,
 }
 
-fn coalesce_line_info(line_table: &mut HashMap<String, Vec<LineInfo>>) {
-    let mut line_map = HashMap::new();
-    for info in line_table.values_mut() {
-        for line_info in info.drain(..) {
-            *line_map.entry(line_info.line).or_default() |= line_info.executed;
+impl<'d> SourceFiles<'d> {
+    /// Returns the id of the source file of `row`, if it has one that passes `filters`
+    fn file_id(
+        &mut self,
+        dwarf: &Dwarf<SectionReader<'d>>,
+        unit: &Unit<SectionReader<'d>>,
+        header: &LineProgramHeader<SectionReader<'d>>,
+        row: &LineRow,
+        module: &Module,
+        filters: &LineInfoFilters,
+    ) -> Option<usize> {
+        // The compilation directory is the directory of the files without one before DWARF 5, so
+        // it is part of the file table
+        let key = (header.offset().0, unit.comp_dir.map(|dir| dir.slice()));
+        let index = row.file_index() as usize;
+
+        if let Some(Some(id)) = self
+            .file_tables
+            .get(&key)
+            .and_then(|table| table.get(index))
+        {
+            return *id;
         }
-        for (line, executed) in line_map
-            .iter()
-            .map(|(l, e)| (*l, *e))
-            .sorted_by(|(l1, _), (l2, _)| l1.cmp(l2))
+
+        let path = get_program_file(dwarf, unit, header, row)
+            .map(|path| filters.resolve_source_path(module, path));
+        let id = if filters.matches_any_source_filter(path.as_deref())
+            && !filters.matches_any_source_skip_filter(path.as_deref())
         {
-            info.push(LineInfo { line, executed })
+            // Safety: We can unwrap here since the source filters only match existing files
+            let path = self.interner.intern(path.unwrap());
+            Some(self.intern(path))
+        } else {
+            None
+        };
+
+        // Indices past the file table (of corrupt line programs) have no file to cache
+        let table = self
+            .file_tables
+            .entry(key)
+            .or_insert_with(|| vec![None; header.file_names().len() + 1]);
+        if let Some(entry) = table.get_mut(index) {
+            *entry = Some(id);
+        }
+
+        id
+    }
+
+    fn intern(&mut self, path: Arc<str>) -> usize {
+        if let Some(id) = self.ids.get(&path) {
+            return *id;
         }
 
-        line_map.clear();
+        let id = self.paths.len();
+        self.paths.push(path.clone());
+        self.ids.insert(path, id);
+
+        id
     }
 }
 
-pub fn gather_line_info(
-    modules: &Modules,
-    filters: &LineInfoFilters,
-) -> HashMap<String, Vec<LineInfo>> {
-    let mut line_table = HashMap::new();
+/// The line records of the units of a module, stored contiguously per source file id. The
+/// records of the current unit are appended as its rows are visited and combined into one record
+/// per line once it is done, which avoids hashing every row.
+#[derive(Default)]
+struct LineRecords {
+    /// Source file id -> the line records of the file
+    files: Vec<Vec<LineInfo>>,
+    /// Source file id -> the number of records of the file before the current unit, for the
+    /// files that have rows in it
+    unit_starts: Vec<Option<usize>>,
+    /// The ids of the source files that have rows in the current unit
+    unit_files: Vec<usize>,
+}
 
-    for module in &modules.table {
-        if module.path == constants::UNKNOWN_MODULE {
-            continue;
+impl LineRecords {
+    fn push(&mut self, file_id: usize, line: u64, executed: bool) {
+        if self.files.len() <= file_id {
+            self.files.resize_with(file_id + 1, Vec::new);
+            self.unit_starts.resize(file_id + 1, None);
         }
 
-        log::info!("Gathering debug information about module {}", module.path);
+        let records = &mut self.files[file_id];
+        if self.unit_starts[file_id].is_none() {
+            self.unit_starts[file_id] = Some(records.len());
+            self.unit_files.push(file_id);
+        }
 
-        match get_module_object_with_debug_info(module) {
-            Ok(Some(object_file)) => {
-                match gather_object_file_debug_info(module, &object_file, &mut line_table, filters) {
-                    Err(err) => log::error!("An error occurred while gathering debug info for {}. Info: {}", module.path, err),
-                    _ => {
-                        log::info!("Gathered debug information about module {}", module.path);
-                    }
-                }
+        records.push(LineInfo { line, executed });
+    }
+
+    /// Combines the records of every line of `records` from `start` on using `semantics`
+    fn combine(records: &mut Vec<LineInfo>, start: usize, semantics: LineHitSemantics) {
+        records[start..].sort_unstable_by_key(|info| info.line);
+
+        let mut len = start;
+        for index in start..records.len() {
+            if len > start && records[len - 1].line == records[index].line {
+                records[len - 1].executed =
+                    semantics.combine(records[len - 1].executed, records[index].executed);
+            } else {
+                records.swap(len, index);
+                len += 1;
             }
-            Ok(None) => log::warn!("Could not find debug info for {}", module.path),
-            Err(err) => log::error!("An error occurred while trying to get determine whether {} has debug info. Info: {}", module.path, err),
+        }
+        records.truncate(len);
+    }
+
+    /// Combines the records of every line of the current unit using `semantics`
+    fn finish_unit(&mut self, semantics: LineHitSemantics) {
+        for file_id in self.unit_files.drain(..) {
+            let start = self.unit_starts[file_id].take().unwrap_or_default();
+            Self::combine(&mut self.files[file_id], start, semantics);
         }
     }
 
-    coalesce_line_info(&mut line_table);
+    /// Combines the records of every line over all the units using `semantics`, since the code of
+    /// a line can be spread over several units (e.g. an inline function of a header). The
+    /// records of different modules are only combined by [`SourceCoverage::coalesce`], which
+    /// counts a line as executed if it has been in any of them.
+    fn finish_module(&mut self, semantics: LineHitSemantics) {
+        for records in &mut self.files {
+            Self::combine(records, 0, semantics);
+        }
+    }
+}
 
-    line_table
+/// A row of a line program along with the address its range ends at, i.e. that of the next row
+pub(crate) struct TableRow {
+    pub(crate) address: u64,
+    pub(crate) end_address: u64,
+    pub(crate) line: u64,
+    pub(crate) discriminator: u64,
+    pub(crate) file_id: usize,
+}
+
+/// The functions of a compilation unit and the rows of its line program whose source files pass
+/// the source filters
+pub(crate) struct UnitTable {
+    pub(crate) functions: Vec<UnitFunction>,
+    pub(crate) function_ranges: Vec<FunctionRange>,
+    pub(crate) rows: Vec<TableRow>,
+}
+
+/// What the coverage of a module is gathered from, read once from its debug information: the
+/// functions and the line program rows of its units and its function symbols. None of it depends
+/// on the coverage, so it is reused for every input the module appears in (see
+/// [`LineTableCache`]), and only the basic blocks of each input are looked up in it.
+pub(crate) struct ModuleLineTable {
+    pub(crate) load_base: u64,
+    /// The paths of the source files that pass the source filters, indexed by their id
+    pub(crate) files: Vec<Arc<str>>,
+    pub(crate) units: Vec<UnitTable>,
+    pub(crate) symbols: FunctionSymbols,
+    /// The basic blocks of the disassembly of the module, sorted by address, for the block
+    /// branches and the complexity of the functions
+    pub(crate) blocks: OnceLock<Vec<StaticBlock>>,
+}
+
+impl ModuleLineTable {
+    /// Warns if the function boundaries of the line table diverge from the symbol table of
+    /// `module`, if the consistency checks are enabled
+    fn check_consistency(
+        &self,
+        module: &Module,
+        object_file: &ObjectFile,
+        filters: &LineInfoFilters,
+    ) {
+        if !filters.consistency_checks {
+            return;
+        }
+
+        // The entry ranges of all subprograms
+        let subprogram_ranges = self
+            .units
+            .iter()
+            .flat_map(|unit| {
+                unit.function_ranges
+                    .iter()
+                    .filter(|(begin, _, index)| *begin == unit.functions[*index].low_pc)
+                    .map(|(begin, end, _)| (*begin, *end))
+            })
+            .collect::<Vec<_>>();
+
+        consistency::check_function_boundaries(module, object_file, &subprogram_ranges);
+    }
+
+    /// Returns the basic blocks of `module`, recovering them the first time they are needed
+    fn blocks(&self, module: &Module) -> &[StaticBlock] {
+        self.blocks
+            .get_or_init(|| match oracle::static_blocks(&module.path) {
+                Ok(mut blocks) => {
+                    blocks.sort_unstable_by_key(|block| (block.start, block.end));
+                    blocks.dedup_by_key(|block| block.start);
+                    blocks
+                }
+                Err(e) => {
+                    log::warn!("Could not recover the basic blocks of {}: {e}", module.path);
+                    Vec::new()
+                }
+            })
+    }
+}
+
+const _: () = assert_send_sync::<ModuleLineTable>();
+
+/// Reads the functions and the line program rows of the unit of `header`, with the ids of their
+/// source files in `source_files`
+fn read_unit<'d>(
+    dwarf: &Dwarf<SectionReader<'d>>,
+    header: UnitHeader<SectionReader<'d>>,
+    module: &Module,
+    filters: &LineInfoFilters,
+    source_files: &mut SourceFiles<'d>,
+    deadline: Option<Instant>,
+) -> anyhow::Result<UnitTable> {
+    check_deadline(deadline)?;
+
+    let unit = dwarf.unit(header)?;
+    let unit = unit.unit_ref(dwarf);
+
+    let (functions, function_ranges) =
+        get_unit_functions(dwarf, &unit, module, filters, &mut source_files.interner)?;
+
+    let mut table_rows = Vec::new();
+
+    if let Some(program) = unit.line_program.clone() {
+        let mut rows = program.rows();
+        // The row whose address range ends where the next row starts
+        let mut pending_row = None;
+        let mut num_rows = 0;
+
+        while let Some((header, row)) = rows.next_row()? {
+            // Corrupt line programs can be (almost) endless
+            num_rows += 1;
+            if num_rows % constants::ROWS_PER_DEADLINE_CHECK == 0 {
+                check_deadline(deadline)?;
+            }
+
+            if let Some((address, line, discriminator, file_id)) = pending_row.take() {
+                table_rows.push(TableRow {
+                    address,
+                    end_address: row.address().max(address + 1),
+                    line,
+                    discriminator,
+                    file_id,
+                });
+            }
+
+            // The end of a sequence only marks the address after its last instruction
+            if row.end_sequence() {
+                continue;
+            }
+
+            let Some(file_id) = source_files.file_id(dwarf, &unit, header, row, module, filters)
+            else {
+                continue;
+            };
+
+            let Some(line) = row.line().map(|v| v.get()) else {
+                continue;
+            };
+
+            pending_row = Some((row.address(), line, row.discriminator(), file_id));
+        }
+
+        if let Some(progress) = filters.progress {
+            progress.rows_scanned(num_rows as u64);
+        }
+
+        if let Some((address, line, discriminator, file_id)) = pending_row {
+            table_rows.push(TableRow {
+                address,
+                end_address: address + 1,
+                line,
+                discriminator,
+                file_id,
+            });
+        }
+    }
+
+    Ok(UnitTable {
+        functions,
+        function_ranges,
+        rows: table_rows,
+    })
+}
+
+/// Reads the units of `headers` on `num_threads` threads, which take the next unit that has not
+/// been read yet until all of them have been, and returns them in order along with the paths of
+/// their source files.
+///
+/// Every thread resolves the source files of its units on its own, so the ids of their rows are
+/// mapped to the ones of the whole module afterwards, in the order the files first appear in the
+/// rows of the units.
+fn read_units_in_parallel<'d>(
+    dwarf: &Dwarf<SectionReader<'d>>,
+    headers: &[UnitHeader<SectionReader<'d>>],
+    module: &Module,
+    filters: &LineInfoFilters,
+    deadline: Option<Instant>,
+    num_threads: usize,
+) -> anyhow::Result<(Vec<UnitTable>, Vec<Arc<str>>)> {
+    let next_unit = AtomicUsize::new(0);
+    // Stops the other threads once one of them has failed (e.g. at the deadline)
+    let failed = AtomicBool::new(false);
+
+    let read_units = || {
+        let mut source_files = SourceFiles::default();
+        let mut units = Vec::new();
+
+        loop {
+            let index = next_unit.fetch_add(1, Ordering::Relaxed);
+            let Some(header) = headers
+                .get(index)
+                .filter(|_| !failed.load(Ordering::Relaxed))
+            else {
+                break;
+            };
+
+            match read_unit(dwarf, *header, module, filters, &mut source_files, deadline) {
+                Ok(unit) => units.push((index, unit)),
+                Err(e) => {
+                    failed.store(true, Ordering::Relaxed);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok((units, source_files.paths))
+    };
+
+    let results = std::thread::scope(|scope| {
+        let handles = (0..num_threads)
+            .map(|_| scope.spawn(read_units))
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    // Unit index -> the thread that read it and the unit
+    let mut units = (0..headers.len()).map(|_| None).collect::<Vec<_>>();
+    let mut thread_paths = Vec::new();
+
+    for (thread, res) in results.into_iter().enumerate() {
+        let (thread_units, paths) = res?;
+
+        for (index, unit) in thread_units {
+            units[index] = Some((thread, unit));
+        }
+        thread_paths.push(paths);
+    }
+
+    let mut source_files = SourceFiles::default();
+    // Thread -> the ids of its source files in the whole module
+    let mut file_ids = thread_paths
+        .iter()
+        .map(|paths| vec![None; paths.len()])
+        .collect::<Vec<_>>();
+
+    let units = units
+        .into_iter()
+        .flatten()
+        .map(|(thread, mut unit)| {
+            for row in &mut unit.rows {
+                let file_id = &mut file_ids[thread][row.file_id];
+                row.file_id = match *file_id {
+                    Some(id) => id,
+                    None => *file_id
+                        .insert(source_files.intern(thread_paths[thread][row.file_id].clone())),
+                };
+            }
+
+            unit
+        })
+        .collect();
+
+    Ok((units, source_files.paths))
+}
+
+/// Reads the line table of `module` from the debug information of `object_file`. Modules with
+/// many units (e.g. large C++ binaries) are read on several threads.
+pub(crate) fn read_line_table(
+    module: &Module,
+    object_file: &ObjectFile,
+    filters: &LineInfoFilters,
+    deadline: Option<Instant>,
+) -> anyhow::Result<ModuleLineTable> {
+    // Threads are not available everywhere (e.g. on wasm32), in which case the units are read on
+    // the current one
+    let max_threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+
+    read_line_table_on_threads(module, object_file, filters, deadline, max_threads)
+}
+
+/// Loads the DWARF sections of `object_file` and calls `f` with them
+fn with_dwarf<T>(
+    object_file: &ObjectFile,
+    f: impl FnOnce(&Dwarf<EndianSlice<gimli::RunTimeEndian>>) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let object = object_file.with_object(|obj| obj);
+
+    let endian = if object.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+
+    let load_section = |id: gimli::SectionId| -> Result<Cow<[u8]>, gimli::Error> {
+        match object.section_by_name(id.name()) {
+            Some(ref section) => Ok(section
+                .uncompressed_data()
+                .unwrap_or(Cow::Borrowed(&[][..]))),
+            None => Ok(Cow::Borrowed(&[][..])),
+        }
+    };
+
+    let borrow_section = |section| gimli::EndianSlice::new(Cow::as_ref(section), endian);
+
+    let dwarf = DwarfSections::load(&load_section)?;
+    f(&dwarf.borrow(&borrow_section))
+}
+
+/// What the line program of a unit records about one of its source files, which tells whether the
+/// file changed since it was compiled. DWARF 5 producers may record the MD5 of its contents, and
+/// every version has room for its modification time and size (which are zero when unknown).
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SourceFileStamp {
+    pub(crate) md5: Option<[u8; 16]>,
+    /// The modification time, in seconds since the epoch
+    pub(crate) timestamp: Option<u64>,
+    pub(crate) size: Option<u64>,
+}
+
+/// Returns the stamps the line programs of `object_file` record for its source files, keyed by
+/// their paths in the debug information
+#[cfg(feature = "native")]
+pub(crate) fn source_file_stamps(
+    object_file: &ObjectFile,
+) -> anyhow::Result<HashMap<String, SourceFileStamp>> {
+    with_dwarf(object_file, |dwarf| {
+        let mut res = HashMap::new();
+        let mut units = dwarf.units();
+
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let Some(program) = &unit.line_program else {
+                continue;
+            };
+            let header = program.header();
+
+            for file in header.file_names() {
+                let Some(path) = get_file_path(dwarf, &unit, header, file) else {
+                    continue;
+                };
+
+                let stamp = SourceFileStamp {
+                    md5: header.file_has_md5().then(|| *file.md5()),
+                    timestamp: Some(file.timestamp()).filter(|timestamp| *timestamp != 0),
+                    size: Some(file.size()).filter(|size| *size != 0),
+                };
+                // The file tables of other units may know more about the same file
+                let known = res.entry(path).or_default();
+                if *known == SourceFileStamp::default() {
+                    *known = stamp;
+                }
+            }
+        }
+
+        Ok(res)
+    })
+}
+
+/// Reads the line table like [`read_line_table`] on up to `max_threads` threads
+fn read_line_table_on_threads(
+    module: &Module,
+    object_file: &ObjectFile,
+    filters: &LineInfoFilters,
+    deadline: Option<Instant>,
+    max_threads: usize,
+) -> anyhow::Result<ModuleLineTable> {
+    let (units, files) = with_dwarf(object_file, |dwarf| {
+        let mut units_iter = dwarf.units();
+        let mut headers = Vec::new();
+        while let Some(header) = units_iter.next()? {
+            headers.push(header);
+        }
+
+        let num_threads = max_threads.min(headers.len() / constants::MIN_UNITS_PER_THREAD);
+
+        if num_threads > 1 {
+            return read_units_in_parallel(dwarf, &headers, module, filters, deadline, num_threads);
+        }
+
+        let mut source_files = SourceFiles::default();
+        let units = headers
+            .into_iter()
+            .map(|header| read_unit(dwarf, header, module, filters, &mut source_files, deadline))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok((units, source_files.paths))
+    })?;
+
+    Ok(ModuleLineTable {
+        load_base: object_file.load_base(),
+        files,
+        units,
+        // Aliases can be spread over several units (e.g. after identical code folding)
+        symbols: aliases::function_symbols(object_file),
+        blocks: OnceLock::new(),
+    })
+}
+
+/// Gathers the coverage of `module` into `coverage` by looking its basic blocks up in `table`
+pub(crate) fn apply_line_table(
+    module: &Module,
+    table: &ModuleLineTable,
+    coverage: &mut SourceCoverage,
+    filters: &LineInfoFilters,
+) {
+    let load_base = table.load_base;
+    let base = load_base + module.segment_offset as u64;
+    let semantics = filters.line_hit_semantics;
+
+    if module.segment_start != 0 {
+        let Some(module_start) = module.segment_start.checked_sub(module.segment_offset) else {
+            log::warn!(
+                "Skipping module {}: its segment offset {:#x} is past its segment start {:#x}",
+                module.path,
+                module.segment_offset,
+                module.segment_start
+            );
+            return;
+        };
+        let module_start = module_start as u64;
+        log::info!(
+            "Module {} was loaded at {module_start:#x} and linked at {load_base:#x} (rebase delta: {:#x})",
+            module.path,
+            module_start.wrapping_sub(load_base)
+        );
+    }
+
+    let mut module_functions = Vec::new();
+    // The source lines of the covered addresses, for the cross-check
+    let mut covered_rows = CoveredRows::new();
+
+    // Source file id -> the lines and branches of all units, converted to the coverage once all
+    // units have been processed
+    let mut module_lines = LineRecords::default();
+    let mut module_branches = HashMap::<usize, Vec<BranchInfo>>::new();
+    // The hits of the discriminators of the current unit, whose allocations are reused for the
+    // next ones: (file id, line, discriminator) -> executed
+    let mut discriminator_hits = HashMap::<(usize, u64, u64), bool>::new();
+    let mut multi_path_lines = HashSet::<(usize, u64)>::new();
+    // (file id, line) -> the indices of the basic blocks the code of the line spans
+    let mut line_blocks = HashMap::<(usize, u64), BTreeSet<usize>>::new();
+    let blocks = filters.block_branches.then(|| table.blocks(module));
+    // Whether any byte of the link time address range `[start, end)` has been executed
+    let range_executed = |start: u64, end: u64| {
+        start
+            .checked_sub(base)
+            .zip(end.checked_sub(base))
+            .is_some_and(|(start, end)| {
+                end <= u32::MAX as u64
+                    && module.bb_bitmap.range_cardinality(start as u32..end as u32) > 0
+            })
+    };
+
+    for unit in &table.units {
+        // Function index -> the first and last lines of the function in its own source file
+        let mut function_lines = vec![(u64::MAX, 0); unit.functions.len()];
+
+        for row in &unit.rows {
+            let Some(start) = row.address.checked_sub(base) else {
+                continue;
+            };
+            let end = start + (row.end_address - row.address);
+
+            if end > u32::MAX as u64 || module.size <= start as usize {
+                continue;
+            }
+
+            let (line, file) = (row.line, &table.files[row.file_id]);
+
+            if let Some(index) = find_function(&unit.function_ranges, row.address) {
+                if unit.functions[index].file.as_ref() == Some(file) {
+                    let (first_line, last_line) = &mut function_lines[index];
+                    *first_line = (*first_line).min(line);
+                    *last_line = (*last_line).max(line);
+                }
+            }
+
+            let (start, end) = (start as u32, end as u32);
+
+            let executed = match semantics {
+                LineHitSemantics::FirstByte => module.bb_bitmap.contains(start),
+                LineHitSemantics::AnyByte => module.bb_bitmap.range_cardinality(start..end) > 0,
+                LineHitSemantics::AllBytes => {
+                    module.bb_bitmap.range_cardinality(start..end) == (end - start) as u64
+                }
+            };
+
+            if filters.cross_check.is_some() && executed {
+                covered_rows
+                    .entry(row.address)
+                    .or_default()
+                    .push((file.to_string(), line));
+            }
+
+            if let Some(blocks) = blocks {
+                let first = blocks.partition_point(|block| block.end <= row.address);
+                let num_blocks = blocks[first..]
+                    .iter()
+                    .take_while(|block| block.start < row.end_address)
+                    .count();
+
+                line_blocks
+                    .entry((row.file_id, line))
+                    .or_default()
+                    .extend(first..first + num_blocks);
+            } else if filters.discriminator_branches {
+                discriminator_hits
+                    .entry((row.file_id, line, row.discriminator))
+                    .and_modify(|hit| *hit = semantics.combine(*hit, executed))
+                    .or_insert(executed);
+            }
+
+            module_lines.push(row.file_id, line, executed);
+        }
+
+        // Only the lines with a non-zero discriminator have been compiled into several blocks
+        multi_path_lines.extend(
+            discriminator_hits
+                .keys()
+                .filter(|(_, _, discriminator)| *discriminator != 0)
+                .map(|(file_id, line, _)| (*file_id, *line)),
+        );
+
+        for ((file_id, line, discriminator), executed) in discriminator_hits.drain() {
+            if multi_path_lines.contains(&(file_id, line)) {
+                module_branches
+                    .entry(file_id)
+                    .or_default()
+                    .push(BranchInfo {
+                        line,
+                        discriminator,
+                        executed,
+                    });
+            }
+        }
+        multi_path_lines.clear();
+
+        module_lines.finish_unit(semantics);
+
+        // With any-byte semantics a function has been executed if any byte of its code has been,
+        // otherwise only if its entry has been
+        let mut function_range_hits = vec![false; unit.functions.len()];
+        if semantics == LineHitSemantics::AnyByte {
+            for (begin, end, index) in &unit.function_ranges {
+                let (Some(begin), Some(end)) = (begin.checked_sub(base), end.checked_sub(base))
+                else {
+                    continue;
+                };
+
+                if end <= u32::MAX as u64 {
+                    function_range_hits[*index] |=
+                        module.bb_bitmap.range_cardinality(begin as u32..end as u32) > 0;
+                }
+            }
+        }
+
+        // The blocks of a function are the ones that start in its address ranges
+        let mut function_blocks = vec![None; unit.functions.len()];
+        if let Some(blocks) = blocks {
+            let block_executed = |address: u64| {
+                blocks
+                    .binary_search_by_key(&address, |block| block.start)
+                    .is_ok_and(|index| range_executed(blocks[index].start, blocks[index].end))
+            };
+
+            for (begin, end, index) in &unit.function_ranges {
+                let first = blocks.partition_point(|block| block.start < *begin);
+                let function_blocks =
+                    function_blocks[*index].get_or_insert_with(FunctionBlocks::default);
+
+                for block in blocks[first..]
+                    .iter()
+                    .take_while(|block| block.start < *end)
+                {
+                    let executed = range_executed(block.start, block.end);
+
+                    function_blocks.blocks.push(executed);
+                    function_blocks.branches.extend(
+                        block
+                            .branch_targets
+                            .iter()
+                            .map(|target| executed && block_executed(*target)),
+                    );
+                }
+            }
+        }
+
+        for (index, function) in unit.functions.iter().enumerate() {
+            if !filters.matches_any_source_filter(function.file.as_deref())
+                || filters.matches_any_source_skip_filter(function.file.as_deref())
+            {
+                continue;
+            }
+
+            let (first_line, last_line) = function_lines[index];
+            let start_line = if function.decl_line != 0 {
+                function.decl_line
+            } else {
+                first_line
+            };
+
+            if start_line == u64::MAX || start_line == 0 {
+                continue;
+            }
+
+            let executed = function_range_hits[index]
+                || function.low_pc.checked_sub(base).is_some_and(|addr| {
+                    addr <= u32::MAX as u64 && module.bb_bitmap.contains(addr as u32)
+                });
+
+            let function_info = FunctionInfo {
+                name: function.name.clone(),
+                module: module.path.clone(),
+                start_line,
+                end_line: last_line.max(start_line),
+                executed,
+                aliases: Vec::new(),
+                blocks: function_blocks[index].take(),
+            };
+
+            module_functions.push(ModuleFunction {
+                low_pc: function.low_pc,
+                file: function.file.as_deref().unwrap().to_string(),
+                info: function_info,
+            });
+        }
+    }
+
+    // Only the lines whose code spans several blocks have branches
+    if let Some(blocks) = blocks {
+        for ((file_id, line), indices) in line_blocks {
+            if indices.len() < 2 {
+                continue;
+            }
+
+            for (position, index) in indices.into_iter().enumerate() {
+                let executed = range_executed(blocks[index].start, blocks[index].end);
+
+                module_branches
+                    .entry(file_id)
+                    .or_default()
+                    .push(BranchInfo {
+                        line,
+                        discriminator: position as u64,
+                        executed,
+                    });
+            }
+        }
+    }
+
+    module_lines.finish_module(semantics);
+
+    for (file_id, lines) in module_lines.files.into_iter().enumerate() {
+        if lines.is_empty() {
+            continue;
+        }
+
+        coverage
+            .lines
+            .entry(table.files[file_id].to_string())
+            .or_default()
+            .extend(lines);
+    }
+
+    for (file_id, branches) in module_branches {
+        coverage
+            .branches
+            .entry(table.files[file_id].to_string())
+            .or_default()
+            .extend(branches);
+    }
+
+    for (file, function_info) in
+        aliases::resolve_aliases(module_functions, &table.symbols, filters.alias_policy)
+    {
+        coverage
+            .functions
+            .entry(file)
+            .or_default()
+            .push(function_info);
+    }
+
+    if let Some(tool) = filters.cross_check {
+        cross_check::check_line_info(module, &covered_rows, tool);
+    }
+}
+
+fn gather_object_file_debug_info(
+    module: &Module,
+    object_file: &ObjectFile,
+    coverage: &mut SourceCoverage,
+    filters: &LineInfoFilters,
+    deadline: Option<Instant>,
+) -> anyhow::Result<()> {
+    let read = || {
+        let stored = filters
+            .line_tables
+            .and_then(|line_tables| line_tables.load(&module.path, object_file, filters));
+
+        let line_table = match stored {
+            Some(line_table) => line_table,
+            None => {
+                let line_table = read_line_table(module, object_file, filters, deadline)?;
+                if let Some(line_tables) = filters.line_tables {
+                    line_tables.store(&module.path, object_file, filters, &line_table);
+                }
+                line_table
+            }
+        };
+
+        line_table.check_consistency(module, object_file, filters);
+
+        Ok(line_table)
+    };
+
+    match filters.line_tables {
+        Some(line_tables) => apply_line_table(
+            module,
+            &*line_tables.get(&module.path, read)?,
+            coverage,
+            filters,
+        ),
+        None => apply_line_table(module, &read()?, coverage, filters),
+    }
+
+    Ok(())
+}
+
+fn coalesce_line_info(line_table: &mut HashMap<String, Vec<LineInfo>>) {
+    for lines in line_table.values_mut() {
+        lines.sort_unstable_by_key(|info| info.line);
+
+        lines.dedup_by(|duplicate, info| {
+            let is_duplicate = duplicate.line == info.line;
+
+            if is_duplicate {
+                info.executed |= duplicate.executed;
+            }
+
+            is_duplicate
+        });
+    }
+}
+
+fn coalesce_function_info(function_table: &mut HashMap<String, Vec<FunctionInfo>>) {
+    for functions in function_table.values_mut() {
+        functions.sort_by(|f1, f2| {
+            (f1.start_line, &f1.name, &f1.module).cmp(&(f2.start_line, &f2.name, &f2.module))
+        });
+
+        functions.dedup_by(|duplicate, function| {
+            let is_duplicate = duplicate.start_line == function.start_line
+                && duplicate.name == function.name
+                && duplicate.module == function.module;
+
+            if is_duplicate {
+                function.executed |= duplicate.executed;
+                function.end_line = function.end_line.max(duplicate.end_line);
+
+                function.aliases.append(&mut duplicate.aliases);
+                function.aliases.sort_unstable();
+                function.aliases.dedup();
+
+                match (&mut function.blocks, duplicate.blocks.take()) {
+                    (Some(blocks), Some(other)) => blocks.merge(&other),
+                    (blocks @ None, other) => *blocks = other,
+                    _ => {}
+                }
+            }
+
+            is_duplicate
+        });
+    }
+}
+
+fn coalesce_branch_info(branch_table: &mut HashMap<String, Vec<BranchInfo>>) {
+    for branches in branch_table.values_mut() {
+        branches.sort_by_key(|branch| (branch.line, branch.discriminator));
+
+        branches.dedup_by(|duplicate, branch| {
+            let is_duplicate =
+                duplicate.line == branch.line && duplicate.discriminator == branch.discriminator;
+
+            if is_duplicate {
+                branch.executed |= duplicate.executed;
+            }
+
+            is_duplicate
+        });
+    }
+}
+
+/// Gathers the line and function coverage of `modules` from their debug information.
+///
+/// This function is re-entrant: every call maps and parses the object files it needs on its own
+/// (unless they are in `filters.object_files`) and keeps no other state between calls, so it can
+/// be called concurrently (e.g. with the modules of different drcov files) from multiple threads.
+pub fn gather_line_info(modules: &Modules, filters: &LineInfoFilters) -> SourceCoverage {
+    gather_line_info_with_timeouts(modules, filters).0
+}
+
+/// Like [`gather_line_info`], but also returns the paths of the modules whose debug information
+/// took longer than `filters.module_timeout` to gather. Those modules contribute no coverage.
+pub fn gather_line_info_with_timeouts(
+    modules: &Modules,
+    filters: &LineInfoFilters,
+) -> (SourceCoverage, Vec<String>) {
+    let mut coverage = SourceCoverage::default();
+    let timed_out_modules = gather_line_info_streaming(modules, filters, |_, module_coverage| {
+        coverage.extend(module_coverage)
+    });
+
+    coverage.coalesce();
+
+    (coverage, timed_out_modules)
+}
+
+/// Like [`gather_line_info`], but hands the coverage of every module to `on_module` as soon as it
+/// has been gathered, along with the module, instead of aggregating the coverage of all modules.
+/// The coverage of a module is coalesced, but source files that several modules share (e.g.
+/// headers) appear in the coverage of each of them.
+///
+/// Returns the paths of the modules whose debug information took longer than
+/// `filters.module_timeout` to gather, which are not handed to `on_module`.
+pub fn gather_line_info_streaming(
+    modules: &Modules,
+    filters: &LineInfoFilters,
+    mut on_module: impl FnMut(&Module, SourceCoverage),
+) -> Vec<String> {
+    let mut timed_out_modules = Vec::new();
+    let captured_builds = modules.captured_builds();
+
+    for module in &modules.table {
+        if module.path == constants::UNKNOWN_MODULE
+            || module.is_pseudo_module()
+            || (module.is_dynamic_loader() && !filters.include_loader)
+        {
+            log::debug!("Skipping pseudo-module {}", module.path);
+            continue;
+        }
+
+        if filters
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            log::warn!(
+                "Skipped gathering debug info for {} after the deadline",
+                module.path
+            );
+            timed_out_modules.push(module.path.clone());
+            continue;
+        }
+
+        log::info!("Gathering debug information about module {}", module.path);
+        let deadline = filters
+            .module_timeout
+            .map(|module_timeout| Instant::now() + module_timeout)
+            .into_iter()
+            .chain(filters.deadline)
+            .min();
+
+        let object_file = match filters.object_files {
+            Some(object_files) => object_files.get(&module.path),
+            None => get_object_with_debug_info(&module.path)
+                .map(|object_file| object_file.map(Arc::new)),
+        };
+
+        match object_file {
+            // Its line coverage would be made of the lines of the wrong build
+            Ok(Some(object_file))
+                if captured_builds
+                    .get(module.path.as_str())
+                    .is_some_and(|captured| object_file.is_other_build(captured)) =>
+            {
+                log::info!("Skipping the line coverage of {}, which was captured from another build of it", module.path)
+            }
+            Ok(Some(object_file)) => {
+                // The coverage of a module whose deadline passed is discarded, since it is only
+                // partial
+                let mut module_coverage = SourceCoverage::default();
+
+                match gather_object_file_debug_info(module, &object_file, &mut module_coverage, filters, deadline) {
+                    Err(err) if err.is::<ModuleTimedOut>() => {
+                        log::warn!("Abandoned gathering debug info for {} after the module timeout", module.path);
+                        timed_out_modules.push(module.path.clone());
+                    }
+                    Err(err) => {
+                        module_coverage.coalesce();
+                        on_module(module, module_coverage);
+                        log::error!("An error occurred while gathering debug info for {}. Info: {}", module.path, err);
+                    }
+                    _ => {
+                        module_coverage.coalesce();
+                        on_module(module, module_coverage);
+                        log::info!("Gathered debug information about module {}", module.path);
+                    }
+                }
+            }
+            Ok(None) => log::warn!("Could not find debug info for {}", module.path),
+            Err(err) => log::error!("An error occurred while trying to get determine whether {} has debug info. Info: {}", module.path, err),
+        }
+
+        if let Some(progress) = filters.progress {
+            progress.module_processed(&module.path);
+        }
+    }
+
+    timed_out_modules
+}
+
+/// Gathers the line and function coverage of `module` from the debug information of
+/// `object_file` instead of looking the debug information up on the file system, e.g. from an
+/// object file parsed with [`ObjectFile::from_bytes`]
+pub fn gather_module_line_info(
+    module: &Module,
+    object_file: &ObjectFile,
+    filters: &LineInfoFilters,
+) -> anyhow::Result<SourceCoverage> {
+    let mut coverage = SourceCoverage::default();
+    gather_object_file_debug_info(module, object_file, &mut coverage, filters, None)?;
+    coverage.coalesce();
+
+    Ok(coverage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{binary_modules, small_binary};
+    use itertools::Itertools;
+
+    fn line_records(coverage: &SourceCoverage) -> Vec<(String, u64, bool)> {
+        coverage
+            .lines
+            .iter()
+            .flat_map(|(file, lines)| {
+                lines
+                    .iter()
+                    .map(|info| (file.clone(), info.line, info.executed))
+            })
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn gather_line_info_is_reentrant() {
+        let modules = binary_modules(&small_binary());
+        let filters = LineInfoFilters::default();
+
+        let expected = line_records(&gather_line_info(&modules, &filters));
+        assert!(!expected.is_empty());
+
+        std::thread::scope(|scope| {
+            let handles = (0..4)
+                .map(|_| scope.spawn(|| gather_line_info(&modules, &filters)))
+                .collect::<Vec<_>>();
+
+            for handle in handles {
+                assert_eq!(line_records(&handle.join().unwrap()), expected);
+            }
+        });
+    }
+
+    #[test]
+    fn reads_the_units_on_several_threads_like_on_one() {
+        let module = &binary_modules(&small_binary()).table[0];
+        let object_file = ObjectFile::from_path(&module.path).unwrap();
+        let filters = LineInfoFilters::default();
+
+        let rows = |table: &ModuleLineTable| {
+            table
+                .units
+                .iter()
+                .flat_map(|unit| &unit.rows)
+                .map(|row| {
+                    let file = table.files[row.file_id].clone();
+                    (row.address, row.end_address, row.line, file)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let expected = read_line_table_on_threads(module, &object_file, &filters, None, 1).unwrap();
+        let table = read_line_table_on_threads(module, &object_file, &filters, None, 4).unwrap();
+
+        assert!(!rows(&expected).is_empty());
+        assert_eq!(table.units.len(), expected.units.len());
+        assert_eq!(rows(&table), rows(&expected));
+    }
+
+    #[test]
+    fn marks_executed_lines_with_missed_blocks_as_partial() {
+        let mut coverage = SourceCoverage::default();
+        coverage.lines.insert(
+            "a.c".to_string(),
+            [(1, true), (2, true), (3, false)]
+                .map(|(line, executed)| LineInfo { line, executed })
+                .to_vec(),
+        );
+        coverage.branches.insert(
+            "a.c".to_string(),
+            [
+                (1, 0, true),
+                (1, 1, true),
+                (2, 0, true),
+                (2, 1, false),
+                (3, 0, false),
+            ]
+            .map(|(line, discriminator, executed)| BranchInfo {
+                line,
+                discriminator,
+                executed,
+            })
+            .to_vec(),
+        );
+
+        assert_eq!(coverage.partial_lines("a.c"), HashSet::from([2]));
+    }
+
+    #[test]
+    fn streaming_hands_over_the_coverage_of_every_module() {
+        let modules = binary_modules(&small_binary());
+        let filters = LineInfoFilters::default();
+
+        let mut streamed = Vec::new();
+        let timed_out_modules =
+            gather_line_info_streaming(&modules, &filters, |module, coverage| {
+                streamed.push((module.path.clone(), coverage))
+            });
+
+        assert!(timed_out_modules.is_empty());
+        assert_eq!(streamed.len(), 1);
+        assert_eq!(streamed[0].0, modules.table[0].path);
+        assert_eq!(
+            line_records(&streamed[0].1),
+            line_records(&gather_line_info(&modules, &filters))
+        );
+    }
+
+    /// A little endian ELF64 file of the given type (2 for executables, 3 for shared objects)
+    /// without sections, whose `PT_LOAD` segments span the given `(vaddr, memsz)` ranges
+    fn elf_with_segments(elf_type: u16, segments: &[(u64, u64)]) -> ObjectFile {
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1];
+        elf.resize(16, 0);
+        elf.extend(elf_type.to_le_bytes());
+        // x86-64, version 1, no entry point, program headers right after the header
+        elf.extend(62u16.to_le_bytes());
+        elf.extend(1u32.to_le_bytes());
+        elf.extend(0u64.to_le_bytes());
+        elf.extend(64u64.to_le_bytes());
+        elf.extend(0u64.to_le_bytes());
+        elf.extend(0u32.to_le_bytes());
+        for field in [64u16, 56, segments.len() as u16, 64, 0, 0] {
+            elf.extend(field.to_le_bytes());
+        }
+
+        for (vaddr, memsz) in segments {
+            elf.extend(1u32.to_le_bytes());
+            elf.extend(5u32.to_le_bytes());
+            for field in [0, *vaddr, *vaddr, 0, *memsz, 0x1000] {
+                elf.extend(field.to_le_bytes());
+            }
+        }
+
+        ObjectFile::from_bytes(elf, "a.out").unwrap()
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_object_files() {
+        let err = ObjectFile::from_bytes(b"not an object file".to_vec(), "notes.txt").unwrap_err();
+        assert!(matches!(err, Error::DebugInfo { .. }), "{err}");
+    }
+
+    #[test]
+    fn load_base_is_the_page_aligned_first_load_segment() {
+        // A non-PIE executable, linked at 0x400000 with its first segment past the headers
+        let executable = elf_with_segments(2, &[(0x40a000, 0x500), (0x400040, 0x1000)]);
+        assert_eq!(executable.load_base(), 0x400000);
+        assert_eq!(executable.image_size(), 0xa500);
+
+        let shared_object = elf_with_segments(3, &[(0, 0x2000), (0x3000, 0x100)]);
+        assert_eq!(shared_object.load_base(), 0);
+        assert_eq!(shared_object.image_size(), 0x3100);
+    }
+
+    #[test]
+    fn line_tables_are_rebased_on_the_load_base() {
+        let row = |address, line| TableRow {
+            address,
+            end_address: address + 0x10,
+            line,
+            discriminator: 0,
+            file_id: 0,
+        };
+        let table = ModuleLineTable {
+            load_base: 0x400000,
+            files: vec!["/src/a.c".into()],
+            units: vec![UnitTable {
+                functions: Vec::new(),
+                function_ranges: Vec::new(),
+                rows: vec![row(0x401000, 7), row(0x401010, 8)],
+            }],
+            symbols: FunctionSymbols::default(),
+            blocks: OnceLock::new(),
+        };
+
+        // The basic blocks of drcov are relative to the start of the module, i.e. the load base
+        let mut module = Module {
+            size: 0xa500,
+            path: "a.out".to_string(),
+            ..Default::default()
+        };
+        module.bb_bitmap.insert_range(0x1000..0x1010);
+        module.bb_starts.insert(0x1000);
+
+        let mut coverage = SourceCoverage::default();
+        apply_line_table(&module, &table, &mut coverage, &LineInfoFilters::default());
+        coverage.coalesce();
+
+        assert_eq!(
+            line_records(&coverage),
+            [
+                ("/src/a.c".to_string(), 7, true),
+                ("/src/a.c".to_string(), 8, false)
+            ]
+        );
+
+        // A segment offset past the segment start cannot be rebased, so the module is skipped
+        module.segment_start = 0x1000;
+        module.segment_offset = 0x2000;
+        let mut coverage = SourceCoverage::default();
+        apply_line_table(&module, &table, &mut coverage, &LineInfoFilters::default());
+        assert!(coverage.lines.is_empty());
+    }
+
+    #[test]
+    fn all_bytes_semantics_combine_the_lines_of_every_unit() {
+        let unit = |address, line| UnitTable {
+            functions: Vec::new(),
+            function_ranges: Vec::new(),
+            rows: vec![TableRow {
+                address,
+                end_address: address + 0x10,
+                line,
+                discriminator: 0,
+                file_id: 0,
+            }],
+        };
+        // The same header line inlined in two units, only one of which has been executed
+        let table = ModuleLineTable {
+            load_base: 0,
+            files: vec!["/src/a.h".into()],
+            units: vec![unit(0x1000, 3), unit(0x2000, 3)],
+            symbols: FunctionSymbols::default(),
+            blocks: OnceLock::new(),
+        };
+        let mut module = Module {
+            size: 0x3000,
+            path: "a.out".to_string(),
+            ..Default::default()
+        };
+        module.bb_bitmap.insert_range(0x1000..0x1010);
+        module.bb_starts.insert(0x1000);
+
+        let executed = |line_hit_semantics| {
+            let filters = LineInfoFilters {
+                line_hit_semantics,
+                ..Default::default()
+            };
+            let mut coverage = SourceCoverage::default();
+            apply_line_table(&module, &table, &mut coverage, &filters);
+            coverage.coalesce();
+            line_records(&coverage)
+                .into_iter()
+                .map(|(_, line, executed)| (line, executed))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(executed(LineHitSemantics::AllBytes), [(3, false)]);
+        assert_eq!(executed(LineHitSemantics::AnyByte), [(3, true)]);
+    }
 }
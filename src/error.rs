@@ -0,0 +1,145 @@
+use crate::limits::LimitExceeded;
+use std::fmt::{Display, Formatter};
+
+/// The errors of the parsing and debug information layers, which tell malformed inputs apart from
+/// missing debug information and I/O errors. They are also carried by the `anyhow::Error`s of the
+/// higher layers, from which they can be retrieved with `downcast_ref`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A drcov log or module table is malformed
+    Parse {
+        message: String,
+        /// The (1-based) line of a text part of the input the error was found at
+        line: Option<usize>,
+        /// The byte offset in the (decompressed) input the error was found at
+        offset: Option<usize>,
+    },
+    /// The object file or the debug information of a module is missing or cannot be read
+    DebugInfo {
+        path: String,
+        message: String,
+    },
+    Io(std::io::Error),
+    /// A filter is not a valid regular expression (or path map)
+    Filter(String),
+    /// An input exceeds one of the [`InputLimits`](crate::limits::InputLimits)
+    Limit(LimitExceeded),
+}
+
+impl Error {
+    pub(crate) fn parse(message: impl Into<String>) -> Self {
+        Error::Parse {
+            message: message.into(),
+            line: None,
+            offset: None,
+        }
+    }
+
+    pub(crate) fn debug_info(path: &str, message: impl Display) -> Self {
+        Error::DebugInfo {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Locates a parse error without a location at `line`, a line of `contents`
+    pub(crate) fn at_line(self, contents: &[u8], line: &[u8]) -> Self {
+        let offset = (line.as_ptr() as usize).wrapping_sub(contents.as_ptr() as usize);
+
+        match self {
+            Error::Parse {
+                message,
+                line: None,
+                offset: None,
+            } if offset <= contents.len() => Error::Parse {
+                message,
+                line: Some(contents[..offset].iter().filter(|b| **b == b'\n').count() + 1),
+                offset: Some(offset),
+            },
+            error => error,
+        }
+    }
+
+    /// Locates a parse error without a location at `offset` of a binary part of the input
+    pub(crate) fn at_offset(self, offset: usize) -> Self {
+        match self {
+            Error::Parse {
+                message,
+                line: None,
+                offset: None,
+            } => Error::Parse {
+                message,
+                line: None,
+                offset: Some(offset),
+            },
+            error => error,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse {
+                message,
+                line: Some(line),
+                ..
+            } => write!(f, "{message} (line {line})"),
+            Error::Parse {
+                message,
+                offset: Some(offset),
+                ..
+            } => write!(f, "{message} (offset {offset:#x})"),
+            Error::Parse { message, .. } => write!(f, "{message}"),
+            Error::DebugInfo { path, message } => write!(f, "{path}: {message}"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Filter(message) => write!(f, "{message}"),
+            Error::Limit(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Limit(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<LimitExceeded> for Error {
+    fn from(e: LimitExceeded) -> Self {
+        Error::Limit(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drcov::{Drcov, DrcovFilters};
+
+    #[test]
+    fn locates_parse_errors() {
+        let log = b"DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x0, zz, 0x0, /bin/true\nBB Table: 0 bbs\n";
+
+        let res = Drcov::from_reader(&log[..], &DrcovFilters::default(), None);
+
+        assert!(matches!(
+            res,
+            Err(Error::Parse {
+                line: Some(5),
+                offset: Some(106),
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,53 @@
+use crate::error::Error;
+use regex::bytes::Regex;
+use std::str::FromStr;
+
+/// A regular expression matched against paths (or names), e.g. of `--module-filters`
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub matcher: Regex,
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let filter = Regex::new(s).map_err(|_| {
+            Error::Filter(format!("Could not create a regular expression from '{s}'"))
+        })?;
+
+        Ok(Self { matcher: filter })
+    }
+}
+
+/// A regular expression whose matches are replaced, given as `<regex>:<replacement>`, e.g. by
+/// `--path-map`
+#[derive(Debug, Clone)]
+pub struct ReplacementFilter {
+    pub matcher: Regex,
+    pub replacement: String,
+}
+
+impl FromStr for ReplacementFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pos = s.find(':').ok_or_else(|| {
+            Error::Filter(format!("Invalid path_map argument: no '=' found in '{s}'"))
+        })?;
+
+        let matcher = Regex::new(&s[..pos]).map_err(|_| {
+            Error::Filter(format!(
+                "Could not create a regular expression from '{}'",
+                &s[..pos]
+            ))
+        })?;
+
+        let res = Self {
+            matcher,
+            replacement: s[pos + 1..].to_string(),
+        };
+
+        Ok(res)
+    }
+}
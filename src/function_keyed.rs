@@ -0,0 +1,227 @@
+#[cfg(feature = "native")]
+use crate::dwarf::SourceCoverage;
+#[cfg(feature = "native")]
+use crate::output::{write_text_output, LineEnding};
+#[cfg(feature = "native")]
+use crate::util::assert_send_sync;
+#[cfg(feature = "native")]
+use serde_json::{json, Value};
+#[cfg(feature = "native")]
+use std::collections::BTreeMap;
+#[cfg(feature = "native")]
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "native")]
+mod constants {
+    /// Bumped whenever the layout of the document changes in an incompatible way
+    pub const FORMAT_VERSION: u64 = 1;
+}
+
+/// Demangles C++ and Rust symbol names, so that functions keep their key across builds whose
+/// mangling differs (e.g. the hash suffix of legacy Rust symbols). Other names are kept as is.
+pub fn demangle(name: &str) -> String {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return format!("{demangled:#}");
+    }
+
+    if name.starts_with("_Z") {
+        if let Ok(symbol) = cpp_demangle::Symbol::new(name) {
+            if let Ok(demangled) = symbol.demangle(&cpp_demangle::DemangleOptions::default()) {
+                return demangled;
+            }
+        }
+    }
+
+    name.to_string()
+}
+
+/// Identifies a function independently of where its lines are, which shift between versions
+#[cfg(feature = "native")]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FunctionKey {
+    pub file: String,
+    pub function: String,
+}
+
+#[cfg(feature = "native")]
+impl Display for FunctionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.function, self.file)
+    }
+}
+
+#[cfg(feature = "native")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    pub executed: bool,
+    pub lines_found: usize,
+    pub lines_hit: usize,
+}
+
+/// Coverage keyed by (source file, demangled function name) instead of line numbers, so that the
+/// coverage of two builds of a target can be compared even though their line numbers differ.
+/// Functions sharing a key (e.g. overloads) are merged.
+#[cfg(feature = "native")]
+#[derive(Debug, Default)]
+pub struct FunctionKeyedCoverage {
+    functions: BTreeMap<FunctionKey, FunctionCoverage>,
+}
+
+#[cfg(feature = "native")]
+const _: () = assert_send_sync::<FunctionKeyedCoverage>();
+
+#[cfg(feature = "native")]
+impl FunctionKeyedCoverage {
+    pub fn from_coverage(coverage: &SourceCoverage) -> Self {
+        let mut functions = BTreeMap::<FunctionKey, FunctionCoverage>::new();
+
+        for (file, file_functions) in &coverage.functions {
+            let lines = coverage
+                .lines
+                .get(file)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+
+            for function in file_functions {
+                let key = FunctionKey {
+                    file: file.clone(),
+                    function: demangle(&function.name),
+                };
+
+                let function_lines = lines
+                    .iter()
+                    .filter(|info| (function.start_line..=function.end_line).contains(&info.line));
+
+                let entry = functions.entry(key).or_default();
+                entry.executed |= function.executed;
+                entry.lines_found += function_lines.clone().count();
+                entry.lines_hit += function_lines.filter(|info| info.executed).count();
+            }
+        }
+
+        Self { functions }
+    }
+
+    pub fn write(&self, path: &str, line_ending: LineEnding) -> anyhow::Result<()> {
+        let functions = self
+            .functions
+            .iter()
+            .map(|(key, coverage)| {
+                json!({
+                    "file": key.file,
+                    "function": key.function,
+                    "executed": coverage.executed,
+                    "lines_found": coverage.lines_found,
+                    "lines_hit": coverage.lines_hit,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let document = json!({
+            "format_version": constants::FORMAT_VERSION,
+            "functions": functions,
+        });
+
+        write_text_output(path, &serde_json::to_string_pretty(&document)?, line_ending)?;
+
+        Ok(())
+    }
+
+    pub fn read(path: &str) -> anyhow::Result<Self> {
+        let document: Value = serde_json::from_slice(&std::fs::read(path)?)?;
+
+        let format_version = document["format_version"].as_u64();
+        if format_version != Some(constants::FORMAT_VERSION) {
+            anyhow::bail!(
+                "Unsupported function coverage format version {format_version:?} (expected {})",
+                constants::FORMAT_VERSION
+            );
+        }
+
+        let mut functions = BTreeMap::new();
+
+        for function in document["functions"].as_array().into_iter().flatten() {
+            let (Some(file), Some(name)) =
+                (function["file"].as_str(), function["function"].as_str())
+            else {
+                anyhow::bail!("Function without a file or name in '{path}'");
+            };
+
+            functions.insert(
+                FunctionKey {
+                    file: file.to_string(),
+                    function: name.to_string(),
+                },
+                FunctionCoverage {
+                    executed: function["executed"].as_bool().unwrap_or_default(),
+                    lines_found: function["lines_found"].as_u64().unwrap_or_default() as usize,
+                    lines_hit: function["lines_hit"].as_u64().unwrap_or_default() as usize,
+                },
+            );
+        }
+
+        Ok(Self { functions })
+    }
+
+    /// Compares the coverage against the coverage of a `baseline` build
+    pub fn compare(&self, baseline: &Self) -> FunctionComparison {
+        let mut res = FunctionComparison::default();
+
+        for (key, coverage) in &self.functions {
+            match baseline.functions.get(key) {
+                None => res.added.push(key.clone()),
+                Some(previous) if coverage.executed && !previous.executed => {
+                    res.newly_covered.push(key.clone())
+                }
+                Some(previous) if !coverage.executed && previous.executed => {
+                    res.no_longer_covered.push(key.clone())
+                }
+                Some(_) => res.unchanged += 1,
+            }
+        }
+
+        res.removed = baseline
+            .functions
+            .keys()
+            .filter(|key| !self.functions.contains_key(*key))
+            .cloned()
+            .collect();
+
+        res
+    }
+}
+
+/// The differences between the function keyed coverage of two builds
+#[cfg(feature = "native")]
+#[derive(Debug, Default)]
+pub struct FunctionComparison {
+    /// Functions that only exist in the current build
+    pub added: Vec<FunctionKey>,
+    /// Functions that only exist in the baseline build
+    pub removed: Vec<FunctionKey>,
+    pub newly_covered: Vec<FunctionKey>,
+    pub no_longer_covered: Vec<FunctionKey>,
+    /// Functions of both builds whose executed status did not change
+    pub unchanged: usize,
+}
+
+#[cfg(feature = "native")]
+impl Display for FunctionComparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sections = [
+            ("Added functions", &self.added),
+            ("Removed functions", &self.removed),
+            ("Newly covered functions", &self.newly_covered),
+            ("No longer covered functions", &self.no_longer_covered),
+        ];
+
+        for (title, keys) in sections {
+            writeln!(f, "{title} ({}):", keys.len())?;
+            for key in keys {
+                writeln!(f, "    {key}")?;
+            }
+        }
+
+        write!(f, "Unchanged functions: {}", self.unchanged)
+    }
+}
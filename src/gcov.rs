@@ -0,0 +1,617 @@
+use crate::cli::CliOptions;
+use crate::drcov::Drcov;
+use crate::dwarf::{FunctionInfo, LineInfo, SourceCoverage};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+mod constants {
+    pub const GCNO_MAGIC: u32 = 0x6763_6e6f;
+    pub const GCDA_MAGIC: u32 = 0x6763_6461;
+
+    /// The oldest gcc release whose note/data layout is supported (records and strings sized in
+    /// bytes, and a checksum in the header)
+    pub const MIN_GCC_MAJOR: u32 = 12;
+
+    /// Data files are terminated by a zero word instead of a record
+    pub const TAG_END: u32 = 0;
+    pub const TAG_FUNCTION: u32 = 0x0100_0000;
+    pub const TAG_BLOCKS: u32 = 0x0141_0000;
+    pub const TAG_ARCS: u32 = 0x0143_0000;
+    pub const TAG_LINES: u32 = 0x0145_0000;
+    pub const TAG_COUNTER_ARCS: u32 = 0x01a1_0000;
+
+    /// The arc is on the spanning tree, its count is derived instead of counted
+    pub const ARC_ON_TREE: u32 = 1;
+
+    pub const GCNO_EXTENSION: &str = "gcno";
+    pub const GCDA_EXTENSION: &str = "gcda";
+
+    /// The flavor given to the coverage parsed from gcov files
+    pub const GCOV_FLAVOR: &str = "gcov";
+}
+
+/// Whether the file at `path` is a gcov note (.gcno) or data (.gcda) file
+pub fn is_gcov_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        extension == constants::GCNO_EXTENSION || extension == constants::GCDA_EXTENSION
+    })
+}
+
+#[derive(Debug, Default)]
+struct Arc {
+    src: usize,
+    dst: usize,
+    on_tree: bool,
+    count: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Function {
+    ident: u32,
+    name: String,
+    source: String,
+    start_line: u64,
+    end_line: u64,
+    num_blocks: usize,
+    arcs: Vec<Arc>,
+    /// Block -> (source file, lines) of the block
+    lines: Vec<(usize, String, Vec<u64>)>,
+}
+
+/// Reads the u32 words of a gcov file, with the string encoding of gcc 12 and later
+struct Reader<'a> {
+    cursor: Cursor<&'a [u8]>,
+    /// The end of the record being read, or of the file outside of records
+    record_end: u64,
+}
+
+impl<'a> Reader<'a> {
+    fn new(contents: &'a [u8]) -> Self {
+        Self {
+            cursor: Cursor::new(contents),
+            record_end: contents.len() as u64,
+        }
+    }
+
+    /// Starts reading a record of `length` bytes at the current position, returning its end
+    fn enter_record(&mut self, length: u64) -> anyhow::Result<u64> {
+        let end = self.position() + length;
+        if end > self.cursor.get_ref().len() as u64 {
+            anyhow::bail!("Truncated record of {length} bytes");
+        }
+
+        self.record_end = end;
+        Ok(end)
+    }
+
+    /// The number of bytes left in the record being read
+    fn remaining(&self) -> u64 {
+        self.record_end.saturating_sub(self.position())
+    }
+
+    fn position(&self) -> u64 {
+        self.cursor.position()
+    }
+
+    fn seek(&mut self, position: u64) {
+        self.cursor.set_position(position);
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.cursor.position() >= self.cursor.get_ref().len() as u64
+    }
+
+    fn read_u32(&mut self) -> anyhow::Result<u32> {
+        Ok(self.cursor.read_u32::<LittleEndian>()?)
+    }
+
+    fn read_u64(&mut self) -> anyhow::Result<u64> {
+        Ok(self.cursor.read_u64::<LittleEndian>()?)
+    }
+
+    /// Strings are stored as their length in bytes (including the terminating NUL) followed by
+    /// the bytes themselves
+    fn read_string(&mut self) -> anyhow::Result<String> {
+        let length = self.read_u32()?;
+        if length as u64 > self.remaining() {
+            anyhow::bail!("String of {length} bytes overruns its record");
+        }
+
+        let mut bytes = vec![0; length as usize];
+        self.cursor.read_exact(&mut bytes)?;
+
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    /// Reads the common header of note and data files, returning the stamp tying them together
+    fn read_header(&mut self, magic: u32, path: &Path) -> anyhow::Result<u32> {
+        if self.read_u32()? != magic {
+            anyhow::bail!("'{}' is not a gcov file", path.display());
+        }
+
+        let version = self.read_u32()?.to_be_bytes();
+        // The major version is encoded as a letter for its tens ('A' = 0) and a digit for its ones
+        let major = match version[0] {
+            tens @ b'A'..=b'Z' => (tens - b'A') as u32 * 10 + version[1].wrapping_sub(b'0') as u32,
+            _ => 0,
+        };
+
+        if major < constants::MIN_GCC_MAJOR {
+            anyhow::bail!(
+                "Unsupported gcov version '{}' of '{}' (gcc {} or newer is required)",
+                String::from_utf8_lossy(&version),
+                path.display(),
+                constants::MIN_GCC_MAJOR
+            );
+        }
+
+        let stamp = self.read_u32()?;
+        let _checksum = self.read_u32()?;
+
+        Ok(stamp)
+    }
+}
+
+/// Parses the control flow graphs and line tables of a .gcno note file. Returns the stamp of the
+/// file and its functions.
+fn parse_notes(path: &Path) -> anyhow::Result<(u32, Vec<Function>)> {
+    let contents = std::fs::read(path)?;
+    let mut reader = Reader::new(&contents);
+
+    let stamp = reader.read_header(constants::GCNO_MAGIC, path)?;
+    let cwd = PathBuf::from(reader.read_string()?);
+    let _has_unexecuted_blocks = reader.read_u32()?;
+
+    let absolute = |file: String| cwd.join(file).to_string_lossy().to_string();
+
+    let mut functions = Vec::<Function>::new();
+
+    while !reader.is_at_end() {
+        let tag = reader.read_u32()?;
+        if tag == constants::TAG_END {
+            break;
+        }
+
+        let length = reader.read_u32()? as u64;
+        let end = reader.enter_record(length)?;
+
+        match (tag, functions.last_mut()) {
+            (constants::TAG_FUNCTION, _) => {
+                let ident = reader.read_u32()?;
+                let _lineno_checksum = reader.read_u32()?;
+                let _cfg_checksum = reader.read_u32()?;
+                let name = reader.read_string()?;
+                let _artificial = reader.read_u32()?;
+                let source = absolute(reader.read_string()?);
+                let start_line = reader.read_u32()? as u64;
+                let _start_column = reader.read_u32()?;
+                let end_line = reader.read_u32()? as u64;
+
+                functions.push(Function {
+                    ident,
+                    name,
+                    source,
+                    start_line,
+                    end_line,
+                    ..Default::default()
+                });
+            }
+            (constants::TAG_BLOCKS, Some(function)) => {
+                let num_blocks = reader.read_u32()? as usize;
+                // Every block but the exit one leaves through the arcs of a record of its own
+                if num_blocks as u64 > contents.len() as u64 - end {
+                    anyhow::bail!(
+                        "Function '{}' of '{}' declares {num_blocks} blocks, more than the rest of the file can describe",
+                        function.name,
+                        path.display()
+                    );
+                }
+
+                function.num_blocks = num_blocks;
+            }
+            (constants::TAG_ARCS, Some(function)) => {
+                let src = reader.read_u32()? as usize;
+
+                while reader.position() < end {
+                    let dst = reader.read_u32()? as usize;
+                    let flags = reader.read_u32()?;
+
+                    if src >= function.num_blocks || dst >= function.num_blocks {
+                        anyhow::bail!(
+                            "Arc {src} -> {dst} of function '{}' of '{}' is not between its {} blocks",
+                            function.name,
+                            path.display(),
+                            function.num_blocks
+                        );
+                    }
+
+                    function.arcs.push(Arc {
+                        src,
+                        dst,
+                        on_tree: flags & constants::ARC_ON_TREE != 0,
+                        count: None,
+                    });
+                }
+            }
+            (constants::TAG_LINES, Some(function)) => {
+                let block = reader.read_u32()? as usize;
+                if block >= function.num_blocks {
+                    anyhow::bail!(
+                        "Lines of block {block} of function '{}' of '{}', which has {} blocks",
+                        function.name,
+                        path.display(),
+                        function.num_blocks
+                    );
+                }
+
+                let mut file = function.source.clone();
+                let mut lines = Vec::new();
+
+                loop {
+                    let line = reader.read_u32()?;
+                    if line != 0 {
+                        lines.push(line as u64);
+                        continue;
+                    }
+
+                    let next_file = reader.read_string()?;
+                    if !lines.is_empty() {
+                        function
+                            .lines
+                            .push((block, file, std::mem::take(&mut lines)));
+                    }
+
+                    if next_file.is_empty() {
+                        break;
+                    }
+
+                    file = absolute(next_file);
+                }
+            }
+            _ => {}
+        }
+
+        reader.seek(end);
+    }
+
+    Ok((stamp, functions))
+}
+
+/// Parses the arc counters of a .gcda data file, keyed by function ident
+fn parse_counters(path: &Path, expected_stamp: u32) -> anyhow::Result<HashMap<u32, Vec<u64>>> {
+    let contents = std::fs::read(path)?;
+    let mut reader = Reader::new(&contents);
+
+    let stamp = reader.read_header(constants::GCDA_MAGIC, path)?;
+    if stamp != expected_stamp {
+        anyhow::bail!(
+            "'{}' does not belong to the same compilation as its note file",
+            path.display()
+        );
+    }
+
+    let mut res = HashMap::new();
+    let mut ident = None;
+
+    while !reader.is_at_end() {
+        let tag = reader.read_u32()?;
+        if tag == constants::TAG_END {
+            break;
+        }
+
+        // Zero counters are written as a negative length and no values
+        let length = reader.read_u32()? as i32;
+        let end = reader.enter_record(length.max(0) as u64)?;
+
+        match tag {
+            constants::TAG_FUNCTION if length > 0 => ident = Some(reader.read_u32()?),
+            constants::TAG_FUNCTION => ident = None,
+            constants::TAG_COUNTER_ARCS => {
+                let Some(ident) = ident else {
+                    reader.seek(end);
+                    continue;
+                };
+
+                // Missing counters count zero when solving the blocks
+                let counters = if length < 0 {
+                    Vec::new()
+                } else {
+                    (0..length / 8)
+                        .map(|_| reader.read_u64())
+                        .collect::<anyhow::Result<_>>()?
+                };
+
+                res.insert(ident, counters);
+            }
+            _ => {}
+        }
+
+        reader.seek(end);
+    }
+
+    Ok(res)
+}
+
+/// Derives the execution count of every block from the counted arcs, the same way gcov does: the
+/// count of a block equals the sum of its incoming arcs and the sum of its outgoing ones, which
+/// is enough to solve the arcs on the spanning tree one at a time.
+fn solve_block_counts(function: &mut Function, counters: &[u64]) -> Vec<Option<u64>> {
+    let mut counters = counters.iter();
+    for arc in function.arcs.iter_mut().filter(|arc| !arc.on_tree) {
+        arc.count = Some(counters.next().copied().unwrap_or_default());
+    }
+
+    let mut blocks = vec![None; function.num_blocks];
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for (block, block_count) in blocks.iter_mut().enumerate() {
+            for outgoing in [true, false] {
+                let arcs = function
+                    .arcs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, arc)| if outgoing { arc.src } else { arc.dst } == block)
+                    .map(|(index, arc)| (index, arc.count))
+                    .collect::<Vec<_>>();
+
+                if arcs.is_empty() {
+                    continue;
+                }
+
+                let known = arcs.iter().filter_map(|(_, count)| *count).sum::<u64>();
+                let unknown = arcs
+                    .iter()
+                    .filter(|(_, count)| count.is_none())
+                    .map(|(index, _)| *index)
+                    .collect::<Vec<_>>();
+
+                match (*block_count, unknown.as_slice()) {
+                    (None, []) => {
+                        *block_count = Some(known);
+                        changed = true;
+                    }
+                    (Some(count), [index]) => {
+                        function.arcs[*index].count = Some(count.saturating_sub(known));
+                        changed = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Parses a gcov note file and, if it exists, its data file into line and function coverage.
+///
+/// Unlike the other inputs, gcov files describe source lines directly, so no debug info is
+/// needed: the coverage is returned alongside a [`Drcov`] without modules. A `.gcda` input is
+/// paired with the `.gcno` next to it and vice versa; a note file without a data file describes
+/// code that never ran.
+pub fn from_file(path: &Path, options: &CliOptions) -> anyhow::Result<(Drcov, SourceCoverage)> {
+    log::info!("Loading gcov files: {}", path.display());
+
+    let notes_path = path.with_extension(constants::GCNO_EXTENSION);
+    let data_path = path.with_extension(constants::GCDA_EXTENSION);
+
+    let filters = options.get_line_info_filters(constants::GCOV_FLAVOR);
+
+    let (stamp, mut functions) = parse_notes(&notes_path)?;
+    let counters = if data_path.is_file() {
+        parse_counters(&data_path, stamp)?
+    } else {
+        log::debug!("No data file for '{}'", notes_path.display());
+        HashMap::new()
+    };
+
+    let module = notes_path.to_string_lossy().to_string();
+    // Source file -> line -> executed
+    let mut lines = HashMap::<String, BTreeMap<u64, bool>>::new();
+    let mut res = SourceCoverage::default();
+
+    for mut function in functions.drain(..) {
+        let function_counters = counters
+            .get(&function.ident)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let blocks = solve_block_counts(&mut function, function_counters);
+        let block_executed = |block: usize| blocks.get(block).copied().flatten().unwrap_or(0) > 0;
+
+        for (block, file, block_lines) in &function.lines {
+            if !filters.matches_any_source_filter(Some(file))
+                || filters.matches_any_source_skip_filter(Some(file))
+            {
+                continue;
+            }
+
+            let file_lines = lines.entry(file.clone()).or_default();
+            for line in block_lines {
+                *file_lines.entry(*line).or_default() |= block_executed(*block);
+            }
+        }
+
+        if filters.matches_any_source_filter(Some(&function.source))
+            && !filters.matches_any_source_skip_filter(Some(&function.source))
+        {
+            res.functions
+                .entry(function.source.clone())
+                .or_default()
+                .push(FunctionInfo {
+                    // The entry block is always block 0
+                    executed: block_executed(0),
+                    name: function.name,
+                    module: module.clone(),
+                    start_line: function.start_line,
+                    end_line: function.end_line,
+                    aliases: Vec::new(),
+                    blocks: None,
+                });
+        }
+    }
+
+    res.lines = lines
+        .into_iter()
+        .map(|(file, lines)| {
+            let lines = lines
+                .into_iter()
+                .map(|(line, executed)| LineInfo { line, executed })
+                .collect();
+            (file, lines)
+        })
+        .collect();
+
+    log::info!("Gcov files loaded");
+
+    Ok((Drcov::without_modules(constants::GCOV_FLAVOR), res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::TempPath;
+    use clap::Parser;
+
+    fn options(path: &Path) -> CliOptions {
+        CliOptions::try_parse_from(["drcov2lcov", "--input", &path.to_string_lossy()]).unwrap()
+    }
+
+    fn executed_lines(coverage: &SourceCoverage, file: &Path) -> Vec<(u64, bool)> {
+        coverage.lines[file.to_string_lossy().as_ref()]
+            .iter()
+            .map(|info| (info.line, info.executed))
+            .collect()
+    }
+
+    #[test]
+    fn reads_the_coverage_of_a_gcc_program() {
+        let dir = TempPath::dir("gcov");
+        let source = dir.join("prog.c");
+        std::fs::write(
+            &source,
+            "int f(int x) {\n  if (x > 1)\n    return 2;\n  return 3;\n}\n\nint main(void) {\n  return f(0) - 3;\n}\n",
+        )
+        .unwrap();
+
+        let gcc = |args: &[&str]| {
+            let status = std::process::Command::new("gcc")
+                .current_dir(dir.path())
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        gcc(&["--coverage", "-O0", "-c", "prog.c", "-o", "prog.o"]);
+        gcc(&["--coverage", "prog.o", "-o", "prog"]);
+
+        let notes = dir.join("prog.gcno");
+        // Without a data file, nothing has been executed
+        let (_, coverage) = from_file(&notes, &options(&notes)).unwrap();
+        assert!(executed_lines(&coverage, &source)
+            .iter()
+            .all(|(_, executed)| !executed));
+
+        let status = std::process::Command::new(dir.join("prog"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let data = dir.join("prog.gcda");
+        let (_, coverage) = from_file(&data, &options(&data)).unwrap();
+        assert_eq!(
+            executed_lines(&coverage, &source),
+            [
+                (1, true),
+                (2, true),
+                (3, false),
+                (4, true),
+                (7, true),
+                (8, true)
+            ]
+        );
+        let mut functions = coverage.functions[source.to_string_lossy().as_ref()]
+            .iter()
+            .map(|function| (function.name.as_str(), function.executed))
+            .collect::<Vec<_>>();
+        functions.sort_unstable();
+        assert_eq!(functions, [("f", true), ("main", true)]);
+
+        // A truncated note file is an error
+        let contents = std::fs::read(&notes).unwrap();
+        std::fs::write(&notes, &contents[..contents.len() / 2]).unwrap();
+        assert!(from_file(&notes, &options(&notes)).is_err());
+    }
+
+    #[test]
+    fn rejects_hostile_block_counts() {
+        let word = |value: u32| value.to_le_bytes();
+        let mut function = Vec::new();
+        for value in [1, 0, 0] {
+            function.extend_from_slice(&word(value));
+        }
+        // An empty name and source file
+        for value in [0, 0, 0, 1, 0, 2] {
+            function.extend_from_slice(&word(value));
+        }
+
+        let notes = |blocks: &[u8]| {
+            let mut notes = Vec::new();
+            for value in [
+                constants::GCNO_MAGIC,
+                u32::from_be_bytes(*b"B23*"),
+                0,
+                0,
+                0,
+                0,
+            ] {
+                notes.extend_from_slice(&word(value));
+            }
+            notes.extend_from_slice(&word(constants::TAG_FUNCTION));
+            notes.extend_from_slice(&word(function.len() as u32));
+            notes.extend_from_slice(&function);
+            notes.extend_from_slice(blocks);
+
+            let path = TempPath::new("hostile.gcno");
+            std::fs::write(path.path(), notes).unwrap();
+            let res = parse_notes(path.path());
+            res.map(|(_, functions)| functions.len())
+        };
+
+        let mut blocks = Vec::new();
+        for value in [constants::TAG_BLOCKS, 4, u32::MAX] {
+            blocks.extend_from_slice(&word(value));
+        }
+        assert!(notes(&blocks).unwrap_err().to_string().contains("blocks"));
+
+        // Arcs between blocks the function does not have
+        let mut arcs = Vec::new();
+        for value in [
+            constants::TAG_BLOCKS,
+            4,
+            2,
+            constants::TAG_ARCS,
+            12,
+            0,
+            5,
+            0,
+        ] {
+            arcs.extend_from_slice(&word(value));
+        }
+        assert!(notes(&arcs).unwrap_err().to_string().contains("Arc 0 -> 5"));
+
+        // A string longer than its record
+        let mut lines = Vec::new();
+        for value in [constants::TAG_LINES, 12, 0, 0, u32::MAX] {
+            lines.extend_from_slice(&word(value));
+        }
+        assert!(notes(&lines).is_err());
+
+        assert_eq!(notes(&[]).unwrap(), 1);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::archive;
+use crate::cli::CliOptions;
+use crate::drcov::Drcov;
+use crate::dwarf::{LineHitSemantics, SourceCoverage};
+use crate::gcov;
+use crate::kcov;
+use crate::lcov;
+use crate::limits::InputLimits;
+use crate::llvm_profile;
+use crate::modoff;
+use crate::perf;
+use crate::pin;
+use crate::qemu;
+use crate::sancov;
+use crate::state;
+use crate::tenet;
+use crate::util::decompress;
+use std::io::Read;
+use std::path::Path;
+
+mod constants {
+    /// How much of an input file is looked at to detect its format
+    pub const SNIFF_SIZE: u64 = 16 * 1024;
+}
+
+/// The input path that stands for the standard input
+pub const STDIN_PATH: &str = "-";
+
+/// Returns whether `path` stands for the standard input
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN_PATH
+}
+
+/// A parsed coverage input
+pub struct ParsedInput {
+    /// The coverage of the input per flavor: a single one, except for coverage states of inputs
+    /// of several flavors
+    pub drcovs: Vec<Drcov>,
+    /// The line coverage of inputs that describe source lines themselves (e.g. gcov files),
+    /// which need no symbolization
+    pub coverage: Option<SourceCoverage>,
+}
+
+/// Parses the `contents` of a drcov log that has been read into memory
+fn parse_drcov_contents(
+    contents: &[u8],
+    options: &CliOptions,
+    limits: &InputLimits,
+) -> anyhow::Result<Drcov> {
+    let drcov = Drcov::from_bytes(
+        &decompress(contents, limits.max_input_size)?,
+        &options.get_drcov_filters(),
+        options.module_table.as_ref(),
+    )?;
+    log::info!("Drcov file loaded");
+    limits.check_coverage(&drcov)?;
+
+    Ok(drcov)
+}
+
+/// Parses the `contents` of a drcov log that has not been read from a file, e.g. an upload
+pub fn parse_drcov_bytes(contents: &[u8], options: &CliOptions) -> anyhow::Result<Drcov> {
+    parse_drcov_contents(contents, options, &options.get_input_limits())
+}
+
+/// The first bytes of the file at `path`, which its format is detected from
+fn read_head(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    std::fs::File::open(path)?
+        .take(constants::SNIFF_SIZE)
+        .read_to_end(&mut head)?;
+
+    Ok(head)
+}
+
+/// Parses the coverage input file at `path`, detecting its format
+pub fn parse_input_file(path: &Path, options: &CliOptions) -> anyhow::Result<ParsedInput> {
+    let limits = options.get_input_limits();
+
+    if is_stdin(path) {
+        // There is nothing to sniff a format from, so the stream has to be a drcov log
+        log::info!("Loading drcov file from the standard input");
+        let contents = limits.read_to_end(std::io::stdin().lock())?;
+        return Ok(ParsedInput {
+            drcovs: vec![parse_drcov_contents(&contents, options, &limits)?],
+            coverage: None,
+        });
+    }
+
+    // Only the drcov logs of archives are inputs
+    if let Some((archive, name)) = archive::split_entry_path(path) {
+        log::info!(
+            "Loading drcov file {name} from archive {}",
+            archive.display()
+        );
+        let contents = archive::read_entry(archive, &name, &limits)?;
+        return Ok(ParsedInput {
+            drcovs: vec![parse_drcov_contents(&contents, options, &limits)?],
+            coverage: None,
+        });
+    }
+
+    limits.check_file_size(path)?;
+
+    let head = read_head(path)?;
+
+    let symbolized = if gcov::is_gcov_file(path) {
+        Some(gcov::from_file(path, options)?)
+    } else if kcov::is_cobertura_report(&head, path) {
+        Some(kcov::from_file(path, options)?)
+    } else if lcov::is_lcov_tracefile(&head) {
+        Some(lcov::from_file(path, options)?)
+    } else {
+        None
+    };
+
+    if let Some((drcov, coverage)) = symbolized {
+        return Ok(ParsedInput {
+            drcovs: vec![drcov],
+            coverage: Some(coverage),
+        });
+    }
+
+    let filters = options.get_drcov_filters();
+
+    let drcov = if sancov::is_sancov_file(path) {
+        sancov::from_file(path, &options.sancov_binaries, &filters)
+    } else if llvm_profile::is_llvm_profile_file(path) {
+        llvm_profile::from_file(
+            path,
+            &options.llvm_profile_binaries,
+            &options.get_binary_resolver(),
+            &filters,
+        )
+    } else if qemu::is_qemu_trace(&head) {
+        qemu::from_file(
+            path,
+            &options.qemu_binaries,
+            options.module_table.as_ref(),
+            &filters,
+        )
+    } else if kcov::is_kcov_trace(&head) {
+        kcov::from_trace(
+            path,
+            &options.kcov_binaries,
+            options.module_table.as_ref(),
+            &filters,
+        )
+    } else if tenet::is_tenet_trace(&head) {
+        tenet::from_file(path, options.module_table.as_ref(), &filters)
+    } else if pin::is_pin_output(&head, path) {
+        pin::from_file(path, options.module_table.as_ref(), &filters)
+    } else if modoff::is_modoff_trace(&head) {
+        modoff::from_file(path, options.module_table.as_ref(), &filters)
+    } else if perf::is_perf_script_output(&head) {
+        perf::from_file(path, options.module_table.as_ref(), &filters)
+    } else if state::is_state_file(&head, path) {
+        let drcovs = state::from_file(path, &options.get_binary_resolver(), &filters)?;
+        for drcov in &drcovs {
+            limits.check_coverage(drcov)?;
+        }
+
+        return Ok(ParsedInput {
+            drcovs,
+            coverage: None,
+        });
+    } else {
+        Drcov::from_file(path, &filters, options.module_table.as_ref()).map_err(Into::into)
+    }?;
+
+    limits.check_coverage(&drcov)?;
+
+    Ok(ParsedInput {
+        drcovs: vec![drcov],
+        coverage: None,
+    })
+}
+
+/// The line hit semantics used for coverage of the given flavor unless others were requested.
+/// Formats that only record a single address per basic block need `any-byte` semantics, since
+/// that address is rarely the first one of a line.
+pub fn default_line_hit_semantics(flavor: &str) -> LineHitSemantics {
+    if sancov::is_sancov_flavor(flavor)
+        || qemu::is_qemu_flavor(flavor)
+        || kcov::is_kcov_trace_flavor(flavor)
+        || perf::is_perf_flavor(flavor)
+        || tenet::is_tenet_flavor(flavor)
+        || modoff::is_modoff_flavor(flavor)
+        || pin::is_pin_flavor(flavor)
+    {
+        LineHitSemantics::AnyByte
+    } else {
+        LineHitSemantics::FirstByte
+    }
+}
@@ -0,0 +1,87 @@
+use crate::dwarf::SourceCoverage;
+use crate::output::{write_text_output, CoverageReport, CoverageWriter, LineEnding};
+use serde_json::{json, Map, Value};
+
+fn location(start_line: u64, end_line: u64) -> Value {
+    json!({
+        "start": { "line": start_line, "column": 0 },
+        "end": { "line": end_line, "column": 1 },
+    })
+}
+
+/// Writes the coverage as an Istanbul/NYC `coverage-final.json` document, in which every line
+/// record becomes a statement and every DWARF subprogram a function.
+pub fn write_istanbul_output(
+    path: &str,
+    coverage: &SourceCoverage,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    let mut res = Map::new();
+
+    let files = coverage.lines.keys().chain(coverage.functions.keys());
+
+    for file in files {
+        if res.contains_key(file) {
+            continue;
+        }
+
+        let mut statement_map = Map::new();
+        let mut statements = Map::new();
+
+        for (id, info) in coverage.lines.get(file).into_iter().flatten().enumerate() {
+            statement_map.insert(id.to_string(), location(info.line, info.line));
+            statements.insert(id.to_string(), json!(info.executed as u64));
+        }
+
+        let mut fn_map = Map::new();
+        let mut functions = Map::new();
+
+        for (id, function) in coverage
+            .functions
+            .get(file)
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            fn_map.insert(
+                id.to_string(),
+                json!({
+                    "name": function.name,
+                    "decl": location(function.start_line, function.start_line),
+                    "loc": location(function.start_line, function.end_line),
+                    "line": function.start_line,
+                }),
+            );
+            functions.insert(id.to_string(), json!(function.executed as u64));
+        }
+
+        res.insert(
+            file.clone(),
+            json!({
+                "path": file,
+                "statementMap": statement_map,
+                "s": statements,
+                "fnMap": fn_map,
+                "f": functions,
+                "branchMap": {},
+                "b": {},
+            }),
+        );
+    }
+
+    write_text_output(path, &serde_json::to_string(&res)?, line_ending)?;
+
+    Ok(())
+}
+
+pub struct IstanbulWriter;
+
+impl CoverageWriter for IstanbulWriter {
+    fn name(&self) -> &'static str {
+        "Istanbul"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_istanbul_output(path, report.coverage, report.line_ending)
+    }
+}
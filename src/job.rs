@@ -0,0 +1,117 @@
+use crate::cli::CliOptions;
+use anyhow::anyhow;
+use clap::Parser;
+use std::path::Path;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// A target of a job: the options of one conversion
+#[derive(Debug)]
+pub struct JobTarget {
+    pub name: String,
+    pub options: CliOptions,
+}
+
+/// Converts the value of option `key` of a job to command line arguments: scalars are passed as
+/// the value of the option, lists repeat the option for each of their items and booleans turn a
+/// flag on (or leave it off)
+fn option_args(key: &str, value: &Yaml) -> anyhow::Result<Vec<String>> {
+    let flag = format!("--{key}");
+
+    let scalar = |value: &Yaml| match value {
+        Yaml::String(value) | Yaml::Real(value) => Ok(value.clone()),
+        Yaml::Integer(value) => Ok(value.to_string()),
+        _ => Err(anyhow!(
+            "Option '{key}' must be a scalar or a list of scalars"
+        )),
+    };
+
+    match value {
+        Yaml::Boolean(true) => Ok(vec![flag]),
+        Yaml::Boolean(false) | Yaml::Null => Ok(Vec::new()),
+        Yaml::Array(values) => values
+            .iter()
+            .map(|value| Ok([flag.clone(), scalar(value)?]))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(|args| args.concat()),
+        value => Ok(vec![flag, scalar(value)?]),
+    }
+}
+
+/// Converts a mapping of options, named like the command line options without their leading
+/// dashes, to command line arguments
+fn mapping_args(mapping: &Yaml, skipped_keys: &[&str]) -> anyhow::Result<Vec<String>> {
+    let Yaml::Hash(mapping) = mapping else {
+        anyhow::bail!("Expected a mapping of options");
+    };
+
+    let mut args = Vec::new();
+
+    for (key, value) in mapping {
+        let key = key
+            .as_str()
+            .ok_or(anyhow!("Option names must be strings"))?;
+
+        if !skipped_keys.contains(&key) {
+            args.extend(option_args(key, value)?);
+        }
+    }
+
+    Ok(args)
+}
+
+/// Parses the job at `path`: a YAML document with a list of `targets`, each a mapping of the
+/// options of one conversion (e.g. `input`, `module-filters`, `output`) along with an optional
+/// `name`. The options of the optional `defaults` mapping apply to every target, unless a target
+/// sets them itself. The options of all targets are validated up front, so that a job with a
+/// mistake in any of them fails before converting anything.
+pub fn parse_job(path: &Path) -> anyhow::Result<Vec<JobTarget>> {
+    log::info!("Loading job: {}", path.display());
+
+    let contents = std::fs::read_to_string(path)?;
+    let documents = YamlLoader::load_from_str(&contents)
+        .map_err(|e| anyhow!("Could not parse job '{}': {e}", path.display()))?;
+    let job = documents
+        .first()
+        .ok_or(anyhow!("Job '{}' is empty", path.display()))?;
+
+    let defaults = &job["defaults"];
+
+    let targets = job["targets"].as_vec().ok_or(anyhow!(
+        "Job '{}' does not list any targets",
+        path.display()
+    ))?;
+
+    let mut res = Vec::with_capacity(targets.len());
+
+    for (index, target) in targets.iter().enumerate() {
+        let name = target["name"]
+            .as_str()
+            .map_or_else(|| format!("target {}", index + 1), str::to_string);
+
+        let target_keys = target
+            .as_hash()
+            .into_iter()
+            .flat_map(|target| target.keys())
+            .filter_map(Yaml::as_str)
+            .collect::<Vec<_>>();
+
+        let default_args = match defaults {
+            Yaml::BadValue => Vec::new(),
+            defaults => mapping_args(defaults, &target_keys)?,
+        };
+
+        let args = std::iter::once("drcov2lcov".to_string())
+            .chain(default_args)
+            .chain(mapping_args(target, &["name"]).map_err(|e| anyhow!("{name}: {e}"))?);
+
+        let options = CliOptions::try_parse_from(args)
+            .map_err(|e| anyhow!("{name}: {e}"))
+            .and_then(|options| options.validate().map_err(|e| anyhow!("{name}: {e}")))?;
+
+        res.push(JobTarget { name, options });
+    }
+
+    log::info!("Job loaded ({} targets)", res.len());
+
+    Ok(res)
+}
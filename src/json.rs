@@ -0,0 +1,143 @@
+use crate::complexity::FunctionComplexity;
+use crate::output::{write_text_output, CoverageReport, CoverageWriter};
+use crate::summary::Summary;
+use itertools::Itertools;
+use serde_json::{json, Value};
+
+mod constants {
+    /// Bumped whenever the layout of the document changes in an incompatible way
+    pub const FORMAT_VERSION: u32 = 1;
+}
+
+/// Writes the coverage as a JSON document mirroring the internal coverage model: the line and
+/// function records of every source file, the executed basic blocks of every module and the
+/// summary totals.
+pub fn write_json_output(path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+    write_text_output(path, &render_json_output(report)?, report.line_ending)?;
+
+    Ok(())
+}
+
+/// Returns the JSON document [`write_json_output`] writes
+pub fn render_json_output(report: &CoverageReport) -> anyhow::Result<String> {
+    let coverage = report.coverage;
+    let summary = Summary::from_coverage(coverage);
+
+    let files = coverage
+        .lines
+        .keys()
+        .chain(coverage.functions.keys())
+        .unique()
+        .sorted()
+        .map(|file| {
+            let partial_lines = coverage.partial_lines(file);
+            let lines = coverage
+                .lines
+                .get(file)
+                .into_iter()
+                .flatten()
+                .map(|info| {
+                    let mut line = json!({ "line": info.line, "executed": info.executed });
+                    if partial_lines.contains(&info.line) {
+                        line["partial"] = json!(true);
+                    }
+                    if let Some(checksum) = report
+                        .checksums
+                        .and_then(|checksums| checksums.get(file, info.line))
+                    {
+                        line["checksum"] = json!(checksum);
+                    }
+                    line
+                })
+                .collect::<Vec<_>>();
+
+            let functions = coverage
+                .functions
+                .get(file)
+                .into_iter()
+                .flatten()
+                .map(|function| {
+                    let mut res = json!({
+                        "name": function.name,
+                        "module": function.module,
+                        "start_line": function.start_line,
+                        "end_line": function.end_line,
+                        "executed": function.executed,
+                    });
+                    if let Some(complexity) = FunctionComplexity::of(function) {
+                        res["complexity"] = json!({
+                            "blocks_found": complexity.blocks_found,
+                            "blocks_hit": complexity.blocks_hit,
+                            "branches_found": complexity.branches_found,
+                            "branches_hit": complexity.branches_hit,
+                            "score": complexity.score(),
+                        });
+                    }
+                    if !function.aliases.is_empty() {
+                        res["aliases"] = json!(function.aliases);
+                    }
+                    res
+                })
+                .collect::<Vec<_>>();
+
+            json!({ "path": file, "lines": lines, "functions": functions })
+        })
+        .collect::<Vec<_>>();
+
+    let modules = report
+        .modules
+        .iter()
+        .sorted_by(|(a, _), (b, _)| a.path.cmp(&b.path))
+        .map(|(module, offsets)| {
+            json!({
+                "path": module.path,
+                "executed_basic_blocks": offsets.iter().collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut res: Value = json!({
+        "format_version": constants::FORMAT_VERSION,
+        "tool_version": env!("CARGO_PKG_VERSION"),
+        "summary": {
+            "lines_found": summary.lines_found,
+            "lines_hit": summary.lines_hit,
+            "functions_found": summary.functions_found,
+            "functions_hit": summary.functions_hit,
+        },
+        "files": files,
+        "modules": modules,
+    });
+
+    if let Some(components) = report.components {
+        res["components"] = components
+            .rollups(coverage)
+            .iter()
+            .map(|rollup| {
+                json!({
+                    "name": rollup.name,
+                    "owner": rollup.owner,
+                    "files": rollup.files,
+                    "lines_found": rollup.summary.lines_found,
+                    "lines_hit": rollup.summary.lines_hit,
+                    "functions_found": rollup.summary.functions_found,
+                    "functions_hit": rollup.summary.functions_hit,
+                })
+            })
+            .collect();
+    }
+
+    Ok(serde_json::to_string_pretty(&res)?)
+}
+
+pub struct JsonWriter;
+
+impl CoverageWriter for JsonWriter {
+    fn name(&self) -> &'static str {
+        "JSON"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_json_output(path, report)
+    }
+}
@@ -0,0 +1,344 @@
+use crate::cli::CliOptions;
+use crate::drcov::{Drcov, DrcovFilters, Modules};
+use crate::dwarf::{FunctionInfo, LineInfo, SourceCoverage};
+use crate::qemu::link_time_modules;
+use crate::util::{parse_capture_group, Hex};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    pub const XML_EXTENSION: &str = "xml";
+
+    /// The flavor given to the coverage parsed from kcov (Cobertura) reports
+    pub const KCOV_FLAVOR: &str = "kcov";
+
+    /// The flavor given to the coverage parsed from kernel KCOV traces
+    pub const KCOV_TRACE_FLAVOR: &str = "kcov-trace";
+
+    lazy_static! {
+        /// A line of a KCOV trace: the program counter of an executed basic block, like the
+        /// programs reading `/sys/kernel/debug/kcov` (and syzkaller) dump them
+        pub static ref KCOV_TRACE_LINE_REGEX: Regex =
+            Regex::new(r"^\s*0x(?P<pc>[[:xdigit:]]+)\s*$").unwrap();
+    }
+}
+
+fn parse_pc(line: &[u8]) -> Option<u64> {
+    let cap = constants::KCOV_TRACE_LINE_REGEX.captures(line)?;
+
+    parse_capture_group::<Hex<usize>>(&cap, "pc").map(|pc| pc.value as u64)
+}
+
+/// Whether `head`, the start of an input file, is a KCOV trace (one `0x<pc>` per line), judging by
+/// its first line
+pub fn is_kcov_trace(head: &[u8]) -> bool {
+    head.split(|b| *b == b'\n')
+        .find(|line| !line.trim_ascii().is_empty())
+        .is_some_and(|line| parse_pc(line).is_some())
+}
+
+/// Whether the coverage of the given flavor has been parsed from a KCOV trace
+pub fn is_kcov_trace_flavor(flavor: &str) -> bool {
+    flavor == constants::KCOV_TRACE_FLAVOR
+}
+
+/// Parses a KCOV trace, the program counters the kernel's KCOV recorded for a task, into the same
+/// model drcov files are parsed into.
+///
+/// The addresses are mapped to modules like the ones of QEMU traces: through `binaries`
+/// (`--kcov-binary`, e.g. a vmlinux without KASLR), which are assumed to be at their link-time
+/// addresses, and through `module_table` for the kernel modules loaded elsewhere. KCOV records the
+/// return address of its callback in every basic block rather than the start of the block, so
+/// every address is recorded as a one byte basic block and `any-byte` line hit semantics are the
+/// default for these inputs.
+pub fn from_trace(
+    path: &Path,
+    binaries: &[String],
+    module_table: Option<&Modules>,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading KCOV trace: {}", path.display());
+
+    let mut modules = link_time_modules(binaries, module_table)?;
+
+    if modules.table.is_empty() {
+        anyhow::bail!(
+            "KCOV traces do not describe the loaded modules (use --kcov-binary or --module-table)"
+        );
+    }
+
+    let contents = std::fs::read(path)?;
+
+    let pcs = contents
+        .split(|b| *b == b'\n')
+        .filter_map(parse_pc)
+        .collect::<HashSet<_>>();
+
+    modules.insert_addresses(pcs.into_iter().map(|pc| (pc, 1)));
+
+    modules.apply_filters(filters);
+
+    log::info!("KCOV trace loaded");
+
+    Ok(Drcov::from_modules(constants::KCOV_TRACE_FLAVOR, modules))
+}
+
+/// Whether the file at `path`, starting with `head`, is a Cobertura XML report, like the
+/// `cobertura.xml` kcov writes for every binary it has run
+pub fn is_cobertura_report(head: &[u8], path: &Path) -> bool {
+    if path
+        .extension()
+        .is_none_or(|extension| extension != constants::XML_EXTENSION)
+    {
+        return false;
+    }
+
+    head.windows(9).any(|window| window == b"<coverage")
+}
+
+fn attribute<T: std::str::FromStr>(node: &roxmltree::Node, name: &str) -> Option<T> {
+    node.attribute(name).and_then(|value| value.parse().ok())
+}
+
+/// Parses the per-binary output of kcov (its `cobertura.xml`, or any other Cobertura report) into
+/// line and function coverage.
+///
+/// kcov only records which source lines were hit, not the addresses behind them, so its coverage
+/// joins the other inputs at the line level and needs no debug info. The address level coverage of
+/// the kernel comes from KCOV traces instead (see [`from_trace`]). Relative file names are
+/// resolved against the first `<source>` of the report.
+pub fn from_file(path: &Path, options: &CliOptions) -> anyhow::Result<(Drcov, SourceCoverage)> {
+    log::info!("Loading Cobertura report: {}", path.display());
+
+    let filters = options.get_line_info_filters(constants::KCOV_FLAVOR);
+
+    let contents = std::fs::read_to_string(path)?;
+    let document = roxmltree::Document::parse_with_options(
+        &contents,
+        roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        },
+    )?;
+
+    let root = document.root_element();
+    if !root.has_tag_name("coverage") {
+        anyhow::bail!("'{}' is not a Cobertura report", path.display());
+    }
+
+    let source_root = root
+        .descendants()
+        .find(|node| node.has_tag_name("source"))
+        .and_then(|node| node.text())
+        .map(|text| Path::new(text.trim()).to_path_buf())
+        .unwrap_or_default();
+
+    // Source file -> line -> executed
+    let mut lines = HashMap::<String, BTreeMap<u64, bool>>::new();
+    let mut res = SourceCoverage::default();
+
+    for package in root
+        .descendants()
+        .filter(|node| node.has_tag_name("package"))
+    {
+        let module = package.attribute("name").unwrap_or_default().to_string();
+
+        for class in package
+            .descendants()
+            .filter(|node| node.has_tag_name("class"))
+        {
+            let Some(filename) = class.attribute("filename") else {
+                continue;
+            };
+
+            let file = source_root.join(filename).to_string_lossy().to_string();
+
+            if !filters.matches_any_source_filter(Some(&file))
+                || filters.matches_any_source_skip_filter(Some(&file))
+            {
+                continue;
+            }
+
+            let file_lines = lines.entry(file.clone()).or_default();
+
+            // Lines of methods are repeated in the lines of their class
+            for line in class.descendants().filter(|node| node.has_tag_name("line")) {
+                let (Some(number), Some(hits)) = (
+                    attribute::<u64>(&line, "number"),
+                    attribute::<u64>(&line, "hits"),
+                ) else {
+                    continue;
+                };
+
+                *file_lines.entry(number).or_default() |= hits > 0;
+            }
+
+            for method in class
+                .descendants()
+                .filter(|node| node.has_tag_name("method"))
+            {
+                let method_lines = method
+                    .descendants()
+                    .filter(|node| node.has_tag_name("line"))
+                    .filter_map(|line| {
+                        Some((
+                            attribute::<u64>(&line, "number")?,
+                            attribute::<u64>(&line, "hits")?,
+                        ))
+                    })
+                    .collect::<Vec<_>>();
+
+                let (Some(start_line), Some(end_line)) = (
+                    method_lines.iter().map(|(number, _)| *number).min(),
+                    method_lines.iter().map(|(number, _)| *number).max(),
+                ) else {
+                    continue;
+                };
+
+                res.functions
+                    .entry(file.clone())
+                    .or_default()
+                    .push(FunctionInfo {
+                        name: method.attribute("name").unwrap_or_default().to_string(),
+                        module: module.clone(),
+                        start_line,
+                        end_line,
+                        executed: method_lines.iter().any(|(_, hits)| *hits > 0),
+                        aliases: Vec::new(),
+                        blocks: None,
+                    });
+            }
+        }
+    }
+
+    res.lines = lines
+        .into_iter()
+        .map(|(file, lines)| {
+            let lines = lines
+                .into_iter()
+                .map(|(line, executed)| LineInfo { line, executed })
+                .collect();
+            (file, lines)
+        })
+        .collect();
+
+    log::info!("Cobertura report loaded");
+
+    Ok((Drcov::without_modules(constants::KCOV_FLAVOR), res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::ObjectFile;
+    use crate::test_fixtures::{small_binary, TempPath};
+    use clap::Parser;
+
+    #[test]
+    fn maps_the_addresses_of_kcov_traces_to_their_modules() {
+        let binary = small_binary();
+        let object_file = ObjectFile::from_path(&binary).unwrap();
+        let load_base = object_file.load_base();
+        let image_end = load_base + object_file.image_size();
+
+        let trace = TempPath::new("trace.kcov");
+        std::fs::write(
+            trace.path(),
+            format!(
+                "0x{:x}\n0x{:x}\n0x{:x}\n0x{:x}\n",
+                load_base + 0x1010,
+                load_base + 0x1004,
+                load_base + 0x1010,
+                image_end + 0x10
+            ),
+        )
+        .unwrap();
+        assert!(is_kcov_trace(&std::fs::read(trace.path()).unwrap()));
+
+        let drcov = from_trace(
+            trace.path(),
+            std::slice::from_ref(&binary),
+            None,
+            &DrcovFilters::default(),
+        )
+        .unwrap();
+
+        let [module] = drcov.modules.table.as_slice() else {
+            panic!("Expected a single module");
+        };
+        assert_eq!(module.path, binary);
+        // The addresses are relative to the load base, and the ones of no module are dropped
+        assert_eq!(
+            module.bb_starts.iter().collect::<Vec<_>>(),
+            vec![0x1004, 0x1010]
+        );
+        assert!(is_kcov_trace_flavor(&drcov.flavor));
+    }
+
+    #[test]
+    fn kcov_traces_need_their_modules() {
+        let trace = TempPath::new("trace.kcov");
+        std::fs::write(trace.path(), "0xffffffff81000000\n").unwrap();
+
+        assert!(from_trace(trace.path(), &[], None, &DrcovFilters::default()).is_err());
+    }
+
+    #[test]
+    fn reads_the_lines_and_methods_of_cobertura_reports() {
+        let report = TempPath::dir("kcov-report");
+        let path = report.join("cobertura.xml");
+        std::fs::write(
+            &path,
+            r#"<?xml version="1.0"?>
+<coverage>
+  <sources><source>/src/</source></sources>
+  <packages>
+    <package name="app">
+      <classes>
+        <class name="main" filename="main.c">
+          <methods>
+            <method name="main">
+              <lines><line number="3" hits="1"/><line number="5" hits="0"/></lines>
+            </method>
+          </methods>
+          <lines>
+            <line number="3" hits="1"/>
+            <line number="5" hits="0"/>
+            <line number="9" hits="0"/>
+          </lines>
+        </class>
+      </classes>
+    </package>
+  </packages>
+</coverage>
+"#,
+        )
+        .unwrap();
+        assert!(is_cobertura_report(&std::fs::read(&path).unwrap(), &path));
+
+        let options =
+            CliOptions::try_parse_from(["drcov2lcov", "--input", &path.to_string_lossy()]).unwrap();
+        let (_, coverage) = from_file(&path, &options).unwrap();
+
+        let lines = &coverage.lines["/src/main.c"];
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| (line.line, line.executed))
+                .collect::<Vec<_>>(),
+            vec![(3, true), (5, false), (9, false)]
+        );
+
+        let [function] = coverage.functions["/src/main.c"].as_slice() else {
+            panic!("Expected a single function");
+        };
+        assert_eq!(
+            (function.start_line, function.end_line, function.executed),
+            (3, 5, true)
+        );
+        assert_eq!(function.module, "app");
+    }
+}
@@ -0,0 +1,435 @@
+use crate::checksum::LineChecksums;
+#[cfg(feature = "native")]
+use crate::cli::CliOptions;
+#[cfg(feature = "native")]
+use crate::drcov::Drcov;
+use crate::dwarf::{BranchInfo, FunctionInfo, LineInfo, SourceCoverage};
+use crate::output::{write_text_output, CoverageReport, CoverageWriter, LineEnding};
+use itertools::Itertools;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+#[cfg(feature = "native")]
+use std::path::Path;
+
+mod constants {
+    /// The flavor given to the coverage read from lcov tracefiles
+    #[cfg(feature = "native")]
+    pub const LCOV_FLAVOR: &str = "lcov";
+}
+
+/// Writes `coverage` as an lcov tracefile, followed by a test (`TN:`) section for each of `tests`
+pub fn write_lcov_output(
+    path: &str,
+    coverage: &SourceCoverage,
+    tests: &[(String, SourceCoverage)],
+    checksums: Option<&LineChecksums>,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    let tracefile = TracefileWriter {
+        checksums,
+        ..Default::default()
+    }
+    .render(coverage, tests);
+
+    write_text_output(path, &tracefile, line_ending)?;
+
+    Ok(())
+}
+
+/// Renders coverage as the records of an lcov tracefile: the line (`DA`) and branch (`BRDA`)
+/// records of every source file (`SF`), plus its function records (`FN`/`FNDA`) on request
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracefileWriter<'c> {
+    /// The MD5 checksums of the source lines, appended to their line records
+    pub checksums: Option<&'c LineChecksums>,
+    /// Whether to write the function records, which the tracefiles of the command line tool leave
+    /// out
+    pub functions: bool,
+}
+
+impl TracefileWriter<'_> {
+    /// Returns `coverage` as a tracefile, followed by a test (`TN:`) section for each of `tests`
+    pub fn render(&self, coverage: &SourceCoverage, tests: &[(String, SourceCoverage)]) -> String {
+        let mut res = String::new();
+
+        self.write_file_records(&mut res, coverage);
+
+        for (name, coverage) in tests {
+            let _ = writeln!(res, "TN:{name}");
+            self.write_file_records(&mut res, coverage);
+        }
+
+        res
+    }
+
+    fn write_file_records(&self, res: &mut String, coverage: &SourceCoverage) {
+        let line_info = &coverage.lines;
+        for file in line_info.keys().sorted() {
+            let _ = writeln!(res, "SF:{file}");
+            if self.functions {
+                write_function_records(res, coverage, file);
+            }
+            write_branch_records(res, coverage, file);
+            for info in &line_info[file] {
+                let _ = write!(
+                    res,
+                    "DA:{},{}",
+                    info.line,
+                    if info.executed { 1 } else { 0 }
+                );
+                if let Some(checksum) = self
+                    .checksums
+                    .and_then(|checksums| checksums.get(file, info.line))
+                {
+                    let _ = write!(res, ",{checksum}");
+                }
+                let _ = writeln!(res);
+            }
+            let _ = writeln!(res, "end_of_record");
+        }
+    }
+}
+
+/// Writes the functions of `file` as function records, in the `FN:<start line>,<name>` form that
+/// every lcov version understands
+fn write_function_records(res: &mut String, coverage: &SourceCoverage, file: &str) {
+    let Some(functions) = coverage.functions.get(file).filter(|f| !f.is_empty()) else {
+        return;
+    };
+
+    for function in functions {
+        let _ = writeln!(res, "FN:{},{}", function.start_line, function.name);
+    }
+
+    for function in functions {
+        let _ = writeln!(
+            res,
+            "FNDA:{},{}",
+            if function.executed { 1 } else { 0 },
+            function.name
+        );
+    }
+
+    let _ = writeln!(res, "FNF:{}", functions.len());
+    let _ = writeln!(
+        res,
+        "FNH:{}",
+        functions
+            .iter()
+            .filter(|function| function.executed)
+            .count()
+    );
+}
+
+/// Writes the discriminator blocks of `file` as branch records. The blocks of a line that has not
+/// been executed at all are marked as such (`-`) rather than as not taken.
+fn write_branch_records(res: &mut String, coverage: &SourceCoverage, file: &str) {
+    let Some(branches) = coverage.branches.get(file).filter(|b| !b.is_empty()) else {
+        return;
+    };
+
+    let executed_lines = coverage
+        .lines
+        .get(file)
+        .into_iter()
+        .flatten()
+        .filter(|info| info.executed)
+        .map(|info| info.line)
+        .collect::<HashSet<_>>();
+
+    for branch in branches {
+        let taken = if branch.executed {
+            "1"
+        } else if executed_lines.contains(&branch.line) {
+            "0"
+        } else {
+            "-"
+        };
+
+        let _ = writeln!(
+            res,
+            "BRDA:{},0,{},{taken}",
+            branch.line, branch.discriminator
+        );
+    }
+
+    let _ = writeln!(res, "BRF:{}", branches.len());
+    let _ = writeln!(
+        res,
+        "BRH:{}",
+        branches.iter().filter(|branch| branch.executed).count()
+    );
+}
+
+/// Reads the line records (`SF`/`DA`) of the lcov tracefile at `path`. Lines with a non-zero
+/// execution count are considered executed; all other records are ignored.
+pub fn read_lcov_lines(path: &str) -> anyhow::Result<HashMap<String, Vec<LineInfo>>> {
+    Ok(read_lcov_coverage(path)?.lines)
+}
+
+/// Whether `head`, the start of an input file, is an lcov tracefile, judging by its first record
+pub fn is_lcov_tracefile(head: &[u8]) -> bool {
+    head.split(|b| *b == b'\n')
+        .map(<[u8]>::trim_ascii)
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| line.starts_with(b"TN:") || line.starts_with(b"SF:"))
+}
+
+/// The function records (`FN`/`FNDA`) of the current file of a tracefile
+#[derive(Default)]
+struct FileFunctions {
+    /// Name -> start and (with lcov 2.0 and later) end line
+    lines: HashMap<String, (u64, Option<u64>)>,
+    /// Name -> execution count
+    counts: HashMap<String, u64>,
+}
+
+impl FileFunctions {
+    fn into_function_info(self, module: &str) -> Vec<FunctionInfo> {
+        self.lines
+            .into_iter()
+            .map(|(name, (start_line, end_line))| FunctionInfo {
+                executed: self.counts.get(&name).is_some_and(|count| *count > 0),
+                name,
+                module: module.to_string(),
+                start_line,
+                end_line: end_line.unwrap_or(start_line).max(start_line),
+                aliases: Vec::new(),
+                blocks: None,
+            })
+            .collect()
+    }
+}
+
+/// Reads the line (`SF`/`DA`), function (`FN`/`FNDA`) and branch (`BRDA`) records of the lcov
+/// tracefile at `path`, see [`parse_tracefile`]
+pub fn read_lcov_coverage(path: &str) -> anyhow::Result<SourceCoverage> {
+    parse_tracefile(&std::fs::read_to_string(path)?, path)
+}
+
+/// Parses the line (`SF`/`DA`), function (`FN`/`FNDA`) and branch (`BRDA`) records of the lcov
+/// tracefile `contents`. Lines, functions and branches with a non-zero execution count are
+/// considered executed; all other records are ignored. Since tracefiles do not name binaries, the
+/// functions are attributed to `name`, which errors refer to the tracefile by as well.
+pub fn parse_tracefile(contents: &str, name: &str) -> anyhow::Result<SourceCoverage> {
+    let mut res = SourceCoverage::default();
+    let mut current_file = None;
+    let mut functions = FileFunctions::default();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+
+        if let Some(file) = line.strip_prefix("SF:") {
+            current_file = Some(file.to_string());
+            continue;
+        }
+
+        if line == "end_of_record" {
+            if let Some(file) = current_file.take() {
+                res.functions
+                    .entry(file)
+                    .or_default()
+                    .extend(std::mem::take(&mut functions).into_function_info(name));
+            }
+            continue;
+        }
+
+        let Some((kind, record)) = line.split_once(':') else {
+            continue;
+        };
+
+        if !matches!(kind, "DA" | "FN" | "FNDA" | "BRDA") {
+            continue;
+        }
+
+        let Some(file) = current_file.as_ref() else {
+            anyhow::bail!(
+                "{name}:{}: {kind} record outside of a file record",
+                line_number + 1
+            );
+        };
+
+        let invalid_record =
+            || anyhow::anyhow!("{name}:{}: Invalid {kind} record '{line}'", line_number + 1);
+
+        match kind {
+            "DA" => {
+                let mut fields = record.split(',');
+                let (Some(Ok(line)), Some(Ok(count))) = (
+                    fields.next().map(str::parse::<u64>),
+                    fields.next().map(str::parse::<i64>),
+                ) else {
+                    return Err(invalid_record());
+                };
+
+                res.lines.entry(file.clone()).or_default().push(LineInfo {
+                    line,
+                    executed: count > 0,
+                });
+            }
+            "FN" => {
+                // FN:<start line>,<name> or FN:<start line>,<end line>,<name>
+                let (start_line, rest) = record.split_once(',').ok_or_else(invalid_record)?;
+                let start_line = start_line.parse::<u64>().map_err(|_| invalid_record())?;
+
+                let (end_line, name) = match rest.split_once(',') {
+                    Some((end_line, name)) if end_line.parse::<u64>().is_ok() => {
+                        (end_line.parse::<u64>().ok(), name)
+                    }
+                    _ => (None, rest),
+                };
+
+                functions
+                    .lines
+                    .insert(name.to_string(), (start_line, end_line));
+            }
+            "BRDA" => {
+                // BRDA:<line>,<block>,<branch>,<taken>, where taken is - if the line has not been
+                // executed at all
+                let fields = record.split(',').collect::<Vec<_>>();
+                let [line, _, branch, taken] = fields[..] else {
+                    return Err(invalid_record());
+                };
+                let line = line.parse::<u64>().map_err(|_| invalid_record())?;
+                let executed = match taken {
+                    "-" => false,
+                    taken => taken.parse::<u64>().map_err(|_| invalid_record())? > 0,
+                };
+
+                let branches = res.branches.entry(file.clone()).or_default();
+                // Branches are told apart by their discriminator, which the tracefiles of other
+                // tools might not have: those are numbered within their line instead
+                let discriminator = branch.parse::<u64>().unwrap_or_else(|_| {
+                    branches.iter().filter(|branch| branch.line == line).count() as u64
+                });
+
+                branches.push(BranchInfo {
+                    line,
+                    discriminator,
+                    executed,
+                });
+            }
+            _ => {
+                // FNDA:<count>,<name>
+                let (count, name) = record.split_once(',').ok_or_else(invalid_record)?;
+                let count = count.parse::<u64>().map_err(|_| invalid_record())?;
+
+                *functions.counts.entry(name.to_string()).or_default() += count;
+            }
+        }
+    }
+
+    if let Some(file) = current_file {
+        res.functions
+            .entry(file)
+            .or_default()
+            .extend(functions.into_function_info(name));
+    }
+
+    res.functions.retain(|_, functions| !functions.is_empty());
+    res.coalesce();
+
+    Ok(res)
+}
+
+/// Parses a previously generated lcov tracefile so that its coverage is unioned with the other
+/// inputs (like `lcov -a` would), at the line level and without any debug info
+#[cfg(feature = "native")]
+pub fn from_file(path: &Path, options: &CliOptions) -> anyhow::Result<(Drcov, SourceCoverage)> {
+    log::info!("Loading lcov tracefile: {}", path.display());
+
+    let filters = options.get_line_info_filters(constants::LCOV_FLAVOR);
+
+    let mut coverage = read_lcov_coverage(&path.to_string_lossy())?;
+
+    let is_included = |file: &String| {
+        filters.matches_any_source_filter(Some(file))
+            && !filters.matches_any_source_skip_filter(Some(file))
+    };
+
+    coverage.lines.retain(|file, _| is_included(file));
+    coverage.functions.retain(|file, _| is_included(file));
+    coverage.branches.retain(|file, _| is_included(file));
+
+    log::info!("lcov tracefile loaded");
+
+    Ok((Drcov::without_modules(constants::LCOV_FLAVOR), coverage))
+}
+
+pub struct LcovWriter;
+
+impl CoverageWriter for LcovWriter {
+    fn name(&self) -> &'static str {
+        "lcov"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_lcov_output(
+            path,
+            report.coverage,
+            report.tests,
+            report.checksums,
+            report.line_ending,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn functions_and_branches_round_trip() {
+        let file = "src/parser.c".to_string();
+        let mut coverage = SourceCoverage::default();
+        coverage.lines.insert(
+            file.clone(),
+            vec![
+                LineInfo {
+                    line: 3,
+                    executed: true,
+                },
+                LineInfo {
+                    line: 4,
+                    executed: false,
+                },
+            ],
+        );
+        coverage.functions.insert(
+            file.clone(),
+            vec![FunctionInfo {
+                name: "parse(char const*, int)".to_string(),
+                module: "coverage.info".to_string(),
+                start_line: 3,
+                end_line: 3,
+                executed: true,
+                aliases: Vec::new(),
+                blocks: None,
+            }],
+        );
+        coverage.branches.insert(
+            file.clone(),
+            vec![
+                BranchInfo {
+                    line: 3,
+                    discriminator: 1,
+                    executed: false,
+                },
+                BranchInfo {
+                    line: 3,
+                    discriminator: 2,
+                    executed: true,
+                },
+            ],
+        );
+
+        let writer = TracefileWriter {
+            functions: true,
+            ..Default::default()
+        };
+        let tracefile = writer.render(&coverage, &[]);
+        let parsed = parse_tracefile(&tracefile, "coverage.info").unwrap();
+
+        assert_eq!(writer.render(&parsed, &[]), tracefile);
+    }
+}
@@ -0,0 +1,195 @@
+//! Generates line coverage from drcov logs, along with the other coverage inputs of the
+//! `drcov2lcov` command line tool.
+//!
+//! Besides running the tool itself ([`run`]), the library exposes its building blocks to embed
+//! the conversion in other tools, e.g. a fuzzing orchestrator:
+//!
+//! - [`Drcov`] parses drcov logs into their [`Modules`] and executed basic blocks, filtered by
+//!   [`DrcovFilters`]
+//! - [`gather_line_info`] symbolizes the executed basic blocks of the modules into the
+//!   [`SourceCoverage`] of their source lines and functions, filtered by [`LineInfoFilters`];
+//!   [`gather_line_info_streaming`] hands over the coverage of every module as soon as it has been
+//!   gathered instead
+//! - [`write_lcov_output`] (or any other [`CoverageWriter`], such as [`LcovWriter`]) writes the
+//!   coverage
+//!
+//! A [`Progress`] given to [`Converter::progress`] (or with the `progress` of the
+//! [`LineInfoFilters`]) receives the progress of a conversion as it goes.
+//!
+//! [`CliOptions`] are the options of the command line tool; [`CliOptions::get_drcov_filters`]
+//! and [`CliOptions::get_line_info_filters`] derive the filters from them.
+//!
+//! Parsing drcov logs and reading debug information fail with an [`Error`], which tells malformed
+//! logs (with the line or offset of the error) apart from missing debug information, I/O errors
+//! and invalid filters.
+
+mod aliases;
+#[cfg(feature = "native")]
+mod archive;
+#[cfg(feature = "native")]
+mod badge;
+mod cache;
+mod callgrind;
+mod checksum;
+#[cfg(feature = "native")]
+pub mod cli;
+mod cobertura;
+#[cfg(feature = "native")]
+mod compat;
+mod complexity;
+mod components;
+#[cfg(feature = "native")]
+mod conformance;
+mod consistency;
+#[cfg(feature = "native")]
+mod conversion;
+#[cfg(feature = "native")]
+mod converter;
+mod cross_check;
+#[cfg(feature = "native")]
+mod debuginfod;
+#[cfg(feature = "native")]
+mod dedup;
+#[cfg(feature = "native")]
+mod demo;
+#[cfg(feature = "native")]
+mod deps;
+pub mod drcov;
+pub mod dwarf;
+mod error;
+mod filter;
+mod function_keyed;
+#[cfg(feature = "native")]
+mod gcov;
+#[cfg(feature = "native")]
+mod input;
+mod istanbul;
+#[cfg(feature = "native")]
+mod job;
+mod json;
+#[cfg(feature = "native")]
+mod kcov;
+pub mod lcov;
+mod lighthouse;
+mod limits;
+mod line_table_store;
+#[cfg(feature = "native")]
+mod llvm_covmap;
+#[cfg(feature = "native")]
+mod llvm_profile;
+#[cfg(feature = "native")]
+mod manifest;
+#[cfg(feature = "native")]
+mod metrics;
+#[cfg(feature = "native")]
+mod modoff;
+#[cfg(feature = "native")]
+mod module_graph;
+mod module_key;
+#[cfg(feature = "native")]
+mod notify;
+mod object_cache;
+mod opencover;
+mod oracle;
+pub mod output;
+#[cfg(feature = "native")]
+mod output_template;
+#[cfg(feature = "native")]
+mod perf;
+#[cfg(feature = "native")]
+mod pin;
+mod progress;
+#[cfg(feature = "native")]
+mod qemu;
+mod read_only;
+#[cfg(feature = "native")]
+mod report;
+#[cfg(test)]
+mod roundtrip_tests;
+#[cfg(feature = "native")]
+mod run_report;
+#[cfg(feature = "native")]
+mod sancov;
+#[cfg(feature = "native")]
+mod scrub;
+#[cfg(feature = "native")]
+mod server;
+mod source_path;
+#[cfg(feature = "native")]
+mod sqlite;
+#[cfg(feature = "native")]
+mod staleness;
+#[cfg(feature = "native")]
+mod state;
+pub mod summary;
+#[cfg(feature = "native")]
+mod tenet;
+#[cfg(test)]
+mod test_fixtures;
+mod util;
+#[cfg(feature = "native")]
+mod verify;
+#[cfg(feature = "native")]
+mod watch;
+
+pub use aliases::AliasPolicy;
+#[cfg(feature = "native")]
+pub use cli::CliOptions;
+#[cfg(feature = "native")]
+pub use converter::Converter;
+pub use cross_check::CrossCheckTool;
+pub use drcov::{BbOffsetBase, Drcov, DrcovFilters, Module, Modules};
+pub use dwarf::{
+    gather_line_info, gather_line_info_streaming, gather_module_line_info, LineHitSemantics,
+    LineInfoFilters, SourceCoverage,
+};
+pub use error::Error;
+pub use filter::{Filter, ReplacementFilter};
+pub use lcov::{write_lcov_output, LcovWriter};
+pub use limits::{InputLimits, LimitExceeded};
+pub use module_key::ModuleKey;
+pub use object_cache::{LineTableCache, ObjectFileCache};
+pub use output::{CoverageReport, CoverageWriter, LineEnding, OutputFormat};
+pub use progress::Progress;
+#[cfg(feature = "native")]
+pub use progress::ProgressBars;
+pub use source_path::SourcePathResolver;
+pub use summary::Summary;
+
+#[cfg(feature = "native")]
+use crate::cli::{CacheCommand, Command};
+#[cfg(feature = "native")]
+use std::path::Path;
+
+/// Runs the command line tool with the given (validated) `options`
+#[cfg(feature = "native")]
+pub fn run(options: CliOptions) -> anyhow::Result<()> {
+    read_only::enforce(options.get_read_only_policy());
+
+    match options.command {
+        Some(Command::Demo { directory, no_open }) => demo::run(directory, no_open),
+        Some(Command::Cache { command }) => {
+            match command {
+                CacheCommand::Info => cache::info(),
+                CacheCommand::Clear { max_size } => cache::prune(max_size),
+            }
+            Ok(())
+        }
+        Some(Command::Report {
+            load_state,
+            formats,
+            output_dir,
+            options,
+        }) => conversion::report(load_state, formats, output_dir, options),
+        Some(Command::CheckProducer { input }) => conformance::check_producer(Path::new(&input)),
+        Some(Command::Serve {
+            listen,
+            workers,
+            options,
+        }) => server::serve(&listen, workers, options),
+        None => match options.job.as_ref() {
+            Some(job) => conversion::run_job(Path::new(job), options.progress.clone()),
+            None => conversion::convert(options, &tokio::runtime::Runtime::new()?),
+        },
+    }
+}
@@ -0,0 +1,49 @@
+use crate::module_key::ModuleKey;
+use crate::output::{write_text_output, CoverageReport, CoverageWriter, LineEnding};
+use itertools::Itertools;
+use roaring::RoaringBitmap;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Writes the executed basic blocks of every module in the `module+offset` text format
+/// understood by the Lighthouse plugin for IDA and Binary Ninja.
+///
+/// `modules` maps each module to the module relative offsets of its executed basic blocks.
+pub fn write_lighthouse_output(
+    path: &str,
+    modules: &HashMap<ModuleKey, RoaringBitmap>,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    let mut res = String::new();
+
+    for (module, offsets) in modules
+        .iter()
+        .sorted_by(|(a, _), (b, _)| a.path.cmp(&b.path))
+    {
+        let module_name = Path::new(&module.path)
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_else(|| module.path.as_str().into());
+
+        for offset in offsets {
+            let _ = writeln!(res, "{module_name}+{offset:#x}");
+        }
+    }
+
+    write_text_output(path, &res, line_ending)?;
+
+    Ok(())
+}
+
+pub struct LighthouseWriter;
+
+impl CoverageWriter for LighthouseWriter {
+    fn name(&self) -> &'static str {
+        "Lighthouse"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_lighthouse_output(path, report.modules, report.line_ending)
+    }
+}
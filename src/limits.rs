@@ -0,0 +1,168 @@
+use crate::drcov::Drcov;
+use crate::error::Error;
+use std::io::Read;
+use std::path::Path;
+use std::time::Duration;
+
+/// Limits on the inputs that are accepted, so that a single malformed or hostile input cannot
+/// exhaust the memory or the symbolization time of a shared conversion service
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InputLimits {
+    /// The maximum size of an input, in bytes, both before and after decompression
+    pub max_input_size: Option<u64>,
+    /// The maximum number of modules an input may carry
+    pub max_modules: Option<usize>,
+    /// The maximum number of basic blocks an input may carry, over all of its modules. The
+    /// entries of drcov logs are counted as their headers declare them, before they are parsed.
+    pub max_basic_blocks: Option<usize>,
+    /// How long the symbolization of an input may take
+    pub max_duration: Option<Duration>,
+}
+
+/// An input that violates one of the [`InputLimits`]. It is returned inside `anyhow` errors;
+/// callers that need to tell rejected inputs apart from malformed ones can downcast to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// Streams are only read up to just past the limit, so their `size` is a lower bound
+    InputSize {
+        size: u64,
+        limit: u64,
+    },
+    Modules {
+        count: usize,
+        limit: usize,
+    },
+    BasicBlocks {
+        count: usize,
+        limit: usize,
+    },
+    Duration {
+        limit: Duration,
+    },
+}
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InputSize { size, limit } => {
+                write!(
+                    f,
+                    "The input has at least {size} bytes, more than the limit of {limit}"
+                )
+            }
+            Self::Modules { count, limit } => {
+                write!(
+                    f,
+                    "The input has {count} modules, more than the limit of {limit}"
+                )
+            }
+            Self::BasicBlocks { count, limit } => write!(
+                f,
+                "The input has {count} basic blocks, more than the limit of {limit}"
+            ),
+            Self::Duration { limit } => write!(
+                f,
+                "The symbolization of the input took longer than the limit of {limit:.2?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl LimitExceeded {
+    /// The limit `error` or one of its causes (e.g. a parse error) reports
+    pub fn find(error: &anyhow::Error) -> Option<&Self> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+}
+
+/// Reads `reader` to its end, failing as soon as more than `limit` bytes have been read (streams,
+/// including decompressed ones, have no size to check up front)
+pub fn read_to_end_bounded<R: Read>(reader: R, limit: Option<u64>) -> Result<Vec<u8>, Error> {
+    let mut contents = Vec::new();
+
+    match limit {
+        Some(limit) => {
+            reader
+                .take(limit.saturating_add(1))
+                .read_to_end(&mut contents)?;
+
+            if contents.len() as u64 > limit {
+                return Err(LimitExceeded::InputSize {
+                    size: contents.len() as u64,
+                    limit,
+                }
+                .into());
+            }
+        }
+        None => {
+            let mut reader = reader;
+            reader.read_to_end(&mut contents)?;
+        }
+    }
+
+    Ok(contents)
+}
+
+impl InputLimits {
+    /// Checks the size of the input file at `path` before it is parsed
+    pub fn check_file_size(&self, path: &Path) -> anyhow::Result<()> {
+        let Some(limit) = self.max_input_size else {
+            return Ok(());
+        };
+
+        let size = std::fs::metadata(path)?.len();
+
+        if size > limit {
+            return Err(LimitExceeded::InputSize { size, limit }.into());
+        }
+
+        Ok(())
+    }
+
+    /// Reads `reader` to its end, failing as soon as more than the maximum input size has been
+    /// read
+    pub fn read_to_end<R: Read>(&self, reader: R) -> anyhow::Result<Vec<u8>> {
+        Ok(read_to_end_bounded(reader, self.max_input_size)?)
+    }
+
+    /// Checks the number of basic blocks the headers of the basic block tables of a drcov log
+    /// declare, `count` over the tables read so far, before the entries are parsed
+    pub fn check_declared_basic_blocks(&self, count: usize) -> Result<(), LimitExceeded> {
+        match self.max_basic_blocks {
+            Some(limit) if count > limit => Err(LimitExceeded::BasicBlocks { count, limit }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the parsed `drcov` before it is symbolized, which is where the bulk of the work of
+    /// a conversion goes. The basic blocks of the formats whose headers declare no count (e.g.
+    /// sancov files or QEMU traces) are only counted here, and distinct blocks are counted once.
+    pub fn check_coverage(&self, drcov: &Drcov) -> anyhow::Result<()> {
+        let modules = &drcov.modules.table;
+
+        if let Some(limit) = self.max_modules {
+            if modules.len() > limit {
+                return Err(LimitExceeded::Modules {
+                    count: modules.len(),
+                    limit,
+                }
+                .into());
+            }
+        }
+
+        if let Some(limit) = self.max_basic_blocks {
+            let count = modules
+                .iter()
+                .map(|module| module.bb_starts.len() as usize)
+                .sum::<usize>();
+
+            if count > limit {
+                return Err(LimitExceeded::BasicBlocks { count, limit }.into());
+            }
+        }
+
+        Ok(())
+    }
+}
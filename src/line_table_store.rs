@@ -0,0 +1,350 @@
+use crate::aliases;
+use crate::cache::{self, AtomicFile};
+use crate::dwarf::{
+    build_id_hex, LineInfoFilters, ModuleLineTable, ObjectFile, PathInterner, TableRow,
+    UnitFunction, UnitTable,
+};
+use crate::read_only;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::UNIX_EPOCH;
+use xxhash_rust::xxh3::xxh3_64;
+
+mod constants {
+    /// The start of every line table file
+    pub const MAGIC: &[u8; 4] = b"D2LT";
+
+    /// Bumped whenever the layout of the line table files changes. It is part of their names as
+    /// well, so the files of other versions are never even opened.
+    pub const FORMAT_VERSION: u32 = 1;
+
+    pub const EXTENSION: &str = "lines";
+}
+
+/// Returns where the line table of the module at `path`, whose debug information is in
+/// `object_file`, is stored in `directory`.
+///
+/// The entries of the directory are the modules, named after their build-id (or a hash of their
+/// path and modification time if they have none), which hold a line table per set of source
+/// filters. Line tables that went through a source path resolver are not stored, since resolvers
+/// cannot be told apart.
+fn line_table_path(
+    directory: &Path,
+    path: &str,
+    object_file: &ObjectFile,
+    filters: &LineInfoFilters,
+) -> Option<PathBuf> {
+    if filters.source_path_resolver.is_some() {
+        return None;
+    }
+
+    let entry = match build_id_hex(object_file.with_object(|obj| obj)) {
+        Some(build_id) => build_id,
+        None => {
+            let modified = std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()?;
+            let modified = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+            format!("{:016x}", xxh3_64(format!("{path}\0{modified}").as_bytes()))
+        }
+    };
+
+    let mut variant = constants::FORMAT_VERSION.to_string();
+    for (kind, filters) in [("+", filters.src_filters), ("-", filters.src_skip_filters)] {
+        for filter in filters {
+            variant.push('\0');
+            variant.push_str(kind);
+            variant.push_str(filter.matcher.as_str());
+        }
+    }
+
+    Some(directory.join(entry).join(format!(
+        "{:016x}.{}",
+        xxh3_64(variant.as_bytes()),
+        constants::EXTENSION
+    )))
+}
+
+fn write_len(writer: &mut impl Write, len: usize) -> std::io::Result<()> {
+    writer.write_u32::<LittleEndian>(len as u32)
+}
+
+fn write_str(writer: &mut impl Write, s: &str) -> std::io::Result<()> {
+    write_len(writer, s.len())?;
+    writer.write_all(s.as_bytes())
+}
+
+fn read_len(reader: &mut impl Read) -> std::io::Result<usize> {
+    Ok(reader.read_u32::<LittleEndian>()? as usize)
+}
+
+fn read_str(reader: &mut impl Read) -> std::io::Result<String> {
+    let mut bytes = vec![0; read_len(reader)?];
+    reader.read_exact(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn invalid_data(what: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, what)
+}
+
+fn write_line_table(writer: &mut impl Write, line_table: &ModuleLineTable) -> std::io::Result<()> {
+    writer.write_all(constants::MAGIC)?;
+    writer.write_u32::<LittleEndian>(constants::FORMAT_VERSION)?;
+    writer.write_u64::<LittleEndian>(line_table.load_base)?;
+
+    write_len(writer, line_table.files.len())?;
+    for file in &line_table.files {
+        write_str(writer, file)?;
+    }
+
+    write_len(writer, line_table.units.len())?;
+    for unit in &line_table.units {
+        write_len(writer, unit.functions.len())?;
+        for function in &unit.functions {
+            write_str(writer, &function.name)?;
+            writer.write_u8(function.file.is_some() as u8)?;
+            if let Some(file) = &function.file {
+                write_str(writer, file)?;
+            }
+            writer.write_u64::<LittleEndian>(function.decl_line)?;
+            writer.write_u64::<LittleEndian>(function.low_pc)?;
+        }
+
+        write_len(writer, unit.function_ranges.len())?;
+        for (begin, end, index) in &unit.function_ranges {
+            writer.write_u64::<LittleEndian>(*begin)?;
+            writer.write_u64::<LittleEndian>(*end)?;
+            write_len(writer, *index)?;
+        }
+
+        write_len(writer, unit.rows.len())?;
+        for row in &unit.rows {
+            writer.write_u64::<LittleEndian>(row.address)?;
+            writer.write_u64::<LittleEndian>(row.end_address)?;
+            writer.write_u64::<LittleEndian>(row.line)?;
+            writer.write_u64::<LittleEndian>(row.discriminator)?;
+            write_len(writer, row.file_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a line table written by [`write_line_table`]. The function symbols are not stored, they
+/// are taken from `object_file` again.
+fn read_line_table(
+    reader: &mut impl Read,
+    object_file: &ObjectFile,
+) -> std::io::Result<ModuleLineTable> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != constants::MAGIC || reader.read_u32::<LittleEndian>()? != constants::FORMAT_VERSION
+    {
+        return Err(invalid_data("Not a line table file of this version"));
+    }
+
+    let load_base = reader.read_u64::<LittleEndian>()?;
+    let mut interner = PathInterner::default();
+
+    let files = (0..read_len(reader)?)
+        .map(|_| Ok(interner.intern(read_str(reader)?)))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let mut units = Vec::new();
+    for _ in 0..read_len(reader)? {
+        let mut functions = Vec::new();
+        for _ in 0..read_len(reader)? {
+            let name = read_str(reader)?;
+            let file = match reader.read_u8()? {
+                0 => None,
+                _ => Some(interner.intern(read_str(reader)?)),
+            };
+
+            functions.push(UnitFunction {
+                name,
+                file,
+                decl_line: reader.read_u64::<LittleEndian>()?,
+                low_pc: reader.read_u64::<LittleEndian>()?,
+            });
+        }
+
+        let mut function_ranges = Vec::new();
+        for _ in 0..read_len(reader)? {
+            let range = (
+                reader.read_u64::<LittleEndian>()?,
+                reader.read_u64::<LittleEndian>()?,
+                read_len(reader)?,
+            );
+
+            if range.2 >= functions.len() {
+                return Err(invalid_data("Function range of an unknown function"));
+            }
+            function_ranges.push(range);
+        }
+
+        let mut rows = Vec::new();
+        for _ in 0..read_len(reader)? {
+            let row = TableRow {
+                address: reader.read_u64::<LittleEndian>()?,
+                end_address: reader.read_u64::<LittleEndian>()?,
+                line: reader.read_u64::<LittleEndian>()?,
+                discriminator: reader.read_u64::<LittleEndian>()?,
+                file_id: read_len(reader)?,
+            };
+
+            if row.file_id >= files.len() || row.end_address < row.address {
+                return Err(invalid_data("Invalid line table row"));
+            }
+            rows.push(row);
+        }
+
+        units.push(UnitTable {
+            functions,
+            function_ranges,
+            rows,
+        });
+    }
+
+    Ok(ModuleLineTable {
+        load_base,
+        files,
+        units,
+        symbols: aliases::function_symbols(object_file),
+        blocks: OnceLock::new(),
+    })
+}
+
+/// Loads the line table of the module at `path` from `directory`, if it has been stored there.
+/// Files that cannot be read are treated like missing ones.
+pub fn load(
+    directory: &Path,
+    path: &str,
+    object_file: &ObjectFile,
+    filters: &LineInfoFilters,
+) -> Option<ModuleLineTable> {
+    let table_path = line_table_path(directory, path, object_file, filters)?;
+    let file = std::fs::File::open(&table_path).ok()?;
+
+    match read_line_table(&mut ZlibDecoder::new(BufReader::new(file)), object_file) {
+        Ok(line_table) => {
+            cache::touch(&table_path);
+            log::debug!(
+                "Loaded the line table of {path} from '{}'",
+                table_path.display()
+            );
+            Some(line_table)
+        }
+        Err(e) => {
+            log::warn!(
+                "Ignoring the line table of {path} at '{}': {e}",
+                table_path.display()
+            );
+            None
+        }
+    }
+}
+
+/// Stores the line table of the module at `path` in `directory`. Failing to store it is only
+/// logged, since the line table is only an optimization.
+pub fn store(
+    directory: &Path,
+    path: &str,
+    object_file: &ObjectFile,
+    filters: &LineInfoFilters,
+    line_table: &ModuleLineTable,
+) {
+    let Some(table_path) = line_table_path(directory, path, object_file, filters) else {
+        return;
+    };
+
+    let res = (|| -> std::io::Result<()> {
+        if let Some(parent) = table_path.parent() {
+            read_only::create_dir_all(parent)?;
+        }
+
+        let file = AtomicFile::new(table_path.clone());
+        let mut writer = ZlibEncoder::new(BufWriter::new(file.create()?), Compression::fast());
+        write_line_table(&mut writer, line_table)?;
+        writer.finish()?.flush()?;
+        file.persist()?;
+
+        Ok(())
+    })();
+
+    match res {
+        Ok(()) => log::debug!(
+            "Stored the line table of {path} in '{}'",
+            table_path.display()
+        ),
+        Err(e) => log::warn!(
+            "Could not store the line table of {path} in '{}': {e}",
+            table_path.display()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::test_binary;
+
+    #[test]
+    fn line_tables_round_trip() {
+        let object_file = ObjectFile::from_path(&test_binary()).unwrap();
+        let line_table = ModuleLineTable {
+            load_base: 0x400000,
+            files: vec!["/src/a.c".into(), "/src/b.h".into()],
+            units: vec![UnitTable {
+                functions: vec![UnitFunction {
+                    name: "main".to_string(),
+                    file: Some("/src/a.c".into()),
+                    decl_line: 3,
+                    low_pc: 0x401000,
+                }],
+                function_ranges: vec![(0x401000, 0x401040, 0)],
+                rows: vec![TableRow {
+                    address: 0x401000,
+                    end_address: 0x401008,
+                    line: 4,
+                    discriminator: 1,
+                    file_id: 1,
+                }],
+            }],
+            symbols: aliases::function_symbols(&object_file),
+            blocks: OnceLock::new(),
+        };
+
+        let mut bytes = Vec::new();
+        write_line_table(&mut bytes, &line_table).unwrap();
+        let read = read_line_table(&mut bytes.as_slice(), &object_file).unwrap();
+
+        assert_eq!(read.load_base, line_table.load_base);
+        assert_eq!(read.files, line_table.files);
+        let (unit, expected) = (&read.units[0], &line_table.units[0]);
+        assert_eq!(unit.functions[0].file, expected.functions[0].file);
+        // The declaration file of the function shares the allocation of the source file
+        assert!(std::sync::Arc::ptr_eq(
+            unit.functions[0].file.as_ref().unwrap(),
+            &read.files[0]
+        ));
+        assert_eq!(unit.function_ranges, expected.function_ranges);
+        assert_eq!(
+            (
+                unit.rows[0].end_address,
+                unit.rows[0].line,
+                unit.rows[0].file_id
+            ),
+            (0x401008, 4, 1)
+        );
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(read_line_table(&mut bytes.as_slice(), &object_file).is_err());
+    }
+}
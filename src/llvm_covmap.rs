@@ -0,0 +1,566 @@
+use crate::llvm_profile::{name_hash, read_uleb128};
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+use object::{Object, ObjectSection};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+mod constants {
+    /// The names of the sections of the coverage mapping headers, in ELF, Mach-O and COFF files
+    pub const COVMAP_SECTIONS: &[&str] = &["__llvm_covmap", ".lcovmap$M"];
+    /// The names of the sections of the coverage mapping function records
+    pub const COVFUN_SECTIONS: &[&str] = &["__llvm_covfun", ".lcovfun$M"];
+
+    /// The first version whose function records are in a section of their own (LLVM 11)
+    pub const MIN_VERSION: u32 = 3;
+    /// The first version whose first file name is the compilation directory (LLVM 13)
+    pub const COMPILATION_DIR_VERSION: u32 = 5;
+
+    /// The size of the header of a function record: its name hash, the size of its mapping data,
+    /// its function hash and the hash of the file names of its translation unit
+    pub const FUNCTION_RECORD_HEADER_SIZE: usize = 28;
+
+    /// The tag of counters that are the difference of two counters (the sums are tagged with 3)
+    pub const COUNTER_SUBTRACT: u64 = 2;
+    /// Set in the (zero) counter of expansion regions
+    pub const EXPANSION_REGION_BIT: u64 = 1 << 2;
+
+    pub const SKIPPED_REGION: u64 = 2;
+    pub const BRANCH_REGION: u64 = 4;
+    pub const MCDC_DECISION_REGION: u64 = 5;
+    pub const MCDC_BRANCH_REGION: u64 = 6;
+
+    /// Set in the end column of gap regions, which do not start the lines they are on
+    pub const GAP_REGION_BIT: u64 = 1 << 31;
+}
+
+/// A counter of a region: a counter of the profile, or the sum or difference of two counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Counter {
+    Zero,
+    Profile(usize),
+    Add(usize),
+    Subtract(usize),
+}
+
+impl Counter {
+    fn decode(value: u64) -> Self {
+        let index = (value >> 2) as usize;
+
+        match value & 0b11 {
+            0 => Self::Zero,
+            1 => Self::Profile(index),
+            constants::COUNTER_SUBTRACT => Self::Subtract(index),
+            _ => Self::Add(index),
+        }
+    }
+
+    /// The count of the counter given the `counters` of the profile of its function. Expressions
+    /// that refer to themselves count as zero.
+    fn evaluate(self, counters: &[u64], expressions: &[(Counter, Counter)], depth: usize) -> u64 {
+        let operands = |index: usize| {
+            expressions
+                .get(index)
+                .filter(|_| depth <= expressions.len())
+                .map(|(lhs, rhs)| {
+                    (
+                        lhs.evaluate(counters, expressions, depth + 1),
+                        rhs.evaluate(counters, expressions, depth + 1),
+                    )
+                })
+        };
+
+        match self {
+            Self::Zero => 0,
+            Self::Profile(index) => counters.get(index).copied().unwrap_or(0),
+            Self::Add(index) => operands(index).map_or(0, |(lhs, rhs)| lhs.saturating_add(rhs)),
+            Self::Subtract(index) => {
+                operands(index).map_or(0, |(lhs, rhs)| lhs.saturating_sub(rhs))
+            }
+        }
+    }
+}
+
+/// A region of source code whose execution count is given by a counter
+#[derive(Debug, PartialEq, Eq)]
+struct CodeRegion {
+    file: usize,
+    line_start: u64,
+    line_end: u64,
+    counter: Counter,
+    /// Gap regions cover the lines between statements, which they do not start
+    gap: bool,
+}
+
+/// The coverage mapping of a function: the regions of its source code and the expressions of
+/// their counters
+#[derive(Debug, Default)]
+struct FunctionMapping {
+    name_hash: u64,
+    function_hash: u64,
+    expressions: Vec<(Counter, Counter)>,
+    regions: Vec<CodeRegion>,
+}
+
+/// The coverage mapping of a binary built with `-fcoverage-mapping` (or `-C instrument-coverage`)
+#[derive(Debug, Default)]
+pub(crate) struct CoverageMapping {
+    /// The paths of the source files of the regions
+    files: Vec<PathBuf>,
+    functions: Vec<FunctionMapping>,
+}
+
+/// Reads a file name of the file names of a translation unit
+fn read_file_name(cursor: &mut Cursor<&[u8]>) -> anyhow::Result<String> {
+    let len = read_uleb128(cursor)? as usize;
+    let mut name = vec![0; len.min(cursor.get_ref().len())];
+    cursor.read_exact(&mut name)?;
+
+    Ok(String::from_utf8_lossy(&name).to_string())
+}
+
+/// Decodes the (possibly zlib compressed) file names of a translation unit. Relative names are
+/// relative to the compilation directory, which comes first since version 5.
+fn parse_file_names(data: &[u8], version: u32) -> anyhow::Result<Vec<PathBuf>> {
+    let mut cursor = Cursor::new(data);
+    let num_names = read_uleb128(&mut cursor)? as usize;
+    let uncompressed_size = read_uleb128(&mut cursor)?;
+    let compressed_size = read_uleb128(&mut cursor)? as usize;
+
+    let decompressed;
+    let mut cursor = if compressed_size == 0 {
+        cursor
+    } else {
+        let start = cursor.position() as usize;
+        let Some(compressed) = start
+            .checked_add(compressed_size)
+            .and_then(|end| data.get(start..end))
+        else {
+            anyhow::bail!("Truncated file names");
+        };
+
+        let mut names = Vec::new();
+        ZlibDecoder::new(compressed)
+            .take(uncompressed_size)
+            .read_to_end(&mut names)?;
+        decompressed = names;
+        Cursor::new(decompressed.as_slice())
+    };
+
+    let mut names = Vec::new();
+    let mut compilation_dir = None;
+
+    for index in 0..num_names {
+        let name = PathBuf::from(read_file_name(&mut cursor)?);
+
+        if version < constants::COMPILATION_DIR_VERSION {
+            names.push(name);
+        } else if index == 0 {
+            compilation_dir = Some(name.clone());
+            names.push(name);
+        } else {
+            names.push(match &compilation_dir {
+                Some(compilation_dir) if name.is_relative() => compilation_dir.join(name),
+                _ => name,
+            });
+        }
+    }
+
+    Ok(names)
+}
+
+/// Decodes the mapping data of the function record of `mapping`, whose file ids index `files`
+fn parse_function_mapping(
+    data: &[u8],
+    files: &[usize],
+    mapping: &mut FunctionMapping,
+) -> anyhow::Result<()> {
+    let mut cursor = Cursor::new(data);
+
+    let num_file_ids = read_uleb128(&mut cursor)? as usize;
+    let file_ids = (0..num_file_ids)
+        .map(|_| {
+            let index = read_uleb128(&mut cursor)? as usize;
+            files
+                .get(index)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("Invalid file name index {index}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let num_expressions = read_uleb128(&mut cursor)? as usize;
+    for _ in 0..num_expressions {
+        let lhs = Counter::decode(read_uleb128(&mut cursor)?);
+        let rhs = Counter::decode(read_uleb128(&mut cursor)?);
+        mapping.expressions.push((lhs, rhs));
+    }
+
+    for file in file_ids {
+        let num_regions = read_uleb128(&mut cursor)?;
+        let mut line_start = 0u64;
+
+        for _ in 0..num_regions {
+            let encoded = read_uleb128(&mut cursor)?;
+            let mut counter = Counter::decode(encoded);
+            let mut code = true;
+
+            if counter == Counter::Zero {
+                if encoded & constants::EXPANSION_REGION_BIT != 0 {
+                    code = false;
+                } else {
+                    match encoded >> 3 {
+                        0 => {}
+                        constants::SKIPPED_REGION => code = false,
+                        constants::BRANCH_REGION => {
+                            read_uleb128(&mut cursor)?;
+                            read_uleb128(&mut cursor)?;
+                            code = false;
+                        }
+                        constants::MCDC_DECISION_REGION => {
+                            read_uleb128(&mut cursor)?;
+                            read_uleb128(&mut cursor)?;
+                            code = false;
+                        }
+                        constants::MCDC_BRANCH_REGION => {
+                            for _ in 0..5 {
+                                read_uleb128(&mut cursor)?;
+                            }
+                            code = false;
+                        }
+                        kind => anyhow::bail!("Unknown region kind {kind}"),
+                    }
+                }
+
+                counter = Counter::Zero;
+            }
+
+            let line_start_delta = read_uleb128(&mut cursor)?;
+            // Column start
+            read_uleb128(&mut cursor)?;
+            let num_lines = read_uleb128(&mut cursor)?;
+            let column_end = read_uleb128(&mut cursor)?;
+
+            line_start = line_start
+                .checked_add(line_start_delta)
+                .ok_or_else(|| anyhow::anyhow!("Invalid region line"))?;
+
+            if code {
+                mapping.regions.push(CodeRegion {
+                    file,
+                    line_start,
+                    line_end: line_start.saturating_add(num_lines),
+                    counter,
+                    gap: column_end & constants::GAP_REGION_BIT != 0,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The offset `offset` is rounded up to, since the records of both sections are 8-byte aligned
+fn align(offset: usize) -> usize {
+    offset.div_ceil(8).saturating_mul(8)
+}
+
+impl CoverageMapping {
+    /// Reads the coverage mapping of `object`, if it has one
+    pub(crate) fn from_object(object: &object::File) -> anyhow::Result<Option<Self>> {
+        let section = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| object.section_by_name(name))
+                .map(|section| section.uncompressed_data())
+                .transpose()
+        };
+
+        match (
+            section(constants::COVMAP_SECTIONS)?,
+            section(constants::COVFUN_SECTIONS)?,
+        ) {
+            (Some(covmap), Some(covfun)) => Self::parse(&covmap, &covfun).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parses the headers of the translation units in `covmap` and the function records in
+    /// `covfun`
+    fn parse(covmap: &[u8], covfun: &[u8]) -> anyhow::Result<Self> {
+        let mut mapping = Self::default();
+        // The hash of the file names of a translation unit -> the indices of its files
+        let mut units = HashMap::new();
+        let mut offset = 0;
+
+        while offset + 16 <= covmap.len() {
+            let mut cursor = Cursor::new(&covmap[offset..]);
+            // The number of function records and the size of the coverage data, which are both
+            // zero since the records moved to a section of their own
+            cursor.read_u32::<LittleEndian>()?;
+            let names_size = cursor.read_u32::<LittleEndian>()? as usize;
+            let coverage_size = cursor.read_u32::<LittleEndian>()? as usize;
+            let version = cursor.read_u32::<LittleEndian>()?;
+
+            if version < constants::MIN_VERSION {
+                anyhow::bail!(
+                    "Unsupported coverage mapping version {version} (LLVM 11 or later is required)"
+                );
+            }
+
+            let names_start = offset + 16;
+            let Some(names) = names_start
+                .checked_add(names_size)
+                .and_then(|end| covmap.get(names_start..end))
+            else {
+                anyhow::bail!("Truncated coverage mapping header");
+            };
+
+            let start = mapping.files.len();
+            mapping.files.extend(parse_file_names(names, version)?);
+            units.insert(
+                name_hash(names),
+                (start..mapping.files.len()).collect::<Vec<_>>(),
+            );
+
+            offset = align(
+                names_start
+                    .saturating_add(names_size)
+                    .saturating_add(coverage_size),
+            );
+        }
+
+        let mut offset = 0;
+
+        while offset + constants::FUNCTION_RECORD_HEADER_SIZE <= covfun.len() {
+            let mut cursor = Cursor::new(&covfun[offset..]);
+            let name_hash = cursor.read_u64::<LittleEndian>()?;
+            let data_size = cursor.read_u32::<LittleEndian>()? as usize;
+            let function_hash = cursor.read_u64::<LittleEndian>()?;
+            let names_hash = cursor.read_u64::<LittleEndian>()?;
+
+            let data_start = offset + constants::FUNCTION_RECORD_HEADER_SIZE;
+            let Some(data) = data_start
+                .checked_add(data_size)
+                .and_then(|end| covfun.get(data_start..end))
+            else {
+                anyhow::bail!("Truncated coverage mapping function record");
+            };
+            offset = align(data_start + data_size);
+
+            let Some(files) = units.get(&names_hash) else {
+                log::debug!("Function record {name_hash:#x} references unknown file names");
+                continue;
+            };
+
+            let mut function = FunctionMapping {
+                name_hash,
+                function_hash,
+                ..Default::default()
+            };
+            parse_function_mapping(data, files, &mut function)?;
+            mapping.functions.push(function);
+        }
+
+        Ok(mapping)
+    }
+
+    /// The PGO name hashes of the functions of the mapping, along with their function hashes
+    pub(crate) fn functions(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.functions
+            .iter()
+            .map(|function| (function.name_hash, function.function_hash))
+    }
+
+    /// Returns the executed lines of every source file, given the counters of the functions of
+    /// the profile by their name and function hashes.
+    ///
+    /// Like llvm-cov, a line counts as executed if a region that starts on it has been executed,
+    /// or, if no region starts on it, if the innermost region that spans it has been.
+    pub(crate) fn executed_lines<'c>(
+        &self,
+        counters: impl Fn(u64, u64) -> Option<&'c [u64]>,
+    ) -> HashMap<PathBuf, HashSet<u64>> {
+        // File -> line -> (the count of the innermost region spanning it, the maximum count of
+        // the regions that start on it)
+        let mut lines: HashMap<usize, BTreeMap<u64, (u64, Option<u64>)>> = HashMap::new();
+
+        for function in &self.functions {
+            let counters = counters(function.name_hash, function.function_hash).unwrap_or_default();
+
+            let mut regions = function
+                .regions
+                .iter()
+                .map(|region| {
+                    let count = region.counter.evaluate(counters, &function.expressions, 0);
+                    (region, count)
+                })
+                .collect::<Vec<_>>();
+            // Outer regions come first, so that the regions nested in them override their counts
+            regions.sort_by_key(|(region, _)| {
+                (
+                    region.file,
+                    region.line_start,
+                    std::cmp::Reverse(region.line_end),
+                )
+            });
+
+            let mut function_lines: HashMap<usize, BTreeMap<u64, (u64, Option<u64>)>> =
+                HashMap::new();
+
+            for (region, count) in regions {
+                let file_lines = function_lines.entry(region.file).or_default();
+
+                for line in region.line_start..=region.line_end {
+                    file_lines.entry(line).or_default().0 = count;
+                }
+
+                if !region.gap {
+                    let start = &mut file_lines.entry(region.line_start).or_default().1;
+                    *start = Some(start.unwrap_or(0).max(count));
+                }
+            }
+
+            // The records of the same function in several translation units (e.g. inline
+            // functions of headers) are merged
+            for (file, function_lines) in function_lines {
+                let file_lines = lines.entry(file).or_default();
+
+                for (line, (wrapped, start)) in function_lines {
+                    let entry = file_lines.entry(line).or_default();
+                    entry.0 = entry.0.max(wrapped);
+                    entry.1 = entry.1.max(start);
+                }
+            }
+        }
+
+        let mut res: HashMap<PathBuf, HashSet<u64>> = HashMap::new();
+
+        for (file, file_lines) in lines {
+            let executed = file_lines
+                .into_iter()
+                .filter(|(_, (wrapped, start))| start.unwrap_or(*wrapped) > 0)
+                .map(|(line, _)| line);
+
+            res.entry(normalize(&self.files[file]))
+                .or_default()
+                .extend(executed);
+        }
+
+        res
+    }
+}
+
+/// Drops the `.` components of `path`, which the file names of the coverage mapping and of the
+/// line programs do not always agree on
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes `value` as ULEB128
+    fn uleb(value: u64) -> Vec<u8> {
+        let mut value = value;
+        let mut res = Vec::new();
+
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                res.push(byte);
+                return res;
+            }
+
+            res.push(byte | 0x80);
+        }
+    }
+
+    #[test]
+    fn maps_counters_to_executed_lines() {
+        // The file names of the unit: the compilation directory and a relative source file
+        let mut names = Vec::new();
+        for name in ["/src", "./lib.c"] {
+            names.extend(uleb(name.len() as u64));
+            names.extend(name.as_bytes());
+        }
+        let names = [uleb(2), uleb(names.len() as u64), uleb(0), names].concat();
+
+        let mut covmap = Vec::new();
+        for field in [0, names.len() as u32, 0, 6] {
+            covmap.extend(field.to_le_bytes());
+        }
+        covmap.extend(&names);
+        covmap.resize(align(covmap.len()), 0);
+
+        // The body spans lines 1 to 10 and counts with counter 0, and the branch on lines 3 to 5
+        // counts with counter 1. Lines 6 to 10 count with counter 0 - counter 1, expression 0.
+        let data = [
+            uleb(1),
+            uleb(1),
+            uleb(1),
+            uleb(0b01),
+            uleb(0b101),
+            uleb(3),
+            uleb(0b01),
+            uleb(1),
+            uleb(1),
+            uleb(9),
+            uleb(2),
+            uleb(0b101),
+            uleb(2),
+            uleb(1),
+            uleb(2),
+            uleb(2),
+            uleb(0b10),
+            uleb(3),
+            uleb(1),
+            uleb(4),
+            uleb(2),
+        ]
+        .concat();
+
+        let mut covfun = Vec::new();
+        covfun.extend(name_hash(b"f").to_le_bytes());
+        covfun.extend((data.len() as u32).to_le_bytes());
+        covfun.extend(7u64.to_le_bytes());
+        covfun.extend(name_hash(&names).to_le_bytes());
+        covfun.extend(&data);
+
+        let mapping = CoverageMapping::parse(&covmap, &covfun).unwrap();
+        assert_eq!(
+            mapping.functions().collect::<Vec<_>>(),
+            [(name_hash(b"f"), 7)]
+        );
+
+        let executed = |counters: Vec<u64>| {
+            let mut lines = mapping
+                .executed_lines(|name, hash| {
+                    (name == name_hash(b"f") && hash == 7).then_some(counters.as_slice())
+                })
+                .remove(Path::new("/src/lib.c"))
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<Vec<_>>();
+            lines.sort_unstable();
+            lines
+        };
+
+        assert_eq!(executed(vec![1, 0]), [1, 2, 6, 7, 8, 9, 10]);
+        assert_eq!(executed(vec![1, 1]), [1, 2, 3, 4, 5]);
+        assert_eq!(executed(vec![0, 0]), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn rejects_truncated_mappings() {
+        let mut covmap = Vec::new();
+        for field in [0u32, 64, 0, 6] {
+            covmap.extend(field.to_le_bytes());
+        }
+
+        assert!(CoverageMapping::parse(&covmap, &[]).is_err());
+    }
+}
@@ -0,0 +1,785 @@
+use crate::drcov::{Drcov, DrcovFilters, Module, Modules};
+use crate::dwarf::{build_id_hex, read_line_table, LineInfoFilters, ObjectFile};
+use crate::llvm_covmap::{normalize, CoverageMapping};
+use crate::state::BinaryResolver;
+use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+use object::{Object, ObjectSymbol, SymbolKind};
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+mod constants {
+    /// `\xfflprofr\x81`, the magic of 64-bit raw profiles
+    pub const RAW_MAGIC_64: u64 = 0xFF6C_7072_6F66_7281;
+    /// `\xfflprofi\x81` read backwards, the magic of indexed profiles
+    pub const INDEXED_MAGIC: u64 = 0x8169_666F_7270_6CFF;
+
+    pub const RAW_EXTENSION: &str = "profraw";
+    pub const INDEXED_EXTENSION: &str = "profdata";
+
+    /// The flavor given to the coverage parsed from LLVM profiles
+    pub const LLVM_PROFILE_FLAVOR: &str = "llvm-profile";
+
+    /// The upper half of the version field holds variant flags
+    pub const VARIANT_MASKS_ALL: u64 = 0xFFFF_FFFF_0000_0000;
+    /// Set when every counter is a single byte that is zero once the counter has been hit
+    pub const VARIANT_MASK_BYTE_COVERAGE: u64 = 1 << 60;
+
+    /// Separates the function names of the names section
+    pub const NAME_SEPARATOR: u8 = 0x01;
+
+    pub const MIN_RAW_VERSION: u64 = 8;
+    pub const MAX_RAW_VERSION: u64 = 10;
+    pub const MIN_INDEXED_VERSION: u64 = 2;
+    pub const MAX_INDEXED_VERSION: u64 = 12;
+    /// The first indexed version that stores MC/DC bitmap bytes after the counters
+    pub const INDEXED_BITMAP_VERSION: u64 = 11;
+}
+
+/// Whether the file at `path` looks like an LLVM instrumentation profile (`.profraw`/`.profdata`)
+pub fn is_llvm_profile_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        extension == constants::RAW_EXTENSION || extension == constants::INDEXED_EXTENSION
+    })
+}
+
+/// The counters of a function of an LLVM instrumentation profile
+#[derive(Debug, Default)]
+struct FunctionCounters {
+    /// The PGO name of the function, unless the profile does not hold it
+    name: Option<String>,
+    counters: Vec<u64>,
+}
+
+/// The counters of an LLVM instrumentation profile
+#[derive(Debug, Default)]
+struct Profile {
+    /// Build-ids of the binaries the profile has been collected from (raw profiles only)
+    binary_ids: Vec<String>,
+    /// (PGO name hash, function hash) -> the counters of the function, summed over its records
+    functions: HashMap<(u64, u64), FunctionCounters>,
+}
+
+impl Profile {
+    fn add(
+        &mut self,
+        name_hash: u64,
+        function_hash: u64,
+        name: Option<String>,
+        counters: impl IntoIterator<Item = u64>,
+    ) {
+        let function = self
+            .functions
+            .entry((name_hash, function_hash))
+            .or_default();
+        function.name = function.name.take().or(name);
+
+        for (index, count) in counters.into_iter().enumerate() {
+            match function.counters.get_mut(index) {
+                Some(counter) => *counter = counter.saturating_add(count),
+                None => function.counters.push(count),
+            }
+        }
+    }
+
+    /// The counters of the function with the given hashes
+    fn counters(&self, name_hash: u64, function_hash: u64) -> Option<&[u64]> {
+        self.functions
+            .get(&(name_hash, function_hash))
+            .map(|function| function.counters.as_slice())
+    }
+
+    /// The symbol names of the functions with a non-zero counter
+    fn executed_functions(&self) -> HashSet<&str> {
+        self.functions
+            .values()
+            .filter(|function| function.counters.iter().any(|count| *count != 0))
+            .filter_map(|function| function.name.as_deref())
+            .map(symbol_name)
+            .collect()
+    }
+}
+
+/// Returns the hash LLVM identifies PGO names with: the lower 64 bits of their MD5
+pub(crate) fn name_hash(name: &[u8]) -> u64 {
+    let digest = md5::compute(name).0;
+    // Safety: We can unwrap here since the digest is always 16 bytes long
+    u64::from_le_bytes(digest[..8].try_into().unwrap())
+}
+
+/// The `len` bytes of `contents` at `start`, or `None` if they are out of bounds
+fn slice_at(contents: &[u8], start: u64, len: u64) -> Option<&[u8]> {
+    let start = usize::try_from(start).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+    contents.get(start..end)
+}
+
+pub(crate) fn read_uleb128(cursor: &mut Cursor<&[u8]>) -> anyhow::Result<u64> {
+    let mut res = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let byte = cursor.read_u8()?;
+        if shift >= 64 {
+            anyhow::bail!("ULEB128 value is too large");
+        }
+
+        res |= u64::from(byte & 0x7f) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(res);
+        }
+    }
+}
+
+/// Decodes the (possibly zlib compressed) names section of a raw profile
+fn parse_names(section: &[u8]) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut cursor = Cursor::new(section);
+    let mut res = Vec::new();
+
+    while (cursor.position() as usize) < section.len() {
+        // The section is padded with zeros
+        if section[cursor.position() as usize..]
+            .iter()
+            .all(|b| *b == 0)
+        {
+            break;
+        }
+
+        let uncompressed_size = read_uleb128(&mut cursor)? as usize;
+        let compressed_size = read_uleb128(&mut cursor)? as usize;
+
+        let start = cursor.position() as usize;
+        let size = if compressed_size == 0 {
+            uncompressed_size
+        } else {
+            compressed_size
+        };
+        let Some(data) = start
+            .checked_add(size)
+            .and_then(|end| section.get(start..end))
+        else {
+            anyhow::bail!("Truncated names section");
+        };
+        cursor.set_position((start + size) as u64);
+
+        let names = if compressed_size == 0 {
+            data.to_vec()
+        } else {
+            // The uncompressed size is untrusted, so it only bounds the decompression
+            let mut names = Vec::new();
+            ZlibDecoder::new(data)
+                .take(uncompressed_size as u64)
+                .read_to_end(&mut names)?;
+            names
+        };
+
+        res.extend(
+            names
+                .split(|b| *b == constants::NAME_SEPARATOR)
+                .filter(|name| !name.is_empty())
+                .map(<[u8]>::to_vec),
+        );
+    }
+
+    Ok(res)
+}
+
+fn parse_raw_profile(contents: &[u8]) -> anyhow::Result<Profile> {
+    let mut cursor = Cursor::new(contents);
+
+    let magic = cursor.read_u64::<LittleEndian>()?;
+    if magic != constants::RAW_MAGIC_64 {
+        anyhow::bail!("Only 64-bit raw profiles are supported (magic {magic:#x})");
+    }
+
+    let version_field = cursor.read_u64::<LittleEndian>()?;
+    let version = version_field & !constants::VARIANT_MASKS_ALL;
+    let byte_coverage = version_field & constants::VARIANT_MASK_BYTE_COVERAGE != 0;
+
+    if !(constants::MIN_RAW_VERSION..=constants::MAX_RAW_VERSION).contains(&version) {
+        anyhow::bail!("Unsupported raw profile version {version}");
+    }
+
+    let mut read = || cursor.read_u64::<LittleEndian>();
+
+    let binary_ids_size = read()?;
+    let num_data = read()?;
+    let padding_before_counters = read()?;
+    let num_counters = read()?;
+    let padding_after_counters = read()?;
+    let (num_bitmap_bytes, padding_after_bitmap) = if version >= 9 {
+        (read()?, read()?)
+    } else {
+        (0, 0)
+    };
+    let names_size = read()?;
+    let mut counters_delta = read()? as i64;
+    if version >= 9 {
+        // BitmapDelta
+        read()?;
+    }
+    // NamesDelta
+    read()?;
+    if version >= 10 {
+        // NumVTables and VNamesSize
+        read()?;
+        read()?;
+    }
+    // ValueKindLast
+    read()?;
+
+    let mut profile = Profile::default();
+    let invalid_header = || anyhow::anyhow!("Invalid raw profile header");
+
+    let binary_ids_end = cursor
+        .position()
+        .checked_add(binary_ids_size)
+        .ok_or_else(invalid_header)?;
+    while cursor.position() < binary_ids_end {
+        let size = cursor.read_u64::<LittleEndian>()?;
+        if size > binary_ids_size {
+            anyhow::bail!("Invalid binary id size {size}");
+        }
+
+        let mut build_id = vec![0; size as usize];
+        cursor.read_exact(&mut build_id)?;
+        // Binary ids are padded to 8 bytes
+        cursor.seek(SeekFrom::Current(((8 - size % 8) % 8) as i64))?;
+
+        profile
+            .binary_ids
+            .push(build_id.iter().map(|b| format!("{b:02x}")).collect());
+    }
+    cursor.set_position(binary_ids_end);
+
+    let data_start = cursor.position();
+    let data_record_size: u64 = if version >= 9 { 64 } else { 48 };
+    let counter_size: u64 = if byte_coverage { 1 } else { 8 };
+    // The sizes of the header are untrusted, so the offsets of the sections must not overflow
+    let counters_start = num_data
+        .checked_mul(data_record_size)
+        .and_then(|size| data_start.checked_add(size))
+        .and_then(|end| end.checked_add(padding_before_counters))
+        .ok_or_else(invalid_header)?;
+    let names_start = num_counters
+        .checked_mul(counter_size)
+        .and_then(|size| counters_start.checked_add(size))
+        .and_then(|end| end.checked_add(padding_after_counters))
+        .and_then(|end| end.checked_add(num_bitmap_bytes))
+        .and_then(|end| end.checked_add(padding_after_bitmap))
+        .ok_or_else(invalid_header)?;
+
+    let Some(names_section) = slice_at(contents, names_start, names_size) else {
+        anyhow::bail!("Truncated raw profile");
+    };
+
+    let names = parse_names(names_section)?
+        .into_iter()
+        .map(|name| (name_hash(&name), String::from_utf8_lossy(&name).to_string()))
+        .collect::<HashMap<_, _>>();
+
+    for index in 0..num_data {
+        cursor.set_position(data_start + index * data_record_size);
+
+        let name_ref = cursor.read_u64::<LittleEndian>()?;
+        let function_hash = cursor.read_u64::<LittleEndian>()?;
+        let counter_ptr = cursor.read_u64::<LittleEndian>()? as i64;
+        let skip = if version >= 9 { 24 } else { 16 };
+        cursor.seek(SeekFrom::Current(skip))?;
+        let record_num_counters = cursor.read_u32::<LittleEndian>()? as u64;
+
+        // Counter pointers are relative to the data record they belong to, so they wrap around
+        // like the pointers they have been computed from
+        let counter_offset = counter_ptr.wrapping_sub(counters_delta) as u64;
+        counters_delta = counters_delta.wrapping_sub(data_record_size as i64);
+
+        let Some(counters) = counters_start
+            .checked_add(counter_offset)
+            .and_then(|start| slice_at(contents, start, record_num_counters * counter_size))
+        else {
+            anyhow::bail!("Counters of data record {index} are out of bounds");
+        };
+
+        let name = names.get(&name_ref).cloned();
+        if name.is_none() {
+            log::debug!("Data record {index} references an unknown name ({name_ref:#x})");
+        }
+
+        if byte_coverage {
+            // Byte counters are cleared once they have been hit
+            let counts = counters.iter().map(|counter| u64::from(*counter == 0));
+            profile.add(name_ref, function_hash, name, counts);
+        } else {
+            let counts = counters
+                .chunks_exact(8)
+                .map(|counter| u64::from_le_bytes(counter.try_into().unwrap()));
+            profile.add(name_ref, function_hash, name, counts);
+        }
+    }
+
+    Ok(profile)
+}
+
+fn parse_indexed_record(
+    data: &[u8],
+    version: u64,
+    name: &[u8],
+    profile: &mut Profile,
+) -> anyhow::Result<()> {
+    let mut cursor = Cursor::new(data);
+    let name_ref = name_hash(name);
+    let name = String::from_utf8_lossy(name).to_string();
+
+    while (cursor.position() as usize) < data.len() {
+        let function_hash = cursor.read_u64::<LittleEndian>()?;
+
+        let num_counters = cursor.read_u64::<LittleEndian>()?;
+        let counters = (0..num_counters)
+            .map(|_| cursor.read_u64::<LittleEndian>())
+            .collect::<Result<Vec<_>, _>>()?;
+        profile.add(name_ref, function_hash, Some(name.clone()), counters);
+
+        if version >= constants::INDEXED_BITMAP_VERSION {
+            let num_bitmap_bytes = cursor.read_u64::<LittleEndian>()?;
+            let Some(size) = num_bitmap_bytes
+                .checked_mul(8)
+                .and_then(|size| i64::try_from(size).ok())
+            else {
+                anyhow::bail!("Invalid number of bitmap bytes {num_bitmap_bytes}");
+            };
+            cursor.seek(SeekFrom::Current(size))?;
+        }
+
+        if version > 2 {
+            // The size includes the field itself
+            let value_profile_size = cursor.read_u32::<LittleEndian>()?;
+            let Some(size) = value_profile_size.checked_sub(4) else {
+                anyhow::bail!("Invalid value profile size {value_profile_size}");
+            };
+            cursor.seek(SeekFrom::Current(i64::from(size)))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_indexed_profile(contents: &[u8]) -> anyhow::Result<Profile> {
+    let mut cursor = Cursor::new(contents);
+
+    let magic = cursor.read_u64::<LittleEndian>()?;
+    if magic != constants::INDEXED_MAGIC {
+        anyhow::bail!("Invalid indexed profile magic {magic:#x}");
+    }
+
+    let version = cursor.read_u64::<LittleEndian>()? & !constants::VARIANT_MASKS_ALL;
+    if !(constants::MIN_INDEXED_VERSION..=constants::MAX_INDEXED_VERSION).contains(&version) {
+        anyhow::bail!("Unsupported indexed profile version {version}");
+    }
+
+    // Unused and HashType
+    cursor.read_u64::<LittleEndian>()?;
+    cursor.read_u64::<LittleEndian>()?;
+    let hash_offset = cursor.read_u64::<LittleEndian>()?;
+
+    // The records are stored in an on-disk chained hash table, which is walked bucket by bucket
+    cursor.set_position(hash_offset);
+    let num_buckets = cursor.read_u64::<LittleEndian>()?;
+    // NumEntries
+    cursor.read_u64::<LittleEndian>()?;
+
+    // Every offset takes 8 bytes, so a count past the end of the file is not allocated for
+    if num_buckets > contents.len() as u64 / 8 {
+        anyhow::bail!("Invalid number of buckets {num_buckets}");
+    }
+
+    let bucket_offsets = (0..num_buckets)
+        .map(|_| cursor.read_u64::<LittleEndian>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut profile = Profile::default();
+
+    for bucket_offset in bucket_offsets.into_iter().filter(|offset| *offset != 0) {
+        cursor.set_position(bucket_offset);
+        let num_entries = cursor.read_u16::<LittleEndian>()?;
+
+        for _ in 0..num_entries {
+            // Hash of the key
+            cursor.read_u64::<LittleEndian>()?;
+            let key_size = cursor.read_u64::<LittleEndian>()?;
+            let data_size = cursor.read_u64::<LittleEndian>()?;
+
+            let key_start = cursor.position();
+            let data_start = key_start.checked_add(key_size);
+            let (Some(key), Some(data)) = (
+                slice_at(contents, key_start, key_size),
+                data_start.and_then(|data_start| slice_at(contents, data_start, data_size)),
+            ) else {
+                anyhow::bail!("Truncated indexed profile");
+            };
+            // Both are in bounds, so their sum cannot overflow
+            cursor.set_position(key_start + key_size + data_size);
+
+            parse_indexed_record(data, version, key, &mut profile)?;
+        }
+    }
+
+    Ok(profile)
+}
+
+/// Returns the symbol name of a PGO name. Functions with internal linkage are prefixed with the
+/// source file they have been defined in (`<file>;<name>`, or `<file>:<name>` by older compilers).
+fn symbol_name(pgo_name: &str) -> &str {
+    pgo_name
+        .rsplit_once(';')
+        .or_else(|| pgo_name.rsplit_once(':'))
+        .map_or(pgo_name, |(_, name)| name)
+}
+
+/// Marks the code of the lines of `object_file` that `mapping` reports as executed given the
+/// counters of `profile` in `module`, going from the lines to their code through the line
+/// programs of the debug information
+fn mark_executed_lines(
+    module: &mut Module,
+    object_file: &ObjectFile,
+    mapping: &CoverageMapping,
+    profile: &Profile,
+) -> anyhow::Result<()> {
+    let executed_lines = mapping
+        .executed_lines(|name_hash, function_hash| profile.counters(name_hash, function_hash));
+
+    let line_table = read_line_table(module, object_file, &LineInfoFilters::default(), None)?;
+    let file_lines = line_table
+        .files
+        .iter()
+        .map(|file| executed_lines.get(&normalize(Path::new(&**file))))
+        .collect::<Vec<_>>();
+
+    for row in line_table.units.iter().flat_map(|unit| &unit.rows) {
+        if !file_lines[row.file_id].is_some_and(|lines| lines.contains(&row.line)) {
+            continue;
+        }
+
+        let offset = |address: u64| {
+            address
+                .checked_sub(line_table.load_base)
+                .and_then(|offset| u32::try_from(offset).ok())
+        };
+
+        if let (Some(start), Some(end)) = (offset(row.address), offset(row.end_address)) {
+            if start < end {
+                module.bb_bitmap.insert_range(start..end);
+                module.bb_starts.insert(start);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Marks the entry of every executed function of `profile` in `module`, for binaries without a
+/// coverage mapping
+fn mark_executed_functions(module: &mut Module, object_file: &ObjectFile, profile: &Profile) {
+    let load_base = object_file.load_base();
+    let object = object_file.with_object(|obj| obj);
+    let executed_functions = profile.executed_functions();
+
+    for symbol in object.symbols() {
+        if symbol.kind() != SymbolKind::Text || symbol.address() == 0 {
+            continue;
+        }
+
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+
+        if !executed_functions.contains(name) {
+            continue;
+        }
+
+        let Some(offset) = symbol
+            .address()
+            .checked_sub(load_base)
+            .and_then(|offset| u32::try_from(offset).ok())
+        else {
+            continue;
+        };
+
+        module.bb_bitmap.insert(offset);
+        module.bb_starts.insert(offset);
+    }
+}
+
+/// Builds the module of `binary` in which the code of the executed lines of `profile` is marked
+/// as executed
+fn module_from_profile(binary: String, profile: &Profile) -> anyhow::Result<Module> {
+    let object_file = ObjectFile::from_path(&binary)?;
+
+    let mut module = Module {
+        size: object_file.image_size() as usize,
+        path: binary,
+        ..Default::default()
+    };
+
+    match CoverageMapping::from_object(object_file.with_object(|obj| obj))? {
+        Some(mapping) => mark_executed_lines(&mut module, &object_file, &mapping, profile)?,
+        None => {
+            log::warn!(
+                "{} has no coverage mapping (-fcoverage-mapping), only the entries of its executed functions are covered",
+                module.path
+            );
+            mark_executed_functions(&mut module, &object_file, profile);
+        }
+    }
+
+    Ok(module)
+}
+
+/// Whether the binary at `path` has been built from the functions of `profile`: whether the
+/// records of its coverage mapping, or else its function symbols, include any of them
+fn defines_functions_of(path: &str, profile: &Profile) -> bool {
+    let Ok(object_file) = ObjectFile::from_path(path) else {
+        return false;
+    };
+    let object = object_file.with_object(|obj| obj);
+
+    match CoverageMapping::from_object(object) {
+        Ok(Some(mapping)) => mapping
+            .functions()
+            .any(|function| profile.functions.contains_key(&function)),
+        _ => {
+            let names = profile
+                .functions
+                .values()
+                .filter_map(|function| function.name.as_deref())
+                .map(symbol_name)
+                .collect::<HashSet<_>>();
+
+            object
+                .symbols()
+                .any(|symbol| symbol.name().is_ok_and(|name| names.contains(name)))
+        }
+    }
+}
+
+/// Parses an LLVM instrumentation profile (a `.profraw` file, or a `.profdata` file merged by
+/// `llvm-profdata`) into the same model drcov files are parsed into.
+///
+/// The counters are mapped to the source lines of the coverage mapping regions of the binaries
+/// the profile has been collected from, and from the lines to their code through the line
+/// programs of the debug information, so that the coverage is symbolized like that of drcov
+/// files. The binaries are found among `binaries`, or by the build-ids recorded in raw profiles
+/// with `resolver`. Profiles without build-ids apply to the `binaries` that define their
+/// functions.
+pub fn from_file(
+    path: &Path,
+    binaries: &[String],
+    resolver: &BinaryResolver,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading LLVM profile: {}", path.display());
+
+    let contents = std::fs::read(path)?;
+
+    let profile = match contents.get(..8) {
+        Some(magic) if magic == constants::INDEXED_MAGIC.to_le_bytes() => {
+            parse_indexed_profile(&contents)?
+        }
+        _ => parse_raw_profile(&contents)?,
+    };
+
+    log::debug!(
+        "Profile holds {} functions ({} executed)",
+        profile.functions.len(),
+        profile.executed_functions().len()
+    );
+
+    let mut candidates = Vec::new();
+
+    if profile.binary_ids.is_empty() {
+        candidates.extend(
+            binaries
+                .iter()
+                .filter(|binary| defines_functions_of(binary, &profile))
+                .cloned(),
+        );
+    } else {
+        for binary in binaries {
+            let build_id = ObjectFile::from_path(binary)
+                .ok()
+                .and_then(|object_file| build_id_hex(object_file.with_object(|obj| obj)));
+
+            if build_id.is_some_and(|build_id| profile.binary_ids.contains(&build_id)) {
+                candidates.push(binary.clone());
+            }
+        }
+
+        if candidates.is_empty() {
+            candidates.extend(
+                profile
+                    .binary_ids
+                    .iter()
+                    .filter_map(|build_id| resolver.find(build_id)),
+            );
+        }
+    }
+
+    if candidates.is_empty() {
+        anyhow::bail!(
+            "Could not find the binary of '{}' (use --llvm-profile-binary or --binary-dir)",
+            path.display()
+        );
+    }
+
+    let mut table = Vec::new();
+
+    for binary in candidates {
+        let binary =
+            String::from_utf8_lossy(&filters.maybe_replace_with_path_map_filter(binary.as_bytes()))
+                .to_string();
+
+        if !filters.matches_any_module_filter(binary.as_bytes())
+            || filters.matches_any_module_skip_filter(binary.as_bytes())
+        {
+            continue;
+        }
+
+        table.push(module_from_profile(binary, &profile)?);
+    }
+
+    log::info!("LLVM profile loaded");
+
+    Ok(Drcov::from_modules(
+        constants::LLVM_PROFILE_FLAVOR,
+        Modules { version: 0, table },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A raw profile of the given version collected from a binary with the build-id `deadbeef`,
+    /// whose only function `main` (of hash 0x1234) has the given counters
+    fn raw_profile(version: u64, counters: &[u64]) -> Vec<u8> {
+        let mut names = [&[4, 0][..], b"main"].concat();
+        names.resize(8, 0);
+
+        let mut header = vec![
+            16,
+            1,
+            0,
+            counters.len() as u64,
+            0,
+            names.len() as u64,
+            0x1000,
+            0,
+        ];
+        if version >= 9 {
+            // The bitmap sizes and delta
+            header.splice(5..5, [0, 0]);
+            header.insert(9, 0);
+        }
+        if version >= 10 {
+            // The virtual tables
+            header.extend([0, 0]);
+        }
+        header.push(1);
+
+        let mut profile = Vec::new();
+        profile.extend(constants::RAW_MAGIC_64.to_le_bytes());
+        profile.extend(version.to_le_bytes());
+        for field in header {
+            profile.extend(field.to_le_bytes());
+        }
+
+        profile.extend(4u64.to_le_bytes());
+        profile.extend([0xde, 0xad, 0xbe, 0xef, 0, 0, 0, 0]);
+
+        let record_start = profile.len();
+        profile.extend(name_hash(b"main").to_le_bytes());
+        profile.extend(0x1234u64.to_le_bytes());
+        // The counters follow the record, at the counters delta of the header
+        profile.extend(0x1000u64.to_le_bytes());
+        let pointers = if version >= 9 { 3 } else { 2 };
+        profile.extend(vec![0; pointers * 8]);
+        profile.extend((counters.len() as u32).to_le_bytes());
+        profile.resize(record_start + if version >= 9 { 64 } else { 48 }, 0);
+
+        for counter in counters {
+            profile.extend(counter.to_le_bytes());
+        }
+        profile.extend(names);
+
+        profile
+    }
+
+    #[test]
+    fn parses_raw_profiles() {
+        for version in [8, 9, 10] {
+            let profile = parse_raw_profile(&raw_profile(version, &[3, 0])).unwrap();
+
+            assert_eq!(profile.binary_ids, ["deadbeef"]);
+            assert_eq!(
+                profile.counters(name_hash(b"main"), 0x1234),
+                Some([3, 0].as_slice())
+            );
+            assert_eq!(profile.executed_functions(), HashSet::from(["main"]));
+        }
+
+        let profile = parse_raw_profile(&raw_profile(10, &[0, 0])).unwrap();
+        assert!(profile.executed_functions().is_empty());
+    }
+
+    #[test]
+    fn rejects_raw_profiles_with_out_of_bounds_sizes() {
+        let profile = raw_profile(10, &[3, 0]);
+        let with_field = |index: usize, value: u64| {
+            let mut profile = profile.clone();
+            let offset = 16 + index * 8;
+            profile[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            parse_raw_profile(&profile)
+        };
+
+        // The number of data records, the number of counters and the counters delta
+        assert!(with_field(1, u64::MAX).is_err());
+        assert!(with_field(3, u64::MAX / 2).is_err());
+        assert!(with_field(8, 0x10_0000).is_err());
+        assert!(parse_raw_profile(&profile[..profile.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn parses_indexed_profiles() {
+        let mut record = Vec::new();
+        for field in [0x1234u64, 2, 5, 0] {
+            record.extend(field.to_le_bytes());
+        }
+        // An empty value profile
+        record.extend(8u32.to_le_bytes());
+        record.extend(0u32.to_le_bytes());
+
+        let mut profile = Vec::new();
+        for field in [constants::INDEXED_MAGIC, 5, 0, 0, 40, 1, 1, 64] {
+            profile.extend(field.to_le_bytes());
+        }
+        profile.extend(1u16.to_le_bytes());
+        for field in [0, 4, record.len() as u64] {
+            profile.extend(field.to_le_bytes());
+        }
+        profile.extend(b"main");
+        profile.extend(&record);
+
+        let parsed = parse_indexed_profile(&profile).unwrap();
+        assert_eq!(
+            parsed.counters(name_hash(b"main"), 0x1234),
+            Some([5, 0].as_slice())
+        );
+
+        // A value profile size that does not even cover itself
+        let truncated = profile.len() - 8;
+        profile[truncated..truncated + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert!(parse_indexed_profile(&profile).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use crate::archive;
+use crate::limits::InputLimits;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Read;
+use std::path::Path;
+use xxhash_rust::xxh3::Xxh3;
+
+mod constants {
+    pub const PATH_COLUMN: &str = "path";
+    pub const SIZE_COLUMN: &str = "size";
+    pub const SHA256_COLUMN: &str = "sha256";
+    pub const XXH3_COLUMN: &str = "xxh3";
+}
+
+/// The size and content hashes of a file, computed in a single pass over it. The SHA-256 hash is
+/// only computed when asked for, since it is several times slower than xxh3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDigests {
+    pub size: u64,
+    pub xxh3: u64,
+    pub sha256: Option<String>,
+}
+
+impl FileDigests {
+    pub fn of(path: &Path, with_sha256: bool) -> std::io::Result<Self> {
+        // The entries of archives are read into memory
+        if let Some((archive, name)) = archive::split_entry_path(path) {
+            let contents = archive::read_entry(archive, &name, &InputLimits::default())
+                .map_err(std::io::Error::other)?;
+            return Self::of_reader(contents.as_slice(), with_sha256);
+        }
+
+        Self::of_reader(std::fs::File::open(path)?, with_sha256)
+    }
+
+    fn of_reader<R: Read>(mut reader: R, with_sha256: bool) -> std::io::Result<Self> {
+        let mut xxh3 = Xxh3::new();
+        let mut sha256 = with_sha256.then(Sha256::new);
+        let mut size = 0;
+        let mut buffer = vec![0u8; 64 * 1024];
+
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+
+            xxh3.update(&buffer[..read]);
+            if let Some(sha256) = sha256.as_mut() {
+                sha256.update(&buffer[..read]);
+            }
+            size += read as u64;
+        }
+
+        Ok(Self {
+            size,
+            xxh3: xxh3.digest(),
+            sha256: sha256.map(|sha256| format!("{:x}", sha256.finalize())),
+        })
+    }
+
+    /// The value the file has in the given manifest column, for the columns that describe the
+    /// contents of a file
+    fn column(&self, column: &str) -> Option<String> {
+        match column {
+            constants::SIZE_COLUMN => Some(self.size.to_string()),
+            constants::SHA256_COLUMN => self.sha256.clone(),
+            constants::XXH3_COLUMN => Some(format!("{:016x}", self.xxh3)),
+            _ => None,
+        }
+    }
+}
+
+/// Checks the files of the corpus directory `corpus` against a manifest written by this tool (a
+/// `--dedup-manifest` or a `--reduce-set-manifest`).
+///
+/// Manifests are tab separated with a `# column...` header line. Every column that describes the
+/// contents of a file (size, sha256, xxh3) is checked, so manifests of either kind can be used.
+/// Entries are matched to the files of the corpus by file name, since the corpus may have been
+/// moved (or its paths scrubbed) since the manifest was written.
+pub fn verify(manifest: &Path, corpus: &Path) -> anyhow::Result<()> {
+    let contents = std::fs::read_to_string(manifest)?;
+    let mut lines = contents.lines();
+
+    let Some(columns) = lines
+        .next()
+        .and_then(|header| header.strip_prefix('#'))
+        .map(|header| header.split('\t').map(str::trim).collect::<Vec<_>>())
+    else {
+        anyhow::bail!(
+            "'{}' does not start with a manifest header",
+            manifest.display()
+        );
+    };
+
+    let Some(path_column) = columns
+        .iter()
+        .position(|column| *column == constants::PATH_COLUMN)
+    else {
+        anyhow::bail!("'{}' does not have a path column", manifest.display());
+    };
+
+    let checked_columns = columns
+        .iter()
+        .enumerate()
+        .filter(|(_, column)| {
+            [
+                constants::SIZE_COLUMN,
+                constants::SHA256_COLUMN,
+                constants::XXH3_COLUMN,
+            ]
+            .contains(column)
+        })
+        .collect::<Vec<_>>();
+
+    if checked_columns.is_empty() {
+        anyhow::bail!(
+            "'{}' does not record the size or a hash of its files",
+            manifest.display()
+        );
+    }
+
+    let with_sha256 = checked_columns
+        .iter()
+        .any(|(_, column)| **column == constants::SHA256_COLUMN);
+
+    let mut expected = HashMap::new();
+
+    for line in lines.filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        let Some(file_name) = fields
+            .get(path_column)
+            .and_then(|path| Path::new(path).file_name())
+        else {
+            anyhow::bail!("Invalid manifest line '{line}'");
+        };
+
+        if expected
+            .insert(file_name.to_os_string(), fields.clone())
+            .is_some()
+        {
+            log::warn!(
+                "'{}' is listed multiple times in the manifest, only its last entry is checked",
+                file_name.to_string_lossy()
+            );
+        }
+    }
+
+    let mut problems = 0usize;
+    let mut verified = 0usize;
+
+    let mut present = BTreeSet::new();
+    for entry in corpus.read_dir()?.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_file()) {
+            present.insert(entry.file_name());
+        }
+    }
+
+    for (file_name, fields) in &expected {
+        let path = corpus.join(file_name);
+
+        if !present.contains(file_name) {
+            log::warn!("Missing: {}", path.display());
+            problems += 1;
+            continue;
+        }
+
+        let digests = FileDigests::of(&path, with_sha256)?;
+
+        let mismatches = checked_columns
+            .iter()
+            .filter(|(index, column)| {
+                fields.get(*index).copied() != digests.column(column).as_deref()
+            })
+            .map(|(_, column)| **column)
+            .collect::<Vec<_>>();
+
+        if mismatches.is_empty() {
+            verified += 1;
+        } else {
+            log::warn!("Changed ({}): {}", mismatches.join(", "), path.display());
+            problems += 1;
+        }
+    }
+
+    for file_name in present
+        .iter()
+        .filter(|file_name| !expected.contains_key(*file_name))
+    {
+        log::info!("Not in the manifest: {}", corpus.join(file_name).display());
+    }
+
+    log::info!(
+        "{verified} of {} files match the manifest '{}'",
+        expected.len(),
+        manifest.display()
+    );
+
+    if problems > 0 {
+        anyhow::bail!("The corpus does not match the manifest ({problems} problems)");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::TempPath;
+
+    #[test]
+    fn only_computes_the_sha256_hash_when_asked_to() {
+        let digests = FileDigests::of_reader(b"abc".as_slice(), false).unwrap();
+        assert_eq!(digests.size, 3);
+        assert_eq!(digests.sha256, None);
+
+        let digests = FileDigests::of_reader(b"abc".as_slice(), true).unwrap();
+        assert_eq!(
+            digests.sha256.as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn verifies_the_files_of_a_corpus() {
+        let corpus = TempPath::dir("manifest-corpus");
+        std::fs::write(corpus.join("a"), b"abc").unwrap();
+        std::fs::write(corpus.join("b"), b"def").unwrap();
+
+        let manifest = TempPath::new("manifest.tsv");
+        let sha256 = |path| FileDigests::of(path, true).unwrap().sha256.unwrap();
+        std::fs::write(
+            manifest.path(),
+            format!(
+                "# path\tsize\tsha256\n/elsewhere/a\t3\t{}\n/elsewhere/b\t3\t{}\n",
+                sha256(&corpus.join("a")),
+                sha256(&corpus.join("b"))
+            ),
+        )
+        .unwrap();
+
+        assert!(verify(manifest.path(), corpus.path()).is_ok());
+
+        std::fs::write(corpus.join("b"), b"ghi").unwrap();
+        assert!(verify(manifest.path(), corpus.path()).is_err());
+
+        std::fs::remove_file(corpus.join("b")).unwrap();
+        assert!(verify(manifest.path(), corpus.path()).is_err());
+    }
+}
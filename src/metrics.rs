@@ -0,0 +1,149 @@
+use crate::cache::AtomicFile;
+use crate::components::ComponentRollup;
+use crate::module_key::ModuleKey;
+use crate::summary::Summary;
+use roaring::RoaringBitmap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::io::Write as _;
+use std::path::PathBuf;
+
+mod constants {
+    /// The prefix of the names of all metrics
+    pub const PREFIX: &str = "drcov2lcov";
+}
+
+/// The labels of a sample: label name -> value
+type Labels<'s> = Vec<(&'s str, &'s str)>;
+
+/// Extracts one of the totals of a summary
+type SummaryTotal = fn(&Summary) -> usize;
+
+/// Escapes a label value as the OpenMetrics text format requires
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The OpenMetrics text exposition of the coverage totals
+#[derive(Debug, Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    /// Adds a gauge metric family `name` with a sample for every set of labels of `samples`
+    fn gauge<'s>(
+        &mut self,
+        name: &str,
+        help: &str,
+        samples: impl IntoIterator<Item = (Labels<'s>, f64)>,
+    ) {
+        let name = format!("{}_{name}", constants::PREFIX);
+        let _ = writeln!(self.text, "# TYPE {name} gauge");
+        let _ = writeln!(self.text, "# HELP {name} {help}");
+
+        for (labels, value) in samples {
+            let labels = labels
+                .iter()
+                .map(|(label, value)| format!("{label}=\"{}\"", escape_label_value(value)))
+                .collect::<Vec<_>>();
+
+            if labels.is_empty() {
+                let _ = writeln!(self.text, "{name} {value}");
+            } else {
+                let _ = writeln!(self.text, "{name}{{{}}} {value}", labels.join(","));
+            }
+        }
+    }
+
+    /// Adds the gauges of the totals of `summaries`, named `<prefix>lines_found` etc.
+    fn summary_gauges<'s>(
+        &mut self,
+        prefix: &str,
+        what: &str,
+        summaries: &[(Labels<'s>, Summary)],
+    ) {
+        let totals: [(&str, &str, SummaryTotal); 4] = [
+            ("lines_found", "Number of instrumented lines", |s| {
+                s.lines_found
+            }),
+            ("lines_hit", "Number of executed lines", |s| s.lines_hit),
+            ("functions_found", "Number of instrumented functions", |s| {
+                s.functions_found
+            }),
+            ("functions_hit", "Number of executed functions", |s| {
+                s.functions_hit
+            }),
+        ];
+
+        for (name, help, total) in totals {
+            self.gauge(
+                &format!("{prefix}{name}"),
+                &format!("{help}{what}"),
+                summaries
+                    .iter()
+                    .map(|(labels, summary)| (labels.clone(), total(summary) as f64)),
+            );
+        }
+    }
+}
+
+/// Writes the coverage totals in the OpenMetrics text format to `path`, for the textfile
+/// collector of the Prometheus node exporter: the totals of `summary`, the number of executed
+/// basic blocks of every module of `modules` and the totals of every component of `components`.
+///
+/// The file is replaced atomically, so the collector never reads a partially written one.
+pub fn write_metrics(
+    path: &str,
+    summary: &Summary,
+    modules: &HashMap<ModuleKey, RoaringBitmap>,
+    components: &[ComponentRollup],
+) -> anyhow::Result<()> {
+    let mut exposition = Exposition::default();
+
+    exposition.summary_gauges("", "", &[(vec![], *summary)]);
+
+    let modules = modules
+        .iter()
+        .map(|(module, blocks)| (module.path.as_str(), blocks))
+        .collect::<BTreeMap<_, _>>();
+    exposition.gauge(
+        "module_blocks_executed",
+        "Number of executed basic blocks of the module",
+        modules
+            .iter()
+            .map(|(module, blocks)| (vec![("module", *module)], blocks.len() as f64)),
+    );
+
+    if !components.is_empty() {
+        let summaries = components
+            .iter()
+            .map(|component| {
+                (
+                    vec![
+                        ("component", component.name.as_str()),
+                        ("owner", component.owner.as_deref().unwrap_or_default()),
+                    ],
+                    component.summary,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        exposition.summary_gauges("component_", " of the component", &summaries);
+    }
+
+    exposition.text.push_str("# EOF\n");
+
+    let atomic_file = AtomicFile::new(PathBuf::from(path));
+    atomic_file
+        .create()?
+        .write_all(exposition.text.as_bytes())?;
+    atomic_file.persist()?;
+
+    log::info!("Coverage metrics written to '{path}'");
+
+    Ok(())
+}
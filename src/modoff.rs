@@ -0,0 +1,194 @@
+use crate::drcov::{Drcov, DrcovFilters, Module, Modules};
+use crate::dwarf::ObjectFile;
+use crate::util::{parse_capture_group, Hex};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    /// The flavor given to the coverage parsed from module+offset traces
+    pub const MODOFF_FLAVOR: &str = "modoff";
+
+    lazy_static! {
+        /// `<module path or name>+[0x]<offset>`, the offset being relative to the image base
+        pub static ref MODOFF_LINE_REGEX: Regex =
+            Regex::new(r"^\s*(?P<module>[^#\s].*?)\s*\+\s*(?:0x)?(?P<offset>[[:xdigit:]]+)\s*$").unwrap();
+    }
+}
+
+fn parse_line(line: &[u8]) -> Option<(String, u64)> {
+    let cap = constants::MODOFF_LINE_REGEX.captures(line)?;
+
+    let module = parse_capture_group::<String>(&cap, "module")?;
+    let offset = parse_capture_group::<Hex<usize>>(&cap, "offset")?.value as u64;
+
+    Some((module, offset))
+}
+
+fn is_comment(line: &[u8]) -> bool {
+    let line = line.trim_ascii();
+    line.is_empty() || line.starts_with(b"#")
+}
+
+/// Whether `head`, the start of an input file, is a module+offset trace, judging by its first line
+pub fn is_modoff_trace(head: &[u8]) -> bool {
+    head.split(|b| *b == b'\n')
+        .find(|line| !is_comment(line))
+        .is_some_and(|line| parse_line(line).is_some())
+}
+
+/// Whether the coverage of the given flavor has been parsed from a module+offset trace
+pub fn is_modoff_flavor(flavor: &str) -> bool {
+    flavor == constants::MODOFF_FLAVOR
+}
+
+/// Resolves the module of a trace line to a path: modules are often only named by their file name,
+/// in which case the module of `module_table` with that name is used
+fn resolve_module_path(module: &str, module_table: Option<&Modules>) -> String {
+    if Path::new(module).is_file() {
+        return module.to_string();
+    }
+
+    module_table
+        .into_iter()
+        .flat_map(|modules| &modules.table)
+        .find(|candidate| candidate.file_name() == module)
+        .map(|candidate| candidate.path.clone())
+        .unwrap_or_else(|| module.to_string())
+}
+
+/// Parses a trace of `<module>+0x<offset>` lines (one per executed address or basic block, as
+/// written by many custom tracers and Lighthouse's module+offset format) into the same model drcov
+/// files are parsed into. Offsets are relative to the image base of their module, and every one of
+/// them is recorded as a one byte basic block like the addresses of QEMU traces. Empty lines and
+/// lines starting with `#` are ignored.
+pub fn from_file(
+    path: &Path,
+    module_table: Option<&Modules>,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading module+offset trace: {}", path.display());
+
+    let contents = std::fs::read(path)?;
+
+    // Module -> offsets
+    let mut offsets = BTreeMap::<String, BTreeSet<u64>>::new();
+
+    for (line_number, line) in contents.split(|b| *b == b'\n').enumerate() {
+        if is_comment(line) {
+            continue;
+        }
+
+        let Some((module, offset)) = parse_line(line) else {
+            anyhow::bail!(
+                "{}:{}: Invalid module+offset line '{}'",
+                path.display(),
+                line_number + 1,
+                String::from_utf8_lossy(line).trim()
+            );
+        };
+
+        if offset > u32::MAX as u64 {
+            log::warn!("Skipping the out of range offset {offset:#x} of module {module}");
+            continue;
+        }
+
+        offsets.entry(module).or_default().insert(offset);
+    }
+
+    let mut modules = Modules {
+        version: 0,
+        table: Vec::new(),
+    };
+
+    for (module, module_offsets) in offsets {
+        let path = resolve_module_path(&module, module_table);
+
+        let image_size = ObjectFile::from_path(&path)
+            .map(|object_file| object_file.image_size())
+            .unwrap_or_default();
+        let last_offset = module_offsets.last().copied().unwrap_or_default();
+
+        let mut module = Module {
+            size: image_size.max(last_offset + 1) as usize,
+            path,
+            ..Default::default()
+        };
+
+        for offset in module_offsets {
+            let offset = offset as u32;
+            module.bb_bitmap.insert(offset);
+            module.bb_starts.insert(offset);
+        }
+
+        modules.table.push(module);
+    }
+
+    modules.apply_filters(filters);
+
+    log::info!("Module+offset trace loaded");
+
+    Ok(Drcov::from_modules(constants::MODOFF_FLAVOR, modules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{drcov_log, TempPath};
+
+    #[test]
+    fn records_the_offsets_of_modoff_traces_in_their_modules() {
+        let trace = TempPath::new("trace.modoff");
+        std::fs::write(
+            trace.path(),
+            concat!(
+                "# Executed basic blocks\n",
+                "libfoo.so+0x10\n",
+                "/bin/app+4\n",
+                "\n",
+                "libfoo.so+0x10\n",
+                "libfoo.so + 0x2a\n",
+            ),
+        )
+        .unwrap();
+        assert!(is_modoff_trace(&std::fs::read(trace.path()).unwrap()));
+
+        let module_table = Modules {
+            version: 0,
+            table: vec![Module {
+                size: 0x1000,
+                segment_start: 0x7f0000000000,
+                path: "/usr/lib/libfoo.so".to_string(),
+                ..Default::default()
+            }],
+        };
+
+        let drcov = from_file(trace.path(), Some(&module_table), &DrcovFilters::default()).unwrap();
+
+        // Modules only named by their file name are resolved through the module table
+        let [app, foo] = drcov.modules.table.as_slice() else {
+            panic!("Expected two modules");
+        };
+        assert_eq!(app.path, "/bin/app");
+        assert_eq!(app.bb_bitmap.iter().collect::<Vec<_>>(), vec![0x4]);
+        assert_eq!(foo.path, "/usr/lib/libfoo.so");
+        assert_eq!(foo.bb_bitmap.iter().collect::<Vec<_>>(), vec![0x10, 0x2a]);
+        assert_eq!(foo.bb_starts.iter().collect::<Vec<_>>(), vec![0x10, 0x2a]);
+        assert!(is_modoff_flavor(&drcov.flavor));
+    }
+
+    #[test]
+    fn rejects_invalid_modoff_lines() {
+        let trace = TempPath::new("trace.modoff");
+        std::fs::write(trace.path(), "libfoo.so+0x10\nlibfoo.so\n").unwrap();
+
+        assert!(from_file(trace.path(), None, &DrcovFilters::default()).is_err());
+    }
+
+    #[test]
+    fn does_not_claim_drcov_logs() {
+        assert!(!is_modoff_trace(&drcov_log("/bin/app", &[(16, 4)])));
+    }
+}
@@ -0,0 +1,262 @@
+use crate::deps::resolve_dependencies;
+use crate::module_key::ModuleKey;
+use crate::output::{write_text_output, LineEnding};
+use itertools::Itertools;
+use roaring::RoaringBitmap;
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+mod constants {
+    /// Bumped whenever the layout of the JSON graph changes
+    pub const FORMAT_VERSION: u64 = 1;
+
+    /// The width of the nodes of the DOT graph, in inches, before they grow with their coverage
+    pub const MIN_NODE_WIDTH: f64 = 1.0;
+}
+
+#[derive(Debug)]
+struct Node {
+    path: String,
+    /// The number of executed basic blocks, or `None` for the dependencies that were not traced
+    covered_blocks: Option<u64>,
+    /// Whether the module was traced from another build than the binary at its path
+    other_build: bool,
+}
+
+impl Node {
+    fn name(&self) -> String {
+        Path::new(&self.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.path.clone())
+    }
+}
+
+/// The traced modules, along with the modules they depend on (`DT_NEEDED`) whether they were
+/// traced or not
+#[derive(Debug, Default)]
+struct ModuleGraph {
+    /// The traced modules come first, sorted by path
+    nodes: Vec<Node>,
+    /// (dependent, dependency) node indices
+    edges: BTreeSet<(usize, usize)>,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl ModuleGraph {
+    /// `modules` maps each module to the module relative offsets of its executed basic blocks.
+    /// Dependencies are matched to the traced modules by their canonical path, or by their file
+    /// name if they cannot be resolved on this machine.
+    fn new(modules: &HashMap<ModuleKey, RoaringBitmap>) -> Self {
+        let mut graph = Self::default();
+        let mut indices = HashMap::<PathBuf, usize>::new();
+
+        for (key, blocks) in modules.iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+            indices
+                .entry(canonical(Path::new(&key.path)))
+                .or_insert(graph.nodes.len());
+            graph.nodes.push(Node {
+                path: key.path.clone(),
+                covered_blocks: Some(blocks.len()),
+                other_build: key.other_build.is_some(),
+            });
+        }
+
+        let num_traced = graph.nodes.len();
+
+        for from in 0..num_traced {
+            // The binaries at the paths of other builds may not need the same libraries
+            if graph.nodes[from].other_build {
+                continue;
+            }
+
+            let dependencies = match resolve_dependencies(Path::new(&graph.nodes[from].path)) {
+                Ok(dependencies) => dependencies,
+                Err(e) => {
+                    log::debug!(
+                        "Could not read the dependencies of {}: {e}",
+                        graph.nodes[from].path
+                    );
+                    continue;
+                }
+            };
+
+            for dependency in dependencies {
+                let path = dependency.unwrap_or_else(PathBuf::from);
+                let canonical = canonical(&path);
+
+                let by_name = || {
+                    let name = path.file_name()?;
+                    graph.nodes[..num_traced]
+                        .iter()
+                        .position(|node| Path::new(&node.path).file_name() == Some(name))
+                };
+
+                let to = match indices.get(&canonical).copied().or_else(by_name) {
+                    Some(to) => to,
+                    None => {
+                        indices.insert(canonical.clone(), graph.nodes.len());
+                        graph.nodes.push(Node {
+                            path: canonical.to_string_lossy().to_string(),
+                            covered_blocks: None,
+                            other_build: false,
+                        });
+                        graph.nodes.len() - 1
+                    }
+                };
+
+                graph.edges.insert((from, to));
+            }
+        }
+
+        graph
+    }
+
+    fn to_dot(&self, scrub: impl Fn(&str) -> String) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut res = String::new();
+
+        let _ = writeln!(res, "digraph modules {{");
+        let _ = writeln!(res, "    node [shape=box, style=filled, fillcolor=white];");
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut name = escape(&node.name());
+            if node.other_build {
+                name.push_str("\\n(another build)");
+            }
+            let tooltip = escape(&scrub(&node.path));
+
+            let _ = match node.covered_blocks {
+                Some(covered_blocks) => writeln!(
+                    res,
+                    "    n{index} [label=\"{name}\\n{covered_blocks} blocks\", tooltip=\"{tooltip}\", width={:.2}{}];",
+                    // Logarithmic, since the largest modules have orders of magnitude more blocks
+                    constants::MIN_NODE_WIDTH + (covered_blocks as f64).ln_1p() / 4.0,
+                    if covered_blocks > 0 {
+                        ", fillcolor=palegreen"
+                    } else {
+                        ""
+                    }
+                ),
+                None => writeln!(
+                    res,
+                    "    n{index} [label=\"{name}\\nnot traced\", tooltip=\"{tooltip}\", style=dashed];"
+                ),
+            };
+        }
+
+        for (from, to) in &self.edges {
+            let _ = writeln!(res, "    n{from} -> n{to};");
+        }
+
+        let _ = writeln!(res, "}}");
+
+        res
+    }
+
+    fn to_json(&self, scrub: impl Fn(&str) -> String) -> serde_json::Value {
+        let nodes = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| {
+                json!({
+                    "id": index,
+                    "name": node.name(),
+                    "path": scrub(&node.path),
+                    "traced": node.covered_blocks.is_some(),
+                    "other_build": node.other_build,
+                    "covered_blocks": node.covered_blocks.unwrap_or_default(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let edges = self
+            .edges
+            .iter()
+            .map(|(from, to)| json!({ "from": from, "to": to }))
+            .collect::<Vec<_>>();
+
+        json!({
+            "format_version": constants::FORMAT_VERSION,
+            "nodes": nodes,
+            "edges": edges,
+        })
+    }
+}
+
+/// Writes the graph of the traced modules of `modules` and of their direct dependencies to
+/// `path`: as JSON if it ends with `.json`, and in the DOT format of Graphviz otherwise. The nodes
+/// are sized by their executed basic blocks and the edges go from every module to the libraries
+/// it needs, so the parts of the dependency tree that were never exercised stand out.
+///
+/// `modules` maps each module to the module relative offsets of its executed basic blocks. Their
+/// binaries are read from their paths, which are scrubbed with `scrub` in the graph.
+pub fn write_module_graph(
+    path: &str,
+    modules: &HashMap<ModuleKey, RoaringBitmap>,
+    scrub: impl Fn(&str) -> String,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    let graph = ModuleGraph::new(modules);
+
+    let contents = if Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension == "json")
+    {
+        serde_json::to_string_pretty(&graph.to_json(scrub))?
+    } else {
+        graph.to_dot(scrub)
+    };
+
+    write_text_output(path, &contents, line_ending)?;
+
+    log::info!(
+        "Module graph of {} modules written to '{path}'",
+        graph.nodes.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::test_binary;
+
+    #[test]
+    fn links_the_traced_modules_to_their_dependencies() {
+        let modules = HashMap::from([
+            (
+                ModuleKey::from_path(test_binary()),
+                RoaringBitmap::from_iter([0x10, 0x20]),
+            ),
+            (ModuleKey::from_path("[vdso]"), RoaringBitmap::new()),
+        ]);
+
+        let graph = ModuleGraph::new(&modules);
+        let exe_node = graph
+            .nodes
+            .iter()
+            .position(|node| node.covered_blocks == Some(2));
+
+        // The test binary links against the C library at least
+        assert!(graph.nodes.len() > 2);
+        assert!(graph.edges.iter().all(|(from, _)| Some(*from) == exe_node));
+        assert!(graph.nodes[2..]
+            .iter()
+            .all(|node| node.covered_blocks.is_none()));
+
+        let dot = graph.to_dot(|path| path.to_string());
+        assert!(dot.contains("2 blocks") && dot.contains("not traced") && dot.contains(" -> "));
+
+        let json = graph.to_json(|path| path.to_string());
+        assert_eq!(json["edges"].as_array().unwrap().len(), graph.edges.len());
+        assert_eq!(json["nodes"][1]["traced"], true);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::drcov::CapturedBuild;
+use crate::dwarf::{build_id_hex, ObjectFile};
+use object::{Architecture, Object};
+use std::cmp::Ordering;
+#[cfg(feature = "native")]
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+
+mod constants {
+    /// The page sizes tracers may round the end of the last segment of a module up to
+    pub const PAGE_SIZES: [u64; 3] = [0x1000, 0x4000, 0x10000];
+}
+
+/// The identity of a module across inputs and runs. Modules with a build-id are identified by it,
+/// along with their architecture and bitness, so that copies of the same binary under different
+/// paths (e.g. in different containers or build directories) are merged. The other modules are
+/// identified by their path.
+///
+/// The path is kept either way, to name the module in the outputs and to find its binary.
+///
+/// Coverage captured from another build of a module than the binary at its path (e.g. one that
+/// was rebuilt between the runs) cannot be attributed to the build-id of that binary. Its key has
+/// the build-id the input recorded for the module, if any, and is otherwise told apart by the
+/// size the module had once loaded.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleKey {
+    pub path: String,
+    pub build_id: Option<String>,
+    /// The architecture of the module, e.g. `x86_64` or `aarch64`
+    pub arch: Option<String>,
+    /// 32 or 64
+    pub bits: Option<u8>,
+    /// The size of the module once loaded (zero if unknown), if the coverage was captured from
+    /// another build of it than the binary at its path
+    pub other_build: Option<u64>,
+}
+
+/// What two module keys are compared by
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Identity<'k> {
+    BuildId(&'k str, Option<&'k str>, Option<u8>),
+    Path(&'k str, Option<u64>),
+}
+
+/// Whether a module that was `loaded_size` bytes large once loaded in the traced process has
+/// another size than a binary of `image_size` bytes once loaded. Tracers round the end of the
+/// last segment up to the page size, which depends on the machine.
+fn is_other_size(image_size: u64, loaded_size: u64) -> bool {
+    loaded_size != image_size
+        && constants::PAGE_SIZES
+            .iter()
+            .all(|page_size| loaded_size != image_size.next_multiple_of(*page_size))
+}
+
+/// Whether the `captured` build of a module is another build than the binary of `image_size`
+/// bytes once loaded with `build_id` at its path. Only binaries with a build-id are checked. The
+/// build-ids are compared if the input recorded one; otherwise only the sizes are, so a rebuild
+/// that kept the size of the module (once rounded up to a page) goes unnoticed.
+pub fn is_other_build(image_size: u64, build_id: Option<&str>, captured: &CapturedBuild) -> bool {
+    match (build_id, captured.build_id) {
+        (None, _) => false,
+        (Some(build_id), Some(captured_build_id)) => {
+            !build_id.eq_ignore_ascii_case(captured_build_id)
+        }
+        (Some(_), None) => captured
+            .loaded_size
+            .is_some_and(|loaded_size| is_other_size(image_size, loaded_size as u64)),
+    }
+}
+
+impl ModuleKey {
+    /// The key of a module whose binary is not available
+    pub fn from_path(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The key of the module at `path`, whose binary is `object`
+    pub fn from_object(path: impl Into<String>, object: &object::File) -> Self {
+        let arch = match object.architecture() {
+            Architecture::Unknown => None,
+            arch => Some(format!("{arch:?}").to_lowercase()),
+        };
+
+        Self {
+            path: path.into(),
+            build_id: build_id_hex(object),
+            arch,
+            bits: Some(if object.is_64() { 64 } else { 32 }),
+            other_build: None,
+        }
+    }
+
+    /// The key of the module at `path`, read from its binary if it can be opened
+    pub fn of_module(path: &str) -> Self {
+        match ObjectFile::from_path(path) {
+            Ok(object_file) => Self::from_object(path, object_file.with_object(|obj| obj)),
+            Err(_) => Self::from_path(path),
+        }
+    }
+
+    fn identity(&self) -> Identity<'_> {
+        match &self.build_id {
+            Some(build_id) => Identity::BuildId(build_id, self.arch.as_deref(), self.bits),
+            None => Identity::Path(&self.path, self.other_build),
+        }
+    }
+}
+
+impl PartialEq for ModuleKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for ModuleKey {}
+
+impl PartialOrd for ModuleKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ModuleKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.identity().cmp(&other.identity())
+    }
+}
+
+impl Hash for ModuleKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity().hash(state);
+    }
+}
+
+impl Display for ModuleKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)?;
+
+        if let Some(build_id) = &self.build_id {
+            write!(f, " (build-id: {build_id})")?;
+        }
+
+        match self.other_build {
+            Some(0) => write!(f, " (another build)")?,
+            Some(size) => write!(f, " (another build, {size:#x} bytes once loaded)")?,
+            None => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// The keys of the modules seen so far, so that the binary of every module path is only opened
+/// once
+#[cfg(feature = "native")]
+#[derive(Debug, Default)]
+pub struct ModuleKeys {
+    /// Path -> the key of the module and the size of its binary once loaded
+    keys: HashMap<String, (ModuleKey, Option<u64>)>,
+}
+
+#[cfg(feature = "native")]
+impl ModuleKeys {
+    fn entry(&mut self, path: &str) -> &(ModuleKey, Option<u64>) {
+        self.keys
+            .entry(path.to_string())
+            .or_insert_with(|| match ObjectFile::from_path(path) {
+                Ok(object_file) => (
+                    ModuleKey::from_object(path, object_file.with_object(|obj| obj)),
+                    Some(object_file.image_size()),
+                ),
+                Err(_) => (ModuleKey::from_path(path), None),
+            })
+    }
+
+    /// The key of the module at `path` of which an input `captured` a build (see
+    /// [`Modules::captured_builds`](crate::drcov::Modules::captured_builds)). Only modules whose
+    /// binary has a build-id are checked for being another build.
+    pub fn key(&mut self, path: &str, captured: &CapturedBuild) -> ModuleKey {
+        let (key, image_size) = self.entry(path);
+
+        match image_size {
+            Some(image_size) if is_other_build(*image_size, key.build_id.as_deref(), captured) => {
+                ModuleKey {
+                    path: key.path.clone(),
+                    build_id: captured.build_id.map(str::to_ascii_lowercase),
+                    arch: key.arch.clone(),
+                    bits: key.bits,
+                    other_build: Some(captured.loaded_size.unwrap_or_default() as u64),
+                }
+            }
+            _ => key.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::test_binary;
+
+    #[test]
+    fn identifies_modules_by_build_id() {
+        let copy = |path: &str, build_id: Option<&str>| ModuleKey {
+            path: path.to_string(),
+            build_id: build_id.map(str::to_string),
+            arch: Some("x86_64".to_string()),
+            bits: Some(64),
+            other_build: None,
+        };
+
+        assert_eq!(
+            copy("/build/app", Some("ab12")),
+            copy("/opt/app", Some("ab12"))
+        );
+        assert_ne!(
+            copy("/build/app", Some("ab12")),
+            copy("/build/app", Some("cd34"))
+        );
+        assert_ne!(copy("/build/app", None), copy("/opt/app", None));
+    }
+
+    #[test]
+    fn tells_builds_apart_by_their_loaded_size() {
+        let captured = |loaded_size| CapturedBuild {
+            loaded_size: Some(loaded_size),
+            build_id: None,
+        };
+        assert!(!is_other_build(0x4020, Some("ab12"), &captured(0x5000)));
+        assert!(!is_other_build(0x4020, Some("ab12"), &captured(0x10000)));
+        assert!(!is_other_build(0x5000, Some("ab12"), &captured(0x5000)));
+        assert!(is_other_build(0x4020, Some("ab12"), &captured(0x7000)));
+        assert!(is_other_build(0x4020, Some("ab12"), &captured(0x4000)));
+        assert!(!is_other_build(0x4020, None, &captured(0x7000)));
+
+        let mut keys = ModuleKeys::default();
+        let exe = test_binary();
+        let key = keys.key(&exe, &CapturedBuild::default());
+        let rebuilt = keys.key(&exe, &captured(0x1000));
+
+        // Only binaries with a build-id are checked
+        if key.build_id.is_some() {
+            assert_eq!(
+                (rebuilt.build_id, rebuilt.other_build),
+                (None, Some(0x1000))
+            );
+            assert_ne!(key, keys.key(&exe, &captured(0x2000)));
+        }
+    }
+
+    #[test]
+    fn tells_builds_of_the_same_size_apart_by_their_build_id() {
+        let build = |build_id| CapturedBuild {
+            loaded_size: Some(0x5000),
+            build_id,
+        };
+
+        // Without a recorded build-id, a rebuild of the same size is taken for the same build
+        assert!(!is_other_build(0x5000, Some("ab12"), &build(None)));
+        assert!(!is_other_build(0x5000, Some("ab12"), &build(Some("AB12"))));
+        assert!(is_other_build(0x5000, Some("ab12"), &build(Some("cd34"))));
+
+        let mut keys = ModuleKeys::default();
+        let exe = test_binary();
+        let key = keys.key(&exe, &CapturedBuild::default());
+
+        if let Some(build_id) = key.build_id.clone() {
+            let same = keys.key(&exe, &build(Some(&build_id)));
+            let other = keys.key(&exe, &build(Some("cd34")));
+
+            assert_eq!((same.other_build, &same), (None, &key));
+            assert_eq!(other.build_id.as_deref(), Some("cd34"));
+            assert!(other.other_build.is_some());
+            assert_ne!(other, key);
+        }
+    }
+}
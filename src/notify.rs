@@ -0,0 +1,338 @@
+use crate::dwarf::SourceCoverage;
+use crate::filter::Filter;
+use crate::read_only;
+use crate::summary::Summary;
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+
+mod constants {
+    use std::time::Duration;
+
+    /// A payload that Slack, Mattermost and Discord-compatible (`/slack`) webhooks all accept
+    pub const DEFAULT_PAYLOAD: &str = r#"{"text": "{message}"}"#;
+
+    pub const PLACEHOLDERS: [&str; 6] = [
+        "{event}",
+        "{message}",
+        "{target}",
+        "{line_rate}",
+        "{lines_hit}",
+        "{lines_found}",
+    ];
+
+    pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+}
+
+/// Something worth telling the people watching a coverage campaign about
+#[derive(Debug, Clone, PartialEq)]
+pub enum Milestone {
+    /// The line coverage rate grew by at least the notification threshold
+    CoverageIncreased { from: f64, to: f64 },
+    /// A watched source file or function has been executed for the first time
+    TargetCovered { kind: &'static str, target: String },
+}
+
+impl Milestone {
+    fn event(&self) -> &'static str {
+        match self {
+            Milestone::CoverageIncreased { .. } => "coverage_increased",
+            Milestone::TargetCovered { .. } => "target_covered",
+        }
+    }
+
+    fn target(&self) -> &str {
+        match self {
+            Milestone::CoverageIncreased { .. } => "",
+            Milestone::TargetCovered { target, .. } => target,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Milestone::CoverageIncreased { from, to } => {
+                format!("Line coverage increased from {from:.1}% to {to:.1}%")
+            }
+            Milestone::TargetCovered { kind, target } => {
+                format!("The {kind} {target} has been covered for the first time")
+            }
+        }
+    }
+}
+
+fn line_rate(summary: &Summary) -> f64 {
+    if summary.lines_found == 0 {
+        0.0
+    } else {
+        summary.lines_hit as f64 * 100.0 / summary.lines_found as f64
+    }
+}
+
+/// Escapes `value` to be embedded in a JSON string
+fn escape_json(value: &str) -> String {
+    let quoted = serde_json::Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// The JSON payload of the notifications, with placeholders that are replaced by the details of
+/// every milestone:
+/// - `{event}`: `coverage_increased` or `target_covered`
+/// - `{message}`: a human readable description of the milestone
+/// - `{target}`: the source file or function that has been covered (empty for coverage increases)
+/// - `{line_rate}`, `{lines_hit}` and `{lines_found}`: the totals of the line coverage, as numbers
+///
+/// The string placeholders are JSON-escaped, so they belong within quotes.
+#[derive(Debug, Clone)]
+pub struct PayloadTemplate {
+    template: String,
+}
+
+impl Default for PayloadTemplate {
+    fn default() -> Self {
+        Self {
+            template: constants::DEFAULT_PAYLOAD.to_string(),
+        }
+    }
+}
+
+impl PayloadTemplate {
+    /// Loads the template at `path`, making sure that it renders valid JSON
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let res = Self {
+            template: std::fs::read_to_string(path)?,
+        };
+
+        let sample = Milestone::TargetCovered {
+            kind: "file",
+            target: "\"sample\"".to_string(),
+        };
+        serde_json::from_str::<serde_json::Value>(&res.render(&sample, &Summary::default()))
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Payload template '{}' does not render valid JSON: {e}",
+                    path.display()
+                )
+            })?;
+
+        Ok(res)
+    }
+
+    /// Replaces the placeholders of the template in a single pass, so that the values are never
+    /// mistaken for placeholders themselves
+    fn render(&self, milestone: &Milestone, summary: &Summary) -> String {
+        let mut res = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            res.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let Some(placeholder) = constants::PLACEHOLDERS
+                .iter()
+                .find(|placeholder| rest.starts_with(*placeholder))
+            else {
+                res.push('{');
+                rest = &rest[1..];
+                continue;
+            };
+
+            let value = match *placeholder {
+                "{event}" => milestone.event().to_string(),
+                "{message}" => escape_json(&milestone.message()),
+                "{target}" => escape_json(milestone.target()),
+                "{line_rate}" => format!("{:.2}", line_rate(summary)),
+                "{lines_hit}" => summary.lines_hit.to_string(),
+                _ => summary.lines_found.to_string(),
+            };
+
+            res.push_str(&value);
+            rest = &rest[placeholder.len()..];
+        }
+
+        res.push_str(rest);
+        res
+    }
+}
+
+/// Posts a notification to a webhook whenever the coverage of a watched campaign reaches a
+/// milestone: its line coverage rate grew by `threshold` percentage points since the last
+/// notification about it, or a source file or function that matches one of `targets` has been
+/// executed for the first time.
+///
+/// The first coverage only sets the baseline of the rate.
+pub struct Notifier {
+    client: reqwest::Client,
+    url: String,
+    payload: PayloadTemplate,
+    threshold: f64,
+    targets: Vec<Filter>,
+    baseline_rate: Option<f64>,
+    /// (kind, target) of the targets that have already been covered
+    covered_targets: HashSet<(&'static str, String)>,
+}
+
+impl Notifier {
+    pub fn new(
+        url: String,
+        payload: PayloadTemplate,
+        threshold: f64,
+        targets: Vec<Filter>,
+    ) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(constants::REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            client,
+            url,
+            payload,
+            threshold,
+            targets,
+            baseline_rate: None,
+            covered_targets: HashSet::new(),
+        })
+    }
+
+    fn is_target(&self, name: &str) -> bool {
+        self.targets
+            .iter()
+            .any(|target| target.matcher.is_match(name.as_bytes()))
+    }
+
+    /// Returns the milestones `coverage` reached since the previous call
+    fn milestones(&mut self, coverage: &SourceCoverage, summary: &Summary) -> Vec<Milestone> {
+        let mut res = Vec::new();
+
+        let rate = line_rate(summary);
+        match self.baseline_rate {
+            Some(baseline) if rate - baseline >= self.threshold => {
+                res.push(Milestone::CoverageIncreased {
+                    from: baseline,
+                    to: rate,
+                });
+                self.baseline_rate = Some(rate);
+            }
+            Some(_) => {}
+            None => self.baseline_rate = Some(rate),
+        }
+
+        let covered_files = coverage
+            .lines
+            .iter()
+            .filter(|(_, lines)| lines.iter().any(|info| info.executed))
+            .map(|(file, _)| ("file", file.clone()));
+
+        let covered_functions = coverage
+            .functions
+            .values()
+            .flatten()
+            .filter(|function| function.executed)
+            .map(|function| ("function", function.name.clone()));
+
+        let newly_covered = covered_files
+            .chain(covered_functions)
+            .filter(|(_, target)| self.is_target(target))
+            .filter(|covered| !self.covered_targets.contains(covered))
+            .collect::<BTreeSet<_>>();
+
+        for (kind, target) in newly_covered {
+            self.covered_targets.insert((kind, target.clone()));
+            res.push(Milestone::TargetCovered { kind, target });
+        }
+
+        res
+    }
+
+    /// Returns the milestones `coverage` reached since the previous call, along with the payloads
+    /// of their notifications
+    pub fn payloads(&mut self, coverage: &SourceCoverage) -> Vec<(Milestone, String)> {
+        let summary = Summary::from_coverage(coverage);
+
+        self.milestones(coverage, &summary)
+            .into_iter()
+            .map(|milestone| {
+                let payload = self.payload.render(&milestone, &summary);
+                (milestone, payload)
+            })
+            .collect()
+    }
+
+    /// Posts a notification about every milestone `coverage` reached since the previous call.
+    /// Failing to deliver a notification is only logged, since it must not stop the campaign.
+    pub fn notify(&mut self, runtime: &tokio::runtime::Runtime, coverage: &SourceCoverage) {
+        for (milestone, payload) in self.payloads(coverage) {
+            log::info!("{}", milestone.message());
+
+            if let Err(e) = read_only::check_network(&self.url) {
+                log::warn!("Could not notify '{}' of a milestone: {e}", self.url);
+                continue;
+            }
+
+            let request = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload);
+
+            match runtime
+                .block_on(async { request.send().await })
+                .and_then(|response| response.error_for_status())
+            {
+                Ok(_) => log::debug!("Notified '{}' of {}", self.url, milestone.event()),
+                Err(e) => log::warn!("Could not notify '{}' of a milestone: {e}", self.url),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::LineInfo;
+
+    fn coverage(file: &str, executed: &[bool]) -> SourceCoverage {
+        let mut res = SourceCoverage::default();
+        res.lines.insert(
+            file.to_string(),
+            executed
+                .iter()
+                .enumerate()
+                .map(|(line, executed)| LineInfo {
+                    line: line as u64 + 1,
+                    executed: *executed,
+                })
+                .collect(),
+        );
+        res
+    }
+
+    #[test]
+    fn notifies_of_milestones_once() {
+        let mut notifier = Notifier::new(
+            "http://localhost/".to_string(),
+            PayloadTemplate::default(),
+            10.0,
+            vec!["parser".parse().unwrap()],
+        )
+        .unwrap();
+
+        let mut milestones = |coverage: &SourceCoverage| {
+            notifier.milestones(coverage, &Summary::from_coverage(coverage))
+        };
+
+        assert!(milestones(&coverage("src/parser.c", &[false, false, false, false])).is_empty());
+        assert_eq!(
+            milestones(&coverage("src/parser.c", &[true, false, false, false])),
+            vec![
+                Milestone::CoverageIncreased {
+                    from: 0.0,
+                    to: 25.0
+                },
+                Milestone::TargetCovered {
+                    kind: "file",
+                    target: "src/parser.c".to_string()
+                }
+            ]
+        );
+        assert!(milestones(&coverage("src/parser.c", &[true, false, false, false])).is_empty());
+    }
+}
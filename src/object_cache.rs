@@ -0,0 +1,420 @@
+use crate::dwarf::{find_object_with_debug_info, LineInfoFilters, ModuleLineTable, ObjectFile};
+use crate::error::Error;
+use crate::line_table_store;
+use crate::util::assert_send_sync;
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The entries of a cache keyed by module path, of which the least recently used ones are evicted
+/// once there are more than `capacity`
+#[derive(Debug)]
+struct LruMap<V> {
+    /// Module path -> when the entry was last used, and the entry
+    entries: HashMap<String, (u64, V)>,
+    /// Incremented whenever an entry is used
+    clock: u64,
+    capacity: Option<usize>,
+}
+
+impl<V> Default for LruMap<V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            clock: 0,
+            capacity: None,
+        }
+    }
+}
+
+impl<V> LruMap<V> {
+    fn get(&mut self, path: &str) -> Option<&V> {
+        self.clock += 1;
+        let (last_used, value) = self.entries.get_mut(path)?;
+        *last_used = self.clock;
+
+        Some(value)
+    }
+
+    fn insert(&mut self, path: String, value: V) {
+        self.clock += 1;
+        self.entries.insert(path, (self.clock, value));
+
+        while self
+            .capacity
+            .is_some_and(|capacity| self.entries.len() > capacity)
+        {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (last_used, _))| *last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values().map(|(_, value)| value)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[derive(Debug)]
+struct CachedObjectFile {
+    /// The modification time of the module when its object file was loaded
+    modified: SystemTime,
+    object_file: Arc<ObjectFile>,
+}
+
+/// The object files with the debug information of the modules, kept parsed between conversions
+/// (e.g. the requests of `drcov2lcov serve`), so that only the first conversion of a module has to
+/// read it and find its debug information.
+///
+/// An object file is loaded again once its module has been modified. Modules without debug
+/// information are looked up again every time, since it may have been installed since. The object
+/// files are read into memory rather than mapped, since a binary that is rebuilt in place would
+/// fault the mapping of a cached one.
+///
+/// Caches that convert untrusted inputs can be bounded with [`ObjectFileCache::with_capacity`],
+/// and restricted to the modules of some directories with [`ObjectFileCache::with_roots`].
+#[derive(Debug, Default)]
+pub struct ObjectFileCache {
+    /// Module path -> the object file with the debug information of the module
+    entries: Mutex<LruMap<CachedObjectFile>>,
+    /// The (canonical) directories the modules must be in, if they are restricted
+    roots: Option<Vec<PathBuf>>,
+}
+
+const _: () = assert_send_sync::<ObjectFileCache>();
+
+impl ObjectFileCache {
+    /// Keeps at most `capacity` object files, evicting the least recently used ones
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        if let Ok(entries) = self.entries.get_mut() {
+            entries.capacity = Some(capacity);
+        }
+        self
+    }
+
+    /// Only loads the object files of the modules (after following symbolic links) under one of
+    /// the `roots` directories. Fails if one of them cannot be resolved, rather than leaving it out
+    /// and loading less restricted modules than asked for.
+    pub fn with_roots(mut self, roots: &[impl AsRef<Path>]) -> Result<Self, Error> {
+        let roots = roots
+            .iter()
+            .map(|root| {
+                let root = root.as_ref();
+                root.canonicalize().map_err(|e| {
+                    Error::Io(std::io::Error::new(
+                        e.kind(),
+                        format!("Could not resolve the directory '{}': {e}", root.display()),
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        self.roots = Some(roots);
+        Ok(self)
+    }
+
+    /// Fails unless the module at `path` is under one of the roots of the cache
+    fn check_root(&self, path: &str) -> Result<(), Error> {
+        let Some(roots) = &self.roots else {
+            return Ok(());
+        };
+
+        let canonical = Path::new(path).canonicalize()?;
+        if !roots.iter().any(|root| canonical.starts_with(root)) {
+            return Err(Error::debug_info(
+                path,
+                "The module is outside of the binary directories",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the object file with the debug information of the module at `path`, if it has any
+    pub fn get(&self, path: &str) -> Result<Option<Arc<ObjectFile>>, Error> {
+        self.check_root(path)?;
+        let modified = std::fs::metadata(path)?.modified()?;
+
+        if let Ok(mut entries) = self.entries.lock() {
+            if let Some(entry) = entries.get(path).filter(|entry| entry.modified == modified) {
+                return Ok(Some(entry.object_file.clone()));
+            }
+        }
+
+        // Loaded without holding the lock, so that the other modules can be looked up meanwhile
+        let Some(object_file) =
+            find_object_with_debug_info(path, ObjectFile::read_from_path)?.map(Arc::new)
+        else {
+            return Ok(None);
+        };
+
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.insert(
+                path.to_string(),
+                CachedObjectFile {
+                    modified,
+                    object_file: object_file.clone(),
+                },
+            );
+        }
+
+        Ok(Some(object_file))
+    }
+
+    /// The number of object files in the cache
+    pub fn len(&self) -> usize {
+        self.entries.lock().map_or(0, |entries| entries.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Default)]
+struct CachedLineTable {
+    /// The modification time of the module when its line table was read
+    modified: Option<SystemTime>,
+    line_table: Option<Arc<ModuleLineTable>>,
+}
+
+/// The line tables of the modules, i.e. their source lines by address and their functions, kept
+/// between the input files of a conversion (and between conversions, e.g. the requests of
+/// `drcov2lcov serve`), so that the debug information of a module is only walked for the first
+/// input it appears in and the others only look their basic blocks up in it.
+///
+/// The line tables depend on the source filters and the source path resolver, so a cache must only
+/// be shared between conversions that use the same ones. A line table is read again once its
+/// module has been modified; the line tables of modules that failed to be read are not cached.
+///
+/// With [`LineTableCache::with_directory`], the line tables are also stored on disk for the later
+/// processes to load them instead of reading the debug information of the modules, and with
+/// [`LineTableCache::with_capacity`] only the most recently used ones are kept in memory.
+#[derive(Default)]
+pub struct LineTableCache {
+    /// Module path -> the line table of the module, locked while it is being read so that the
+    /// inputs processed concurrently wait for it instead of reading it as well
+    entries: Mutex<LruMap<Arc<Mutex<CachedLineTable>>>>,
+    directory: Option<PathBuf>,
+}
+
+const _: () = assert_send_sync::<LineTableCache>();
+
+impl Debug for LineTableCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LineTableCache")
+            .field("len", &self.len())
+            .field("directory", &self.directory)
+            .finish()
+    }
+}
+
+impl LineTableCache {
+    /// A cache that also stores the line tables in `directory`, keyed by the build-id of their
+    /// module (or its path and modification time) and by the source filters
+    pub fn with_directory(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: Some(directory.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Keeps at most `capacity` line tables in memory, evicting the least recently used ones
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        if let Ok(entries) = self.entries.get_mut() {
+            entries.capacity = Some(capacity);
+        }
+        self
+    }
+
+    /// Loads the line table of the module at `path` from the directory of the cache, if any
+    pub(crate) fn load(
+        &self,
+        path: &str,
+        object_file: &ObjectFile,
+        filters: &LineInfoFilters,
+    ) -> Option<ModuleLineTable> {
+        line_table_store::load(self.directory.as_deref()?, path, object_file, filters)
+    }
+
+    /// Stores the line table of the module at `path` in the directory of the cache, if any
+    pub(crate) fn store(
+        &self,
+        path: &str,
+        object_file: &ObjectFile,
+        filters: &LineInfoFilters,
+        line_table: &ModuleLineTable,
+    ) {
+        if let Some(directory) = &self.directory {
+            line_table_store::store(directory, path, object_file, filters, line_table);
+        }
+    }
+
+    /// Returns the cached line table of the module at `path`, reading it with `read` if it is not
+    /// cached yet or its module has been modified since
+    pub(crate) fn get(
+        &self,
+        path: &str,
+        read: impl FnOnce() -> anyhow::Result<ModuleLineTable>,
+    ) -> anyhow::Result<Arc<ModuleLineTable>> {
+        let modified = std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok();
+
+        let entry = match self.entries.lock() {
+            Ok(mut entries) => match entries.get(path) {
+                Some(entry) => entry.clone(),
+                None => {
+                    let entry = Arc::<Mutex<CachedLineTable>>::default();
+                    entries.insert(path.to_string(), entry.clone());
+                    entry
+                }
+            },
+            Err(_) => return read().map(Arc::new),
+        };
+
+        let Ok(mut entry) = entry.lock() else {
+            return read().map(Arc::new);
+        };
+
+        match &entry.line_table {
+            Some(line_table) if modified.is_some() && entry.modified == modified => {
+                Ok(line_table.clone())
+            }
+            _ => {
+                let line_table = Arc::new(read()?);
+                entry.modified = modified;
+                entry.line_table = modified.is_some().then(|| line_table.clone());
+
+                Ok(line_table)
+            }
+        }
+    }
+
+    /// The number of line tables in the cache, not counting those that are being read
+    pub fn len(&self) -> usize {
+        self.entries.lock().map_or(0, |entries| {
+            entries
+                .values()
+                .filter(|entry| {
+                    entry
+                        .try_lock()
+                        .is_ok_and(|entry| entry.line_table.is_some())
+                })
+                .count()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aliases::FunctionSymbols;
+    use crate::test_fixtures::{small_binary, test_binary, TempPath};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::OnceLock;
+
+    #[test]
+    fn evicts_the_least_recently_used_entries() {
+        let mut entries = LruMap {
+            capacity: Some(2),
+            ..Default::default()
+        };
+        entries.insert("a".to_string(), 1);
+        entries.insert("b".to_string(), 2);
+        assert_eq!(entries.get("a"), Some(&1));
+
+        entries.insert("c".to_string(), 3);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries.get("b"), None);
+        assert_eq!(entries.get("a"), Some(&1));
+        assert_eq!(entries.get("c"), Some(&3));
+    }
+
+    #[test]
+    fn only_loads_the_regular_files_of_its_roots() {
+        let dir = TempPath::dir("roots");
+        let fifo = dir.join("fifo");
+        let created = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .is_ok_and(|status| status.success());
+
+        let cache = ObjectFileCache::default()
+            .with_roots(&[dir.path()])
+            .unwrap();
+        assert!(cache.get(&test_binary()).is_err());
+        if created {
+            assert!(cache.get(&fifo.to_string_lossy()).is_err());
+        }
+        assert!(cache.is_empty());
+
+        // A root that cannot be resolved is not left out, which would let more modules be loaded
+        assert!(ObjectFileCache::default()
+            .with_roots(&[dir.path(), &dir.join("missing")])
+            .is_err());
+    }
+
+    #[test]
+    fn is_shared_between_threads() {
+        let path = small_binary();
+        let object_files = ObjectFileCache::default();
+        let line_tables = LineTableCache::default();
+        let reads = AtomicUsize::new(0);
+
+        let read = || {
+            reads.fetch_add(1, Ordering::Relaxed);
+            // Long enough for the other threads to wait for the line table
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            Ok(ModuleLineTable {
+                load_base: 0,
+                files: Vec::new(),
+                units: Vec::new(),
+                symbols: FunctionSymbols::default(),
+                blocks: OnceLock::new(),
+            })
+        };
+
+        let (object_files_found, tables) = std::thread::scope(|scope| {
+            let handles = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (
+                            object_files.get(&path).unwrap().is_some(),
+                            line_tables.get(&path, read).unwrap(),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .unzip::<_, _, Vec<_>, Vec<_>>()
+        });
+
+        assert!(object_files_found.into_iter().all(|found| found));
+        assert_eq!(object_files.len(), 1);
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+        assert!(tables.iter().all(|table| Arc::ptr_eq(table, &tables[0])));
+        assert_eq!(line_tables.len(), 1);
+    }
+}
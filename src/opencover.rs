@@ -0,0 +1,202 @@
+use crate::dwarf::{FunctionInfo, SourceCoverage};
+use crate::output::{write_text_output, CoverageReport, CoverageWriter, LineEnding};
+use crate::util::xml_escape;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+#[derive(Debug, Default)]
+struct Totals {
+    sequence_points: usize,
+    visited_sequence_points: usize,
+    methods: usize,
+    visited_methods: usize,
+}
+
+impl Totals {
+    fn add(&mut self, other: &Totals) {
+        self.sequence_points += other.sequence_points;
+        self.visited_sequence_points += other.visited_sequence_points;
+        self.methods += other.methods;
+        self.visited_methods += other.visited_methods;
+    }
+
+    fn write_summary(&self, res: &mut String, indent: &str) {
+        let sequence_coverage = if self.sequence_points == 0 {
+            0.0
+        } else {
+            self.visited_sequence_points as f64 * 100.0 / self.sequence_points as f64
+        };
+
+        let _ = writeln!(
+            res,
+            r#"{indent}<Summary numSequencePoints="{}" visitedSequencePoints="{}" numBranchPoints="0" visitedBranchPoints="0" sequenceCoverage="{sequence_coverage:.2}" branchCoverage="0" numMethods="{}" visitedMethods="{}" />"#,
+            self.sequence_points, self.visited_sequence_points, self.methods, self.visited_methods
+        );
+    }
+}
+
+fn module_hash(module: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    module.hash(&mut hasher);
+    format!("{:016X}", hasher.finish())
+}
+
+fn file_name(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+/// Writes the coverage as an OpenCover XML report (as consumed by ReportGenerator).
+///
+/// Modules map to `Module`s, the source files of a module to `Class`es, DWARF subprograms to
+/// `Method`s and the line records of each subprogram to `SequencePoint`s.
+pub fn write_opencover_output(
+    path: &str,
+    coverage: &SourceCoverage,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    // module -> source file -> functions
+    let mut modules = BTreeMap::<&str, BTreeMap<&str, Vec<&FunctionInfo>>>::new();
+
+    for (file, functions) in &coverage.functions {
+        for function in functions {
+            modules
+                .entry(function.module.as_str())
+                .or_default()
+                .entry(file.as_str())
+                .or_default()
+                .push(function);
+        }
+    }
+
+    let mut body = String::new();
+    let mut session_totals = Totals::default();
+    let mut sequence_point_id = 0usize;
+
+    for (module, files) in &modules {
+        let mut module_totals = Totals::default();
+        let mut classes = String::new();
+
+        for (file_id, (file, functions)) in files.iter().enumerate() {
+            let file_id = file_id + 1;
+            let mut class_totals = Totals::default();
+            let mut methods = String::new();
+
+            for function in functions {
+                let mut method_totals = Totals {
+                    methods: 1,
+                    visited_methods: function.executed as usize,
+                    ..Default::default()
+                };
+                let mut sequence_points = String::new();
+
+                for (ordinal, info) in coverage.function_lines(file, function).enumerate() {
+                    sequence_point_id += 1;
+                    method_totals.sequence_points += 1;
+                    method_totals.visited_sequence_points += info.executed as usize;
+
+                    let _ = writeln!(
+                        sequence_points,
+                        r#"                <SequencePoint vc="{}" uspid="{sequence_point_id}" ordinal="{ordinal}" offset="{ordinal}" sl="{line}" sc="1" el="{line}" ec="2" bec="0" bev="0" fileid="{file_id}" />"#,
+                        info.executed as usize,
+                        line = info.line,
+                    );
+                }
+
+                let _ = writeln!(
+                    methods,
+                    r#"            <Method visited="{}" cyclomaticComplexity="1" sequenceCoverage="0" branchCoverage="0" isConstructor="false" isGetter="false" isSetter="false" isStatic="true">"#,
+                    function.executed
+                );
+                method_totals.write_summary(&mut methods, "              ");
+                let _ = writeln!(
+                    methods,
+                    "              <Name>void {}::{}()</Name>",
+                    xml_escape(file_name(file)),
+                    xml_escape(&function.name)
+                );
+                let _ = writeln!(methods, r#"              <FileRef uid="{file_id}" />"#);
+                let _ = writeln!(methods, "              <SequencePoints>");
+                methods.push_str(&sequence_points);
+                let _ = writeln!(methods, "              </SequencePoints>");
+                let _ = writeln!(methods, "              <BranchPoints />");
+                let _ = writeln!(methods, "            </Method>");
+
+                class_totals.add(&method_totals);
+            }
+
+            let _ = writeln!(classes, "        <Class>");
+            class_totals.write_summary(&mut classes, "          ");
+            let _ = writeln!(
+                classes,
+                "          <FullName>{}</FullName>",
+                xml_escape(file_name(file))
+            );
+            let _ = writeln!(classes, "          <Methods>");
+            classes.push_str(&methods);
+            let _ = writeln!(classes, "          </Methods>");
+            let _ = writeln!(classes, "        </Class>");
+
+            module_totals.add(&class_totals);
+        }
+
+        let _ = writeln!(body, r#"    <Module hash="{}">"#, module_hash(module));
+        module_totals.write_summary(&mut body, "      ");
+        let _ = writeln!(
+            body,
+            "      <ModulePath>{}</ModulePath>",
+            xml_escape(module)
+        );
+        let _ = writeln!(
+            body,
+            "      <ModuleName>{}</ModuleName>",
+            xml_escape(file_name(module))
+        );
+        let _ = writeln!(body, "      <Files>");
+        for (file_id, file) in files.keys().enumerate() {
+            let _ = writeln!(
+                body,
+                r#"        <File uid="{}" fullPath="{}" />"#,
+                file_id + 1,
+                xml_escape(file)
+            );
+        }
+        let _ = writeln!(body, "      </Files>");
+        let _ = writeln!(body, "      <Classes>");
+        body.push_str(&classes);
+        let _ = writeln!(body, "      </Classes>");
+        let _ = writeln!(body, "    </Module>");
+
+        session_totals.add(&module_totals);
+    }
+
+    let mut res = String::new();
+    let _ = writeln!(res, r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    let _ = writeln!(res, "<CoverageSession>");
+    session_totals.write_summary(&mut res, "  ");
+    let _ = writeln!(res, "  <Modules>");
+    res.push_str(&body);
+    let _ = writeln!(res, "  </Modules>");
+    let _ = writeln!(res, "</CoverageSession>");
+
+    write_text_output(path, &res, line_ending)?;
+
+    Ok(())
+}
+
+pub struct OpenCoverWriter;
+
+impl CoverageWriter for OpenCoverWriter {
+    fn name(&self) -> &'static str {
+        "OpenCover"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_opencover_output(path, report.coverage, report.line_ending)
+    }
+}
@@ -0,0 +1,478 @@
+use crate::dwarf::ObjectFile;
+#[cfg(feature = "native")]
+use crate::module_key::ModuleKey;
+#[cfg(feature = "native")]
+use crate::output::{write_text_output, LineEnding};
+use crate::read_only;
+use itertools::Itertools;
+use object::{Architecture, Object};
+#[cfg(feature = "native")]
+use roaring::RoaringBitmap;
+#[cfg(feature = "native")]
+use std::collections::HashMap;
+#[cfg(feature = "native")]
+use std::fmt::Write;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::Regex;
+
+    /// The disassembler the basic blocks are recovered with, from GNU binutils
+    pub const OBJDUMP: &str = "objdump";
+
+    /// The prefixes objdump prints before the mnemonic of an instruction
+    pub const PREFIXES: [&str; 11] = [
+        "bnd", "notrack", "lock", "rep", "repz", "repe", "repnz", "repne", "data16", "cs", "ds",
+    ];
+
+    lazy_static! {
+        /// The header of a function, e.g. `0000000000001139 <square>:`
+        pub static ref FUNCTION_REGEX: Regex =
+            Regex::new(r"^(?P<address>[0-9a-f]+) <(?P<name>.+)>:$").unwrap();
+        pub static ref INSTRUCTION_REGEX: Regex =
+            Regex::new(r"^\s+(?P<address>[0-9a-f]+):\s+(?P<instruction>.*)$").unwrap();
+        /// The target of a direct branch, e.g. `1196 <main+0x31>`
+        pub static ref TARGET_REGEX: Regex =
+            Regex::new(r"(?:^|[\s,])(?P<target>[0-9a-f]+) <[^>]*>").unwrap();
+    }
+}
+
+/// The instruction sets whose control flow can be recovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InstructionSet {
+    X86,
+    Aarch64,
+}
+
+/// Where the control flow goes after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    /// To the next instruction, within the same basic block
+    Next,
+    /// To the next instruction, in a new basic block (calls and system calls)
+    Call,
+    /// To the target or the next instruction
+    Branch(Option<u64>),
+    /// Only to the target
+    Jump(Option<u64>),
+    /// To an unknown target (e.g. through a jump table)
+    IndirectJump,
+    /// Nowhere within the function (returns, traps)
+    Stop,
+}
+
+impl Flow {
+    fn ends_block(self) -> bool {
+        self != Flow::Next
+    }
+
+    fn falls_through(self) -> bool {
+        matches!(self, Flow::Next | Flow::Call | Flow::Branch(_))
+    }
+
+    fn target(self) -> Option<u64> {
+        match self {
+            Flow::Branch(target) | Flow::Jump(target) => target,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Instruction {
+    address: u64,
+    flow: Flow,
+    /// Alignment padding between the functions, which is never executed
+    padding: bool,
+}
+
+impl Instruction {
+    /// Parses an instruction of the objdump listing, e.g. `jle    1196 <main+0x31>`
+    fn parse(address: u64, instruction: &str, set: InstructionSet) -> Self {
+        let mut tokens = instruction.split_whitespace().peekable();
+        while tokens
+            .next_if(|token| constants::PREFIXES.contains(token))
+            .is_some()
+        {}
+
+        let mnemonic = tokens.next().unwrap_or_default();
+        let operands = tokens.join(" ");
+        let target = constants::TARGET_REGEX
+            .captures(&operands)
+            .and_then(|captures| u64::from_str_radix(&captures["target"], 16).ok());
+
+        let flow = match set {
+            InstructionSet::X86 => match mnemonic {
+                "jmp" | "jmpq" | "ljmp" if operands.starts_with('*') => Flow::IndirectJump,
+                "jmp" | "jmpq" | "ljmp" => Flow::Jump(target),
+                _ if mnemonic.starts_with('j') || mnemonic.starts_with("loop") => {
+                    Flow::Branch(target)
+                }
+                _ if mnemonic.starts_with("call") || mnemonic.starts_with("lcall") => Flow::Call,
+                "syscall" | "sysenter" | "int" => Flow::Call,
+                _ if mnemonic.starts_with("ret") || mnemonic.starts_with("iret") => Flow::Stop,
+                "hlt" | "ud2" | "int3" => Flow::Stop,
+                _ => Flow::Next,
+            },
+            InstructionSet::Aarch64 => match mnemonic {
+                "b" => Flow::Jump(target),
+                "bl" | "svc" => Flow::Call,
+                "cbz" | "cbnz" | "tbz" | "tbnz" => Flow::Branch(target),
+                _ if mnemonic.starts_with("b.") || mnemonic.starts_with("bc.") => {
+                    Flow::Branch(target)
+                }
+                _ if mnemonic.starts_with("blr") => Flow::Call,
+                _ if mnemonic.starts_with("br") && mnemonic != "brk" => Flow::IndirectJump,
+                _ if mnemonic.starts_with("ret") || mnemonic == "eret" => Flow::Stop,
+                "brk" | "udf" => Flow::Stop,
+                _ => Flow::Next,
+            },
+        };
+
+        let padding = mnemonic.starts_with("nop")
+            || mnemonic == "int3"
+            || (mnemonic == "xchg" && operands == "%ax,%ax");
+
+        Self {
+            address,
+            flow,
+            padding,
+        }
+    }
+}
+
+/// A basic block recovered from the disassembly of a module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticBlock {
+    /// The (link time) address range `[start, end)` of the block
+    pub start: u64,
+    pub end: u64,
+    pub function: String,
+    /// Whether the block can be reached from the entry of its function
+    pub reachable: bool,
+    /// Where the conditional branch that ends the block goes: its target, if it is a known address
+    /// of the function, and the next block. Empty for the blocks that do not end with one.
+    pub branch_targets: Vec<u64>,
+}
+
+/// Splits the instructions of `function` into basic blocks: a block starts at the entry of the
+/// function, at every target of its direct branches and after every instruction that transfers
+/// control, like the blocks of DynamoRIO. `end` is where the last instruction ends.
+///
+/// The blocks that can be reached from the entry by the direct branches are reachable. The targets
+/// of indirect jumps are unknown, so every block of a function with one is reachable, save for the
+/// padding.
+fn function_blocks(function: &str, instructions: &[Instruction], end: u64) -> Vec<StaticBlock> {
+    let Some(first) = instructions.first() else {
+        return Vec::new();
+    };
+
+    let contains = |address: u64| (first.address..end).contains(&address);
+
+    let mut leaders = vec![false; instructions.len()];
+    leaders[0] = true;
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        if instruction.flow.ends_block() && index + 1 < instructions.len() {
+            leaders[index + 1] = true;
+        }
+
+        if let Some(target) = instruction.flow.target().filter(|target| contains(*target)) {
+            if let Ok(index) = instructions.binary_search_by_key(&target, |i| i.address) {
+                leaders[index] = true;
+            }
+        }
+    }
+
+    // Block -> the index of its first instruction and one past its last
+    let bounds = leaders
+        .iter()
+        .positions(|leader| *leader)
+        .chain([instructions.len()])
+        .tuple_windows()
+        .collect::<Vec<(usize, usize)>>();
+    let block_of = |address: u64| {
+        bounds.partition_point(|(first, _)| instructions[*first].address <= address) - 1
+    };
+
+    let has_indirect_jump = instructions
+        .iter()
+        .any(|instruction| instruction.flow == Flow::IndirectJump);
+    let is_padding = |(first, last): (usize, usize)| {
+        instructions[first..last]
+            .iter()
+            .all(|instruction| instruction.padding)
+    };
+
+    let mut reachable = vec![false; bounds.len()];
+    let mut pending = vec![0];
+
+    while let Some(block) = pending.pop() {
+        if std::mem::replace(&mut reachable[block], true) {
+            continue;
+        }
+
+        let last = &instructions[bounds[block].1 - 1];
+        if last.flow.falls_through() && block + 1 < bounds.len() {
+            pending.push(block + 1);
+        }
+
+        if let Some(target) = last.flow.target().filter(|target| contains(*target)) {
+            pending.push(block_of(target));
+        }
+    }
+
+    bounds
+        .iter()
+        .enumerate()
+        .map(|(block, (first, last))| {
+            let block_end = instructions.get(*last).map_or(end, |i| i.address);
+            let branch_targets = match instructions[*last - 1].flow {
+                Flow::Branch(target) => target
+                    .filter(|target| contains(*target))
+                    .into_iter()
+                    .chain((block + 1 < bounds.len()).then_some(block_end))
+                    .collect(),
+                _ => Vec::new(),
+            };
+
+            StaticBlock {
+                start: instructions[*first].address,
+                end: block_end,
+                function: function.to_string(),
+                reachable: reachable[block] || (has_indirect_jump && !is_padding((*first, *last))),
+                branch_targets,
+            }
+        })
+        .collect()
+}
+
+/// Recovers the basic blocks of the functions of an `objdump -d` listing
+fn parse_disassembly(listing: &str, set: InstructionSet) -> Vec<StaticBlock> {
+    let mut functions = Vec::<(String, Vec<Instruction>)>::new();
+
+    for line in listing.lines() {
+        if let Some(captures) = constants::FUNCTION_REGEX.captures(line) {
+            functions.push((captures["name"].to_string(), Vec::new()));
+        } else if let Some(captures) = constants::INSTRUCTION_REGEX.captures(line) {
+            let (Ok(address), Some((_, instructions))) = (
+                u64::from_str_radix(&captures["address"], 16),
+                functions.last_mut(),
+            ) else {
+                continue;
+            };
+
+            instructions.push(Instruction::parse(address, &captures["instruction"], set));
+        }
+    }
+
+    let mut res = Vec::new();
+
+    for (index, (name, instructions)) in functions.iter().enumerate() {
+        // The size of the last instruction is not in the listing, so it ends where the next
+        // function starts
+        let end = functions[index + 1..]
+            .iter()
+            .find_map(|(_, instructions)| instructions.first())
+            .map(|instruction| instruction.address)
+            .filter(|next| instructions.last().is_some_and(|last| last.address < *next))
+            .or_else(|| instructions.last().map(|last| last.address + 1))
+            .unwrap_or_default();
+
+        res.extend(function_blocks(name, instructions, end));
+    }
+
+    res
+}
+
+/// Disassembles the module at `path` with objdump and recovers its basic blocks
+pub fn static_blocks(path: &str) -> anyhow::Result<Vec<StaticBlock>> {
+    let object_file = ObjectFile::from_path(path)?;
+    let set = match object_file.with_object(|obj| obj).architecture() {
+        Architecture::X86_64 | Architecture::X86_64_X32 | Architecture::I386 => InstructionSet::X86,
+        Architecture::Aarch64 => InstructionSet::Aarch64,
+        arch => anyhow::bail!("Recovering the basic blocks of {arch:?} code is not supported"),
+    };
+
+    let output = read_only::tool(constants::OBJDUMP)
+        .args(["-d", "-w", "--no-show-raw-insn"])
+        .arg(path)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Could not run {}: {e}", constants::OBJDUMP))?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} failed: {}",
+            constants::OBJDUMP,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(parse_disassembly(
+        &String::from_utf8_lossy(&output.stdout),
+        set,
+    ))
+}
+
+/// Writes the basic block coverage of every module of `modules` against the reachable basic
+/// blocks of its disassembly to `path`: the covered and reachable blocks of every module, followed
+/// by the blocks that have never been covered.
+///
+/// `modules` maps each module to the module relative offsets of its executed bytes. A reachable
+/// block is covered if any of its bytes has been executed; blocks that have been executed although
+/// they seem unreachable count as reachable.
+#[cfg(feature = "native")]
+pub fn write_oracle_report(
+    path: &str,
+    modules: &HashMap<ModuleKey, RoaringBitmap>,
+    scrub: impl Fn(&str) -> String,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    let mut summaries = String::new();
+    let mut never_covered = String::new();
+
+    for (module, executed) in modules
+        .iter()
+        .sorted_by(|(a, _), (b, _)| a.path.cmp(&b.path))
+    {
+        let blocks = match static_blocks(&module.path) {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                log::warn!("Could not recover the basic blocks of {}: {e}", module.path);
+                continue;
+            }
+        };
+
+        let load_base = ObjectFile::from_path(&module.path)?.load_base();
+        let module_path = scrub(&module.path);
+        let (mut reachable, mut covered) = (0, 0);
+
+        for block in blocks {
+            let (Some(start), Some(end)) = (
+                block.start.checked_sub(load_base),
+                block.end.checked_sub(load_base),
+            ) else {
+                continue;
+            };
+
+            if end > u32::MAX as u64 {
+                continue;
+            }
+
+            let is_covered = executed.range_cardinality(start as u32..end as u32) > 0;
+            if !block.reachable && !is_covered {
+                continue;
+            }
+
+            reachable += 1;
+            if is_covered {
+                covered += 1;
+            } else {
+                let _ = writeln!(
+                    never_covered,
+                    "{module_path}\t{start:#x}\t{}\t{}",
+                    end - start,
+                    block.function
+                );
+            }
+        }
+
+        let rate = if reachable == 0 {
+            0.0
+        } else {
+            covered as f64 * 100.0 / reachable as f64
+        };
+
+        log::info!(
+            "{covered} of the {reachable} reachable basic blocks of {} covered ({rate:.2}%)",
+            module.path
+        );
+        let _ = writeln!(
+            summaries,
+            "# {module_path}: {covered}/{reachable} reachable basic blocks covered ({rate:.2}%)"
+        );
+    }
+
+    write_text_output(
+        path,
+        &format!("{summaries}# module\toffset\tsize\tfunction\n{never_covered}"),
+        line_ending,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_the_reachable_basic_blocks() {
+        let listing = "\
+0000000000001139 <square>:
+    1139:\tpush   %rbp
+    113a:\tcmp    $0x1,%edi
+    113d:\tjle    1144 <square+0xb>
+    113f:\tcall   1139 <square>
+    1144:\tret
+    1145:\tjmp    1139 <square>
+    1147:\tcs nopw 0x0(%rax,%rax,1)
+
+0000000000001150 <main>:
+    1150:\tnotrack jmp *%rax
+    1153:\tmov    %eax,%edi
+    1155:\tret
+    1156:\txchg   %ax,%ax
+";
+
+        let blocks = parse_disassembly(listing, InstructionSet::X86)
+            .into_iter()
+            .map(|block| (block.start, block.end, block.reachable))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            blocks,
+            vec![
+                (0x1139, 0x113f, true),
+                (0x113f, 0x1144, true),
+                (0x1144, 0x1145, true),
+                (0x1145, 0x1147, false),
+                (0x1147, 0x1150, false),
+                (0x1150, 0x1153, true),
+                (0x1153, 0x1156, true),
+                (0x1156, 0x1157, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn records_where_the_conditional_branches_go() {
+        let listing = "\
+0000000000001139 <square>:
+    1139:\tcmp    $0x1,%edi
+    113c:\tjle    1144 <square+0xb>
+    113e:\tjmp    1139 <square>
+    1140:\tjne    1000 <other>
+    1142:\tnop
+    1143:\tnop
+    1144:\tret
+";
+
+        let blocks = parse_disassembly(listing, InstructionSet::X86)
+            .into_iter()
+            .map(|block| (block.start, block.branch_targets))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            blocks,
+            vec![
+                (0x1139, vec![0x1144, 0x113e]),
+                (0x113e, vec![]),
+                // Targets outside of the function are unknown
+                (0x1140, vec![0x1142]),
+                (0x1142, vec![]),
+                (0x1144, vec![]),
+            ]
+        );
+    }
+}
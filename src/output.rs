@@ -0,0 +1,105 @@
+use crate::callgrind::CallgrindWriter;
+use crate::checksum::LineChecksums;
+use crate::cobertura::CoberturaWriter;
+use crate::components::ComponentMap;
+use crate::dwarf::SourceCoverage;
+use crate::istanbul::IstanbulWriter;
+use crate::json::JsonWriter;
+use crate::lcov::LcovWriter;
+use crate::lighthouse::LighthouseWriter;
+use crate::module_key::ModuleKey;
+use crate::opencover::OpenCoverWriter;
+use crate::read_only;
+#[cfg(feature = "native")]
+use crate::sqlite::SqliteWriter;
+use clap::ValueEnum;
+use roaring::RoaringBitmap;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The finalized (merged, relativized and scrubbed) coverage that is handed to every
+/// [`CoverageWriter`]
+#[derive(Debug, Clone, Copy)]
+pub struct CoverageReport<'c> {
+    /// Source file -> line and function coverage
+    pub coverage: &'c SourceCoverage,
+    /// Module -> module relative offsets of the executed basic blocks
+    pub modules: &'c HashMap<ModuleKey, RoaringBitmap>,
+    /// MD5 checksums of the source lines, if they were requested
+    pub checksums: Option<&'c LineChecksums>,
+    /// Named coverage that is also written as separate tests by the formats that support them
+    /// (e.g. the iterations of persistent-mode harnesses)
+    pub tests: &'c [(String, SourceCoverage)],
+    /// The components the coverage is rolled up by, if a component map was given
+    pub components: Option<&'c ComponentMap>,
+    /// The line endings of text outputs
+    pub line_ending: LineEnding,
+}
+
+/// The line endings text outputs are written with
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+/// Writes a text output: always UTF-8 without a byte order mark, since some report ingestion tools
+/// are strict about the encoding of coverage files, and with the given line endings
+pub fn write_text_output(
+    path: impl AsRef<Path>,
+    contents: &str,
+    line_ending: LineEnding,
+) -> std::io::Result<()> {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+
+    match line_ending {
+        LineEnding::Lf => read_only::write(path, contents),
+        LineEnding::Crlf => {
+            read_only::write(path, contents.replace("\r\n", "\n").replace('\n', "\r\n"))
+        }
+    }
+}
+
+/// A coverage output format.
+///
+/// Implement this to plug in a custom format; a writer receives the finalized per-file line table
+/// and the module metadata and writes them to the given path.
+pub trait CoverageWriter {
+    /// Human readable name of the format, used in diagnostics
+    fn name(&self) -> &'static str;
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()>;
+}
+
+/// The formats the main output (`--output`) can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum OutputFormat {
+    Lcov,
+    Json,
+    Cobertura,
+    Istanbul,
+    #[value(name = "opencover")]
+    OpenCover,
+    Callgrind,
+    Lighthouse,
+    #[cfg(feature = "native")]
+    Sqlite,
+}
+
+impl OutputFormat {
+    /// Returns the writer registered for the format
+    pub fn writer(self) -> Box<dyn CoverageWriter> {
+        match self {
+            OutputFormat::Lcov => Box::new(LcovWriter),
+            OutputFormat::Json => Box::new(JsonWriter),
+            OutputFormat::Cobertura => Box::new(CoberturaWriter),
+            OutputFormat::Istanbul => Box::new(IstanbulWriter),
+            OutputFormat::OpenCover => Box::new(OpenCoverWriter),
+            OutputFormat::Callgrind => Box::new(CallgrindWriter),
+            OutputFormat::Lighthouse => Box::new(LighthouseWriter),
+            #[cfg(feature = "native")]
+            OutputFormat::Sqlite => Box::new(SqliteWriter),
+        }
+    }
+}
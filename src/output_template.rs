@@ -0,0 +1,161 @@
+use crate::archive;
+use crate::drcov::Drcov;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod constants {
+    pub const TARGET_PLACEHOLDER: &str = "{target}";
+    pub const DATE_PLACEHOLDER: &str = "{date}";
+
+    /// The target of inputs without any modules
+    pub const UNKNOWN_TARGET: &str = "coverage";
+
+    pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+}
+
+/// The name of an output file with placeholders, e.g. `{target}.{date}.info`, resolved once the
+/// inputs have been parsed:
+/// - `{target}` is the file name of the first module of the first input (usually the traced
+///   executable)
+/// - `{date}` is the modification time of the first input, as `YYYYMMDDTHHMMSSZ` in UTC
+///
+/// Both come from the inputs rather than the conversion, so that converting the same inputs again
+/// writes to the same file.
+#[derive(Debug, Clone)]
+pub struct OutputTemplate {
+    template: String,
+}
+
+impl FromStr for OutputTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Any other placeholder is most likely a typo
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or(format!("Unterminated placeholder in output template '{s}'"))?;
+            let placeholder = &rest[start..start + end + 1];
+
+            if placeholder != constants::TARGET_PLACEHOLDER
+                && placeholder != constants::DATE_PLACEHOLDER
+            {
+                return Err(format!(
+                    "Unknown placeholder {placeholder} in output template '{s}' (expected {} or {})",
+                    constants::TARGET_PLACEHOLDER,
+                    constants::DATE_PLACEHOLDER
+                ));
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(Self {
+            template: s.to_string(),
+        })
+    }
+}
+
+impl OutputTemplate {
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Returns the directory every resolution of the template is in: the one of the part of the
+    /// template before its first placeholder
+    pub fn directory(&self) -> &Path {
+        let prefix = &self.template[..self.template.find('{').unwrap_or(self.template.len())];
+
+        let directory = if prefix.ends_with(std::path::is_separator) {
+            Some(Path::new(prefix))
+        } else {
+            Path::new(prefix).parent()
+        };
+
+        directory
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+    }
+
+    /// Resolves the placeholders of the template for `target`
+    pub fn resolve(&self, target: &OutputTarget) -> String {
+        self.template
+            .replace(constants::TARGET_PLACEHOLDER, &target.name)
+            .replace(
+                constants::DATE_PLACEHOLDER,
+                &format_timestamp(target.timestamp),
+            )
+    }
+}
+
+/// What the placeholders of an [`OutputTemplate`] are resolved from
+#[derive(Debug, Clone)]
+pub struct OutputTarget {
+    name: String,
+    timestamp: SystemTime,
+}
+
+impl OutputTarget {
+    /// The target of the input at `input_file`, which has been parsed into `drcov`. The entries
+    /// of archives have the modification time of their archive, and inputs without one (i.e. the
+    /// standard input) are timestamped with the current time.
+    pub fn of(input_file: &Path, drcov: &Drcov) -> Self {
+        let name = drcov
+            .modules
+            .table
+            .iter()
+            .find(|module| !module.is_pseudo_module())
+            .map_or(constants::UNKNOWN_TARGET, |module| module.file_name())
+            .to_string();
+
+        let file = archive::split_entry_path(input_file).map_or(input_file, |(archive, _)| archive);
+
+        let timestamp = std::fs::metadata(file)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or_else(|_| SystemTime::now());
+
+        Self { name, timestamp }
+    }
+}
+
+impl Default for OutputTarget {
+    fn default() -> Self {
+        Self {
+            name: constants::UNKNOWN_TARGET.to_string(),
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+/// Formats `timestamp` as `YYYYMMDDTHHMMSSZ`, using the civil-from-days algorithm of
+/// http://howardhinnant.github.io/date_algorithms.html
+fn format_timestamp(timestamp: SystemTime) -> String {
+    let seconds = timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, time) = (
+        seconds / constants::SECONDS_PER_DAY,
+        seconds % constants::SECONDS_PER_DAY,
+    );
+
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
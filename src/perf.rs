@@ -0,0 +1,233 @@
+use crate::drcov::{Drcov, DrcovFilters, Module, Modules};
+use crate::util::{parse_capture_group, Hex};
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    /// Straight-line runs between two branches longer than this are not trusted to be real
+    /// (e.g. a thread that migrated between samples of a lossy trace)
+    pub const MAX_BLOCK_SIZE: u64 = 64 * 1024;
+
+    /// The flavor given to the coverage parsed from perf traces
+    pub const PERF_FLAVOR: &str = "perf";
+
+    lazy_static! {
+        /// `--show-mmap-events` lines: `... PERF_RECORD_MMAP2 <pid>/<tid>: [0x<start>(0x<len>) @ <pgoff> ...]: <prot> <path>`
+        pub static ref MMAP_EVENT_REGEX: Regex = Regex::new(
+            r"PERF_RECORD_MMAP2?\s+-?\d+/-?\d+:\s+\[0x(?P<start>[[:xdigit:]]+)\(0x(?P<len>[[:xdigit:]]+)\)\s+@\s+(?:0x)?(?P<pgoff>[[:xdigit:]]+)[^\]]*\]:\s+(?P<prot>\S+)\s+(?P<path>.+?)\s*$"
+        )
+        .unwrap();
+        /// Branch samples (`--itrace=b`): `<comm> <tid> [<cpu>] <time>: <period> branches:<mods>: <from> <sym> (<dso>) => <to> <sym> (<dso>)`
+        pub static ref BRANCH_SAMPLE_REGEX: Regex = Regex::new(
+            r"\bbranches(?::\w+)?:\s+(?P<from>[[:xdigit:]]+)\b.*=>\s+(?P<to>[[:xdigit:]]+)\b"
+        )
+        .unwrap();
+        /// Branch samples printed with `-F ip,addr,...`: `<from> ... => <to> ...`
+        pub static ref BRANCH_FIELDS_REGEX: Regex =
+            Regex::new(r"^\s*(?P<from>[[:xdigit:]]+)\b.*=>\s+(?P<to>[[:xdigit:]]+)\b").unwrap();
+        /// Samples printed with `-F ip,...`, e.g. a plain list of executed addresses
+        pub static ref IP_FIELDS_REGEX: Regex =
+            Regex::new(r"^\s*(?:0x)?(?P<ip>[[:xdigit:]]+)(?:\s|$)").unwrap();
+        pub static ref THREAD_REGEX: Regex =
+            Regex::new(r"^\s*.+?\s+(?P<tid>\d+)(?:/\d+)?\s+\[\d+\]").unwrap();
+    }
+}
+
+/// Whether `head`, the start of an input file, is `perf script` output of branch samples (e.g.
+/// decoded Intel PT traces) or of the mmap events of a recording
+pub fn is_perf_script_output(head: &[u8]) -> bool {
+    head.split(|b| *b == b'\n').any(|line| {
+        constants::MMAP_EVENT_REGEX.is_match(line) || constants::BRANCH_SAMPLE_REGEX.is_match(line)
+    })
+}
+
+/// Whether the coverage of the given flavor has been parsed from perf traces
+pub fn is_perf_flavor(flavor: &str) -> bool {
+    flavor == constants::PERF_FLAVOR
+}
+
+/// Turns an executable mapping of a `--show-mmap-events` line into a module. The module offsets
+/// are derived from the file offset of the mapping, since perf only records executable mappings.
+fn module_from_mmap_event(line: &[u8]) -> Option<Module> {
+    let cap = constants::MMAP_EVENT_REGEX.captures(line)?;
+
+    let start = parse_capture_group::<Hex<usize>>(&cap, "start")?.value;
+    let size = parse_capture_group::<Hex<usize>>(&cap, "len")?.value;
+    let pgoff = parse_capture_group::<Hex<usize>>(&cap, "pgoff")?.value;
+    let prot = parse_capture_group::<String>(&cap, "prot")?;
+    let path = parse_capture_group::<String>(&cap, "path")?;
+
+    // Anonymous and kernel provided mappings ([vdso], //anon, ...) are not modules
+    if !prot.contains('x') || !path.starts_with('/') {
+        return None;
+    }
+
+    Some(Module {
+        size,
+        segment_start: start,
+        segment_offset: pgoff,
+        path,
+        ..Default::default()
+    })
+}
+
+/// An executed range of code: the target of a branch up to (and including the first byte of) the
+/// next branch taken by the same thread
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct ExecutedRange {
+    start: u64,
+    size: u32,
+}
+
+fn parse_executed_ranges(contents: &[u8]) -> BTreeSet<ExecutedRange> {
+    let mut res = BTreeSet::new();
+    // Thread -> target of its last branch
+    let mut last_targets = HashMap::<Vec<u8>, u64>::new();
+
+    let hex = |cap: &regex::bytes::Captures, name| {
+        parse_capture_group::<Hex<usize>>(cap, name).map(|value| value.value as u64)
+    };
+
+    for line in contents.split(|b| *b == b'\n') {
+        if constants::MMAP_EVENT_REGEX.is_match(line) {
+            continue;
+        }
+
+        let branch = constants::BRANCH_SAMPLE_REGEX
+            .captures(line)
+            .or_else(|| constants::BRANCH_FIELDS_REGEX.captures(line));
+
+        let Some(cap) = branch else {
+            if let Some(ip) = constants::IP_FIELDS_REGEX
+                .captures(line)
+                .and_then(|cap| hex(&cap, "ip"))
+            {
+                res.insert(ExecutedRange { start: ip, size: 1 });
+            }
+
+            continue;
+        };
+
+        let thread = constants::THREAD_REGEX
+            .captures(line)
+            .and_then(|cap| cap.name("tid"))
+            .map(|tid| tid.as_bytes().to_vec())
+            .unwrap_or_default();
+
+        let (Some(from), Some(to)) = (hex(&cap, "from"), hex(&cap, "to")) else {
+            continue;
+        };
+
+        // Trace begin/end markers have a zero address on one side
+        if from != 0 {
+            let start = match last_targets.get(&thread) {
+                Some(&target) if target <= from && from - target < constants::MAX_BLOCK_SIZE => {
+                    target
+                }
+                _ => from,
+            };
+
+            res.insert(ExecutedRange {
+                start,
+                size: (from - start + 1) as u32,
+            });
+        }
+
+        if to != 0 {
+            res.insert(ExecutedRange { start: to, size: 1 });
+            last_targets.insert(thread, to);
+        } else {
+            last_targets.remove(&thread);
+        }
+    }
+
+    res
+}
+
+/// Parses `perf script` output into the same model drcov files are parsed into.
+///
+/// For branch samples (`perf script --itrace=b` of an Intel PT recording) the code between the
+/// target of a branch and the next branch of the same thread is what ran, so the basic blocks are
+/// reconstructed from consecutive samples. The executed addresses are mapped to modules through
+/// the mmap events of the output (`--show-mmap-events`) and `module_table` (`--module-table`).
+pub fn from_file(
+    path: &Path,
+    module_table: Option<&Modules>,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading perf script output: {}", path.display());
+
+    let contents = std::fs::read(path)?;
+
+    let mut modules = module_table.cloned().unwrap_or(Modules {
+        version: 0,
+        table: Vec::new(),
+    });
+
+    modules.table.extend(
+        contents
+            .split(|b| *b == b'\n')
+            .filter_map(module_from_mmap_event),
+    );
+
+    if modules.table.is_empty() {
+        anyhow::bail!(
+            "The perf output does not contain mmap events (use perf script --show-mmap-events or --module-table)"
+        );
+    }
+
+    let ranges = parse_executed_ranges(&contents);
+
+    modules.insert_addresses(ranges.into_iter().map(|range| (range.start, range.size)));
+
+    modules.apply_filters(filters);
+
+    log::info!("Perf script output loaded");
+
+    Ok(Drcov::from_modules(constants::PERF_FLAVOR, modules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconstructs_basic_blocks_per_thread() {
+        let script = b"\
+            app  1001 [000]  1.000001:  1 branches:u:  401000 main+0x0 (/bin/app) =>  401100 f+0x0 (/bin/app)
+            app  1002 [001]  1.000002:  1 branches:u:       0 [unknown] ([unknown]) =>  402000 g+0x0 (/bin/app)
+            app  1001 [000]  1.000003:  1 branches:u:  401120 f+0x20 (/bin/app) =>  401010 main+0x10 (/bin/app)
+            app  1002 [001]  1.000004:  1 branches:u:  402040 g+0x40 (/bin/app) =>       0 [unknown] ([unknown])
+            app  1002 [001]  1.000005:  1 branches:u:  402080 g+0x80 (/bin/app) =>  403000 h+0x0 (/bin/app)
+            app  1001 [000]  1.000006:  1 branches:u:  4a1010 k+0x10 (/bin/app) =>  401500 main+0x500 (/bin/app)
+";
+
+        let ranges = parse_executed_ranges(script)
+            .into_iter()
+            .map(|range| (range.start, range.size))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ranges,
+            [
+                // Thread 1001 starts with a branch, whose source is all that is known to have run
+                (0x401000, 1),
+                (0x401010, 1),
+                (0x401100, 1),
+                // From the target of the first branch of thread 1001 to its second branch
+                (0x401100, 0x21),
+                (0x401500, 1),
+                // The trace of thread 1002 begins at 0x402000 and ends after 0x402040
+                (0x402000, 1),
+                (0x402000, 0x41),
+                (0x402080, 1),
+                (0x403000, 1),
+                // Too far from the last target of thread 1001 to be a basic block
+                (0x4a1010, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::drcov::{Drcov, DrcovFilters, Module, Modules};
+use crate::util::{parse_capture_group, Hex};
+use std::path::Path;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    /// The file name Pin basic block tools write their output to by default
+    pub const BBLOCKS_FILE_PREFIX: &str = "bblocks";
+
+    /// The flavor given to the coverage parsed from the output of Pin tools
+    pub const PIN_FLAVOR: &str = "pin";
+
+    lazy_static! {
+        /// An image loaded into the traced process: `IMG: <path> 0x<low> 0x<high>` (or
+        /// `Image <path> 0x<low>-0x<high>`, `Loaded image: <path> 0x<low>:0x<high>`), with the
+        /// highest address being inclusive
+        pub static ref IMAGE_LINE_REGEX: Regex = Regex::new(
+            r"^\s*(?:IMG|Image|Loaded image)\s*:?\s+(?P<path>\S.*?)\s+0x(?P<low>[[:xdigit:]]+)\s*[-:,\s]\s*0x(?P<high>[[:xdigit:]]+)\s*$"
+        )
+        .unwrap();
+        /// An executed basic block: `[BBL[:]] 0x<address> [<size> [<count>]]`, the fields separated
+        /// by whitespace or commas
+        pub static ref BBL_LINE_REGEX: Regex = Regex::new(
+            r"^\s*(?:BBL\s*:?\s*)?0x(?P<address>[[:xdigit:]]+)(?:\s*[,\s]\s*(?P<size>\d+)(?:\s*[,\s]\s*(?P<count>\d+))?)?\s*$"
+        )
+        .unwrap();
+    }
+}
+
+/// Whether the file at `path`, starting with `head`, is the output of a Pin basic block tool: a
+/// `bblocks*.out` file, or one that lists the images loaded into the traced process
+pub fn is_pin_output(head: &[u8], path: &Path) -> bool {
+    let is_bblocks_file = path.file_name().is_some_and(|name| {
+        name.to_string_lossy()
+            .starts_with(constants::BBLOCKS_FILE_PREFIX)
+    });
+
+    let mut lines = head.split(|b| *b == b'\n');
+
+    if is_bblocks_file {
+        lines.any(|line| constants::BBL_LINE_REGEX.is_match(line))
+    } else {
+        lines.any(|line| constants::IMAGE_LINE_REGEX.is_match(line))
+    }
+}
+
+/// Whether the coverage of the given flavor has been parsed from the output of a Pin tool
+pub fn is_pin_flavor(flavor: &str) -> bool {
+    flavor == constants::PIN_FLAVOR
+}
+
+fn module_from_image_line(line: &[u8]) -> Option<Module> {
+    let cap = constants::IMAGE_LINE_REGEX.captures(line)?;
+
+    let low = parse_capture_group::<Hex<usize>>(&cap, "low")?.value;
+    let high = parse_capture_group::<Hex<usize>>(&cap, "high")?.value;
+    let path = parse_capture_group::<String>(&cap, "path")?;
+
+    Some(Module {
+        size: high.checked_sub(low)? + 1,
+        segment_start: low,
+        path,
+        ..Default::default()
+    })
+}
+
+/// Parses the output of a Pin basic block tool (`bblocks.out` and the like, or the basic block
+/// profiles of InstLib based tools) into the same model drcov files are parsed into.
+///
+/// Every executed basic block is a line of `0x<address>`, optionally followed by its size and its
+/// execution count; blocks without a size are recorded as one byte blocks and blocks with a zero
+/// count are skipped. The addresses are mapped to modules through the image lines of the output
+/// (`IMG: <path> 0x<low> 0x<high>`) and `module_table` (`--module-table`). Lines of any other kind
+/// (comments, headers, summaries) are ignored.
+pub fn from_file(
+    path: &Path,
+    module_table: Option<&Modules>,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading Pin tool output: {}", path.display());
+
+    let contents = std::fs::read(path)?;
+
+    let mut modules = module_table.cloned().unwrap_or(Modules {
+        version: 0,
+        table: Vec::new(),
+    });
+
+    modules.table.extend(
+        contents
+            .split(|b| *b == b'\n')
+            .filter_map(module_from_image_line),
+    );
+
+    if modules.table.is_empty() {
+        anyhow::bail!(
+            "The Pin tool output does not describe the loaded images (use --module-table)"
+        );
+    }
+
+    modules.insert_addresses(
+        contents
+            .split(|b| *b == b'\n')
+            .filter_map(|line| constants::BBL_LINE_REGEX.captures(line))
+            .filter_map(|cap| {
+                let address = parse_capture_group::<Hex<usize>>(&cap, "address")?;
+
+                if parse_capture_group::<u64>(&cap, "count") == Some(0) {
+                    return None;
+                }
+
+                let size = parse_capture_group::<u32>(&cap, "size").unwrap_or(1).max(1);
+
+                Some((address.value as u64, size))
+            }),
+    );
+
+    modules.apply_filters(filters);
+
+    log::info!("Pin tool output loaded");
+
+    Ok(Drcov::from_modules(constants::PIN_FLAVOR, modules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{drcov_log, TempPath};
+
+    #[test]
+    fn maps_the_basic_blocks_of_pin_output_to_its_images() {
+        let output = TempPath::dir("pin");
+        let path = output.join("bblocks.out");
+        std::fs::write(
+            &path,
+            concat!(
+                "# Basic block profile\n",
+                "IMG: /bin/app 0x400000 0x400fff\n",
+                "BBL: 0x400010 8 3\n",
+                "0x400020, 4, 0\n",
+                "0x400030\n",
+                "0x500000 4 1\n",
+            ),
+        )
+        .unwrap();
+        assert!(is_pin_output(&std::fs::read(&path).unwrap(), &path));
+
+        let drcov = from_file(&path, None, &DrcovFilters::default()).unwrap();
+
+        let [module] = drcov.modules.table.as_slice() else {
+            panic!("Expected a single module");
+        };
+        assert_eq!(module.path, "/bin/app");
+        assert_eq!(module.size, 0x1000);
+        // Blocks without a size are one byte long, and the ones never executed or of no image
+        // are dropped
+        assert_eq!(
+            module.bb_bitmap.iter().collect::<Vec<_>>(),
+            vec![0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x30]
+        );
+        assert_eq!(
+            module.bb_starts.iter().collect::<Vec<_>>(),
+            vec![0x10, 0x30]
+        );
+        assert!(is_pin_flavor(&drcov.flavor));
+    }
+
+    #[test]
+    fn detects_pin_output_by_its_images() {
+        let path = Path::new("pin.out");
+
+        assert!(is_pin_output(b"IMG: /bin/app 0x400000 0x400fff\n", path));
+        assert!(!is_pin_output(b"0x400010 8 3\n", path));
+    }
+
+    #[test]
+    fn does_not_claim_drcov_logs() {
+        let log = drcov_log("/bin/app", &[(16, 4)]);
+
+        assert!(!is_pin_output(&log, Path::new("drcov.app.log")));
+        assert!(!is_pin_output(&log, Path::new("bblocks.out")));
+    }
+}
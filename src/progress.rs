@@ -0,0 +1,136 @@
+use std::fmt::{Debug, Formatter};
+use std::path::Path;
+
+/// Receives the progress of a conversion, which otherwise gives no feedback until all of its
+/// inputs have been processed. Every method does nothing by default.
+///
+/// The input files are processed concurrently, so the methods can be called from several threads
+/// at once.
+pub trait Progress: Send + Sync {
+    /// `num_files` (more) input files are going to be processed
+    fn files_found(&self, _num_files: usize) {}
+
+    /// The input file at `path` has been parsed
+    fn file_parsed(&self, _path: &Path) {}
+
+    /// The debug information of the module at `path` has been processed
+    fn module_processed(&self, _path: &str) {}
+
+    /// `num_rows` (more) rows of the line programs of a module have been scanned
+    fn rows_scanned(&self, _num_rows: u64) {}
+
+    /// The input file at `path` has been processed, successfully or not
+    fn file_processed(&self, _path: &Path) {}
+
+    /// All the input files that were found have been processed
+    fn finished(&self) {}
+}
+
+impl Debug for dyn Progress + '_ {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+#[cfg(feature = "native")]
+pub use bars::ProgressBars;
+
+#[cfg(feature = "native")]
+mod bars {
+    use super::Progress;
+    use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    mod constants {
+        pub const TEMPLATE: &str =
+            "{spinner} [{elapsed_precise}] {wide_bar} {pos}/{len} files ({msg})";
+    }
+
+    /// Draws the progress of the conversions as a progress bar of the processed input files on
+    /// the standard error, unless it is not a terminal
+    pub struct ProgressBars {
+        bars: MultiProgress,
+        /// The bar of the input files that are being processed, if any
+        files: Mutex<Option<ProgressBar>>,
+        num_files_parsed: AtomicU64,
+        num_modules: AtomicU64,
+        num_rows: AtomicU64,
+    }
+
+    impl ProgressBars {
+        /// Draws the bars in `bars`, which log records can be printed above with
+        /// `indicatif_log_bridge`
+        pub fn new(bars: MultiProgress) -> Self {
+            Self {
+                bars,
+                files: Mutex::new(None),
+                num_files_parsed: AtomicU64::new(0),
+                num_modules: AtomicU64::new(0),
+                num_rows: AtomicU64::new(0),
+            }
+        }
+
+        fn update_message(&self) {
+            let Ok(files) = self.files.lock() else {
+                return;
+            };
+
+            if let Some(files) = files.as_ref() {
+                files.set_message(format!(
+                    "{} parsed, {} modules, {} line rows",
+                    self.num_files_parsed.load(Ordering::Relaxed),
+                    self.num_modules.load(Ordering::Relaxed),
+                    self.num_rows.load(Ordering::Relaxed)
+                ));
+            }
+        }
+    }
+
+    impl Progress for ProgressBars {
+        fn files_found(&self, num_files: usize) {
+            let Ok(mut files) = self.files.lock() else {
+                return;
+            };
+
+            let files = files.get_or_insert_with(|| {
+                let style = ProgressStyle::with_template(constants::TEMPLATE)
+                    .unwrap_or_else(|_| ProgressStyle::default_bar());
+                self.bars.add(ProgressBar::new(0).with_style(style))
+            });
+            files.inc_length(num_files as u64);
+        }
+
+        fn file_parsed(&self, _path: &Path) {
+            self.num_files_parsed.fetch_add(1, Ordering::Relaxed);
+            self.update_message();
+        }
+
+        fn module_processed(&self, _path: &str) {
+            self.num_modules.fetch_add(1, Ordering::Relaxed);
+            self.update_message();
+        }
+
+        fn rows_scanned(&self, num_rows: u64) {
+            self.num_rows.fetch_add(num_rows, Ordering::Relaxed);
+        }
+
+        fn file_processed(&self, _path: &Path) {
+            if let Ok(files) = self.files.lock() {
+                if let Some(files) = files.as_ref() {
+                    files.inc(1);
+                }
+            }
+        }
+
+        fn finished(&self) {
+            if let Ok(mut files) = self.files.lock() {
+                if let Some(files) = files.take() {
+                    files.finish_and_clear();
+                    self.bars.remove(&files);
+                }
+            }
+        }
+    }
+}
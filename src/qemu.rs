@@ -0,0 +1,173 @@
+use crate::drcov::{Drcov, DrcovFilters, Module, Modules};
+use crate::dwarf::ObjectFile;
+use crate::util::{parse_capture_group, Hex};
+use std::collections::HashSet;
+use std::path::Path;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    /// The flavor given to the coverage parsed from QEMU traces
+    pub const QEMU_FLAVOR: &str = "qemu";
+
+    lazy_static! {
+        /// A line of the execlog TCG plugin: `<cpu>, 0x<pc>, 0x<opcode>, "<disassembly>"[, ...]`
+        pub static ref EXECLOG_LINE_REGEX: Regex =
+            Regex::new(r#"^\s*\d+,\s*0x(?P<pc>[[:xdigit:]]+),\s*0x[[:xdigit:]]+,\s*""#).unwrap();
+        /// A line of `-d exec` logs: `Trace <cpu>: <host tb> [<cs base>/<pc>/<flags>/<cflags>] <symbol>`
+        /// (or `Trace <host tb> [<pc>] <symbol>` with older releases)
+        pub static ref EXEC_TRACE_LINE_REGEX: Regex = Regex::new(
+            r"^Trace\s+(?:\d+:\s+)?0x[[:xdigit:]]+\s+\[(?:[[:xdigit:]]+/)?(?P<pc>[[:xdigit:]]+)[/\]]"
+        )
+        .unwrap();
+    }
+}
+
+fn parse_pc(line: &[u8]) -> Option<u64> {
+    let cap = constants::EXECLOG_LINE_REGEX
+        .captures(line)
+        .or_else(|| constants::EXEC_TRACE_LINE_REGEX.captures(line))?;
+
+    parse_capture_group::<Hex<usize>>(&cap, "pc").map(|pc| pc.value as u64)
+}
+
+/// Whether `head`, the start of an input file, is a trace of QEMU's execlog plugin or a `-d exec`
+/// log, judging by its first line
+pub fn is_qemu_trace(head: &[u8]) -> bool {
+    head.split(|b| *b == b'\n')
+        .find(|line| !line.trim_ascii().is_empty())
+        .is_some_and(|line| parse_pc(line).is_some())
+}
+
+/// Whether the coverage of the given flavor has been parsed from a QEMU trace
+pub fn is_qemu_flavor(flavor: &str) -> bool {
+    flavor == constants::QEMU_FLAVOR
+}
+
+/// The modules of `module_table`, followed by `binaries`, which are assumed to run at their
+/// link-time addresses (non-PIE executables, kernel images)
+pub(crate) fn link_time_modules(
+    binaries: &[String],
+    module_table: Option<&Modules>,
+) -> anyhow::Result<Modules> {
+    let mut modules = module_table.cloned().unwrap_or(Modules {
+        version: 0,
+        table: Vec::new(),
+    });
+
+    for binary in binaries {
+        let object_file = ObjectFile::from_path(binary)?;
+
+        modules.table.push(Module {
+            size: object_file.image_size() as usize,
+            segment_start: object_file.load_base() as usize,
+            path: binary.clone(),
+            ..Default::default()
+        });
+    }
+
+    Ok(modules)
+}
+
+/// Parses a QEMU trace into the same model drcov files are parsed into.
+///
+/// The traces only hold guest addresses, which are mapped to modules through `module_table`
+/// (`--module-table`, e.g. the maps of a user-mode guest) and through `binaries`, which are
+/// assumed to run at their link-time addresses (non-PIE executables, kernel images). Every address
+/// is recorded as a one byte basic block, since the traces do not describe basic block
+/// boundaries; this is why `any-byte` line hit semantics are the default for these inputs.
+pub fn from_file(
+    path: &Path,
+    binaries: &[String],
+    module_table: Option<&Modules>,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading QEMU trace: {}", path.display());
+
+    let mut modules = link_time_modules(binaries, module_table)?;
+
+    if modules.table.is_empty() {
+        anyhow::bail!(
+            "QEMU traces do not describe the loaded modules (use --qemu-binary or --module-table)"
+        );
+    }
+
+    let contents = std::fs::read(path)?;
+
+    let pcs = contents
+        .split(|b| *b == b'\n')
+        .filter_map(parse_pc)
+        .collect::<HashSet<_>>();
+
+    modules.insert_addresses(pcs.into_iter().map(|pc| (pc, 1)));
+
+    modules.apply_filters(filters);
+
+    log::info!("QEMU trace loaded");
+
+    Ok(Drcov::from_modules(constants::QEMU_FLAVOR, modules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{drcov_log, TempPath};
+
+    fn guest_modules() -> Modules {
+        Modules {
+            version: 0,
+            table: vec![Module {
+                size: 0x1000,
+                segment_start: 0x400000,
+                path: "/bin/guest".to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn maps_the_addresses_of_qemu_traces_to_their_modules() {
+        let trace = TempPath::new("execlog.txt");
+        std::fs::write(
+            trace.path(),
+            concat!(
+                "0, 0x400010, 0x90, \"nop\"\n",
+                "0, 0x400004, 0x90, \"nop\"\n",
+                "1, 0x400010, 0x90, \"nop\"\n",
+                "Trace 0: 0x7f0000 [00000000/0000000000400020/00000000/00000000] main\n",
+                "0, 0x500000, 0x90, \"nop\"\n",
+            ),
+        )
+        .unwrap();
+        assert!(is_qemu_trace(&std::fs::read(trace.path()).unwrap()));
+
+        let drcov = from_file(
+            trace.path(),
+            &[],
+            Some(&guest_modules()),
+            &DrcovFilters::default(),
+        )
+        .unwrap();
+
+        let [module] = drcov.modules.table.as_slice() else {
+            panic!("Expected a single module");
+        };
+        assert_eq!(module.path, "/bin/guest");
+        // Every address is a one byte block, and the ones of no module are dropped
+        assert_eq!(
+            module.bb_bitmap.iter().collect::<Vec<_>>(),
+            vec![0x4, 0x10, 0x20]
+        );
+        assert_eq!(
+            module.bb_starts.iter().collect::<Vec<_>>(),
+            vec![0x4, 0x10, 0x20]
+        );
+        assert!(is_qemu_flavor(&drcov.flavor));
+    }
+
+    #[test]
+    fn does_not_claim_drcov_logs() {
+        assert!(!is_qemu_trace(&drcov_log("/bin/guest", &[(16, 4)])));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::cache::AtomicFile;
+use std::io::{Error, ErrorKind};
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+
+/// The side effects `--read-only` allows a conversion of untrusted inputs: writing to the outputs
+/// given on the command line and to the caches of the tool, and accessing the network only with
+/// `--allow-network`. Every file of a conversion is written through [`write`] or
+/// [`create_dir_all`] (or an [`AtomicFile`], which checks its final path) and the external tools run
+/// through [`tool`], so that no input can lead to other side effects.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOnlyPolicy {
+    /// The files and directories (along with everything below them) that may be written
+    writable: Vec<PathBuf>,
+    allow_network: bool,
+}
+
+/// The policy of the conversions of the process, if they run with `--read-only`
+static POLICY: RwLock<Option<ReadOnlyPolicy>> = RwLock::new(None);
+
+/// Makes `path` absolute and resolves its `.` and `..` components along with the symbolic links
+/// of its existing ancestors, so that writes cannot escape the writable paths through them
+fn resolve(path: &Path) -> PathBuf {
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+
+    let mut res = loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            break canonical;
+        }
+
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(component)) => {
+                missing.push(component);
+                existing = parent;
+            }
+            _ => break absolute.clone(),
+        }
+    };
+
+    for component in missing.into_iter().rev() {
+        match component {
+            Component::ParentDir => {
+                res.pop();
+            }
+            Component::Normal(name) => res.push(name),
+            _ => {}
+        }
+    }
+
+    res
+}
+
+impl ReadOnlyPolicy {
+    #[cfg(feature = "native")]
+    pub fn new(writable: impl IntoIterator<Item = PathBuf>, allow_network: bool) -> Self {
+        Self {
+            writable: writable.into_iter().map(|path| resolve(&path)).collect(),
+            allow_network,
+        }
+    }
+
+    /// Allows writing to `path` as well, along with everything below it
+    #[cfg(feature = "native")]
+    pub fn with_writable(mut self, path: &Path) -> Self {
+        self.writable.push(resolve(path));
+        self
+    }
+
+    fn allows_write(&self, path: &Path) -> bool {
+        let path = resolve(path);
+
+        self.writable.iter().any(|writable| {
+            path.starts_with(writable) || AtomicFile::is_temp_path_of(&path, writable)
+        })
+    }
+}
+
+/// Enforces `policy` on everything the process writes or sends from now on; `None` lifts it
+#[cfg(feature = "native")]
+pub fn enforce(policy: Option<ReadOnlyPolicy>) {
+    if let Ok(mut current) = POLICY.write() {
+        *current = policy;
+    }
+}
+
+/// Fails if the read-only policy does not allow writing to `path`
+pub fn check_write(path: impl AsRef<Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+
+    match POLICY.read().as_deref() {
+        Ok(Some(policy)) if !policy.allows_write(path) => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!(
+                "--read-only does not allow writing to '{}', which is not an output",
+                path.display()
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Fails if the read-only policy does not allow sending a request to `url`
+#[cfg(feature = "native")]
+pub fn check_network(url: &str) -> std::io::Result<()> {
+    match POLICY.read().as_deref() {
+        Ok(Some(policy)) if !policy.allow_network => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            format!("--read-only does not allow accessing '{url}' without --allow-network"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Writes `contents` to the file at `path`, if the policy allows it
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    check_write(&path)?;
+    std::fs::write(path, contents)
+}
+
+/// Creates the directory at `path` along with its missing parents, if the policy allows it
+pub fn create_dir_all(path: impl AsRef<Path>) -> std::io::Result<()> {
+    check_write(&path)?;
+    std::fs::create_dir_all(path)
+}
+
+/// A command running the external tool `program`. The tools cannot be confined to the writable
+/// paths, so the ones that run during a conversion only read (save for `genhtml`, whose output
+/// directory is checked beforehand), and without `--allow-network` they do not get the
+/// `DEBUGINFOD_URLS` the binutils and LLVM tools fetch debug information with.
+pub fn tool(program: &str) -> std::process::Command {
+    let mut command = std::process::Command::new(program);
+
+    if let Ok(Some(policy)) = POLICY.read().as_deref() {
+        if !policy.allow_network {
+            command.env_remove("DEBUGINFOD_URLS");
+        }
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "native")]
+    use crate::test_fixtures::{drcov_log, TempPath};
+    #[cfg(feature = "native")]
+    use rusty_fork::rusty_fork_test;
+
+    #[test]
+    fn only_allows_writing_to_the_outputs() {
+        let directory = std::env::temp_dir().join(format!("drcov2lcov-ro-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("badges")).unwrap();
+
+        let policy = ReadOnlyPolicy::new(
+            [directory.join("coverage.info"), directory.join("badges")],
+            false,
+        );
+        let atomic_file = AtomicFile::new(directory.join("coverage.info"));
+
+        let allowed = [
+            directory.join("coverage.info"),
+            directory.join("badges/coverage.svg"),
+            directory.join("badges/new/../app.svg"),
+            atomic_file.temp_path().to_path_buf(),
+        ];
+        let denied = [
+            directory.join("other.info"),
+            directory.join("badges/../other.info"),
+            directory.join(".other.info.1.0.tmp"),
+        ];
+
+        let res = (
+            allowed.map(|path| policy.allows_write(&path)),
+            denied.map(|path| policy.allows_write(&path)),
+        );
+        let _ = std::fs::remove_dir_all(&directory);
+
+        assert_eq!(res, ([true; 4], [false; 3]));
+    }
+
+    /// The files below `directory`
+    #[cfg(feature = "native")]
+    fn files(directory: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+
+        for entry in std::fs::read_dir(directory).unwrap().flatten() {
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => files.extend(self::files(&entry.path())),
+                _ => files.push(entry.path()),
+            }
+        }
+
+        files
+    }
+
+    // The policy applies to the whole process, so the conversion runs in a process of its own
+    #[cfg(feature = "native")]
+    rusty_fork_test! {
+        #[test]
+        fn conversions_with_every_output_only_write_their_outputs() {
+            use crate::cli::CliOptions;
+            use clap::Parser;
+
+            let directory = TempPath::dir("read-only");
+            // The caches of the tool, and anything written to the home directory, end up in here
+            std::env::set_var("HOME", directory.join("home"));
+            std::env::set_var("XDG_CACHE_HOME", directory.join("home/.cache"));
+
+            // The reduce-set and the dedup manifest need an input directory
+            let inputs = directory.join("inputs");
+            std::fs::create_dir_all(&inputs).unwrap();
+            // A small binary rather than the test binary, whose debug information and disassembly
+            // take minutes, since only what is written matters here
+            std::fs::write(
+                inputs.join("drcov.test.log"),
+                drcov_log("/bin/true", &[(0x1000, 64)]),
+            )
+            .unwrap();
+
+            let outputs = directory.join("outputs");
+            std::fs::create_dir_all(&outputs).unwrap();
+            let output = |name: &str| outputs.join(name).to_string_lossy().to_string();
+
+            let mut args = vec![
+                "drcov2lcov".to_string(),
+                "--directory".to_string(),
+                inputs.to_string_lossy().to_string(),
+                "--read-only".to_string(),
+                "--no-progress".to_string(),
+                "--output".to_string(),
+                output("coverage.info"),
+            ];
+            let optional_outputs = [
+                ("--badge-dir", "badges"),
+                ("--metrics-output", "metrics.prom"),
+                ("--reduce-set-path", "reduce-set.tsv"),
+                ("--lighthouse-output", "lighthouse.txt"),
+                ("--module-graph", "modules.dot"),
+                ("--reachable-oracle", "oracle.txt"),
+                ("--callgrind-output", "callgrind.out"),
+                ("--opencover-output", "opencover.xml"),
+                ("--istanbul-output", "coverage-final.json"),
+                ("--sqlite-output", "coverage.sqlite"),
+                ("--state-output", "state.covstate"),
+                ("--function-keyed-output", "functions.json"),
+                ("--dedup-manifest", "dedup.txt"),
+                ("--run-report", "run-report.json"),
+            ];
+            for (option, name) in optional_outputs {
+                args.extend([option.to_string(), output(name)]);
+            }
+            args.extend(["--cache-dir".to_string(), output("line-tables")]);
+
+            let options = CliOptions::try_parse_from(args)
+                .unwrap()
+                .validate()
+                .unwrap();
+            crate::run(options).unwrap();
+
+            // Every output has been written, and nothing else
+            for (_, name) in optional_outputs {
+                assert!(outputs.join(name).exists(), "{name} was not written");
+            }
+            for file in files(directory.path()) {
+                assert!(
+                    file.starts_with(&inputs) || file.starts_with(&outputs),
+                    "'{}' was written",
+                    file.display()
+                );
+            }
+
+            // Nothing outside of the outputs may be written
+            let outside = directory.join("outside.info");
+            assert!(check_write(&outside).is_err());
+            assert!(write(&outside, "").is_err() && !outside.exists());
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::output::OutputFormat;
+use crate::read_only;
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+mod constants {
+    pub const HTML_FORMAT: &str = "html";
+    pub const HTML_DIRECTORY: &str = "html";
+}
+
+/// The page of the HTML report with the coverage of every component (see `--component-map`)
+pub const COMPONENTS_PAGE: &str = "components.html";
+
+/// A format `report` renders a saved coverage state in: any output format, or an HTML report
+/// generated from the lcov output by `genhtml`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportFormat {
+    Html,
+    Output(OutputFormat),
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case(constants::HTML_FORMAT) {
+            return Ok(Self::Html);
+        }
+
+        OutputFormat::from_str(s, true)
+            .map(Self::Output)
+            .map_err(|_| format!("Unknown report format '{s}'"))
+    }
+}
+
+impl ReportFormat {
+    /// The path under `output_dir` the format is written to
+    pub fn output_path(self, output_dir: &Path) -> PathBuf {
+        let file_name = match self {
+            ReportFormat::Html => constants::HTML_DIRECTORY,
+            ReportFormat::Output(OutputFormat::Lcov) => "coverage.info",
+            ReportFormat::Output(OutputFormat::Json) => "coverage.json",
+            ReportFormat::Output(OutputFormat::Cobertura) => "cobertura.xml",
+            ReportFormat::Output(OutputFormat::Istanbul) => "coverage-final.json",
+            ReportFormat::Output(OutputFormat::OpenCover) => "opencover.xml",
+            ReportFormat::Output(OutputFormat::Callgrind) => "callgrind.out",
+            ReportFormat::Output(OutputFormat::Lighthouse) => "lighthouse.txt",
+            ReportFormat::Output(OutputFormat::Sqlite) => "coverage.sqlite",
+        };
+
+        output_dir.join(file_name)
+    }
+}
+
+/// Renders the lcov file at `lcov` as an HTML report in `html_directory` with `genhtml`
+pub fn generate_html(lcov: &Path, html_directory: &Path) -> anyhow::Result<()> {
+    // genhtml writes nothing but the report
+    read_only::check_write(html_directory)?;
+    let status = read_only::tool("genhtml")
+        .arg("--quiet")
+        .arg(lcov)
+        .arg("--output-directory")
+        .arg(html_directory)
+        .status()
+        .map_err(|e| anyhow::anyhow!("Could not run genhtml (is lcov installed?): {e}"))?;
+
+    if !status.success() {
+        anyhow::bail!("genhtml failed ({status})");
+    }
+
+    log::info!(
+        "HTML report written to '{}'",
+        html_directory.join("index.html").display()
+    );
+
+    Ok(())
+}
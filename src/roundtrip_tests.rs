@@ -0,0 +1,225 @@
+//! Property tests that feed randomized module tables and basic block sets through the whole
+//! pipeline (parse -> aggregate -> lcov write -> re-parse) and check that nothing is lost or
+//! invented on the way, whatever format or merge path the coverage takes.
+
+use crate::aliases::FunctionSymbols;
+use crate::drcov::{Drcov, DrcovFilters};
+use crate::dwarf::{
+    apply_line_table, LineInfoFilters, ModuleLineTable, SourceCoverage, TableRow, UnitTable,
+};
+use crate::lcov::{read_lcov_coverage, write_lcov_output};
+use crate::output::LineEnding;
+use crate::summary::Summary;
+use crate::test_fixtures::TempPath;
+use itertools::Itertools;
+use proptest::prelude::*;
+use roaring::RoaringBitmap;
+use std::sync::OnceLock;
+
+/// Modules are laid out this far apart so that their ranges never overlap
+const MODULE_STRIDE: u64 = 0x1000_0000;
+
+/// The number of source files of the generated line tables
+const NUM_SOURCE_FILES: usize = 4;
+
+/// A module of a generated drcov log and the basic blocks executed in it
+#[derive(Debug, Clone)]
+struct GeneratedModule {
+    path: String,
+    size: u32,
+    /// (module relative start, size)
+    blocks: Vec<(u32, u16)>,
+}
+
+fn module_strategy(
+    path: String,
+    size: std::ops::Range<u32>,
+) -> impl Strategy<Value = GeneratedModule> {
+    size.prop_flat_map(move |size| {
+        // Blocks have to end before the end of their module to be kept
+        let block = (0..size - 1).prop_flat_map(move |start| {
+            let max_size = (size - 1 - start).clamp(1, 64) as u16;
+            (Just(start), 1..=max_size)
+        });
+
+        prop::collection::vec(block, 0..200).prop_map({
+            let path = path.clone();
+            move |blocks| GeneratedModule {
+                path: path.clone(),
+                size,
+                blocks,
+            }
+        })
+    })
+}
+
+fn modules_strategy() -> impl Strategy<Value = Vec<GeneratedModule>> {
+    (1..6usize).prop_flat_map(|num_modules| {
+        (0..num_modules)
+            .map(|index| module_strategy(format!("/nonexistent/lib{index}.so"), 0x1000..0x10_0000))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Writes a version 2 drcov log of `modules`, the first of them being loaded at
+/// [`MODULE_STRIDE`], the second at twice that and so on
+fn write_drcov_log(modules: &[GeneratedModule], path: &str) {
+    let mut contents = format!(
+        "DRCOV VERSION: 2\n\
+         DRCOV FLAVOR: drcov\n\
+         Module Table: version 2, count {}\n\
+         Columns: id, base, end, entry, path\n",
+        modules.len()
+    );
+
+    for (id, module) in modules.iter().enumerate() {
+        let base = MODULE_STRIDE * (id as u64 + 1);
+        contents += &format!(
+            "{id:3}, {base:#018x}, {:#018x}, 0x0000000000000000, {}\n",
+            base + module.size as u64,
+            module.path
+        );
+    }
+
+    let num_bb = modules
+        .iter()
+        .map(|module| module.blocks.len())
+        .sum::<usize>();
+    contents += &format!("BB Table: {num_bb} bbs\n");
+
+    let mut contents = contents.into_bytes();
+    for (id, module) in modules.iter().enumerate() {
+        for (start, size) in &module.blocks {
+            contents.extend_from_slice(&start.to_le_bytes());
+            contents.extend_from_slice(&size.to_le_bytes());
+            contents.extend_from_slice(&(id as u16).to_le_bytes());
+        }
+    }
+
+    std::fs::write(path, contents).unwrap();
+}
+
+fn parse_drcov_log(path: &str) -> Drcov {
+    Drcov::from_file(path, &DrcovFilters::default(), None).unwrap()
+}
+
+/// The rows of a line table of a single unit, as (source file id, line, module relative start,
+/// size) tuples, whose addresses are within the first `size` bytes of a module
+fn line_table_strategy(size: u32) -> impl Strategy<Value = Vec<(usize, u64, u32, u32)>> {
+    let row = (0..NUM_SOURCE_FILES, 1..200u64, 0..size - 64, 1..64u32);
+    prop::collection::vec(row, 1..100)
+}
+
+/// A line table of a module loaded at zero, without functions, made of `rows` (see
+/// [`line_table_strategy`])
+fn line_table(rows: &[(usize, u64, u32, u32)]) -> ModuleLineTable {
+    ModuleLineTable {
+        load_base: 0,
+        files: (0..NUM_SOURCE_FILES)
+            .map(|index| format!("/src/file{index}.c").into())
+            .collect(),
+        units: vec![UnitTable {
+            functions: Vec::new(),
+            function_ranges: Vec::new(),
+            rows: rows
+                .iter()
+                .map(|(file_id, line, start, size)| TableRow {
+                    address: *start as u64,
+                    end_address: (*start + *size) as u64,
+                    line: *line,
+                    discriminator: 0,
+                    file_id: *file_id,
+                })
+                .collect(),
+        }],
+        symbols: FunctionSymbols::default(),
+        blocks: OnceLock::new(),
+    }
+}
+
+/// Writes `coverage` as an lcov tracefile and reads it back
+fn lcov_round_trip(coverage: &SourceCoverage) -> SourceCoverage {
+    let lcov = TempPath::new("coverage.info");
+    write_lcov_output(&lcov.display(), coverage, &[], None, LineEnding::Lf).unwrap();
+    read_lcov_coverage(&lcov.display()).unwrap()
+}
+
+fn line_records(coverage: &SourceCoverage) -> Vec<(String, u64, bool)> {
+    coverage
+        .lines
+        .iter()
+        .flat_map(|(file, lines)| {
+            lines
+                .iter()
+                .map(|info| (file.clone(), info.line, info.executed))
+        })
+        .sorted()
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn drcov_logs_round_trip(modules in modules_strategy()) {
+        let log = TempPath::new("drcov.log");
+        write_drcov_log(&modules, &log.display());
+
+        let drcov = parse_drcov_log(&log.display());
+        prop_assert_eq!(drcov.modules.table.len(), modules.len());
+
+        for (parsed, generated) in drcov.modules.table.iter().zip(&modules) {
+            prop_assert_eq!(&parsed.path, &generated.path);
+            prop_assert_eq!(parsed.size, generated.size as usize);
+
+            let starts = generated.blocks.iter().map(|(start, _)| *start).collect::<RoaringBitmap>();
+            let mut bytes = RoaringBitmap::new();
+            for (start, size) in &generated.blocks {
+                bytes.insert_range(*start..*start + *size as u32);
+            }
+
+            prop_assert_eq!(&parsed.bb_starts, &starts);
+            prop_assert_eq!(&parsed.bb_bitmap, &bytes);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn line_coverage_round_trips_through_lcov(
+        module in module_strategy("/nonexistent/app".to_string(), 0x1000..0x1001),
+        rows in line_table_strategy(0x1000),
+    ) {
+        let log = TempPath::new("drcov.log");
+        write_drcov_log(&[module], &log.display());
+
+        let drcov = parse_drcov_log(&log.display());
+        let mut coverage = SourceCoverage::default();
+        apply_line_table(
+            &drcov.modules.table[0],
+            &line_table(&rows),
+            &mut coverage,
+            &LineInfoFilters::default(),
+        );
+        coverage.coalesce();
+        let summary = Summary::from_coverage(&coverage);
+
+        let reparsed = lcov_round_trip(&coverage);
+        let reparsed_summary = Summary::from_coverage(&reparsed);
+
+        prop_assert_eq!(line_records(&reparsed), line_records(&coverage));
+        prop_assert_eq!(reparsed_summary.lines_found, summary.lines_found);
+        prop_assert_eq!(reparsed_summary.lines_hit, summary.lines_hit);
+
+        // Unioning coverage with itself, through any path, changes nothing
+        let mut merged = reparsed.clone();
+        merged.merge(coverage.clone());
+        prop_assert_eq!(line_records(&merged), line_records(&coverage));
+
+        // Unioning it with the same lines unexecuted changes nothing either
+        let mut unexecuted = reparsed;
+        for info in unexecuted.lines.values_mut().flatten() {
+            info.executed = false;
+        }
+        merged.merge(unexecuted);
+        prop_assert_eq!(line_records(&merged), line_records(&coverage));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::cli::CliOptions;
+use crate::output::{write_text_output, LineEnding};
+use crate::read_only;
+use crate::summary::Summary;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod constants {
+    /// Bumped whenever the layout of the report changes
+    pub const FORMAT_VERSION: u64 = 1;
+}
+
+/// What happened to an input file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputOutcome {
+    Converted,
+    /// Identical to an input processed before it
+    Duplicate,
+    Failed,
+}
+
+impl InputOutcome {
+    fn name(self) -> &'static str {
+        match self {
+            InputOutcome::Converted => "converted",
+            InputOutcome::Duplicate => "duplicate",
+            InputOutcome::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct InputRecord {
+    path: String,
+    size: Option<u64>,
+    outcome: InputOutcome,
+    error: Option<String>,
+    duration: Option<Duration>,
+}
+
+/// A record of what a conversion did, written with `--run-report` to be attached to bug reports:
+/// the inputs and what happened to them, the options, the timings and the versions of the external
+/// tools the conversion ran. It is only ever written to the local file system.
+#[derive(Debug)]
+pub struct RunReport {
+    started: Instant,
+    started_at: SystemTime,
+    inputs: Vec<InputRecord>,
+    processing: Duration,
+    writing: Duration,
+}
+
+impl Default for RunReport {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+            inputs: Vec::new(),
+            processing: Duration::ZERO,
+            writing: Duration::ZERO,
+        }
+    }
+}
+
+/// The first line `<tool> --version` prints, if the tool can be run
+fn tool_version(tool: &str) -> Option<String> {
+    let output = read_only::tool(tool).arg("--version").output().ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .chain(String::from_utf8_lossy(&output.stderr).lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+impl RunReport {
+    pub fn add_input(
+        &mut self,
+        path: &Path,
+        outcome: InputOutcome,
+        error: Option<String>,
+        duration: Option<Duration>,
+    ) {
+        self.inputs.push(InputRecord {
+            path: path.to_string_lossy().to_string(),
+            size: std::fs::metadata(path).ok().map(|meta| meta.len()),
+            outcome,
+            error,
+            duration,
+        });
+    }
+
+    /// Adds the time it took to process a batch of input files
+    pub fn add_processing_time(&mut self, duration: Duration) {
+        self.processing += duration;
+    }
+
+    /// Adds the time it took to write the outputs
+    pub fn add_writing_time(&mut self, duration: Duration) {
+        self.writing += duration;
+    }
+
+    /// The external tools the conversion runs with `options`, along with their versions
+    fn backends(options: &CliOptions) -> Vec<Value> {
+        let mut tools = Vec::new();
+
+        if options.verify_output {
+            tools.push("lcov");
+        }
+
+        if options.reachable_oracle.is_some() {
+            tools.push("objdump");
+        }
+
+        if let Some(cross_check) = options.cross_check {
+            tools.push(cross_check.name());
+        }
+
+        tools
+            .into_iter()
+            .map(|tool| json!({ "name": tool, "version": tool_version(tool) }))
+            .collect()
+    }
+
+    /// Writes the report to `path`, along with the `outcome` of the conversion and the `summary`
+    /// of its coverage. Paths are scrubbed like the paths of the outputs.
+    pub fn write(
+        &self,
+        path: &str,
+        options: &CliOptions,
+        summary: &Summary,
+        outcome: &anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let scrubber = options.get_scrubber();
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| {
+                json!({
+                    "path": scrubber.scrub(&input.path),
+                    "size": input.size,
+                    "outcome": input.outcome.name(),
+                    "error": input.error,
+                    "duration_ms": input.duration.map(millis),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let started_at = self
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+
+        let report = json!({
+            "format_version": constants::FORMAT_VERSION,
+            "tool_version": env!("CARGO_PKG_VERSION"),
+            "platform": {
+                "os": std::env::consts::OS,
+                "arch": std::env::consts::ARCH,
+            },
+            "arguments": std::env::args().skip(1).map(|arg| scrubber.scrub(&arg)).collect::<Vec<_>>(),
+            "started_at": started_at,
+            "timings": {
+                "total_ms": millis(self.started.elapsed()),
+                "processing_ms": millis(self.processing),
+                "writing_ms": millis(self.writing),
+            },
+            "inputs": inputs,
+            "backends": Self::backends(options),
+            "summary": {
+                "lines_found": summary.lines_found,
+                "lines_hit": summary.lines_hit,
+                "functions_found": summary.functions_found,
+                "functions_hit": summary.functions_hit,
+            },
+            "error": outcome.as_ref().err().map(|e| e.to_string()),
+        });
+
+        write_text_output(
+            path,
+            &serde_json::to_string_pretty(&report)?,
+            LineEnding::Lf,
+        )?;
+
+        log::info!("Run report written to '{path}'");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use crate::drcov::{Drcov, DrcovFilters, Module, Modules};
+use crate::dwarf::ObjectFile;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+mod constants {
+    pub const SANCOV_MAGIC_64: u64 = 0xC0BF_FFFF_FFFF_FF64;
+    pub const SANCOV_MAGIC_32: u64 = 0xC0BF_FFFF_FFFF_FF32;
+
+    pub const SANCOV_EXTENSION: &str = "sancov";
+
+    /// The flavor given to the coverage parsed from .sancov files
+    pub const SANCOV_FLAVOR: &str = "sancov";
+}
+
+/// Whether the file at `path` looks like a SanitizerCoverage PC file
+pub fn is_sancov_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == constants::SANCOV_EXTENSION)
+}
+
+/// Returns the name of the module a .sancov file has been dumped for. The runtime names the files
+/// `<module name>.<pid>.sancov`.
+fn module_name(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_string_lossy();
+
+    let name = match stem.rsplit_once('.') {
+        Some((name, pid)) if pid.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => stem.as_ref(),
+    };
+
+    Some(name.to_string())
+}
+
+/// Finds the binary a .sancov file belongs to, either among `binaries` or next to the file itself
+fn find_module_binary(path: &Path, binaries: &[String]) -> anyhow::Result<String> {
+    let Some(name) = module_name(path) else {
+        anyhow::bail!("Could not determine the module of '{}'", path.display());
+    };
+
+    let candidates = binaries
+        .iter()
+        .map(PathBuf::from)
+        .chain(path.parent().map(|parent| parent.join(&name)));
+
+    for candidate in candidates {
+        if candidate
+            .file_name()
+            .is_some_and(|file_name| *file_name == *name)
+            && candidate.is_file()
+        {
+            return Ok(candidate.to_string_lossy().to_string());
+        }
+    }
+
+    anyhow::bail!(
+        "Could not find the binary of module '{name}' for '{}' (use --sancov-binary)",
+        path.display()
+    )
+}
+
+fn parse_pcs(contents: &[u8]) -> anyhow::Result<Vec<u64>> {
+    let mut cursor = Cursor::new(contents);
+
+    let magic = cursor.read_u64::<LittleEndian>()?;
+    let pc_size = match magic {
+        constants::SANCOV_MAGIC_64 => 8,
+        constants::SANCOV_MAGIC_32 => 4,
+        _ => anyhow::bail!("Invalid .sancov magic {magic:#x}"),
+    };
+
+    let num_pcs = (contents.len() - 8) / pc_size;
+    let mut res = Vec::with_capacity(num_pcs);
+
+    for _ in 0..num_pcs {
+        let pc = match pc_size {
+            8 => cursor.read_u64::<LittleEndian>()?,
+            _ => cursor.read_u32::<LittleEndian>()? as u64,
+        };
+        res.push(pc);
+    }
+
+    Ok(res)
+}
+
+/// Parses a SanitizerCoverage `.sancov` PC file into the same model drcov files are parsed into.
+///
+/// The file holds the module relative offsets of the instrumented edges that were executed, or
+/// their addresses for position dependent executables. Each
+/// offset is recorded as a one byte basic block, since the file does not describe basic block
+/// boundaries; this is why `any-byte` line hit semantics are the default for these inputs.
+pub fn from_file(
+    path: &Path,
+    binaries: &[String],
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading sancov file: {}", path.display());
+
+    let pcs = parse_pcs(&std::fs::read(path)?)?;
+
+    let binary = find_module_binary(path, binaries)?;
+    let binary =
+        String::from_utf8_lossy(&filters.maybe_replace_with_path_map_filter(binary.as_bytes()))
+            .to_string();
+
+    let mut table = Vec::new();
+
+    if filters.matches_any_module_filter(binary.as_bytes())
+        && !filters.matches_any_module_skip_filter(binary.as_bytes())
+    {
+        let object_file = ObjectFile::from_path(&binary)?;
+        // Position dependent executables are mapped at zero, so their PCs are absolute addresses
+        let load_base = object_file.load_base();
+
+        let mut module = Module {
+            size: object_file.image_size() as usize,
+            path: binary,
+            ..Default::default()
+        };
+
+        for pc in pcs {
+            let Some(offset) = pc
+                .checked_sub(load_base)
+                .and_then(|offset| u32::try_from(offset).ok())
+            else {
+                continue;
+            };
+
+            module.bb_bitmap.insert(offset);
+            module.bb_starts.insert(offset);
+        }
+
+        table.push(module);
+    }
+
+    log::info!("Sancov file loaded");
+
+    Ok(Drcov::from_modules(
+        constants::SANCOV_FLAVOR,
+        Modules { version: 0, table },
+    ))
+}
+
+/// Whether the coverage of the given flavor has been parsed from a .sancov file
+pub fn is_sancov_flavor(flavor: &str) -> bool {
+    flavor == constants::SANCOV_FLAVOR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{small_binary, small_non_pie_binary, TempPath};
+
+    fn sancov_file(magic: u64, pcs: &[u64]) -> Vec<u8> {
+        let mut contents = magic.to_le_bytes().to_vec();
+        for pc in pcs {
+            match magic {
+                constants::SANCOV_MAGIC_32 => {
+                    contents.extend_from_slice(&(*pc as u32).to_le_bytes())
+                }
+                _ => contents.extend_from_slice(&pc.to_le_bytes()),
+            }
+        }
+        contents
+    }
+
+    #[test]
+    fn reads_32_and_64_bit_sancov_files() {
+        let dir = TempPath::dir("sancov");
+        // Named after the module and the pid of the process that dumped it
+        let path = dir.join("hello.4242.sancov");
+        assert!(is_sancov_file(&path));
+
+        for magic in [constants::SANCOV_MAGIC_32, constants::SANCOV_MAGIC_64] {
+            std::fs::write(&path, sancov_file(magic, &[0x1130, 0x1010, 0x1130])).unwrap();
+
+            let drcov = from_file(&path, &[small_binary()], &DrcovFilters::default()).unwrap();
+            assert!(is_sancov_flavor(&drcov.flavor));
+
+            let [module] = drcov.modules.table.as_slice() else {
+                panic!("Expected a single module");
+            };
+            assert_eq!(module.path, small_binary());
+            // Every PC is a one byte basic block
+            assert_eq!(
+                module.bb_bitmap.iter().collect::<Vec<_>>(),
+                [0x1010, 0x1130]
+            );
+            assert_eq!(module.bb_starts, module.bb_bitmap);
+        }
+    }
+
+    #[test]
+    fn rebases_the_pcs_of_position_dependent_executables() {
+        let binary = small_non_pie_binary();
+        let object_file = ObjectFile::from_path(&binary).unwrap();
+        let load_base = object_file.load_base();
+        assert_ne!(load_base, 0);
+
+        let dir = TempPath::dir("sancov");
+        let path = dir.join("hello-non-pie.4242.sancov");
+        // A PC below the load base is not part of the executable
+        let pcs = [load_base + 0x1130, load_base + 0x1010, load_base - 0x10];
+        std::fs::write(&path, sancov_file(constants::SANCOV_MAGIC_64, &pcs)).unwrap();
+
+        let drcov = from_file(&path, &[binary], &DrcovFilters::default()).unwrap();
+
+        let [module] = drcov.modules.table.as_slice() else {
+            panic!("Expected a single module");
+        };
+        assert_eq!(
+            module.bb_bitmap.iter().collect::<Vec<_>>(),
+            [0x1010, 0x1130]
+        );
+        assert!(module
+            .bb_bitmap
+            .iter()
+            .all(|offset| (offset as usize) < module.size));
+    }
+
+    #[test]
+    fn rejects_files_with_another_magic() {
+        let err = parse_pcs(&sancov_file(0xC0BF_FFFF_FFFF_FF16, &[0x10])).unwrap_err();
+        assert!(err.to_string().contains("magic"), "{err}");
+        assert!(parse_pcs(&[0x64, 0xff]).is_err());
+    }
+}
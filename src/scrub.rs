@@ -0,0 +1,197 @@
+use crate::dwarf::SourceCoverage;
+use crate::filter::ReplacementFilter;
+use crate::module_key::ModuleKey;
+use crate::util::assert_send_sync;
+use roaring::RoaringBitmap;
+use std::collections::HashMap;
+
+/// Rewrites every path and piece of metadata that ends up in an output file, so that coverage
+/// artifacts can be shared without leaking details about the machine they were produced on.
+#[derive(Debug, Clone)]
+pub struct Scrubber<'r> {
+    pub scrub_filters: &'r [ReplacementFilter],
+}
+
+const _: () = assert_send_sync::<Scrubber>();
+
+impl Scrubber<'_> {
+    pub fn is_enabled(&self) -> bool {
+        !self.scrub_filters.is_empty()
+    }
+
+    /// Applies all scrub filters to `input`, in the order they were given
+    pub fn scrub(&self, input: &str) -> String {
+        self.scrub_filters
+            .iter()
+            .fold(input.to_string(), |acc, filter| {
+                String::from_utf8_lossy(
+                    &filter
+                        .matcher
+                        .replace_all(acc.as_bytes(), filter.replacement.as_bytes()),
+                )
+                .to_string()
+            })
+    }
+
+    pub fn scrub_coverage(&self, coverage: SourceCoverage) -> SourceCoverage {
+        if !self.is_enabled() {
+            return coverage;
+        }
+
+        let mut res = SourceCoverage::default();
+
+        for (file, lines) in coverage.lines {
+            res.lines
+                .entry(self.scrub(&file))
+                .or_default()
+                .extend(lines);
+        }
+
+        for (file, functions) in coverage.functions {
+            let functions = functions.into_iter().map(|mut function| {
+                function.module = self.scrub(&function.module);
+                function
+            });
+
+            res.functions
+                .entry(self.scrub(&file))
+                .or_default()
+                .extend(functions);
+        }
+
+        for (file, branches) in coverage.branches {
+            res.branches
+                .entry(self.scrub(&file))
+                .or_default()
+                .extend(branches);
+        }
+
+        // Different paths might have been scrubbed to the same one
+        res.coalesce();
+
+        res
+    }
+
+    pub fn scrub_modules(
+        &self,
+        modules: HashMap<ModuleKey, RoaringBitmap>,
+    ) -> HashMap<ModuleKey, RoaringBitmap> {
+        let mut res = HashMap::<ModuleKey, RoaringBitmap>::new();
+
+        for (mut key, bitmap) in modules {
+            key.path = self.scrub(&key.path);
+            *res.entry(key).or_default() |= bitmap;
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::Deduplicator;
+    use crate::drcov::{Drcov, DrcovFilters};
+    use crate::dwarf::{FunctionInfo, LineInfo};
+    use crate::manifest::FileDigests;
+    use crate::output::LineEnding;
+    use crate::state::CoverageState;
+    use crate::test_fixtures::{drcov_log, TempPath};
+    use std::path::Path;
+
+    #[test]
+    fn scrubs_every_output_the_same_way() {
+        let scrub_filters = ["^/home/[^/]+/:/work/".parse::<ReplacementFilter>().unwrap()];
+        let scrubber = Scrubber {
+            scrub_filters: &scrub_filters,
+        };
+
+        // Paths that are scrubbed to the same one are merged
+        let mut coverage = SourceCoverage::default();
+        for (file, line, executed) in [
+            ("/home/alice/proj/main.c", 3, true),
+            ("/work/proj/main.c", 5, false),
+        ] {
+            coverage
+                .lines
+                .insert(file.to_string(), vec![LineInfo { line, executed }]);
+        }
+        coverage.functions.insert(
+            "/home/alice/proj/main.c".to_string(),
+            vec![FunctionInfo {
+                name: "main".to_string(),
+                module: "/home/alice/proj/app".to_string(),
+                start_line: 3,
+                end_line: 5,
+                executed: true,
+                aliases: Vec::new(),
+                blocks: None,
+            }],
+        );
+
+        let coverage = scrubber.scrub_coverage(coverage);
+        assert_eq!(
+            coverage.lines.keys().collect::<Vec<_>>(),
+            ["/work/proj/main.c"]
+        );
+        assert_eq!(
+            coverage.lines["/work/proj/main.c"]
+                .iter()
+                .map(|line| (line.line, line.executed))
+                .collect::<Vec<_>>(),
+            [(3, true), (5, false)]
+        );
+        assert_eq!(
+            coverage.functions["/work/proj/main.c"][0].module,
+            "/work/proj/app"
+        );
+
+        let key = |path: &str| ModuleKey {
+            path: path.to_string(),
+            ..Default::default()
+        };
+        let modules = scrubber.scrub_modules(HashMap::from([
+            (key("/home/alice/proj/app"), RoaringBitmap::from([1])),
+            (key("/work/proj/app"), RoaringBitmap::from([2])),
+        ]));
+        let [(key, bitmap)] = modules.into_iter().collect::<Vec<_>>().try_into().unwrap();
+        assert_eq!(key.path, "/work/proj/app");
+        assert_eq!(bitmap.iter().collect::<Vec<_>>(), [1, 2]);
+
+        let drcov = Drcov::from_bytes(
+            &drcov_log("/home/alice/proj/app", &[(16, 4)]),
+            &DrcovFilters::default(),
+            None,
+        )
+        .unwrap();
+        let mut state = CoverageState::default();
+        state.add(&drcov);
+
+        let state_path = TempPath::new("state.covstate");
+        state.write(&state_path.display(), &scrubber).unwrap();
+        let state = std::fs::read_to_string(state_path.path()).unwrap();
+        assert!(state.contains("/work/proj/app"));
+        assert!(!state.contains("/home/alice"));
+
+        let digests = FileDigests {
+            size: 4,
+            xxh3: 1,
+            sha256: None,
+        };
+        let mut deduplicator = Deduplicator::default();
+        deduplicator.find_duplicate(Path::new("/home/alice/logs/a.log"), digests.clone());
+        deduplicator.find_duplicate(Path::new("/home/alice/logs/b.log"), digests);
+
+        let manifest_path = TempPath::new("dedup.tsv");
+        deduplicator
+            .write_manifest(
+                &manifest_path.display(),
+                |path| scrubber.scrub(path),
+                LineEnding::default(),
+            )
+            .unwrap();
+        let manifest = std::fs::read_to_string(manifest_path.path()).unwrap();
+        assert!(manifest.contains("\t/work/logs/b.log\t/work/logs/a.log\n"));
+        assert!(!manifest.contains("/home/alice"));
+    }
+}
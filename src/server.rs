@@ -0,0 +1,297 @@
+use crate::cli::CliOptions;
+use crate::conversion::convert_contents;
+use crate::debuginfod::DebuginfodClient;
+use crate::limits::LimitExceeded;
+use crate::object_cache::{LineTableCache, ObjectFileCache};
+use crate::output::OutputFormat;
+use clap::{Parser, ValueEnum};
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Instant;
+use tiny_http::{Header, Method, Request, Response};
+
+mod constants {
+    /// The name the uploads are converted under, e.g. in the test names of their iterations
+    pub const UPLOAD_NAME: &str = "upload";
+
+    /// How many modules are kept cached unless `--max-cached-modules` is given
+    pub const DEFAULT_MAX_CACHED_MODULES: usize = 64;
+}
+
+/// The response to a request
+#[derive(Debug, PartialEq)]
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Reply {
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into(),
+        }
+    }
+
+    /// The reply to an upload that could not be converted: uploads that exceed one of the input
+    /// limits are told apart from malformed ones
+    fn error(e: anyhow::Error) -> Self {
+        match LimitExceeded::find(&e) {
+            Some(LimitExceeded::Duration { .. }) => Self::text(503, e.to_string()),
+            Some(_) => Self::text(413, e.to_string()),
+            None => Self::text(400, e.to_string()),
+        }
+    }
+}
+
+/// Converts the drcov logs uploaded over HTTP with the same options, keeping the object files and
+/// the line tables of their modules loaded between the requests
+struct Server {
+    options: CliOptions,
+    object_files: Arc<ObjectFileCache>,
+    line_tables: Arc<LineTableCache>,
+    debuginfod: Option<DebuginfodClient>,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl Server {
+    /// `options` are parsed like the options of a conversion, whose inputs are the uploads
+    fn new(options: Vec<String>) -> anyhow::Result<Self> {
+        let args = [
+            "drcov2lcov".to_string(),
+            "--input".to_string(),
+            "-".to_string(),
+        ]
+        .into_iter()
+        .chain(options);
+
+        let mut options = CliOptions::try_parse_from(args)?.validate()?;
+        let max_cached_modules = *options
+            .max_cached_modules
+            .get_or_insert(constants::DEFAULT_MAX_CACHED_MODULES);
+
+        // The module paths come from the uploads, so only the binary directories are trusted
+        if options.binary_dirs.is_empty() {
+            anyhow::bail!("The modules of the uploads are only read from the binary directories, give at least one with --binary-dir");
+        }
+
+        let object_files = Arc::new(
+            ObjectFileCache::default()
+                .with_capacity(max_cached_modules)
+                .with_roots(&options.binary_dirs)?,
+        );
+        options.object_files = Some(object_files.clone());
+        let line_tables = Arc::new(options.get_line_table_cache());
+        options.line_tables = Some(line_tables.clone());
+
+        Ok(Self {
+            debuginfod: options.get_debuginfod_client()?,
+            options,
+            object_files,
+            line_tables,
+            runtime: tokio::runtime::Runtime::new()?,
+        })
+    }
+
+    /// Converts the drcov log read from `body` to `format`
+    fn convert(&self, body: impl Read, format: OutputFormat) -> Reply {
+        let contents = match self.options.get_input_limits().read_to_end(body) {
+            Ok(contents) => contents,
+            Err(e) => return Reply::error(e),
+        };
+
+        let res = convert_contents(
+            &self.options,
+            &self.runtime,
+            self.debuginfod.as_ref(),
+            constants::UPLOAD_NAME,
+            &contents,
+            format,
+        );
+
+        match res {
+            Ok(body) => Reply {
+                status: 200,
+                content_type: match format {
+                    OutputFormat::Json => "application/json",
+                    _ => "text/plain; charset=utf-8",
+                },
+                body,
+            },
+            Err(e) => Reply::error(e),
+        }
+    }
+
+    /// Handles a request for `url`:
+    /// - `POST /convert?format=lcov|json` converts the drcov log of the body (lcov by default)
+    /// - `GET /health` tells that the server is up
+    fn handle(&self, method: &Method, url: &str, body: impl Read) -> Reply {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+        match (method, path) {
+            (Method::Post, "/convert") => {
+                let format = query
+                    .split('&')
+                    .find_map(|parameter| parameter.strip_prefix("format="))
+                    .unwrap_or("lcov");
+
+                match OutputFormat::from_str(format, true) {
+                    Ok(format @ (OutputFormat::Lcov | OutputFormat::Json)) => {
+                        self.convert(body, format)
+                    }
+                    _ => Reply::text(
+                        400,
+                        format!("Unsupported format '{format}', expected lcov or json"),
+                    ),
+                }
+            }
+            (Method::Get, "/health") => Reply::text(200, "ok\n"),
+            (_, "/convert" | "/health") => Reply::text(405, "Method not allowed\n"),
+            _ => Reply::text(404, "Not found\n"),
+        }
+    }
+
+    fn respond(&self, mut request: Request) {
+        let started = Instant::now();
+        let method = request.method().clone();
+        let url = request.url().to_string();
+
+        let reply = self.handle(&method, &url, request.as_reader());
+
+        log::info!(
+            "{method} {url}: {} in {:.2?} ({} object files and {} line tables cached)",
+            reply.status,
+            started.elapsed(),
+            self.object_files.len(),
+            self.line_tables.len()
+        );
+
+        let mut response = Response::from_string(reply.body).with_status_code(reply.status);
+        if let Ok(header) = Header::from_bytes("Content-Type", reply.content_type) {
+            response.add_header(header);
+        }
+
+        if let Err(e) = request.respond(response) {
+            log::warn!("Could not respond to {method} {url}: {e}");
+        }
+    }
+}
+
+/// Serves conversions on `listen` until the process is killed, converting up to `workers` uploads
+/// at once. `options` are parsed like the options of a conversion and apply to every upload.
+pub fn serve(listen: &str, workers: u16, options: Vec<String>) -> anyhow::Result<()> {
+    let server = Server::new(options)?;
+    let http = tiny_http::Server::http(listen)
+        .map_err(|e| anyhow::anyhow!("Could not listen on '{listen}': {e}"))?;
+
+    log::info!("Listening on {listen} with {workers} workers");
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                for request in http.incoming_requests() {
+                    server.respond(request);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{test_binary, test_binary_log, TempPath};
+    use std::path::Path;
+
+    /// The directory of the test binary, which the uploads of the tests trace
+    fn binary_dir() -> String {
+        Path::new(&test_binary())
+            .parent()
+            .unwrap()
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[test]
+    fn converts_uploads_with_the_modules_cached() {
+        let log = test_binary_log(&[(0, u16::MAX)]);
+
+        let server = Server::new(vec![
+            "--source-filters".to_string(),
+            r"server\.rs$".to_string(),
+            "--binary-dir".to_string(),
+            binary_dir(),
+        ])
+        .unwrap();
+
+        let lcov = server.handle(&Method::Post, "/convert", log.as_slice());
+        let json = server.handle(&Method::Post, "/convert?format=json", log.as_slice());
+
+        assert_eq!(lcov.status, 200);
+        assert!(lcov.body.contains("server.rs\nDA:"));
+        assert_eq!(json.status, 200);
+        assert!(serde_json::from_str::<serde_json::Value>(&json.body).is_ok());
+        assert_eq!(server.object_files.len(), 1);
+        assert_eq!(server.line_tables.len(), 1);
+
+        assert_eq!(
+            server
+                .handle(&Method::Post, "/convert", &b"garbage"[..])
+                .status,
+            400
+        );
+        assert_eq!(
+            server
+                .handle(&Method::Post, "/convert?format=sqlite", log.as_slice())
+                .status,
+            400
+        );
+        assert_eq!(server.handle(&Method::Get, "/convert", &[][..]).status, 405);
+    }
+
+    #[test]
+    fn only_reads_the_modules_of_the_binary_directories() {
+        let log = test_binary_log(&[(0, u16::MAX)]);
+        let elsewhere = TempPath::dir("elsewhere");
+
+        assert!(Server::new(Vec::new()).is_err());
+
+        let server = Server::new(vec![
+            "--source-filters".to_string(),
+            r"server\.rs$".to_string(),
+            "--binary-dir".to_string(),
+            elsewhere.display(),
+        ])
+        .unwrap();
+
+        let lcov = server.handle(&Method::Post, "/convert", log.as_slice());
+
+        assert_eq!(lcov.status, 200);
+        assert!(!lcov.body.contains("server.rs"));
+        assert!(server.object_files.is_empty());
+    }
+
+    #[test]
+    fn rejects_uploads_that_exceed_the_limits() {
+        let log = test_binary_log(&[(0, 16), (16, 16)]);
+        let status = |options: &[&str]| {
+            let options = ["--binary-dir".to_string(), binary_dir()]
+                .into_iter()
+                .chain(options.iter().map(|option| option.to_string()))
+                .collect();
+            let server = Server::new(options).unwrap();
+            server
+                .handle(&Method::Post, "/convert", log.as_slice())
+                .status
+        };
+
+        assert_eq!(status(&["--max-input-size", "16"]), 413);
+        assert_eq!(status(&["--max-basic-blocks", "1"]), 413);
+        // The deadline has passed before the first module is symbolized
+        assert_eq!(status(&["--input-timeout", "0"]), 503);
+    }
+}
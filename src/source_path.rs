@@ -0,0 +1,26 @@
+use std::fmt::{Debug, Formatter};
+
+/// Rewrites the paths of the source files the debug information of the modules names, e.g. to
+/// map the paths of a build farm or a container to a local checkout when a regular expression
+/// (`--path-map`) is not enough. It is invoked for every source file path before the path is
+/// matched against the source filters and used as the `SF` of the coverage.
+pub trait SourcePathResolver: Send + Sync {
+    /// Returns the path the source file at `path` of the module at `module` is reported under, or
+    /// `None` to keep `path`
+    fn resolve(&self, module: &str, path: &str) -> Option<String>;
+}
+
+impl<F> SourcePathResolver for F
+where
+    F: Fn(&str, &str) -> Option<String> + Send + Sync,
+{
+    fn resolve(&self, module: &str, path: &str) -> Option<String> {
+        self(module, path)
+    }
+}
+
+impl Debug for dyn SourcePathResolver + '_ {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SourcePathResolver")
+    }
+}
@@ -0,0 +1,143 @@
+use crate::dwarf::SourceCoverage;
+use crate::module_key::ModuleKey;
+use crate::output::{CoverageReport, CoverageWriter};
+use crate::read_only;
+use crate::summary::Summary;
+use roaring::RoaringBitmap;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod constants {
+    pub const SCHEMA: &str = r#"
+        CREATE TABLE IF NOT EXISTS runs (
+            id INTEGER PRIMARY KEY,
+            timestamp INTEGER NOT NULL,
+            tool_version TEXT NOT NULL,
+            lines_found INTEGER NOT NULL,
+            lines_hit INTEGER NOT NULL,
+            functions_found INTEGER NOT NULL,
+            functions_hit INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS modules (
+            id INTEGER PRIMARY KEY,
+            run_id INTEGER NOT NULL REFERENCES runs(id),
+            path TEXT NOT NULL,
+            executed_blocks INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS files (
+            id INTEGER PRIMARY KEY,
+            run_id INTEGER NOT NULL REFERENCES runs(id),
+            path TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS lines (
+            file_id INTEGER NOT NULL REFERENCES files(id),
+            line INTEGER NOT NULL,
+            executed INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS functions (
+            file_id INTEGER NOT NULL REFERENCES files(id),
+            name TEXT NOT NULL,
+            module TEXT NOT NULL,
+            start_line INTEGER NOT NULL,
+            end_line INTEGER NOT NULL,
+            executed INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS files_run_id ON files(run_id);
+        CREATE INDEX IF NOT EXISTS lines_file_id ON lines(file_id);
+        CREATE INDEX IF NOT EXISTS functions_file_id ON functions(file_id);
+    "#;
+}
+
+/// Appends the coverage as a new run to the SQLite database at `path`, creating the database if
+/// needed. Keeping every run in the same database allows querying coverage trends across runs.
+pub fn write_sqlite_output(
+    path: &str,
+    coverage: &SourceCoverage,
+    modules: &HashMap<ModuleKey, RoaringBitmap>,
+) -> anyhow::Result<()> {
+    read_only::check_write(path)?;
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(constants::SCHEMA)?;
+
+    let transaction = connection.transaction()?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let summary = Summary::from_coverage(coverage);
+
+    transaction.execute(
+        "INSERT INTO runs (timestamp, tool_version, lines_found, lines_hit, functions_found, functions_hit) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            timestamp,
+            env!("CARGO_PKG_VERSION"),
+            summary.lines_found,
+            summary.lines_hit,
+            summary.functions_found,
+            summary.functions_hit
+        ],
+    )?;
+    let run_id = transaction.last_insert_rowid();
+
+    {
+        let mut insert_module = transaction
+            .prepare("INSERT INTO modules (run_id, path, executed_blocks) VALUES (?1, ?2, ?3)")?;
+        let mut insert_file =
+            transaction.prepare("INSERT INTO files (run_id, path) VALUES (?1, ?2)")?;
+        let mut insert_line = transaction
+            .prepare("INSERT INTO lines (file_id, line, executed) VALUES (?1, ?2, ?3)")?;
+        let mut insert_function = transaction.prepare(
+            "INSERT INTO functions (file_id, name, module, start_line, end_line, executed) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+
+        for (module, bb_offsets) in modules {
+            insert_module.execute(params![run_id, module.path, bb_offsets.len()])?;
+        }
+
+        let files = coverage.lines.keys().chain(coverage.functions.keys());
+        let mut file_ids = HashMap::new();
+
+        for file in files {
+            if file_ids.contains_key(file) {
+                continue;
+            }
+
+            insert_file.execute(params![run_id, file])?;
+            let file_id = transaction.last_insert_rowid();
+            file_ids.insert(file, file_id);
+
+            for info in coverage.lines.get(file).into_iter().flatten() {
+                insert_line.execute(params![file_id, info.line, info.executed])?;
+            }
+
+            for function in coverage.functions.get(file).into_iter().flatten() {
+                insert_function.execute(params![
+                    file_id,
+                    function.name,
+                    function.module,
+                    function.start_line,
+                    function.end_line,
+                    function.executed
+                ])?;
+            }
+        }
+    }
+
+    transaction.commit()?;
+
+    Ok(())
+}
+
+pub struct SqliteWriter;
+
+impl CoverageWriter for SqliteWriter {
+    fn name(&self) -> &'static str {
+        "SQLite"
+    }
+
+    fn write(&self, path: &str, report: &CoverageReport) -> anyhow::Result<()> {
+        write_sqlite_output(path, report.coverage, report.modules)
+    }
+}
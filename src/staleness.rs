@@ -0,0 +1,354 @@
+use crate::dwarf::{source_file_stamps, ObjectFile, SourceCoverage, SourceFileStamp};
+use crate::source_path::SourcePathResolver;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+mod constants {
+    /// How many stale source files are listed in the warning
+    pub const MAX_LISTED_FILES: usize = 10;
+}
+
+/// Why a source file probably no longer matches the line numbers of the coverage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Staleness {
+    /// Its MD5 differs from the one recorded in the debug information of the binary
+    Md5Differs,
+    /// Its size differs from the one recorded in the debug information of the binary
+    SizeDiffers,
+    /// It has been modified after the modification time recorded in the debug information
+    ModifiedSinceCompiled,
+    /// It has been modified that long after the binary was built, which is all there is to go by
+    /// when the debug information records nothing about it
+    NewerThanBinary(Duration),
+}
+
+/// A source file that has changed since the newest binary it has been compiled into was built
+#[derive(Debug)]
+pub struct StaleSource {
+    pub file: String,
+    pub binary: String,
+    pub staleness: Staleness,
+}
+
+impl Display for StaleSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let binary = Path::new(&self.binary)
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        match &self.staleness {
+            Staleness::Md5Differs => write!(
+                f,
+                "{} (its MD5 differs from the one recorded in '{binary}')",
+                self.file
+            ),
+            Staleness::SizeDiffers => write!(
+                f,
+                "{} (its size differs from the one recorded in '{binary}')",
+                self.file
+            ),
+            Staleness::ModifiedSinceCompiled => write!(
+                f,
+                "{} (modified after it was compiled into '{binary}')",
+                self.file
+            ),
+            Staleness::NewerThanBinary(newer_by) => write!(
+                f,
+                "{} (modified {}s after '{binary}' was built)",
+                self.file,
+                newer_by.as_secs()
+            ),
+        }
+    }
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Returns the stamps the debug information of `binary` records for its source files, keyed by
+/// the paths they are reported under
+fn binary_stamps(
+    binary: &str,
+    resolver: Option<&dyn SourcePathResolver>,
+) -> HashMap<String, SourceFileStamp> {
+    let stamps = ObjectFile::with_debug_info(binary)
+        .map_err(anyhow::Error::from)
+        .and_then(|object_file| source_file_stamps(&object_file));
+
+    match stamps {
+        Ok(stamps) => stamps
+            .into_iter()
+            .map(|(path, stamp)| {
+                let path = resolver
+                    .and_then(|resolver| resolver.resolve(binary, &path))
+                    .unwrap_or(path);
+                (path, stamp)
+            })
+            .collect(),
+        Err(e) => {
+            log::debug!("Could not read the source file stamps of '{binary}': {e}");
+            HashMap::new()
+        }
+    }
+}
+
+/// Checks the source file at `file` against what the debug information recorded about it when it
+/// was compiled. Returns `None` if it recorded too little to tell.
+fn check_stamp(file: &str, stamp: &SourceFileStamp) -> Option<Option<Staleness>> {
+    // The contents are all that matter, so the MD5 settles it (e.g. for fresh checkouts, whose
+    // files are all newer than the binaries)
+    if let Some(md5) = stamp.md5 {
+        let contents = std::fs::read(file).ok()?;
+        return Some((md5::compute(contents).0 != md5).then_some(Staleness::Md5Differs));
+    }
+
+    let meta = std::fs::metadata(file).ok()?;
+
+    if stamp.size.is_some_and(|size| size != meta.len()) {
+        return Some(Some(Staleness::SizeDiffers));
+    }
+
+    let timestamp = stamp.timestamp?;
+    let source_time = meta
+        .modified()
+        .ok()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()?;
+
+    Some((source_time.as_secs() > timestamp).then_some(Staleness::ModifiedSinceCompiled))
+}
+
+/// Finds the source files of `coverage` that have changed since the binaries they have been
+/// compiled into were built, with `stamps` returning what the debug information of a binary
+/// records about its source files.
+///
+/// The MD5, modification time and size of a source file recorded in the debug information are
+/// preferred; the modification time of the newest binary, which stands in for its build time, is
+/// only compared against otherwise. Source files whose binaries are unknown (no function coverage)
+/// or unreadable are not checked.
+fn find_stale_sources_with(
+    coverage: &SourceCoverage,
+    mut stamps: impl FnMut(&str) -> HashMap<String, SourceFileStamp>,
+) -> Vec<StaleSource> {
+    let mut binary_stamps = HashMap::<&str, HashMap<String, SourceFileStamp>>::new();
+    let mut binary_times = HashMap::<&str, Option<SystemTime>>::new();
+    let mut res = Vec::new();
+
+    for file in coverage.lines.keys().sorted() {
+        let binaries = coverage
+            .functions
+            .get(file)
+            .into_iter()
+            .flatten()
+            .map(|function| function.module.as_str())
+            .unique()
+            .collect::<Vec<_>>();
+
+        let recorded = binaries.iter().find_map(|binary| {
+            let stamp = binary_stamps
+                .entry(binary)
+                .or_insert_with(|| stamps(binary))
+                .get(file)?;
+            check_stamp(file, stamp).map(|staleness| (*binary, staleness))
+        });
+
+        if let Some((binary, staleness)) = recorded {
+            if let Some(staleness) = staleness {
+                res.push(StaleSource {
+                    file: file.clone(),
+                    binary: binary.to_string(),
+                    staleness,
+                });
+            }
+            continue;
+        }
+
+        let Some(source_time) = modified(file) else {
+            continue;
+        };
+
+        let newest_binary = binaries
+            .into_iter()
+            .filter_map(|binary| {
+                binary_times
+                    .entry(binary)
+                    .or_insert_with(|| modified(binary))
+                    .map(|time| (binary, time))
+            })
+            .max_by_key(|(_, time)| *time);
+
+        let Some((binary, binary_time)) = newest_binary else {
+            continue;
+        };
+
+        if let Ok(newer_by) = source_time.duration_since(binary_time) {
+            if !newer_by.is_zero() {
+                res.push(StaleSource {
+                    file: file.clone(),
+                    binary: binary.to_string(),
+                    staleness: Staleness::NewerThanBinary(newer_by),
+                });
+            }
+        }
+    }
+
+    res
+}
+
+/// Finds the source files of `coverage` that have changed since the binaries they have been
+/// compiled into were built (see [`find_stale_sources_with`]), with `resolver` rewriting the
+/// source paths of their debug information like it did for the coverage
+pub fn find_stale_sources(
+    coverage: &SourceCoverage,
+    resolver: Option<&dyn SourcePathResolver>,
+) -> Vec<StaleSource> {
+    find_stale_sources_with(coverage, |binary| binary_stamps(binary, resolver))
+}
+
+/// Warns about the source files that have changed since their binaries were built, since reports
+/// annotated from them (checksums, HTML) would silently be misleading
+pub fn warn_about_stale_sources(
+    coverage: &SourceCoverage,
+    resolver: Option<&dyn SourcePathResolver>,
+) {
+    let stale_sources = find_stale_sources(coverage, resolver);
+
+    if stale_sources.is_empty() {
+        return;
+    }
+
+    let listed = stale_sources
+        .iter()
+        .take(constants::MAX_LISTED_FILES)
+        .map(|stale| format!("    {stale}"))
+        .join("\n");
+
+    let more = stale_sources
+        .len()
+        .checked_sub(constants::MAX_LISTED_FILES)
+        .filter(|more| *more > 0)
+        .map(|more| format!("\n    ... and {more} more"))
+        .unwrap_or_default();
+
+    log::warn!(
+        "{} source files have changed since the binaries they were compiled into were built; the annotated sources probably do not match the coverage:\n{listed}{more}",
+        stale_sources.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::{FunctionInfo, LineInfo};
+    use crate::test_fixtures::{small_binary, TempPath};
+    use std::fs::FileTimes;
+
+    fn coverage(files: &[String], binary: &str) -> SourceCoverage {
+        let mut res = SourceCoverage::default();
+
+        for file in files {
+            res.lines.insert(
+                file.clone(),
+                vec![LineInfo {
+                    line: 1,
+                    executed: true,
+                }],
+            );
+            res.functions.insert(
+                file.clone(),
+                vec![FunctionInfo {
+                    name: "f".to_string(),
+                    module: binary.to_string(),
+                    start_line: 1,
+                    end_line: 1,
+                    executed: true,
+                    aliases: Vec::new(),
+                    blocks: None,
+                }],
+            );
+        }
+
+        res
+    }
+
+    #[test]
+    fn reads_the_source_file_stamps_of_binaries() {
+        let object_file = ObjectFile::from_path(&small_binary()).unwrap();
+        let stamps = source_file_stamps(&object_file).unwrap();
+
+        assert!(
+            stamps.keys().any(|path| path.ends_with("hello.rs")),
+            "{stamps:?}"
+        );
+    }
+
+    #[test]
+    fn prefers_the_stamps_of_the_debug_information_to_the_build_time() {
+        let dir = TempPath::dir("staleness");
+        let binary = dir.join("app");
+        std::fs::write(&binary, "").unwrap();
+        // Every source file is newer than the binary, like after a fresh checkout
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&binary)
+            .unwrap()
+            .set_times(FileTimes::new().set_modified(an_hour_ago))
+            .unwrap();
+
+        let files = ["same.c", "edited.c", "unrecorded.c", "touched.c"]
+            .map(|name| dir.join(name).to_string_lossy().to_string());
+        for file in &files {
+            std::fs::write(file, "int main(void) { return 0; }\n").unwrap();
+        }
+
+        let recorded = |md5: &[u8]| SourceFileStamp {
+            md5: Some(md5::compute(md5).0),
+            ..Default::default()
+        };
+        let stamps = HashMap::from([
+            (
+                files[0].clone(),
+                recorded(b"int main(void) { return 0; }\n"),
+            ),
+            (
+                files[1].clone(),
+                recorded(b"int main(void) { return 1; }\n"),
+            ),
+            (
+                files[3].clone(),
+                SourceFileStamp {
+                    timestamp: Some(
+                        an_hour_ago
+                            .duration_since(SystemTime::UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs(),
+                    ),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        let binary = binary.to_string_lossy().to_string();
+        let stale_sources = find_stale_sources_with(&coverage(&files, &binary), |_| stamps.clone())
+            .into_iter()
+            .map(|stale| (stale.file, stale.staleness))
+            .collect::<HashMap<_, _>>();
+
+        assert_eq!(stale_sources.len(), 3, "{stale_sources:?}");
+        assert!(!stale_sources.contains_key(&files[0]));
+        assert_eq!(stale_sources[&files[1]], Staleness::Md5Differs);
+        assert!(matches!(
+            stale_sources[&files[2]],
+            Staleness::NewerThanBinary(_)
+        ));
+        assert_eq!(stale_sources[&files[3]], Staleness::ModifiedSinceCompiled);
+    }
+}
@@ -0,0 +1,496 @@
+use crate::drcov::{Drcov, DrcovFilters, Module, Modules};
+use crate::dwarf::debug_file_path_by_build_id;
+use crate::module_key::{ModuleKey, ModuleKeys};
+use crate::read_only;
+use crate::scrub::Scrubber;
+use crate::util::assert_send_sync;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use roaring::RoaringBitmap;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::OnceLock;
+
+mod constants {
+    /// Bumped whenever the layout of the state file changes, along with a migration of the files
+    /// of the previous version
+    pub const FORMAT_VERSION: u64 = 3;
+
+    pub const STATE_EXTENSION: &str = "covstate";
+
+    /// The flavor of the modules of state files that only recorded the flavors of their inputs,
+    /// when these were of different flavors
+    pub const MIXED_FLAVOR: &str = "mixed";
+}
+
+/// Whether the file at `path`, starting with `head`, is a coverage state file written with
+/// `--state-output`. Files that have been renamed are recognized by their contents, since state
+/// files are the only JSON inputs.
+pub fn is_state_file(head: &[u8], path: &Path) -> bool {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == constants::STATE_EXTENSION)
+    {
+        return true;
+    }
+
+    head.first() == Some(&b'{')
+}
+
+/// The executed code of a module, relative to the start of the module
+#[derive(Debug, Default, Clone)]
+pub struct ModuleState {
+    /// The path in the key is the path the module had where the coverage was captured. It is
+    /// only used to resolve modules without a build-id, or as a hint for the ones that have one.
+    pub key: ModuleKey,
+    /// The flavor of the inputs the coverage was captured in, which decides how it is symbolized
+    pub flavor: String,
+    pub size: usize,
+    pub executed: RoaringBitmap,
+    pub bb_starts: RoaringBitmap,
+}
+
+/// Coverage keyed by module build-id rather than module path.
+///
+/// The state holds the executed code of every module, not its line coverage, so it can be
+/// captured in one environment and symbolized in another where the binaries live under entirely
+/// different paths: [`BinaryResolver`] maps the build-ids back to binaries at report time.
+/// The coverage of a module is kept apart for every flavor it has been captured in.
+#[derive(Debug, Default)]
+pub struct CoverageState {
+    modules: BTreeMap<(String, ModuleKey), ModuleState>,
+    keys: ModuleKeys,
+}
+
+const _: () = assert_send_sync::<CoverageState>();
+
+/// Upgrades a state document of one format version to the next one
+type Migration = fn(&mut Value);
+
+/// The migration of format version `n` to `n + 1` is at index `n - 1`
+const MIGRATIONS: [Migration; 2] = [
+    // 1 -> 2: Modules are identified by their architecture and bitness along with their build-id,
+    // which the modules of older files are assumed to share with the binaries they resolve to
+    |document| {
+        for module in document["modules"].as_array_mut().into_iter().flatten() {
+            module["arch"] = Value::Null;
+            module["bits"] = Value::Null;
+        }
+    },
+    // 2 -> 3: Modules record the flavor of their coverage instead of the file recording the
+    // flavors of all of its inputs
+    |document| {
+        let flavors = document["flavors"].take();
+        let flavor = match flavors.as_array().map(Vec::as_slice) {
+            Some([flavor]) => flavor.clone(),
+            _ => constants::MIXED_FLAVOR.into(),
+        };
+
+        for module in document["modules"].as_array_mut().into_iter().flatten() {
+            module["flavor"] = flavor.clone();
+        }
+    },
+];
+
+const _: () = assert!(MIGRATIONS.len() as u64 == constants::FORMAT_VERSION - 1);
+
+/// Migrates a state document written by an older version of the tool to the current format.
+/// Documents of newer formats are rejected instead of being misread.
+fn migrate(document: &mut Value) -> anyhow::Result<()> {
+    let Some(format_version) = document["format_version"].as_u64().filter(|v| *v > 0) else {
+        anyhow::bail!("Not a coverage state file: it does not have a format version");
+    };
+
+    if format_version > constants::FORMAT_VERSION {
+        anyhow::bail!(
+            "The state file has format version {format_version}, which a newer version of drcov2lcov wrote (this one reads up to version {}). Upgrade drcov2lcov to read it",
+            constants::FORMAT_VERSION
+        );
+    }
+
+    for migration in &MIGRATIONS[format_version as usize - 1..] {
+        migration(document);
+    }
+
+    if format_version < constants::FORMAT_VERSION {
+        log::info!(
+            "Migrated the state file from format version {format_version} to {}",
+            constants::FORMAT_VERSION
+        );
+        document["format_version"] = constants::FORMAT_VERSION.into();
+    }
+
+    Ok(())
+}
+
+fn encode_bitmap(bitmap: &RoaringBitmap) -> String {
+    let mut bytes = Vec::with_capacity(bitmap.serialized_size());
+    // Safety: We can unwrap here since writing to a Vec never fails
+    bitmap.serialize_into(&mut bytes).unwrap();
+
+    STANDARD.encode(bytes)
+}
+
+fn decode_bitmap(value: &Value) -> anyhow::Result<RoaringBitmap> {
+    let Some(encoded) = value.as_str() else {
+        anyhow::bail!("Expected a base64 encoded bitmap");
+    };
+
+    Ok(RoaringBitmap::deserialize_from(
+        STANDARD.decode(encoded)?.as_slice(),
+    )?)
+}
+
+impl CoverageState {
+    /// Adds the coverage of a parsed input. The coverage of modules captured from another build
+    /// than the binary at their path is kept under the build-id the input recorded for it, and
+    /// left out if there is none, since the build-id of its build is unknown.
+    pub fn add(&mut self, drcov: &Drcov) {
+        let captured_builds = drcov.modules.captured_builds();
+
+        for module in &drcov.modules.table {
+            if module.is_pseudo_module() || module.bb_bitmap.is_empty() {
+                continue;
+            }
+
+            let mut key = self.keys.key(
+                &module.path,
+                &captured_builds
+                    .get(module.path.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+            );
+            if key.other_build.is_some() && key.build_id.is_none() {
+                log::debug!("Leaving the coverage of {key} out of the state");
+                continue;
+            }
+            key.other_build = None;
+
+            let state = self
+                .modules
+                .entry((drcov.flavor.clone(), key.clone()))
+                .or_insert_with(|| ModuleState {
+                    key,
+                    flavor: drcov.flavor.clone(),
+                    ..Default::default()
+                });
+
+            // Segments are recorded relative to their own start, the state relative to the module's
+            let segment_offset = module.segment_offset as u32;
+            state.size = state.size.max(module.segment_offset + module.size);
+            state.executed.extend(
+                module
+                    .bb_bitmap
+                    .iter()
+                    .filter_map(|offset| offset.checked_add(segment_offset)),
+            );
+            state.bb_starts.extend(module.bb_module_offsets());
+        }
+    }
+
+    pub fn write(&self, path: &str, scrubber: &Scrubber) -> anyhow::Result<()> {
+        let modules = self
+            .modules
+            .values()
+            .map(|module| {
+                json!({
+                    "build_id": module.key.build_id,
+                    "arch": module.key.arch,
+                    "bits": module.key.bits,
+                    "path": scrubber.scrub(&module.key.path),
+                    "flavor": module.flavor,
+                    "size": module.size,
+                    "executed": encode_bitmap(&module.executed),
+                    "basic_blocks": encode_bitmap(&module.bb_starts),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let document = json!({
+            "format_version": constants::FORMAT_VERSION,
+            "modules": modules,
+        });
+
+        read_only::write(path, serde_json::to_string(&document)?)?;
+
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let mut document: Value = serde_json::from_slice(&std::fs::read(path)?)?;
+        migrate(&mut document)?;
+
+        let mut res = Self::default();
+
+        for module in document["modules"].as_array().into_iter().flatten() {
+            let Some(path) = module["path"].as_str() else {
+                anyhow::bail!("Module without a path in state file");
+            };
+
+            let state = ModuleState {
+                key: ModuleKey {
+                    path: path.to_string(),
+                    build_id: module["build_id"].as_str().map(str::to_string),
+                    arch: module["arch"].as_str().map(str::to_string),
+                    bits: module["bits"].as_u64().map(|bits| bits as u8),
+                    other_build: None,
+                },
+                flavor: module["flavor"]
+                    .as_str()
+                    .unwrap_or(constants::MIXED_FLAVOR)
+                    .to_string(),
+                size: module["size"].as_u64().unwrap_or_default() as usize,
+                executed: decode_bitmap(&module["executed"])?,
+                bb_starts: decode_bitmap(&module["basic_blocks"])?,
+            };
+
+            res.modules
+                .insert((state.flavor.clone(), state.key.clone()), state);
+        }
+
+        Ok(res)
+    }
+
+    /// Turns the state back into the model drcov files are parsed into, one per flavor, resolving
+    /// every module to a binary (or separate debug info file) of the current environment
+    pub fn into_drcovs(self, resolver: &BinaryResolver, filters: &DrcovFilters) -> Vec<Drcov> {
+        // Flavor -> module table
+        let mut tables = BTreeMap::<String, Vec<Module>>::new();
+
+        for module in self.modules.into_values() {
+            let Some(path) = resolver.resolve(&module, filters) else {
+                log::warn!(
+                    "Could not resolve module '{}' (build-id: {}), skipping it",
+                    module.key.path,
+                    module.key.build_id.as_deref().unwrap_or("none")
+                );
+                continue;
+            };
+
+            if !filters.matches_any_module_filter(path.as_bytes())
+                || filters.matches_any_module_skip_filter(path.as_bytes())
+            {
+                continue;
+            }
+
+            log::debug!("Resolved module '{}' to '{path}'", module.key.path);
+
+            tables.entry(module.flavor).or_default().push(Module {
+                size: module.size,
+                path,
+                build_id: module.key.build_id,
+                bb_bitmap: module.executed,
+                bb_starts: module.bb_starts,
+                ..Default::default()
+            });
+        }
+
+        if tables.is_empty() {
+            return vec![Drcov::without_modules(constants::MIXED_FLAVOR)];
+        }
+
+        tables
+            .into_iter()
+            .map(|(flavor, table)| Drcov::from_modules(&flavor, Modules { version: 0, table }))
+            .collect()
+    }
+}
+
+/// Maps build-ids back to binaries at report time. The directories are only indexed the first
+/// time a binary is looked up, so one resolver is shared by every input of a conversion.
+#[derive(Debug, Default)]
+pub struct BinaryResolver {
+    directories: Vec<String>,
+    /// Build-id -> path of a binary with that build-id
+    binaries: OnceLock<HashMap<String, String>>,
+}
+
+impl BinaryResolver {
+    /// Creates a resolver of the binaries found (recursively) under `directories`
+    pub fn new(directories: &[String]) -> Self {
+        Self {
+            directories: directories.to_vec(),
+            binaries: OnceLock::new(),
+        }
+    }
+
+    /// Indexes the binaries of the directories by their build-id
+    fn binaries(&self) -> &HashMap<String, String> {
+        fn index_directory(directory: &Path, binaries: &mut HashMap<String, String>) {
+            let Ok(read_dir) = directory.read_dir() else {
+                return;
+            };
+
+            for entry in read_dir.flatten() {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+
+                let path = entry.path();
+
+                if file_type.is_dir() {
+                    index_directory(&path, binaries);
+                } else if file_type.is_file() {
+                    let path = path.to_string_lossy().to_string();
+
+                    if let Some(build_id) = ModuleKey::of_module(&path).build_id {
+                        binaries.entry(build_id).or_insert(path);
+                    }
+                }
+            }
+        }
+
+        self.binaries.get_or_init(|| {
+            let mut binaries = HashMap::new();
+
+            for directory in &self.directories {
+                index_directory(Path::new(directory), &mut binaries);
+            }
+
+            log::info!("Indexed {} binaries by build-id", binaries.len());
+
+            binaries
+        })
+    }
+
+    /// Finds a binary for `module`: the path it was captured under (with the path map filters
+    /// applied) if it still has the same build-id, otherwise a binary with its build-id from the
+    /// indexed directories, otherwise its separate debug info file
+    pub fn resolve(&self, module: &ModuleState, filters: &DrcovFilters) -> Option<String> {
+        let path = String::from_utf8_lossy(
+            &filters.maybe_replace_with_path_map_filter(module.key.path.as_bytes()),
+        )
+        .to_string();
+
+        let Some(build_id) = &module.key.build_id else {
+            return Path::new(&path).is_file().then_some(path);
+        };
+
+        if ModuleKey::of_module(&path).build_id.as_ref() == Some(build_id) {
+            return Some(path);
+        }
+
+        self.find(build_id)
+    }
+
+    /// Finds a binary with the given build-id among the indexed directories, falling back to its
+    /// separate debug info file
+    pub fn find(&self, build_id: &str) -> Option<String> {
+        self.binaries()
+            .get(build_id)
+            .cloned()
+            .or_else(|| debug_file_path_by_build_id(build_id))
+    }
+}
+
+/// Parses a coverage state file and resolves its modules with `resolver`, returning their
+/// coverage per flavor
+pub fn from_file(
+    path: &Path,
+    resolver: &BinaryResolver,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Vec<Drcov>> {
+    log::info!("Loading coverage state: {}", path.display());
+
+    let state = CoverageState::read(path)?;
+
+    Ok(state.into_drcovs(resolver, filters))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{test_binary, test_binary_log, TempPath};
+
+    #[test]
+    fn migrates_older_state_files() {
+        let mut document = json!({
+            "format_version": 1,
+            "flavors": ["drcov"],
+            "modules": [{"build_id": "ab12", "path": "/build/app", "size": 16}],
+        });
+        let mut newer = json!({ "format_version": constants::FORMAT_VERSION + 1 });
+
+        let mut mixed = json!({
+            "format_version": 2,
+            "flavors": ["drcov", "sancov"],
+            "modules": [{"build_id": "ab12", "path": "/build/app", "size": 16}],
+        });
+
+        migrate(&mut document).unwrap();
+        migrate(&mut mixed).unwrap();
+
+        assert_eq!(document["format_version"], constants::FORMAT_VERSION);
+        assert_eq!(document["modules"][0]["arch"], Value::Null);
+        assert_eq!(document["modules"][0]["flavor"], "drcov");
+        assert_eq!(mixed["modules"][0]["flavor"], constants::MIXED_FLAVOR);
+        assert!(migrate(&mut newer).is_err());
+    }
+
+    #[test]
+    fn state_files_round_trip() {
+        let filters = DrcovFilters::default();
+        let drcov =
+            Drcov::from_bytes(&test_binary_log(&[(16, 4), (64, 2)]), &filters, None).unwrap();
+        let mut state = CoverageState::default();
+        state.add(&drcov);
+
+        let path = TempPath::new("state.covstate");
+        let scrubber = Scrubber { scrub_filters: &[] };
+        state.write(&path.display(), &scrubber).unwrap();
+        assert!(is_state_file(
+            &std::fs::read(path.path()).unwrap(),
+            path.path()
+        ));
+
+        let read = CoverageState::read(path.path()).unwrap();
+        assert_eq!(
+            read.modules.keys().collect::<Vec<_>>(),
+            state.modules.keys().collect::<Vec<_>>()
+        );
+
+        // The test binary is still at its path with the same build-id
+        let [resolved] = read
+            .into_drcovs(&BinaryResolver::new(&[]), &filters)
+            .try_into()
+            .unwrap();
+        let module = &resolved.modules.table[0];
+        assert_eq!(resolved.flavor, "drcov");
+        assert_eq!(module.path, test_binary());
+        assert_eq!(
+            module.bb_bitmap.iter().collect::<Vec<_>>(),
+            [16, 17, 18, 19, 64, 65]
+        );
+        assert_eq!(module.bb_starts.iter().collect::<Vec<_>>(), [16, 64]);
+    }
+
+    #[test]
+    fn keeps_the_flavor_of_every_module() {
+        let filters = DrcovFilters::default();
+        let drcov =
+            Drcov::from_bytes(&test_binary_log(&[(16, 4), (64, 2)]), &filters, None).unwrap();
+        let sancov = Drcov::from_modules("sancov", drcov.modules.clone());
+
+        let mut state = CoverageState::default();
+        state.add(&drcov);
+        state.add(&sancov);
+
+        let path = TempPath::new("state.covstate");
+        state
+            .write(&path.display(), &Scrubber { scrub_filters: &[] })
+            .unwrap();
+
+        // The coverage of every flavor is symbolized with its own line hit semantics
+        let resolved = CoverageState::read(path.path())
+            .unwrap()
+            .into_drcovs(&BinaryResolver::new(&[]), &filters);
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|drcov| (drcov.flavor.as_str(), drcov.modules.table.len()))
+                .collect::<Vec<_>>(),
+            [("drcov", 1), ("sancov", 1)]
+        );
+    }
+}
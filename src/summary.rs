@@ -0,0 +1,66 @@
+use crate::dwarf::SourceCoverage;
+use crate::util::assert_send_sync;
+use std::fmt::{Display, Formatter};
+
+/// Totals of a line coverage report, as `lcov --summary` would compute them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub lines_found: usize,
+    pub lines_hit: usize,
+    pub functions_found: usize,
+    pub functions_hit: usize,
+}
+
+const _: () = assert_send_sync::<Summary>();
+
+impl Summary {
+    pub fn from_coverage(coverage: &SourceCoverage) -> Self {
+        let lines = coverage.lines.values().flatten();
+        let functions = coverage.functions.values().flatten();
+
+        Self {
+            lines_found: lines.clone().count(),
+            lines_hit: lines.filter(|info| info.executed).count(),
+            functions_found: functions.clone().count(),
+            functions_hit: functions.filter(|info| info.executed).count(),
+        }
+    }
+
+    /// Adds the lines and functions of the source `file` of `coverage` to the totals
+    pub fn add_file(&mut self, coverage: &SourceCoverage, file: &str) {
+        let lines = coverage.lines.get(file).into_iter().flatten();
+        let functions = coverage.functions.get(file).into_iter().flatten();
+
+        self.lines_found += lines.clone().count();
+        self.lines_hit += lines.filter(|info| info.executed).count();
+        self.functions_found += functions.clone().count();
+        self.functions_hit += functions.filter(|info| info.executed).count();
+    }
+}
+
+fn percentage(hit: usize, found: usize) -> f64 {
+    if found == 0 {
+        0.0
+    } else {
+        hit as f64 * 100.0 / found as f64
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "lines......: {:.1}% ({} of {} lines)",
+            percentage(self.lines_hit, self.lines_found),
+            self.lines_hit,
+            self.lines_found
+        )?;
+        write!(
+            f,
+            "functions..: {:.1}% ({} of {} functions)",
+            percentage(self.functions_hit, self.functions_found),
+            self.functions_hit,
+            self.functions_found
+        )
+    }
+}
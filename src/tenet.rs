@@ -0,0 +1,138 @@
+use crate::drcov::{Drcov, DrcovFilters, Modules};
+use crate::util::{parse_capture_group, Hex};
+use std::collections::HashSet;
+use std::path::Path;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    /// The flavor given to the coverage parsed from Tenet traces
+    pub const TENET_FLAVOR: &str = "tenet";
+
+    lazy_static! {
+        /// The program counter of a trace line: `<reg>=<value>,...,rip=0x<pc>,...,mw=<addr>:<data>`.
+        /// Lines only hold the registers that changed, but the program counter always does.
+        pub static ref PC_REGEX: Regex =
+            Regex::new(r"(?:^|,)\s*(?:rip|eip|pc)=(?:0x)?(?P<pc>[[:xdigit:]]+)\s*(?:,|$)").unwrap();
+        /// A whole line of the trace, i.e. comma separated `<name>=<value>` pairs
+        pub static ref LINE_REGEX: Regex =
+            Regex::new(r"^\s*[[:alnum:]]+=[^,=]+(?:,\s*[[:alnum:]]+=[^,=]+)*\s*$").unwrap();
+    }
+}
+
+fn parse_pc(line: &[u8]) -> Option<u64> {
+    let cap = constants::PC_REGEX.captures(line)?;
+
+    parse_capture_group::<Hex<usize>>(&cap, "pc").map(|pc| pc.value as u64)
+}
+
+/// Whether `head`, the start of an input file, is a Tenet execution trace, judging by its first line
+pub fn is_tenet_trace(head: &[u8]) -> bool {
+    head.split(|b| *b == b'\n')
+        .find(|line| !line.trim_ascii().is_empty())
+        .is_some_and(|line| constants::LINE_REGEX.is_match(line) && parse_pc(line).is_some())
+}
+
+/// Whether the coverage of the given flavor has been parsed from a Tenet trace
+pub fn is_tenet_flavor(flavor: &str) -> bool {
+    flavor == constants::TENET_FLAVOR
+}
+
+/// Parses a Tenet execution trace into the same model drcov files are parsed into.
+///
+/// The traces log the registers (and memory) every instruction changed, so the addresses of the
+/// executed instructions are the program counters of their lines. They are mapped to modules
+/// through `module_table` (`--module-table`), since the traces do not describe the loaded modules,
+/// and recorded as one byte basic blocks like the addresses of QEMU traces.
+pub fn from_file(
+    path: &Path,
+    module_table: Option<&Modules>,
+    filters: &DrcovFilters,
+) -> anyhow::Result<Drcov> {
+    log::info!("Loading Tenet trace: {}", path.display());
+
+    let Some(mut modules) = module_table.cloned() else {
+        anyhow::bail!("Tenet traces do not describe the loaded modules (use --module-table)");
+    };
+
+    let contents = std::fs::read(path)?;
+
+    let pcs = contents
+        .split(|b| *b == b'\n')
+        .filter_map(parse_pc)
+        .collect::<HashSet<_>>();
+
+    modules.insert_addresses(pcs.into_iter().map(|pc| (pc, 1)));
+
+    modules.apply_filters(filters);
+
+    log::info!("Tenet trace loaded");
+
+    Ok(Drcov::from_modules(constants::TENET_FLAVOR, modules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drcov::Module;
+    use crate::test_fixtures::{drcov_log, TempPath};
+
+    fn traced_modules() -> Modules {
+        Modules {
+            version: 0,
+            table: vec![Module {
+                size: 0x1000,
+                segment_start: 0x400000,
+                path: "/bin/traced".to_string(),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn maps_the_program_counters_of_tenet_traces_to_their_modules() {
+        let trace = TempPath::new("trace.tenet");
+        std::fs::write(
+            trace.path(),
+            concat!(
+                "rax=0x1,rbx=0x2,rip=0x400010\n",
+                "rax=0x3,rip=0x400004,mw=0x7ffc0000:ff\n",
+                "rip=0x400010\n",
+                "rbx=0x4\n",
+                "rip=0x500000\n",
+            ),
+        )
+        .unwrap();
+        assert!(is_tenet_trace(&std::fs::read(trace.path()).unwrap()));
+
+        let drcov = from_file(
+            trace.path(),
+            Some(&traced_modules()),
+            &DrcovFilters::default(),
+        )
+        .unwrap();
+
+        let [module] = drcov.modules.table.as_slice() else {
+            panic!("Expected a single module");
+        };
+        assert_eq!(module.path, "/bin/traced");
+        // Every program counter is a one byte block, and the ones of no module are dropped
+        assert_eq!(module.bb_bitmap.iter().collect::<Vec<_>>(), vec![0x4, 0x10]);
+        assert_eq!(module.bb_starts.iter().collect::<Vec<_>>(), vec![0x4, 0x10]);
+        assert!(is_tenet_flavor(&drcov.flavor));
+    }
+
+    #[test]
+    fn tenet_traces_need_their_modules() {
+        let trace = TempPath::new("trace.tenet");
+        std::fs::write(trace.path(), "rip=0x400010\n").unwrap();
+
+        assert!(from_file(trace.path(), None, &DrcovFilters::default()).is_err());
+    }
+
+    #[test]
+    fn does_not_claim_drcov_logs() {
+        assert!(!is_tenet_trace(&drcov_log("/bin/traced", &[(16, 4)])));
+    }
+}
@@ -0,0 +1,146 @@
+//! The fixtures shared by the tests: temporary files and directories, and drcov logs of the test
+//! binary (or of a small binary), whose debug information the tests symbolize.
+
+use crate::drcov::{Module, Modules};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+/// A path in the temporary directory, unique to the process and the call, whose file or directory
+/// is removed once dropped
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let unique = COUNTER.fetch_add(1, Ordering::Relaxed);
+        Self(
+            std::env::temp_dir().join(format!("drcov2lcov-{}-{unique}-{name}", std::process::id())),
+        )
+    }
+
+    /// A new, empty directory
+    pub fn dir(name: &str) -> Self {
+        let dir = Self::new(name);
+        std::fs::create_dir_all(&dir.0).unwrap();
+        dir
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    pub fn display(&self) -> String {
+        self.0.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        } else {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
+
+/// The path of the test binary
+pub fn test_binary() -> String {
+    std::env::current_exe()
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Builds a hello world program with debug information into `name` next to the test binary, with
+/// the `rustc` of the toolchain next to the test binary and the extra `args`, unless it has been
+/// built already
+fn build_hello(name: &str, args: &[&str]) -> String {
+    let dir = Path::new(&test_binary()).with_file_name("small-binary");
+    let binary = dir.join(name);
+
+    if !binary.is_file() {
+        // Built aside and renamed, since test processes may build it concurrently
+        let build_dir = TempPath::dir("small-binary");
+        let source = build_dir.join("hello.rs");
+        std::fs::write(&source, "fn main() {\n    println!(\"Hello\");\n}\n").unwrap();
+
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = std::process::Command::new(rustc)
+            .args(["-g", "-C", "opt-level=0"])
+            .args(args)
+            .arg("-o")
+            .arg(build_dir.join(name))
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::rename(build_dir.join(name), &binary).unwrap();
+    }
+
+    binary.to_string_lossy().to_string()
+}
+
+/// The path of a small binary with debug information, built once from a hello world program with
+/// the `rustc` of the toolchain next to the test binary. Its debug information is read in a
+/// fraction of the time the one of the test binary takes, for the tests that read it several times.
+pub fn small_binary() -> String {
+    static PATH: OnceLock<String> = OnceLock::new();
+
+    PATH.get_or_init(|| build_hello("hello", &[])).clone()
+}
+
+/// The [`small_binary`] linked as a position dependent executable, whose load base is not zero
+pub fn small_non_pie_binary() -> String {
+    static PATH: OnceLock<String> = OnceLock::new();
+
+    PATH.get_or_init(|| build_hello("hello-non-pie", &["-C", "relocation-model=static"]))
+        .clone()
+}
+
+/// The module table of a process whose only module is the binary at `path`, mapped at zero and as
+/// large as the basic block offsets allow
+pub fn binary_modules(path: &str) -> Modules {
+    Modules {
+        version: 2,
+        table: vec![Module {
+            size: u32::MAX as usize,
+            path: path.to_string(),
+            ..Default::default()
+        }],
+    }
+}
+
+/// A drcov log of a process whose only module is the binary at `path`, mapped at zero and as
+/// large as the basic block offsets allow, in which the `(offset, size)` basic blocks of the binary
+/// have been executed
+pub fn drcov_log(path: &str, blocks: &[(u32, u16)]) -> Vec<u8> {
+    let mut log = format!(
+        "DRCOV VERSION: 2\nDRCOV FLAVOR: drcov\nModule Table: version 2, count 1\nColumns: id, base, end, entry, path\n  0, 0x0, 0x{:x}, 0x0, {path}\nBB Table: {} bbs\n",
+        u32::MAX,
+        blocks.len()
+    )
+    .into_bytes();
+
+    for (offset, size) in blocks {
+        log.extend_from_slice(&offset.to_le_bytes());
+        log.extend_from_slice(&size.to_le_bytes());
+        log.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    log
+}
+
+/// A drcov log of the [`binary_modules`] of the test binary in which the `(offset, size)` basic blocks of the test
+/// binary have been executed
+pub fn test_binary_log(blocks: &[(u32, u16)]) -> Vec<u8> {
+    drcov_log(&test_binary(), blocks)
+}
@@ -1,4 +1,16 @@
+use crate::error::Error;
+use crate::limits::read_to_end_bounded;
+use flate2::read::MultiGzDecoder;
 use regex::bytes::Captures;
+use std::borrow::Cow;
+use std::io::Read;
+#[cfg(feature = "native")]
+use std::path::Path;
+
+mod constants {
+    pub const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+    pub const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+}
 
 pub struct Hex<T> {
     pub value: T,
@@ -20,3 +32,91 @@ pub fn parse_capture_group<F: std::str::FromStr>(cap: &Captures<'_>, name: &str)
 
     res
 }
+
+/// Escapes the characters that are not allowed to appear verbatim in XML text and attribute values
+pub fn xml_escape(input: &str) -> Cow<'_, str> {
+    if !input.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(input);
+    }
+
+    let mut res = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            _ => res.push(c),
+        }
+    }
+
+    Cow::Owned(res)
+}
+
+/// Returns `path` relative to `root` (which is expected to be canonical). Paths outside of `root`
+/// are returned unchanged.
+#[cfg(feature = "native")]
+pub fn relative_path(path: &str, root: &Path) -> String {
+    let path = Path::new(path);
+
+    if let Ok(relative) = path.strip_prefix(root) {
+        return relative.to_string_lossy().to_string();
+    }
+
+    // DWARF paths are not canonical (e.g. they may contain `..` components or symlinks)
+    path.canonicalize()
+        .ok()
+        .and_then(|canonical| {
+            canonical
+                .strip_prefix(root)
+                .ok()
+                .map(|relative| relative.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+/// Fails to compile unless `T` can be shared and sent across threads. Used to guarantee that the
+/// types embedders drive conversions with stay usable from thread pools and async runtimes.
+pub const fn assert_send_sync<T: Send + Sync>() {}
+
+#[cfg(feature = "native")]
+fn decode_zstd(contents: &[u8], limit: Option<u64>) -> Result<Vec<u8>, Error> {
+    read_to_end_bounded(zstd::stream::read::Decoder::new(contents)?, limit)
+}
+
+/// The zstd library is only linked into the native builds
+#[cfg(not(feature = "native"))]
+fn decode_zstd(_contents: &[u8], _limit: Option<u64>) -> Result<Vec<u8>, Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "zstd compressed inputs require the native feature",
+    )
+    .into())
+}
+
+/// Decompresses `contents` if they are gzip or zstd compressed (judging by their magic bytes rather
+/// than a file extension), and borrows them otherwise. Fails as soon as more than `limit` bytes
+/// have been inflated, since a few kilobytes can inflate to gigabytes.
+pub fn decompress(contents: &[u8], limit: Option<u64>) -> Result<Cow<'_, [u8]>, Error> {
+    if contents.starts_with(constants::GZIP_MAGIC) {
+        read_to_end_bounded(MultiGzDecoder::new(contents), limit).map(Cow::Owned)
+    } else if contents.starts_with(constants::ZSTD_MAGIC) {
+        decode_zstd(contents, limit).map(Cow::Owned)
+    } else {
+        Ok(Cow::Borrowed(contents))
+    }
+}
+
+/// Reads `reader` to its end, transparently decompressing the contents like [`decompress`]. Both
+/// the compressed and the decompressed contents are bounded by `limit`.
+pub fn read_maybe_compressed<R: Read>(reader: R, limit: Option<u64>) -> Result<Vec<u8>, Error> {
+    let contents = read_to_end_bounded(reader, limit)?;
+
+    let decompressed = match decompress(&contents, limit)? {
+        Cow::Owned(res) => Some(res),
+        Cow::Borrowed(_) => None,
+    };
+
+    Ok(decompressed.unwrap_or(contents))
+}
@@ -0,0 +1,191 @@
+use crate::dwarf::SourceCoverage;
+use crate::read_only;
+use crate::summary::Summary;
+use crate::util::parse_capture_group;
+use regex::bytes::Regex;
+
+mod constants {
+    use lazy_static::lazy_static;
+    use regex::bytes::Regex;
+
+    lazy_static! {
+        pub static ref LCOV_SUMMARY_LINES_REGEX: Regex =
+            Regex::new(r"lines\.*:\s*[\d.]+%\s*\((?P<hit>\d+) of (?P<found>\d+) lines?\)").unwrap();
+        pub static ref LCOV_SUMMARY_FUNCTIONS_REGEX: Regex =
+            Regex::new(r"functions\.*:\s*[\d.]+%\s*\((?P<hit>\d+) of (?P<found>\d+) functions?\)")
+                .unwrap();
+        pub static ref LCOV_SUMMARY_BRANCHES_REGEX: Regex = Regex::new(
+            r"branches\.*:\s*[\d.]+%\s*\((?P<hit>\d+) of (?P<found>\d+) branch(?:es)?\)"
+        )
+        .unwrap();
+    }
+}
+
+/// The `(hit, found)` totals of a line of an `lcov --summary` report, unless lcov found no data
+/// for them
+fn reported_totals(report: &[u8], regex: &Regex) -> Option<(usize, usize)> {
+    let cap = regex.captures(report)?;
+
+    Some((
+        parse_capture_group::<usize>(&cap, "hit").unwrap_or_default(),
+        parse_capture_group::<usize>(&cap, "found").unwrap_or_default(),
+    ))
+}
+
+/// Compares the totals of the `lcov --summary` `report` of the tracefile at `path` to the ones of
+/// `coverage`. The branch totals are only compared if lcov reports them, since lcov 1.x leaves
+/// branches out of its summaries by default.
+fn check_report(path: &str, report: &[u8], coverage: &SourceCoverage) -> anyhow::Result<()> {
+    let summary = Summary::from_coverage(coverage);
+
+    let lines = match reported_totals(report, &constants::LCOV_SUMMARY_LINES_REGEX) {
+        Some(lines) => lines,
+        // lcov reports "no data found" instead of a summary for empty tracefiles
+        None if summary.lines_found == 0 => (0, 0),
+        None => anyhow::bail!(
+            "lcov could not summarize '{path}':\n{}",
+            String::from_utf8_lossy(report)
+        ),
+    };
+
+    let functions =
+        reported_totals(report, &constants::LCOV_SUMMARY_FUNCTIONS_REGEX).unwrap_or((0, 0));
+
+    let branches = coverage.branches.values().flatten();
+    let expected_branches = (
+        branches.clone().filter(|branch| branch.executed).count(),
+        branches.count(),
+    );
+
+    let totals = [
+        (
+            "lines",
+            Some(lines),
+            (summary.lines_hit, summary.lines_found),
+        ),
+        (
+            "functions",
+            Some(functions),
+            (summary.functions_hit, summary.functions_found),
+        ),
+        (
+            "branches",
+            reported_totals(report, &constants::LCOV_SUMMARY_BRANCHES_REGEX),
+            expected_branches,
+        ),
+    ];
+
+    for (kind, reported, expected) in totals {
+        let Some((hit, found)) = reported else {
+            continue;
+        };
+
+        if (hit, found) != expected {
+            anyhow::bail!(
+                "lcov disagrees with the computed summary of '{path}': lcov reports {hit} of {found} {kind} hit, expected {} of {}",
+                expected.0,
+                expected.1
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `lcov --summary` on the tracefile we produced and compares its line, function and branch
+/// totals to the ones of `coverage`.
+///
+/// Returns `Ok(())` without checking anything if lcov is not installed.
+pub fn verify_lcov_output(path: &str, coverage: &SourceCoverage) -> anyhow::Result<()> {
+    let output = match read_only::tool("lcov").arg("--summary").arg(path).output() {
+        Ok(output) => output,
+        Err(e) => {
+            log::warn!("Could not run lcov, skipping the verification of '{path}'. Reason: {e}");
+            return Ok(());
+        }
+    };
+
+    // lcov prints the summary to stderr or stdout depending on its version
+    let mut report = output.stdout;
+    report.extend_from_slice(&output.stderr);
+
+    check_report(path, &report, coverage)?;
+
+    log::info!("lcov agrees with the computed summary of '{path}'");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf::{BranchInfo, FunctionInfo, LineInfo};
+
+    fn coverage() -> SourceCoverage {
+        let mut coverage = SourceCoverage::default();
+        coverage.lines.insert(
+            "/src/main.c".to_string(),
+            vec![
+                LineInfo {
+                    line: 3,
+                    executed: true,
+                },
+                LineInfo {
+                    line: 5,
+                    executed: false,
+                },
+            ],
+        );
+        coverage.functions.insert(
+            "/src/main.c".to_string(),
+            vec![FunctionInfo {
+                name: "main".to_string(),
+                module: "/bin/app".to_string(),
+                start_line: 3,
+                end_line: 5,
+                executed: true,
+                aliases: Vec::new(),
+                blocks: None,
+            }],
+        );
+        coverage.branches.insert(
+            "/src/main.c".to_string(),
+            vec![
+                BranchInfo {
+                    line: 3,
+                    discriminator: 1,
+                    executed: true,
+                },
+                BranchInfo {
+                    line: 3,
+                    discriminator: 2,
+                    executed: false,
+                },
+            ],
+        );
+
+        coverage
+    }
+
+    #[test]
+    fn compares_the_line_function_and_branch_totals() {
+        let check = |report: &str| check_report("lcov.info", report.as_bytes(), &coverage());
+
+        // lcov 1.x leaves the branches out unless asked for them
+        assert!(check("Summary coverage rate:\n  lines......: 50.0% (1 of 2 lines)\n  functions..: 100.0% (1 of 1 function)\n  branches...: no data found\n").is_ok());
+        assert!(check("Summary coverage rate:\n  source files: 1\n  lines.......: 50.0% (1 of 2 lines)\n  functions...: 100.0% (1 of 1 function)\n  branches....: 50.0% (1 of 2 branches)\n").is_ok());
+
+        assert!(check(
+            "  lines......: 50.0% (1 of 2 lines)\n  functions..: 0.0% (0 of 1 function)\n"
+        )
+        .is_err());
+        assert!(
+            check("  lines......: 50.0% (1 of 2 lines)\n  functions..: no data found\n").is_err()
+        );
+        assert!(check("  lines......: 50.0% (1 of 2 lines)\n  functions..: 100.0% (1 of 1 function)\n  branches...: 100.0% (2 of 2 branches)\n").is_err());
+        assert!(check(
+            "  lines......: 100.0% (2 of 2 lines)\n  functions..: 100.0% (1 of 1 function)\n"
+        )
+        .is_err());
+    }
+}
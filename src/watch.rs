@@ -0,0 +1,144 @@
+use crate::cache::AtomicFile;
+use crate::cli::CliOptions;
+use crate::dwarf::SourceCoverage;
+use crate::lcov::write_lcov_output;
+use crate::output::LineEnding;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// What an input file looked like on the previous poll
+#[derive(Debug, PartialEq, Eq)]
+struct FileState {
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileState {
+    fn of(path: &Path) -> Option<Self> {
+        let meta = std::fs::metadata(path).ok()?;
+
+        Some(Self {
+            size: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+}
+
+/// Keeps polling the inputs for new files and calls `on_new_input_files` with every batch of them.
+///
+/// A file is only picked up once its size and modification time have not changed between two
+/// polls, so that logs which are still being written by a running target are not parsed half way.
+/// This never returns unless `on_new_input_files` fails.
+pub fn run(
+    options: &CliOptions,
+    mut on_new_input_files: impl FnMut(&[PathBuf]) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let interval = Duration::from_secs(options.watch_interval);
+
+    let mut processed = HashSet::new();
+    let mut pending = HashMap::<PathBuf, FileState>::new();
+
+    log::info!(
+        "Watching for new input files every {}s (press Ctrl-C to stop)",
+        options.watch_interval
+    );
+
+    loop {
+        let mut ready = Vec::new();
+
+        for input_file in options.get_input_files() {
+            if processed.contains(&input_file) {
+                continue;
+            }
+
+            let Some(state) = FileState::of(&input_file) else {
+                continue;
+            };
+
+            if pending.get(&input_file) == Some(&state) {
+                pending.remove(&input_file);
+                ready.push(input_file);
+            } else {
+                pending.insert(input_file, state);
+            }
+        }
+
+        if !ready.is_empty() {
+            log::info!("Processing {} new input files", ready.len());
+
+            on_new_input_files(&ready)?;
+            processed.extend(ready);
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+/// Keeps only the files under the project root (`--relative-to`, or the current directory) that
+/// have at least one executed line, so that the editor is not flooded with system headers and
+/// files nobody is looking at
+pub fn project_coverage(coverage: &SourceCoverage, options: &CliOptions) -> SourceCoverage {
+    let root = options
+        .relative_to
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+
+    let lines = coverage
+        .lines
+        .iter()
+        .filter(|(file, lines)| {
+            Path::new(file).starts_with(&root) && lines.iter().any(|info| info.executed)
+        })
+        .map(|(file, lines)| (file.clone(), lines.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let functions = coverage
+        .functions
+        .iter()
+        .filter(|(file, _)| lines.contains_key(*file))
+        .map(|(file, functions)| (file.clone(), functions.clone()))
+        .collect();
+
+    let branches = coverage
+        .branches
+        .iter()
+        .filter(|(file, _)| lines.contains_key(*file))
+        .map(|(file, branches)| (file.clone(), branches.clone()))
+        .collect();
+
+    SourceCoverage {
+        lines,
+        functions,
+        branches,
+    }
+}
+
+/// Writes `coverage` as an lcov file at `path` the way the VS Code Coverage Gutters extension
+/// expects it to be updated: the file is written under a temporary name and then renamed over the
+/// previous one, so the extension never reads a partially written report
+pub fn write_vscode_watch_file(
+    path: &str,
+    coverage: &SourceCoverage,
+    line_ending: LineEnding,
+) -> anyhow::Result<()> {
+    let atomic_file = AtomicFile::new(PathBuf::from(path));
+
+    write_lcov_output(
+        &atomic_file.temp_path().to_string_lossy(),
+        coverage,
+        &[],
+        None,
+        line_ending,
+    )?;
+    atomic_file.persist()?;
+
+    log::info!(
+        "Updated '{path}' with the coverage of {} project files",
+        coverage.lines.len()
+    );
+
+    Ok(())
+}